@@ -7,10 +7,17 @@ use std::{
 
 use lib::{camara::Camara, deteccion::Deteccion};
 
+/// Detección junto con la imagen analizada, que ya no está en el directorio de la cámara
+pub struct DeteccionCamara {
+    pub deteccion: Deteccion,
+    pub imagen: Vec<u8>,
+    pub extension: String,
+}
+
 pub struct HiloDeteccionCamara {
     camara: Camara,
     ruta: String,
-    enviar_deteccion: Sender<Deteccion>,
+    enviar_deteccion: Sender<DeteccionCamara>,
     detener_deteccion: Receiver<()>,
 }
 
@@ -18,7 +25,7 @@ impl HiloDeteccionCamara {
     pub fn new(
         camara: Camara,
         ruta: String,
-        enviar_deteccion: Sender<Deteccion>,
+        enviar_deteccion: Sender<DeteccionCamara>,
         detener_deteccion: Receiver<()>,
     ) -> Self {
         Self {
@@ -78,6 +85,7 @@ impl HiloDeteccionCamara {
             }
         };
 
+        let imagen = std::fs::read(imagen_random.path()).map_err(|e| e.to_string())?;
         std::fs::remove_file(imagen_random.path()).map_err(|e| e.to_string())?;
 
         let mut etiquetas = HashMap::new();
@@ -92,10 +100,22 @@ impl HiloDeteccionCamara {
             id_camara: self.camara.id,
             etiquetas,
             posicion: self.camara.posicion_en_rango_aleatoria(),
+            imagen: None,
         };
 
+        let extension = imagen_random
+            .path()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_string();
+
         self.enviar_deteccion
-            .send(deteccion)
+            .send(DeteccionCamara {
+                deteccion,
+                imagen,
+                extension,
+            })
             .map_err(|e| e.to_string())?;
 
         Ok(())
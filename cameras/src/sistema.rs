@@ -1,7 +1,8 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     io,
     sync::mpsc::{Receiver, Sender},
+    time::Duration,
 };
 
 use lib::{
@@ -17,26 +18,46 @@ use lib::{
     },
 };
 use messaging_client::cliente::{
-    jetstream::{js_suscripcion::JSSuscripcion, JetStream},
+    jetstream::{js_suscripcion::JSSuscripcion, object_store::ConfigObjetos, JetStream},
     suscripcion::Suscripcion,
     Cliente,
 };
 
 use crate::{
     estado::Estado,
-    hilo_deteccion_camara::HiloDeteccionCamara,
+    hilo_deteccion_camara::{DeteccionCamara, HiloDeteccionCamara},
     interfaz::{comando::Comando, interpretar_comando, respuesta::Respuesta},
 };
 
+/// Cuánto se espera la confirmación del stream al publicar una detección
+const TIEMPO_LIMITE_ACK_DETECCION: Duration = Duration::from_secs(2);
+
+/// Cuántas veces se vuelve a publicar una detección que no fue confirmada
+const REINTENTOS_DETECCION: u32 = 2;
+
+/// Object store donde se guardan las imágenes que dispararon un incidente
+const BUCKET_IMAGENES: &str = "camaras";
+
+/// Detección de un incidente que todavía no confirmó el stream
+struct DeteccionPendiente {
+    /// Id con el que se publica, así el stream descarta las que ya había guardado
+    id: String,
+    deteccion: Deteccion,
+    /// Imagen que falta subir al object store con el nombre de `deteccion.imagen`
+    imagen: Option<Vec<u8>>,
+}
+
 /// Sistema central de camaras
 pub struct Sistema {
     pub estado: Estado,
     pub configuracion: Configuracion,
     enviar_respuesta: Sender<Respuesta>,
     recibir_comandos: Receiver<Comando>,
-    recibir_deteccion: Receiver<Deteccion>,
-    enviar_deteccion: Sender<Deteccion>,
+    recibir_deteccion: Receiver<DeteccionCamara>,
+    enviar_deteccion: Sender<DeteccionCamara>,
     detener_deteccion: HashMap<u64, Sender<()>>,
+    /// Se conservan entre reconexiones y se republican con el mismo id
+    detecciones_pendientes: VecDeque<DeteccionPendiente>,
 }
 
 impl Sistema {
@@ -56,6 +77,7 @@ impl Sistema {
             recibir_deteccion,
             enviar_deteccion,
             detener_deteccion: HashMap::new(),
+            detecciones_pendientes: VecDeque::new(),
         }
     }
 
@@ -118,6 +140,11 @@ impl Sistema {
             ..Default::default()
         })?;
 
+        jet_stream.crear_object_store(&ConfigObjetos {
+            bucket: BUCKET_IMAGENES.to_string(),
+            ..Default::default()
+        })?;
+
         jet_stream.crear_consumer(
             "camaras",
             ConsumerConfig {
@@ -149,6 +176,7 @@ impl Sistema {
                 &mut sub_incidentes_finalizados,
                 &mut sub_comandos_remotos,
                 &sub_incidentes,
+                &mut jet_stream,
             )?;
         }
     }
@@ -230,6 +258,7 @@ impl Sistema {
         sub_incidentes_finalizados: &mut JSSuscripcion,
         sub_comandos: &mut JSSuscripcion,
         sub_incidentes: &Suscripcion,
+        jet_stream: &mut JetStream,
     ) -> io::Result<()> {
         self.leer_incidentes(
             cliente,
@@ -239,7 +268,7 @@ impl Sistema {
         )?;
         self.leer_comandos(cliente)?;
         self.leer_comandos_remotos(cliente, sub_comandos)?;
-        self.leer_detecciones(cliente)?;
+        self.leer_detecciones(jet_stream)?;
 
         std::thread::sleep(std::time::Duration::from_millis(5));
 
@@ -308,15 +337,76 @@ impl Sistema {
     }
 
     /// Lee las detecciones enviadas por las cámaras
-    fn leer_detecciones(&mut self, cliente: &Cliente) -> io::Result<()> {
-        while let Ok(deteccion) = self.recibir_deteccion.try_recv() {
+    fn leer_detecciones(&mut self, jet_stream: &mut JetStream) -> io::Result<()> {
+        while let Ok(DeteccionCamara {
+            mut deteccion,
+            imagen,
+            extension,
+        }) = self.recibir_deteccion.try_recv()
+        {
             println!("Detección recibida: {:?}", deteccion);
 
             if deteccion.es_incidente() {
                 println!("Incidente detectado");
 
-                cliente.publicar("incidentes.deteccion", &deteccion.serializar(), None)?;
+                let id = format!("{}-{}", deteccion.id_camara, rand::random::<u64>());
+                deteccion.imagen = Some(format!("{}/{}.{}", deteccion.id_camara, id, extension));
+                self.detecciones_pendientes.push_back(DeteccionPendiente {
+                    id,
+                    deteccion,
+                    imagen: Some(imagen),
+                });
+            }
+        }
+
+        self.publicar_detecciones_pendientes(jet_stream)
+    }
+
+    /// Publica las detecciones pendientes en orden. Si el stream no confirma
+    /// una, o se perdió la conexión, queda pendiente para el próximo intento
+    fn publicar_detecciones_pendientes(&mut self, jet_stream: &mut JetStream) -> io::Result<()> {
+        let mut imagenes = jet_stream.object_store(BUCKET_IMAGENES);
+
+        while let Some(pendiente) = self.detecciones_pendientes.front_mut() {
+            if let (Some(imagen), Some(nombre)) = (&pendiente.imagen, &pendiente.deteccion.imagen) {
+                match imagenes.put(nombre.as_str(), &mut imagen.as_slice()) {
+                    Ok(info) => println!("Imagen guardada como {}", info.name),
+                    Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                        eprintln!("El object store no confirmó la imagen, se reintentará");
+                        return Ok(());
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::Other => {
+                        // La detección se publica igual, sin imagen
+                        eprintln!("No se pudo guardar la imagen: {}", e);
+                        pendiente.deteccion.imagen = None;
+                    }
+                    Err(e) => return Err(e),
+                }
+                pendiente.imagen = None;
+            }
+
+            match jet_stream.publicar_con_id(
+                "incidentes.deteccion",
+                &pendiente.deteccion.serializar(),
+                &pendiente.id,
+                TIEMPO_LIMITE_ACK_DETECCION,
+                REINTENTOS_DETECCION,
+            ) {
+                Ok(ack) if ack.duplicate => {
+                    println!("Detección ya guardada con secuencia {}", ack.seq)
+                }
+                Ok(ack) => println!("Detección guardada con secuencia {}", ack.seq),
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                    eprintln!("El stream no confirmó la detección, se reintentará");
+                    return Ok(());
+                }
+                Err(e) if e.kind() == io::ErrorKind::Other => {
+                    eprintln!("No se pudo guardar la detección: {}", e)
+                }
+                Err(e) => return Err(e),
             }
+
+            self.detecciones_pendientes.pop_front();
         }
 
         Ok(())
@@ -488,6 +578,6 @@ impl Sistema {
     fn responder(&self, respuesta: Respuesta) -> io::Result<()> {
         self.enviar_respuesta
             .send(respuesta)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            .map_err(io::Error::other)
     }
 }
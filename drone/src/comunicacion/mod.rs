@@ -9,7 +9,10 @@ use lib::{
     configuracion::Configuracion,
     dron::Dron,
     incidente::Incidente,
-    jet_stream::{consumer_config::ConsumerConfig, stream_config::StreamConfig},
+    jet_stream::{
+        consumer_config::ConsumerConfig,
+        stream_config::{StorageType, StreamConfig},
+    },
     serializables::Serializable,
 };
 use messaging_client::cliente::{jetstream::JetStream, Cliente};
@@ -50,6 +53,8 @@ impl Comunicacion {
             jet_stream.crear_stream(&StreamConfig {
                 name: "drones".to_string(),
                 subjects: vec!["drones.*.comandos".to_string()],
+                // Los comandos solo sirven mientras el dron está volando
+                storage: StorageType::Memory,
                 ..Default::default()
             })?;
 
@@ -124,7 +124,6 @@ impl Sistema {
 }
 
 #[cfg(test)]
-
 mod tests {
     use lib::{configuracion::Configuracion, coordenadas::Coordenadas, dron::Dron};
 
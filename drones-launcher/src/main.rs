@@ -5,7 +5,6 @@ use drone::{comunicacion::Comunicacion, sistema::Sistema};
 use lib::{configuracion::Configuracion, dron::Dron};
 
 /// Programa que carga varios drones con sus respectivas configuraciones y los lanza en paralelo.
-
 fn main() {
     let config = Configuracion::desde_argv().expect("Error al cargar configuración");
 
@@ -35,7 +34,7 @@ fn main() {
         let mut config_dron =
             Configuracion::leer(&ruta).expect("Error al leer configuración de dron");
 
-        let mut nombre = ruta.split(&['/', '\\'][..]).last().unwrap();
+        let mut nombre = ruta.split(&['/', '\\'][..]).next_back().unwrap();
         nombre = nombre.split('.').next().unwrap();
 
         if let Ok(id) = nombre.parse::<u64>() {
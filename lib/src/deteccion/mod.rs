@@ -10,6 +10,8 @@ pub struct Deteccion {
     pub id_camara: u64,
     pub posicion: Coordenadas,
     pub etiquetas: HashMap<String, f64>,
+    /// Nombre en el object store de la imagen que disparó la detección
+    pub imagen: Option<String>,
 }
 
 impl Deteccion {
@@ -47,11 +49,13 @@ impl Serializable for Deteccion {
         let id_camara = deserializador.sacar_elemento()?;
         let posicion = deserializador.sacar_elemento_serializable()?;
         let etiquetas = deserializador.sacar_elemento_serializable()?;
+        let imagen = deserializador.sacar_elemento_serializable()?;
 
         Ok(Self {
             id_camara,
             posicion,
             etiquetas,
+            imagen,
         })
     }
 
@@ -62,6 +66,7 @@ impl Serializable for Deteccion {
         serializador.agregar_elemento(&self.id_camara);
         serializador.agregar_elemento_serializable(&self.posicion);
         serializador.agregar_elemento_serializable(&self.etiquetas);
+        serializador.agregar_elemento_serializable(&self.imagen);
 
         serializador.bytes
     }
@@ -78,6 +83,7 @@ mod test {
             id_camara: 1,
             posicion: Coordenadas::from_lat_lon(1., 2.),
             etiquetas: HashMap::new(),
+            imagen: Some("1/imagen.jpg".to_string()),
         };
 
         deteccion.etiquetas.insert("incendios".to_string(), 90.3);
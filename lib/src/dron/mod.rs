@@ -194,7 +194,6 @@ impl Serializable for Dron {
 }
 
 #[cfg(test)]
-
 mod tests {
     use crate::{coordenadas::Coordenadas, incidente::Incidente, serializables::Serializable};
 
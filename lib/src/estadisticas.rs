@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Result;
+
+/// Tópico al que se le pide al servidor sus estadísticas
+pub const TOPICO_ESTADISTICAS: &str = "$SYS.REQ.SERVER.VARZ";
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+/// Estadísticas del servidor, para monitorearlo
+pub struct EstadisticasServidor {
+    /// Veces que se desconectó a un cliente por no leer los mensajes a tiempo
+    pub slow_consumers: u64,
+}
+
+impl EstadisticasServidor {
+    /// Forma la estructura desde un json
+    pub fn from_json(json: &str) -> Result<EstadisticasServidor> {
+        serde_json::from_str(json)
+    }
+
+    /// Forma un json desde la estructura
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self)
+    }
+}
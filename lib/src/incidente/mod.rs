@@ -73,7 +73,6 @@ impl Serializable for Incidente {
 }
 
 #[cfg(test)]
-
 mod tests {
     use crate::serializables::Serializable;
 
@@ -0,0 +1,33 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use super::{stream_config::StreamConfig, stream_state::JetStreamStreamState};
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JSActualizarStreamRespuesta {
+    pub r#type: String,
+    pub config: StreamConfig,
+    pub created: String,
+    pub state: JetStreamStreamState,
+    pub ts: String,
+}
+
+impl JSActualizarStreamRespuesta {
+    pub fn new(config: StreamConfig, created: String, state: JetStreamStreamState) -> Self {
+        Self {
+            r#type: "io.nats.jetstream.api.v1.stream_update_response".to_string(),
+            config,
+            created,
+            state,
+            ts: Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
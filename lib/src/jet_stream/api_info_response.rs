@@ -3,18 +3,20 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct JSApiInfoResponse {
     pub r#type: String,
-    pub memory: i32,
-    pub storage: i32,
-    pub reserved_memory: i32,
-    pub reserved_storage: i32,
-    pub streams: i32,
-    pub consumers: i32,
+    /// Bytes guardados en memoria por todos los streams
+    pub memory: u64,
+    /// Bytes guardados en disco por todos los streams
+    pub storage: u64,
+    pub reserved_memory: u64,
+    pub reserved_storage: u64,
+    pub streams: u64,
+    pub consumers: u64,
     pub limits: JSApiInfoLimits,
     pub api: JSApiInfoApi,
 }
 
 impl JSApiInfoResponse {
-    pub fn new(streams: i32, consumers: i32) -> Self {
+    pub fn new(streams: u64, consumers: u64) -> Self {
         JSApiInfoResponse {
             r#type: "io.nats.jetstream.api.v1.account_info_response".to_string(),
             memory: 0,
@@ -42,13 +44,13 @@ impl JSApiInfoResponse {
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct JSApiInfoLimits {
-    pub max_memory: i32,
-    pub max_storage: i32,
-    pub max_streams: i32,
-    pub max_consumers: i32,
-    pub max_ack_pending: i32,
-    pub memory_max_stream_bytes: i32,
-    pub storage_max_stream_bytes: i32,
+    pub max_memory: i64,
+    pub max_storage: i64,
+    pub max_streams: i64,
+    pub max_consumers: i64,
+    pub max_ack_pending: i64,
+    pub memory_max_stream_bytes: i64,
+    pub storage_max_stream_bytes: i64,
     pub max_bytes_required: bool,
 }
 
@@ -77,15 +79,17 @@ impl JSApiInfoLimits {
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct JSApiInfoApi {
-    pub total: i32,
-    pub errors: i32,
+    /// Peticiones a `$JS.API` recibidas
+    pub total: u64,
+    /// Peticiones que se respondieron con un error
+    pub errors: u64,
 }
 
 impl JSApiInfoApi {
     pub fn new() -> Self {
         Self {
-            total: -1,
-            errors: -1,
+            total: 0,
+            errors: 0,
         }
     }
 
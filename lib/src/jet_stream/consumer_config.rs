@@ -1,13 +1,190 @@
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 
+/// Tiempo que se espera el ack de un mensaje si no se configura `ack_wait`
+const ACK_WAIT_PREDETERMINADO: Duration = Duration::from_secs(30);
+
 #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct ConsumerConfig {
+    /// Nombre de un consumer durable. Si está vacío el consumer es efímero
+    #[serde(default)]
     pub durable_name: String,
+    /// Nombre de un consumer efímero. Si no se indica lo asigna el servidor
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
     pub filter_subject: Option<String>,
     pub filter_subjects: Option<Vec<String>>,
+    /// Desde dónde del stream empieza a entregar mensajes el consumer
+    #[serde(default)]
+    pub deliver_policy: DeliverPolicy,
+    /// Primera secuencia a entregar con `by_start_sequence`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opt_start_seq: Option<u64>,
+    /// Fecha RFC 3339 del primer mensaje a entregar con `by_start_time`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opt_start_time: Option<String>,
+    /// Si los mensajes se entregan lo más rápido posible o respetando el tiempo entre ellos
+    #[serde(default)]
+    pub replay_policy: ReplayPolicy,
+    /// Cómo se confirman los mensajes entregados
+    #[serde(default)]
+    pub ack_policy: AckPolicy,
+    /// Cuánto se espera un ack antes de volver a entregar el mensaje. Cero usa el predeterminado de 30 segundos
+    #[serde(default, with = "serde_nanos")]
+    pub ack_wait: Duration,
+    /// Cantidad máxima de veces que se entrega un mensaje, -1 o 0 para ilimitado
+    #[serde(default)]
+    pub max_deliver: i64,
+    /// Cantidad máxima de mensajes entregados esperando ack, -1 o 0 para ilimitado
+    #[serde(default)]
+    pub max_ack_pending: i64,
+    /// Esperas entre reentregas sucesivas. Si está presente reemplaza a `ack_wait`
+    #[serde(default, with = "serde_nanos", skip_serializing_if = "Vec::is_empty")]
+    pub backoff: Vec<Duration>,
+    /// Tópico al que el servidor publica los mensajes. Si no está presente el consumer es pull
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deliver_subject: Option<String>,
+    /// Grupo de suscriptores que reciben los mensajes de un consumer push
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deliver_group: Option<String>,
+    /// Si el servidor pide confirmación al cliente antes de seguir enviando mensajes push
+    #[serde(default)]
+    pub flow_control: bool,
+    /// Cada cuánto se envía un latido si no hay mensajes para entregar. Cero para no enviar
+    #[serde(default, with = "serde_nanos")]
+    pub idle_heartbeat: Duration,
+    /// Límite de velocidad de entrega en bits por segundo, cero para ilimitado
+    #[serde(default)]
+    pub rate_limit_bps: u64,
+    /// Tiempo sin pedidos ni acks después del cual se elimina un consumer efímero
+    #[serde(default, with = "serde_nanos")]
+    pub inactive_threshold: Duration,
+}
+
+/// Primer mensaje del stream que entrega un consumer nuevo
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliverPolicy {
+    /// Todos los mensajes guardados
+    #[default]
+    All,
+    /// Solo el último mensaje guardado
+    Last,
+    /// Solo los mensajes que lleguen después de crear el consumer
+    New,
+    /// Desde la secuencia `opt_start_seq`
+    ByStartSequence,
+    /// Desde el primer mensaje guardado en `opt_start_time` o después
+    ByStartTime,
+    /// El último mensaje guardado de cada tópico
+    LastPerSubject,
+}
+
+/// Ritmo al que un consumer entrega los mensajes guardados
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplayPolicy {
+    /// Lo más rápido posible
+    #[default]
+    Instant,
+    /// Con el mismo tiempo entre mensajes con el que se guardaron
+    Original,
+}
+
+/// Política de confirmación de los mensajes entregados por un consumer
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AckPolicy {
+    /// Los mensajes se dan por confirmados al entregarlos
+    None,
+    /// Confirmar un mensaje confirma también todos los anteriores
+    All,
+    /// Cada mensaje se confirma por separado
+    #[default]
+    Explicit,
 }
 
 impl ConsumerConfig {
+    pub fn es_durable(&self) -> bool {
+        !self.durable_name.is_empty()
+    }
+
+    /// Nombre con el que se identifica el consumer en el stream y en los tópicos de la API
+    pub fn nombre(&self) -> &str {
+        if self.es_durable() {
+            &self.durable_name
+        } else {
+            self.name.as_deref().unwrap_or_default()
+        }
+    }
+
+    pub fn es_push(&self) -> bool {
+        self.deliver_subject.is_some()
+    }
+
+    /// Fecha de `opt_start_time` en nanosegundos desde epoch
+    pub fn tiempo_inicio(&self) -> Option<i64> {
+        let fecha = chrono::DateTime::parse_from_rfc3339(self.opt_start_time.as_ref()?).ok()?;
+        fecha.timestamp_nanos_opt()
+    }
+
+    /// El nombre no puede tener espacios, puntos ni comodines, y si se indican
+    /// `name` y `durable_name` tienen que coincidir. `opt_start_seq` y
+    /// `opt_start_time` solo se usan con su política de
+    /// entrega. Las opciones de entrega push no se pueden usar en un consumer
+    /// pull, y el control de flujo necesita latidos para detectar un cliente trabado
+    pub fn es_valida(&self) -> bool {
+        let nombre_valido = !self
+            .nombre()
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '.' | '*' | '>' | '/' | '\\'));
+        let nombres_coinciden = !self.es_durable()
+            || self
+                .name
+                .as_ref()
+                .is_none_or(|nombre| *nombre == self.durable_name);
+        if !nombre_valido || !nombres_coinciden {
+            return false;
+        }
+
+        let inicio_valido = match self.deliver_policy {
+            DeliverPolicy::ByStartSequence => {
+                self.opt_start_seq.is_some() && self.opt_start_time.is_none()
+            }
+            DeliverPolicy::ByStartTime => {
+                self.opt_start_seq.is_none() && self.tiempo_inicio().is_some()
+            }
+            _ => self.opt_start_seq.is_none() && self.opt_start_time.is_none(),
+        };
+        if !inicio_valido {
+            return false;
+        }
+
+        if self.es_push() {
+            return !self.flow_control || !self.idle_heartbeat.is_zero();
+        }
+
+        self.deliver_group.is_none()
+            && !self.flow_control
+            && self.idle_heartbeat.is_zero()
+            && self.rate_limit_bps == 0
+    }
+
+    /// Cuánto se espera el ack de un mensaje que ya se entregó `entregas` veces
+    pub fn espera_ack(&self, entregas: u64) -> Duration {
+        if let Some(ultima) = self.backoff.last() {
+            let indice = (entregas.saturating_sub(1) as usize).min(self.backoff.len() - 1);
+            return self.backoff.get(indice).copied().unwrap_or(*ultima);
+        }
+
+        if self.ack_wait.is_zero() {
+            ACK_WAIT_PREDETERMINADO
+        } else {
+            self.ack_wait
+        }
+    }
+
     pub fn from_json(json: &str) -> serde_json::Result<Self> {
         serde_json::from_str(json)
     }
@@ -16,3 +193,92 @@ impl ConsumerConfig {
         serde_json::to_string(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{AckPolicy, ConsumerConfig, DeliverPolicy};
+
+    #[test]
+    fn valores_predeterminados() {
+        let config = ConsumerConfig::from_json(r#"{"durable_name":"c"}"#).unwrap();
+
+        assert_eq!(config.ack_policy, AckPolicy::Explicit);
+        assert_eq!(config.deliver_policy, DeliverPolicy::All);
+        assert_eq!(config.espera_ack(1), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn espera_ack_con_backoff() {
+        let config = ConsumerConfig::from_json(
+            r#"{"durable_name":"c","ack_policy":"all","backoff":[1000000000,5000000000]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.ack_policy, AckPolicy::All);
+        assert_eq!(config.espera_ack(1), Duration::from_secs(1));
+        assert_eq!(config.espera_ack(2), Duration::from_secs(5));
+        assert_eq!(config.espera_ack(7), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn opciones_push_requieren_deliver_subject() {
+        let mut config = ConsumerConfig {
+            durable_name: "c".to_string(),
+            flow_control: true,
+            idle_heartbeat: Duration::from_secs(5),
+            ..Default::default()
+        };
+        assert!(!config.es_valida());
+
+        config.deliver_subject = Some("entrega".to_string());
+        assert!(config.es_valida());
+
+        config.idle_heartbeat = Duration::ZERO;
+        assert!(!config.es_valida());
+    }
+
+    #[test]
+    fn politicas_de_entrega() {
+        let config = ConsumerConfig::from_json(
+            r#"{"durable_name":"c","deliver_policy":"by_start_time","opt_start_time":"2024-06-01T12:00:00Z"}"#,
+        )
+        .unwrap();
+        assert!(config.es_valida());
+        assert_eq!(config.tiempo_inicio(), Some(1_717_243_200_000_000_000));
+
+        let config = ConsumerConfig::from_json(
+            r#"{"durable_name":"c","deliver_policy":"last_per_subject","opt_start_seq":3}"#,
+        )
+        .unwrap();
+        assert_eq!(config.deliver_policy, DeliverPolicy::LastPerSubject);
+        assert!(!config.es_valida());
+
+        let config = ConsumerConfig {
+            durable_name: "c".to_string(),
+            deliver_policy: DeliverPolicy::ByStartSequence,
+            ..Default::default()
+        };
+        assert!(!config.es_valida());
+    }
+
+    #[test]
+    fn nombre_de_consumers_efimeros() {
+        let config = ConsumerConfig::from_json(r#"{"name":"efimero"}"#).unwrap();
+        assert!(!config.es_durable());
+        assert_eq!(config.nombre(), "efimero");
+        assert!(config.es_valida());
+
+        let config = ConsumerConfig {
+            durable_name: "durable".to_string(),
+            name: Some("otro".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(config.nombre(), "durable");
+        assert!(!config.es_valida());
+
+        let config = ConsumerConfig::from_json(r#"{"durable_name":"a.b"}"#).unwrap();
+        assert!(!config.es_valida());
+    }
+}
@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+use super::{consumer_config::ConsumerConfig, consumer_info::SecuenciaInfo};
+
+/// Estado de un consumer durable que se guarda junto al stream para
+/// recuperarlo cuando el servidor se reinicia
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ConsumerEstado {
+    pub config: ConsumerConfig,
+    pub created: String,
+    /// Últimas secuencias entregadas
+    pub delivered: SecuenciaInfo,
+    /// Mensajes entregados que todavía esperan ack
+    #[serde(default)]
+    pub pending: Vec<EntregaPendiente>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct EntregaPendiente {
+    pub stream_seq: u64,
+    /// Secuencia del consumer de la última entrega
+    pub consumer_seq: u64,
+    /// Cantidad de veces que se entregó el mensaje
+    pub deliveries: u64,
+}
+
+impl ConsumerEstado {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
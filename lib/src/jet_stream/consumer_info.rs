@@ -7,4 +7,28 @@ pub struct ConsumerInfo {
     pub config: ConsumerConfig,
     pub created: String,
     pub ts: String,
+    /// Últimas secuencias entregadas
+    #[serde(default)]
+    pub delivered: SecuenciaInfo,
+    /// Secuencias hasta las que todos los mensajes están confirmados
+    #[serde(default)]
+    pub ack_floor: SecuenciaInfo,
+    /// Mensajes entregados que esperan ack
+    #[serde(default)]
+    pub num_ack_pending: u64,
+    /// Mensajes esperando ack que se entregaron más de una vez
+    #[serde(default)]
+    pub num_redelivered: u64,
+    /// Pedidos de mensajes esperando respuesta
+    #[serde(default)]
+    pub num_waiting: u64,
+    /// Mensajes del stream que todavía no se entregaron
+    #[serde(default)]
+    pub num_pending: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SecuenciaInfo {
+    pub consumer_seq: u64,
+    pub stream_seq: u64,
 }
@@ -1,23 +1,19 @@
-use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
-use super::consumer_config::ConsumerConfig;
+use super::consumer_info::ConsumerInfo;
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct JSConsumerInfoRespuesta {
     pub r#type: String,
-    pub config: ConsumerConfig,
-    pub created: String,
-    pub ts: String,
+    #[serde(flatten)]
+    pub info: ConsumerInfo,
 }
 
 impl JSConsumerInfoRespuesta {
-    pub fn new(config: ConsumerConfig) -> Self {
+    pub fn new(info: ConsumerInfo) -> Self {
         Self {
             r#type: "io.nats.jetstream.api.v1.consumer_info_response".to_string(),
-            config,
-            created: Utc::now().to_rfc3339(),
-            ts: Utc::now().to_rfc3339(),
+            info,
         }
     }
 
@@ -6,20 +6,28 @@ use super::consumer_config::ConsumerConfig;
 #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct JSCrearConsumerRespuesta {
     pub r#type: String,
+    /// Nombre del consumer, asignado por el servidor si es efímero y no se indicó
+    #[serde(default)]
+    pub name: String,
     pub config: ConsumerConfig,
     pub created: String,
     pub ts: String,
     pub did_create: bool,
+    /// Mensajes del stream que el consumer todavía tiene que entregar
+    #[serde(default)]
+    pub num_pending: u64,
 }
 
 impl JSCrearConsumerRespuesta {
-    pub fn new(config: ConsumerConfig, se_creo: bool) -> Self {
+    pub fn new(config: ConsumerConfig, se_creo: bool, pendientes: u64) -> Self {
         Self {
             r#type: "io.nats.jetstream.api.v1.consumer_create_response".to_string(),
+            name: config.nombre().to_string(),
             config,
             created: Utc::now().to_rfc3339(),
             ts: Utc::now().to_rfc3339(),
             did_create: se_creo,
+            num_pending: pendientes,
         }
     }
 
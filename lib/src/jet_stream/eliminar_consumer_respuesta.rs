@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JSEliminarConsumerRespuesta {
+    pub r#type: String,
+    pub success: bool,
+}
+
+impl JSEliminarConsumerRespuesta {
+    pub fn new() -> Self {
+        Self {
+            r#type: "io.nats.jetstream.api.v1.consumer_delete_response".to_string(),
+            success: true,
+        }
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
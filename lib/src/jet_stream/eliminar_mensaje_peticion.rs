@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+/// Cuerpo de `$JS.API.STREAM.MSG.DELETE.<stream>`
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JSPeticionEliminarMensaje {
+    pub seq: u64,
+    /// Si es `false` el contenido del mensaje se sobrescribe en disco con datos
+    /// aleatorios, además de eliminarlo del stream
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_erase: bool,
+}
+
+impl JSPeticionEliminarMensaje {
+    pub fn new(seq: u64, borrar: bool) -> Self {
+        Self {
+            seq,
+            no_erase: !borrar,
+        }
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
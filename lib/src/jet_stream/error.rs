@@ -0,0 +1,172 @@
+use serde::{Deserialize, Serialize};
+
+/// Error de la API de JetStream, con los mismos códigos que usa NATS
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JSError {
+    /// Código HTTP del error
+    pub code: u16,
+    /// Código de error propio de JetStream
+    pub err_code: u16,
+    pub description: String,
+}
+
+impl JSError {
+    pub fn new(code: u16, err_code: u16, description: &str) -> Self {
+        Self {
+            code,
+            err_code,
+            description: description.to_string(),
+        }
+    }
+
+    pub fn peticion_invalida() -> Self {
+        Self::new(400, 10003, "bad request")
+    }
+
+    pub fn stream_no_encontrado() -> Self {
+        Self::new(404, 10059, "stream not found")
+    }
+
+    /// Ya existe un stream con ese nombre y otra configuración
+    pub fn nombre_stream_en_uso() -> Self {
+        Self::new(
+            400,
+            10058,
+            "stream name already in use with a different configuration",
+        )
+    }
+
+    pub fn configuracion_stream_invalida(descripcion: &str) -> Self {
+        Self::new(
+            500,
+            10052,
+            &format!("invalid stream configuration: {}", descripcion),
+        )
+    }
+
+    pub fn creacion_stream_fallida(descripcion: &str) -> Self {
+        Self::new(500, 10049, descripcion)
+    }
+
+    pub fn eliminacion_stream_fallida(descripcion: &str) -> Self {
+        Self::new(500, 10050, descripcion)
+    }
+
+    pub fn consumer_no_encontrado() -> Self {
+        Self::new(404, 10014, "consumer not found")
+    }
+
+    /// Ya existe un consumer con ese nombre y otra configuración
+    pub fn nombre_consumer_en_uso() -> Self {
+        Self::new(400, 10013, "consumer name already in use")
+    }
+
+    pub fn nombre_stream_no_coincide() -> Self {
+        Self::new(400, 10056, "stream name in subject does not match request")
+    }
+
+    pub fn mensaje_excede_maximo() -> Self {
+        Self::new(400, 10054, "message size exceeds maximum allowed")
+    }
+
+    pub fn maximo_mensajes_excedido() -> Self {
+        Self::new(503, 10077, "maximum messages exceeded")
+    }
+
+    pub fn maximo_bytes_excedido() -> Self {
+        Self::new(503, 10077, "maximum bytes exceeded")
+    }
+
+    /// El stream no pudo escribir el mensaje en su almacenamiento
+    pub fn almacenamiento_fallido(descripcion: &str) -> Self {
+        Self::new(503, 10077, descripcion)
+    }
+
+    /// El último mensaje del tópico no es el que esperaba el publicador
+    pub fn ultima_secuencia_incorrecta(ultima: u64) -> Self {
+        Self::new(400, 10071, &format!("wrong last sequence: {}", ultima))
+    }
+
+    pub fn rollup_no_permitido() -> Self {
+        Self::new(400, 10115, "rollup not permitted")
+    }
+
+    pub fn maximo_consumers_alcanzado() -> Self {
+        Self::new(400, 10026, "maximum consumers limit reached")
+    }
+
+    pub fn maximo_streams_alcanzado() -> Self {
+        Self::new(400, 10027, "maximum number of streams reached")
+    }
+
+    /// Guardar el mensaje superaría el `max_storage` de la cuenta
+    pub fn almacenamiento_insuficiente() -> Self {
+        Self::new(503, 10047, "insufficient storage resources available")
+    }
+
+    pub fn espejo_no_actualizable() -> Self {
+        Self::new(400, 10055, "stream mirror configuration can not be updated")
+    }
+
+    pub fn snapshot_fallido(descripcion: &str) -> Self {
+        Self::new(500, 10063, &format!("snapshot failed: {}", descripcion))
+    }
+
+    pub fn restauracion_fallida(descripcion: &str) -> Self {
+        Self::new(500, 10062, &format!("restore failed: {}", descripcion))
+    }
+
+    pub fn stream_existente_al_restaurar() -> Self {
+        Self::new(400, 10130, "stream name already in use, cannot restore")
+    }
+
+    pub fn mensaje_no_encontrado() -> Self {
+        Self::new(404, 10037, "no message found")
+    }
+
+    /// No se pudo eliminar el mensaje pedido con `$JS.API.STREAM.MSG.DELETE`
+    pub fn eliminacion_mensaje_fallida(descripcion: &str) -> Self {
+        Self::new(500, 10057, descripcion)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JSErrorRespuesta {
+    pub r#type: String,
+    pub error: JSError,
+}
+
+impl JSErrorRespuesta {
+    pub fn new(tipo: &str, error: JSError) -> Self {
+        Self {
+            r#type: tipo.to_string(),
+            error,
+        }
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JSError, JSErrorRespuesta};
+
+    #[test]
+    fn formato_de_la_respuesta_de_error() {
+        let respuesta = JSErrorRespuesta::new(
+            "io.nats.jetstream.api.v1.stream_info_response",
+            JSError::stream_no_encontrado(),
+        );
+
+        assert_eq!(
+            respuesta.to_json().unwrap(),
+            r#"{"type":"io.nats.jetstream.api.v1.stream_info_response","error":{"code":404,"err_code":10059,"description":"stream not found"}}"#
+        );
+    }
+}
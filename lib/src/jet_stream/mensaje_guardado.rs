@@ -0,0 +1,64 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+/// Mensaje guardado en un stream, como lo devuelve `$JS.API.STREAM.MSG.GET.<stream>`.
+/// Los headers y el payload van en base64
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JSMensajeGuardado {
+    pub subject: String,
+    pub seq: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hdrs: Option<String>,
+    #[serde(default)]
+    pub data: String,
+    pub time: String,
+}
+
+impl JSMensajeGuardado {
+    pub fn new(
+        subject: String,
+        seq: u64,
+        header: Option<&[u8]>,
+        payload: &[u8],
+        time: String,
+    ) -> Self {
+        Self {
+            subject,
+            seq,
+            hdrs: header.map(|header| STANDARD.encode(header)),
+            data: STANDARD.encode(payload),
+            time,
+        }
+    }
+
+    /// Headers del mensaje tal como se publicaron
+    pub fn header(&self) -> Option<Vec<u8>> {
+        self.hdrs
+            .as_ref()
+            .and_then(|hdrs| STANDARD.decode(hdrs).ok())
+    }
+
+    pub fn payload(&self) -> Vec<u8> {
+        STANDARD.decode(&self.data).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JSMensajeGuardado;
+
+    #[test]
+    fn headers_y_payload_en_base64() {
+        let mensaje = JSMensajeGuardado::new(
+            "a.b".to_string(),
+            3,
+            Some(b"NATS/1.0\r\nA: 1\r\n\r\n"),
+            &[0, 159, 146, 150],
+            "2024-01-01T00:00:00Z".to_string(),
+        );
+
+        assert_eq!(mensaje.data, "AJ+Slg==");
+        assert_eq!(mensaje.payload(), vec![0, 159, 146, 150]);
+        assert_eq!(mensaje.header(), Some(b"NATS/1.0\r\nA: 1\r\n\r\n".to_vec()));
+    }
+}
@@ -1,15 +1,35 @@
+pub mod actualizar_stream_respuesta;
 pub mod admin_nombres_streams_respuesta;
 pub mod api_info_response;
 pub mod consumer_config;
+pub mod consumer_estado;
 pub mod consumer_info;
 pub mod consumer_info_respuesta;
 pub mod consumer_list_respuesta;
 pub mod crear_consumer_peticion;
 pub mod crear_consumer_respuesta;
 pub mod crear_stream_respuesta;
+pub mod eliminar_consumer_respuesta;
+pub mod eliminar_mensaje_peticion;
+pub mod eliminar_mensaje_respuesta;
+pub mod eliminar_stream_respuesta;
+pub mod error;
+pub mod mensaje_guardado;
 pub mod nombres_consumers_respuesta;
+pub mod objeto_info;
+pub mod obtener_mensaje_peticion;
+pub mod obtener_mensaje_respuesta;
+pub mod pub_ack;
+pub mod purgar_stream_peticion;
+pub mod purgar_stream_respuesta;
+pub mod restaurar_stream_respuesta;
+pub mod siguiente_mensaje_peticion;
+pub mod snapshot_peticion;
+pub mod snapshot_respuesta;
 pub mod stream_config;
 pub mod stream_info;
+pub mod stream_info_peticion;
 pub mod stream_info_respuesta;
 pub mod stream_list_response;
+pub mod stream_source;
 pub mod stream_state;
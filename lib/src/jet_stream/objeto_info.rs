@@ -0,0 +1,52 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+/// Mensaje de metadatos de un objeto de un object store. Se guarda en
+/// `$O.<bucket>.M.<nombre en base64>` y describe los chunks publicados en
+/// `$O.<bucket>.C.<nuid>`
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ObjetoInfo {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Valores libres que acompañan al objeto
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+    pub bucket: String,
+    /// Identifica el tópico de los chunks del objeto
+    pub nuid: String,
+    /// Tamaño total en bytes
+    pub size: u64,
+    /// Momento en el que se guardó, en formato RFC3339
+    pub mtime: String,
+    pub chunks: u64,
+    /// `SHA-256=` seguido del hash del contenido en base64 url
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub digest: String,
+    /// Marca de borrado, los chunks ya se eliminaron
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deleted: bool,
+}
+
+impl ObjetoInfo {
+    /// Información de un objeto vacío guardado ahora
+    pub fn new(bucket: &str, name: &str, nuid: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            bucket: bucket.to_string(),
+            nuid: nuid.to_string(),
+            mtime: Utc::now().to_rfc3339(),
+            ..Default::default()
+        }
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
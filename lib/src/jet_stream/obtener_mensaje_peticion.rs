@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+
+/// Cuerpo de `$JS.API.STREAM.MSG.GET.<stream>` y `$JS.API.DIRECT.GET.<stream>`.
+/// Se indica una de tres formas de elegir el mensaje:
+/// - solo `seq`: el mensaje con esa secuencia
+/// - `last_by_subj`: el último mensaje guardado en el tópico
+/// - `next_by_subj`: el primer mensaje del tópico con secuencia mayor o igual a `seq`
+///
+/// Los tópicos pueden tener comodines
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JSPeticionObtenerMensaje {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_by_subj: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_by_subj: Option<String>,
+}
+
+impl JSPeticionObtenerMensaje {
+    pub fn por_secuencia(secuencia: u64) -> Self {
+        Self {
+            seq: Some(secuencia),
+            ..Default::default()
+        }
+    }
+
+    pub fn ultimo_del_topico(topico: &str) -> Self {
+        Self {
+            last_by_subj: Some(topico.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn siguiente_del_topico(topico: &str, desde: u64) -> Self {
+        Self {
+            seq: Some(desde),
+            next_by_subj: Some(topico.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JSPeticionObtenerMensaje;
+
+    #[test]
+    fn solo_se_serializan_los_campos_indicados() {
+        assert_eq!(
+            JSPeticionObtenerMensaje::por_secuencia(7)
+                .to_json()
+                .unwrap(),
+            r#"{"seq":7}"#
+        );
+        assert_eq!(
+            JSPeticionObtenerMensaje::siguiente_del_topico("a.*", 3)
+                .to_json()
+                .unwrap(),
+            r#"{"seq":3,"next_by_subj":"a.*"}"#
+        );
+    }
+}
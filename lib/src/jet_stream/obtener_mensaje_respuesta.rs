@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+use super::mensaje_guardado::JSMensajeGuardado;
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JSObtenerMensajeRespuesta {
+    pub r#type: String,
+    pub message: JSMensajeGuardado,
+}
+
+impl JSObtenerMensajeRespuesta {
+    pub fn new(message: JSMensajeGuardado) -> Self {
+        Self {
+            r#type: "io.nats.jetstream.api.v1.stream_msg_get_response".to_string(),
+            message,
+        }
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// Confirmación que envía un stream al guardar un mensaje publicado con reply
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JSPubAck {
+    pub stream: String,
+    /// Secuencia asignada al mensaje en el stream
+    pub seq: u64,
+    /// Si el mensaje ya se había guardado antes y se descartó
+    #[serde(default)]
+    pub duplicate: bool,
+}
+
+impl JSPubAck {
+    pub fn new(stream: String, seq: u64, duplicate: bool) -> Self {
+        Self {
+            stream,
+            seq,
+            duplicate,
+        }
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+/// Cuerpo opcional de `$JS.API.STREAM.PURGE.<stream>`. Sin cuerpo se purga todo el stream
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JSPeticionPurgarStream {
+    /// Solo se purgan los mensajes de los tópicos que coincidan
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    /// Se purgan los mensajes con secuencia menor a esta
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+    /// Se conservan los últimos `keep` mensajes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep: Option<u64>,
+}
+
+impl JSPeticionPurgarStream {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
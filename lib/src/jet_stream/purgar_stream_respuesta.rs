@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JSPurgarStreamRespuesta {
+    pub r#type: String,
+    pub success: bool,
+    /// Cantidad de mensajes eliminados
+    pub purged: u64,
+}
+
+impl JSPurgarStreamRespuesta {
+    pub fn new(purgados: u64) -> Self {
+        Self {
+            r#type: "io.nats.jetstream.api.v1.stream_purge_response".to_string(),
+            success: true,
+            purged: purgados,
+        }
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
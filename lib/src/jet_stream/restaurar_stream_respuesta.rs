@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+/// Respuesta a `$JS.API.STREAM.RESTORE.<stream>`. Los bloques del snapshot se
+/// envían como peticiones a `deliver_subject`, y al final un mensaje vacío
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JSRestaurarStreamRespuesta {
+    pub r#type: String,
+    pub deliver_subject: String,
+}
+
+impl JSRestaurarStreamRespuesta {
+    pub fn new(deliver_subject: String) -> Self {
+        Self {
+            r#type: "io.nats.jetstream.api.v1.stream_restore_response".to_string(),
+            deliver_subject,
+        }
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
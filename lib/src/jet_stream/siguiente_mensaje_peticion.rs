@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Cuerpo de `$JS.API.CONSUMER.MSG.NEXT.<stream>.<consumer>` para pedir mensajes a un consumer pull
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JSPeticionSiguienteMensaje {
+    /// Cantidad máxima de mensajes a entregar
+    #[serde(default = "batch_predeterminado")]
+    pub batch: usize,
+    /// Cuánto tiempo queda abierto el pedido, cero para que no venza
+    #[serde(
+        default,
+        with = "serde_nanos",
+        skip_serializing_if = "Duration::is_zero"
+    )]
+    pub expires: Duration,
+    /// Si no hay mensajes disponibles se responde enseguida en lugar de esperar
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_wait: bool,
+    /// Cantidad máxima de bytes a entregar, cero para ilimitado
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub max_bytes: u64,
+    /// Cada cuánto se avisa que el pedido sigue abierto mientras no haya mensajes
+    #[serde(
+        default,
+        with = "serde_nanos",
+        skip_serializing_if = "Duration::is_zero"
+    )]
+    pub idle_heartbeat: Duration,
+}
+
+fn batch_predeterminado() -> usize {
+    1
+}
+
+fn is_zero(valor: &u64) -> bool {
+    *valor == 0
+}
+
+impl JSPeticionSiguienteMensaje {
+    pub fn new(batch: usize) -> Self {
+        Self {
+            batch,
+            ..Default::default()
+        }
+    }
+
+    /// Acepta tanto el JSON como un número con el tamaño del batch (la forma vieja del pedido).
+    /// Un cuerpo vacío pide un solo mensaje
+    pub fn parsear(bytes: &[u8]) -> serde_json::Result<Self> {
+        let texto = String::from_utf8_lossy(bytes);
+        let texto = texto.trim();
+
+        if texto.is_empty() {
+            return Ok(Self::new(1));
+        }
+
+        if let Ok(batch) = texto.parse::<usize>() {
+            return Ok(Self::new(batch));
+        }
+
+        Self::from_json(texto)
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::JSPeticionSiguienteMensaje;
+
+    #[test]
+    fn parsear_formas_del_pedido() {
+        assert_eq!(
+            JSPeticionSiguienteMensaje::parsear(b"").unwrap(),
+            JSPeticionSiguienteMensaje::new(1)
+        );
+        assert_eq!(
+            JSPeticionSiguienteMensaje::parsear(b"5").unwrap(),
+            JSPeticionSiguienteMensaje::new(5)
+        );
+
+        let peticion = JSPeticionSiguienteMensaje::parsear(
+            br#"{"batch":10,"expires":2000000000,"no_wait":true}"#,
+        )
+        .unwrap();
+        assert_eq!(peticion.batch, 10);
+        assert_eq!(peticion.expires, Duration::from_secs(2));
+        assert!(peticion.no_wait);
+        assert_eq!(peticion.max_bytes, 0);
+    }
+}
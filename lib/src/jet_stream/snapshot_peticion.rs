@@ -0,0 +1,37 @@
+use serde::{Deserialize, Serialize};
+
+/// Cuerpo de `$JS.API.STREAM.SNAPSHOT.<stream>`
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JSPeticionSnapshot {
+    /// Tópico al que se envían los bloques del snapshot. Cada bloque llega con un
+    /// `reply_to` al que hay que responder para recibir los siguientes, y el final
+    /// se indica con un mensaje vacío
+    pub deliver_subject: String,
+    /// No se incluye el estado de los consumers
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub no_consumers: bool,
+    /// Bytes de cada bloque, cero para el valor por defecto del servidor
+    #[serde(default, skip_serializing_if = "es_cero")]
+    pub chunk_size: usize,
+}
+
+fn es_cero(valor: &usize) -> bool {
+    *valor == 0
+}
+
+impl JSPeticionSnapshot {
+    pub fn new(deliver_subject: &str) -> Self {
+        Self {
+            deliver_subject: deliver_subject.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
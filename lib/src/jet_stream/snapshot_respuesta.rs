@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+
+use super::{stream_config::StreamConfig, stream_state::JetStreamStreamState};
+
+/// Respuesta a `$JS.API.STREAM.SNAPSHOT.<stream>`, antes de que empiecen a llegar los bloques
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JSSnapshotRespuesta {
+    pub r#type: String,
+    pub config: StreamConfig,
+    pub state: JetStreamStreamState,
+}
+
+impl JSSnapshotRespuesta {
+    pub fn new(config: StreamConfig, state: JetStreamStreamState) -> Self {
+        Self {
+            r#type: "io.nats.jetstream.api.v1.stream_snapshot_response".to_string(),
+            config,
+            state,
+        }
+    }
+
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
@@ -2,10 +2,15 @@ use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 
+use super::stream_source::StreamSource;
+
 #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct StreamConfig {
     /// Un nombre para el Stream. No debe tener espacios, tabulaciones ni caracteres de punto `.`
     pub name: String,
+    /// Descripción opcional del Stream
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
     /// Cuánto puede crecer el Stream en bytes totales antes de que se active la política de descarte configurada
     pub max_bytes: i64,
     /// Cuánto puede crecer el Stream en mensajes totales antes de que se active la política de descarte configurada
@@ -23,9 +28,103 @@ pub struct StreamConfig {
     pub max_msg_size: i32,
     /// No me importa, per el CLI de nats lo requiere
     pub num_replicas: i32,
+    /// Qué hacer cuando el Stream alcanza alguno de sus límites
+    #[serde(default)]
+    pub discard: DiscardPolicy,
+    /// Dónde se guardan los mensajes. No se puede cambiar una vez creado el Stream
+    #[serde(default)]
+    pub storage: StorageType,
+    /// Tiempo durante el cual se descartan los mensajes con un `Nats-Msg-Id` ya
+    /// guardado. Cero usa el predeterminado de 2 minutos
+    #[serde(default, with = "serde_nanos")]
+    pub duplicate_window: Duration,
+    /// Cuántos mensajes se conservan por tópico, los más viejos se descartan.
+    /// Cero o negativo para ilimitado
+    #[serde(default)]
+    pub max_msgs_per_subject: i64,
+    /// Si el stream responde a `$JS.API.DIRECT.GET.<stream>`
+    #[serde(default)]
+    pub allow_direct: bool,
+    /// Si se acepta el header `Nats-Rollup`, que reemplaza los mensajes anteriores del tópico
+    #[serde(default)]
+    pub allow_rollup_hdrs: bool,
+    /// Stream del que este es una copia exacta, con las mismas secuencias. Un
+    /// espejo no puede tener tópicos propios ni otras fuentes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<StreamSource>,
+    /// Streams de los que se copian mensajes además de los publicados en `subjects`.
+    /// Los mensajes copiados reciben secuencias propias de este stream
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<StreamSource>,
+}
+
+/// Ventana de duplicados si no se configura `duplicate_window`
+const VENTANA_DUPLICADOS_PREDETERMINADA: Duration = Duration::from_secs(120);
+
+/// Política de descarte cuando el Stream llega a `max_msgs` o `max_bytes`
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscardPolicy {
+    /// Se eliminan los mensajes más viejos para hacer lugar a los nuevos
+    #[default]
+    Old,
+    /// Se rechazan los mensajes nuevos
+    New,
+}
+
+/// Dónde guarda el Stream sus mensajes
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageType {
+    /// En archivos, se conservan al reiniciar el servidor
+    #[default]
+    File,
+    /// En memoria, se pierden al reiniciar el servidor
+    Memory,
 }
 
 impl StreamConfig {
+    /// El nombre no puede estar vacío ni tener espacios, puntos, comodines o separadores de ruta
+    pub fn nombre_valido(&self) -> bool {
+        !self.name.is_empty()
+            && !self
+                .name
+                .chars()
+                .any(|c| c.is_whitespace() || matches!(c, '.' | '*' | '>' | '/' | '\\'))
+    }
+
+    /// Un espejo no puede tener tópicos ni fuentes, y no se puede copiar dos veces
+    /// el mismo stream ni copiarse a sí mismo
+    pub fn origenes_validos(&self) -> bool {
+        if let Some(mirror) = &self.mirror {
+            return self.subjects.is_empty()
+                && self.sources.is_empty()
+                && !mirror.name.is_empty()
+                && mirror.name != self.name;
+        }
+
+        self.sources.iter().enumerate().all(|(i, source)| {
+            !source.name.is_empty()
+                && source.name != self.name
+                && !self.sources[..i]
+                    .iter()
+                    .any(|otro| otro.name == source.name)
+        })
+    }
+
+    /// Streams de los que se copian mensajes, sea como espejo o como fuentes
+    pub fn origenes(&self) -> impl Iterator<Item = &StreamSource> {
+        self.mirror.iter().chain(self.sources.iter())
+    }
+
+    pub fn ventana_duplicados(&self) -> Duration {
+        if self.duplicate_window.is_zero() {
+            VENTANA_DUPLICADOS_PREDETERMINADA
+        } else {
+            self.duplicate_window
+        }
+    }
+
     pub fn from_json(json: &str) -> serde_json::Result<Self> {
         serde_json::from_str(json)
     }
@@ -36,3 +135,68 @@ impl StreamConfig {
         serde_json::to_string(&clon)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{StorageType, StreamConfig};
+    use crate::jet_stream::stream_source::StreamSource;
+
+    #[test]
+    fn origenes_validos() {
+        let fuentes = StreamConfig {
+            name: "archivo".to_string(),
+            subjects: vec!["archivo.>".to_string()],
+            sources: vec![StreamSource::new("a"), StreamSource::new("b")],
+            ..Default::default()
+        };
+        assert!(fuentes.origenes_validos());
+        assert_eq!(fuentes.origenes().count(), 2);
+
+        let repetida = StreamConfig {
+            sources: vec![StreamSource::new("a"), StreamSource::new("a")],
+            ..fuentes.clone()
+        };
+        assert!(!repetida.origenes_validos());
+
+        let a_si_mismo = StreamConfig {
+            sources: vec![StreamSource::new("archivo")],
+            ..fuentes.clone()
+        };
+        assert!(!a_si_mismo.origenes_validos());
+
+        let espejo = StreamConfig {
+            name: "copia".to_string(),
+            mirror: Some(StreamSource::new("a")),
+            ..Default::default()
+        };
+        assert!(espejo.origenes_validos());
+
+        let espejo_con_topicos = StreamConfig {
+            subjects: vec!["copia".to_string()],
+            ..espejo.clone()
+        };
+        assert!(!espejo_con_topicos.origenes_validos());
+
+        let espejo_con_fuentes = StreamConfig {
+            sources: vec![StreamSource::new("b")],
+            ..espejo
+        };
+        assert!(!espejo_con_fuentes.origenes_validos());
+    }
+
+    #[test]
+    fn almacenamiento_por_defecto_en_archivo() {
+        let config = StreamConfig::from_json(r#"{"name":"a","max_bytes":-1,"max_msgs":-1,"max_consumers":-1,"max_age":0,"max_msg_size":-1,"num_replicas":1}"#).unwrap();
+        assert_eq!(config.storage, StorageType::File);
+
+        let memoria = StreamConfig {
+            storage: StorageType::Memory,
+            ..config
+        };
+        assert!(memoria.to_json().unwrap().contains(r#""storage":"memory""#));
+        assert_eq!(
+            StreamConfig::from_json(&memoria.to_json().unwrap()).unwrap(),
+            memoria
+        );
+    }
+}
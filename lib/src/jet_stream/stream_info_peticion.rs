@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+/// Cuerpo opcional de `$JS.API.STREAM.INFO.<stream>`
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JSPeticionStreamInfo {
+    /// Si está presente, se informa la cantidad de mensajes de cada tópico que coincida
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subjects_filter: Option<String>,
+}
+
+impl JSPeticionStreamInfo {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string(self)
+    }
+}
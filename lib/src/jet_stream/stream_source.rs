@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+/// Stream del que otro stream copia mensajes, como espejo (`mirror`) o como fuente (`sources`)
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct StreamSource {
+    /// Nombre del stream de origen
+    pub name: String,
+    /// Secuencia del origen desde la que se empieza a copiar si el stream está vacío
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opt_start_seq: Option<u64>,
+    /// Solo se copian los mensajes de los tópicos que coinciden con el filtro
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter_subject: Option<String>,
+}
+
+impl StreamSource {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+}
@@ -1,15 +1,26 @@
+use std::collections::HashMap;
+
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct JetStreamStreamState {
-    messages: u64,
-    bytes: u64,
-    first_seq: u64,
-    first_ts: String,
-    last_seq: u64,
-    last_ts: String,
-    consumer_count: u64,
+    pub messages: u64,
+    pub bytes: u64,
+    pub first_seq: u64,
+    pub first_ts: String,
+    pub last_seq: u64,
+    pub last_ts: String,
+    pub consumer_count: u64,
+    /// Cantidad de mensajes eliminados entre `first_seq` y `last_seq`
+    #[serde(default)]
+    pub num_deleted: u64,
+    /// Cantidad de tópicos distintos que tienen mensajes en el stream
+    #[serde(default)]
+    pub num_subjects: u64,
+    /// Cantidad de mensajes por tópico, solo si se pidió con `subjects_filter`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subjects: Option<HashMap<String, u64>>,
 }
 
 impl JetStreamStreamState {
@@ -22,6 +33,9 @@ impl JetStreamStreamState {
             last_seq: 0,
             last_ts: Utc::now().to_rfc3339(),
             consumer_count: 0,
+            num_deleted: 0,
+            num_subjects: 0,
+            subjects: None,
         }
     }
 }
@@ -4,6 +4,7 @@ pub mod coordenadas;
 pub mod csv;
 pub mod deteccion;
 pub mod dron;
+pub mod estadisticas;
 pub mod incidente;
 pub mod jet_stream;
 pub mod parseador;
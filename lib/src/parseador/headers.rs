@@ -0,0 +1,130 @@
+/// Versión del formato de headers de NATS, es la primera línea de todos los headers
+const VERSION: &str = "NATS/1.0";
+
+/// Headers de un mensaje (HPUB/HMSG) en el formato de NATS:
+///
+/// ```text
+/// NATS/1.0 [estado [descripción]]\r\n
+/// Clave: valor\r\n
+/// \r\n
+/// ```
+///
+/// El estado se usa en las respuestas del servidor (por ejemplo `404 No Messages`)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Headers {
+    pub estado: Option<u16>,
+    pub descripcion: Option<String>,
+    valores: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Headers de estado, sin valores (ejemplo: `NATS/1.0 408 Request Timeout`)
+    pub fn con_estado(estado: u16, descripcion: &str) -> Self {
+        Self {
+            estado: Some(estado),
+            descripcion: Some(descripcion.to_string()),
+            valores: Vec::new(),
+        }
+    }
+
+    /// Agrega un valor. Una misma clave puede tener varios valores
+    pub fn insertar(&mut self, clave: &str, valor: &str) {
+        self.valores.push((clave.to_string(), valor.to_string()));
+    }
+
+    /// Devuelve el primer valor de la clave (sin distinguir mayúsculas)
+    pub fn obtener(&self, clave: &str) -> Option<&str> {
+        self.valores
+            .iter()
+            .find(|(c, _)| c.eq_ignore_ascii_case(clave))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn valores(&self) -> &[(String, String)] {
+        &self.valores
+    }
+
+    pub fn serializar(&self) -> Vec<u8> {
+        let mut texto = VERSION.to_string();
+
+        if let Some(estado) = self.estado {
+            texto.push_str(&format!(" {}", estado));
+            if let Some(descripcion) = &self.descripcion {
+                texto.push_str(&format!(" {}", descripcion));
+            }
+        }
+        texto.push_str("\r\n");
+
+        for (clave, valor) in &self.valores {
+            texto.push_str(&format!("{}: {}\r\n", clave, valor));
+        }
+        texto.push_str("\r\n");
+
+        texto.into_bytes()
+    }
+
+    /// Devuelve `None` si los bytes no empiezan con `NATS/1.0`
+    pub fn parsear(bytes: &[u8]) -> Option<Self> {
+        let texto = String::from_utf8_lossy(bytes);
+        let mut lineas = texto.split("\r\n");
+
+        let primera = lineas.next()?.strip_prefix(VERSION)?.trim();
+        let mut headers = Self::new();
+
+        if !primera.is_empty() {
+            let (estado, descripcion) = match primera.split_once(' ') {
+                Some((estado, descripcion)) => (estado, Some(descripcion.trim().to_string())),
+                None => (primera, None),
+            };
+            headers.estado = Some(estado.parse().ok()?);
+            headers.descripcion = descripcion;
+        }
+
+        for linea in lineas {
+            if let Some((clave, valor)) = linea.split_once(':') {
+                headers.insertar(clave.trim(), valor.trim());
+            }
+        }
+
+        Some(headers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Headers;
+
+    #[test]
+    fn serializar_y_parsear() {
+        let mut headers = Headers::new();
+        headers.insertar("Nats-Msg-Id", "abc");
+        headers.insertar("Otro", "valor con espacios");
+
+        let bytes = headers.serializar();
+        assert_eq!(
+            bytes,
+            b"NATS/1.0\r\nNats-Msg-Id: abc\r\nOtro: valor con espacios\r\n\r\n"
+        );
+
+        let parseado = Headers::parsear(&bytes).unwrap();
+        assert_eq!(parseado, headers);
+        assert_eq!(parseado.obtener("nats-msg-id"), Some("abc"));
+    }
+
+    #[test]
+    fn parsear_estado() {
+        let headers = Headers::parsear(b"NATS/1.0 404 No Messages\r\n\r\n").unwrap();
+        assert_eq!(headers.estado, Some(404));
+        assert_eq!(headers.descripcion, Some("No Messages".to_string()));
+
+        let headers = Headers::parsear(b"NATS/1.0 100\r\n\r\n").unwrap();
+        assert_eq!(headers.estado, Some(100));
+        assert_eq!(headers.descripcion, None);
+
+        assert!(Headers::parsear(b"HTTP/1.1 200 OK\r\n\r\n").is_none());
+    }
+}
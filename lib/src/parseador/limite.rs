@@ -0,0 +1,21 @@
+use std::fmt::Display;
+
+/// Límites del protocolo que un mensaje superó. Después de uno de estos errores
+/// el parser descarta todo lo que recibe y hay que cerrar la conexión
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimiteExcedido {
+    /// El payload de un `PUB` o `HPUB` es más grande que `max_payload`
+    Payload,
+    /// La línea de control es más larga que `max_control_line`
+    LineaDeControl,
+}
+
+impl Display for LimiteExcedido {
+    /// El mensaje de error del protocolo
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimiteExcedido::Payload => write!(f, "'Maximum Payload Violation'"),
+            LimiteExcedido::LineaDeControl => write!(f, "'Maximum Control Line Exceeded'"),
+        }
+    }
+}
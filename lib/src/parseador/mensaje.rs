@@ -1,4 +1,7 @@
-use super::{parametros_conectar::ParametrosConectar, parametros_info::ParametrosInfo};
+use super::{
+    limite::LimiteExcedido, parametros_conectar::ParametrosConectar,
+    parametros_info::ParametrosInfo,
+};
 
 #[derive(Debug)]
 
@@ -14,6 +17,8 @@ pub enum Mensaje {
     Desuscribir(String, Option<u64>),
     // Mensaje de error (cuando no se pudo parsear el mensaje)
     Error(String),
+    // Un mensaje superó los límites del parser, no se puede seguir leyendo
+    LimiteExcedido(LimiteExcedido),
     // Mensaje para generar la conexión
     Conectar(ParametrosConectar),
     // Mensaje para preservar la conexión
@@ -30,14 +35,13 @@ pub enum Mensaje {
 
 /// Formatea el payload de la publicación
 pub fn formatear_payload_debug(payload: &[u8]) -> String {
-    let mut str = String::from_utf8_lossy(payload).to_string();
+    let str = String::from_utf8_lossy(payload);
 
-    if str.len() > 100 {
-        str.truncate(100);
-        str.push_str("...");
+    // Se corta por caracteres, un payload binario puede tener caracteres de varios bytes
+    match str.char_indices().nth(100) {
+        Some((fin, _)) => format!("{}...", &str[..fin]),
+        None => str.to_string(),
     }
-
-    str
 }
 /// Formatea los mensajes de publicación
 pub fn formatear_mensaje_debug(mensaje: &Mensaje) -> String {
@@ -1,8 +1,11 @@
+pub mod headers;
+pub mod limite;
 pub mod mensaje;
 pub mod parametros_conectar;
 pub mod parametros_info;
 mod resultado_linea;
 
+use self::limite::LimiteExcedido;
 use self::mensaje::Mensaje;
 use self::parametros_conectar::ParametrosConectar;
 use self::parametros_info::ParametrosInfo;
@@ -15,8 +18,12 @@ pub struct Parseador {
     continuar_en_indice: usize,
     /// La primera linea del mensaje que se está parseando (ejemplo: se encontró un PUB y falta leer el payload)
     actual: Option<ResultadoLinea>,
-    /// Los headers del mensaje que se está parseando ahora
-    header: Option<Vec<u8>>,
+    /// Bytes que puede tener como máximo el payload de un mensaje
+    max_payload: Option<usize>,
+    /// Largo máximo de la línea de control (la primera línea de cada mensaje)
+    max_control_line: Option<usize>,
+    /// Límite que se superó, a partir de ahí se ignoran los bytes recibidos
+    limite_excedido: Option<LimiteExcedido>,
 }
 
 /// La responsabilidad del parser es recibir bytes de la conexión y tranformarlos a mensajes
@@ -41,12 +48,29 @@ impl Parseador {
             bytes_pendientes: Vec::new(),
             continuar_en_indice: 0,
             actual: None,
-            header: None,
+            max_payload: None,
+            max_control_line: None,
+            limite_excedido: None,
+        }
+    }
+
+    /// Parser que rechaza los mensajes con un payload de más de `max_payload`
+    /// bytes o con una línea de control de más de `max_control_line` bytes, para
+    /// no acumular bytes sin límite
+    pub fn con_limites(max_payload: usize, max_control_line: usize) -> Self {
+        Self {
+            max_payload: Some(max_payload),
+            max_control_line: Some(max_control_line),
+            ..Self::new()
         }
     }
 
     /// Agrega bytes al parser
     pub fn agregar_bytes(&mut self, bytes: &[u8]) {
+        if self.limite_excedido.is_some() {
+            return;
+        }
+
         self.bytes_pendientes.extend_from_slice(bytes);
     }
 
@@ -76,6 +100,10 @@ impl Parseador {
     ///
     /// Para cada caso, avanza en la lectura de los bytes y devuelve el mensaje correspondiente
     pub fn proximo_mensaje(&mut self) -> Option<Mensaje> {
+        if self.limite_excedido.is_some() {
+            return None;
+        }
+
         // Si actualmente se está parseando un PUB buscamos el payload
         if let Some(ResultadoLinea::Pub(topic, reply_to, total_bytes)) = &self.actual {
             // No hay suficientes bytes para el payload
@@ -97,43 +125,24 @@ impl Parseador {
             return resultado;
         }
 
-        // Si actualmente se está parseando un HPUB buscamos el payload
+        // Si actualmente se está parseando un HPUB buscamos los headers y el payload
         if let Some(ResultadoLinea::Hpub(topic, reply_to, headers_bytes, total_bytes)) =
             &self.actual
         {
-            let bytes_totales_con_salto_de_linea = *total_bytes + 2;
-
-            // Si ya habíamos encontrado los headers antes,
-            // tenemos todo para buscar el payload y si está completo devolver el mensaje
-            if let Some(headers) = &self.header {
-                // No hay suficientes bytes para el payload
-                if self.bytes_pendientes.len() < bytes_totales_con_salto_de_linea {
-                    return None;
-                }
+            let (headers, payload) = self.headers_y_payload(*headers_bytes, *total_bytes)?;
 
-                self.continuar_en_indice = bytes_totales_con_salto_de_linea;
-
-                // Si hay suficientes bytes para el payload, devolvemos el mensaje
-                let resultado = Some(Mensaje::PublicarConHeader(
-                    topic.to_string(),
-                    reply_to.clone(),
-                    headers.clone(),
-                    self.bytes_pendientes[..*total_bytes].to_vec(),
-                ));
+            let resultado = Some(Mensaje::PublicarConHeader(
+                topic.to_string(),
+                reply_to.clone(),
+                headers,
+                payload,
+            ));
 
-                self.resetear_todo();
+            self.continuar_en_indice = *total_bytes + 2;
 
-                return resultado;
-            } else {
-                // Si no encontramos los headers antes, buscamos los headers
-                if self.bytes_pendientes.len() < *headers_bytes {
-                    return None;
-                }
+            self.resetear_todo();
 
-                self.header = Some(self.bytes_pendientes[..*headers_bytes].to_vec());
-                self.continuar_en_indice = *headers_bytes;
-                return self.proximo_mensaje();
-            }
+            return resultado;
         }
 
         // Si actualmente se está parseando un MSG buscamos el payload
@@ -160,51 +169,48 @@ impl Parseador {
             return resultado;
         }
 
-        // Si actualmente se está parseando un HMSG buscamos el payload
+        // Si actualmente se está parseando un HMSG buscamos los headers y el payload
         if let Some(ResultadoLinea::Hmsg(topic, sid, reply_to, headers_bytes, total_bytes)) =
             &self.actual
         {
-            let bytes_totales_con_salto_de_linea = *total_bytes + 2;
-
-            // Si ya habíamos encontrado los headers antes,
-            // tenemos todo para buscar el payload y si está completo devolver el mensaje
-            if let Some(headers) = &self.header {
-                // No hay suficientes bytes para el payload
-                if self.bytes_pendientes.len() < bytes_totales_con_salto_de_linea {
-                    return None;
-                }
-
-                self.continuar_en_indice = bytes_totales_con_salto_de_linea;
+            let (headers, payload) = self.headers_y_payload(*headers_bytes, *total_bytes)?;
 
-                // Si hay suficientes bytes para el payload, devolvemos el mensaje
-                let resultado = Some(Mensaje::PublicacionConHeader(
-                    topic.to_string(),
-                    sid.to_string(),
-                    reply_to.clone(),
-                    headers.clone(),
-                    self.bytes_pendientes[..*total_bytes].to_vec(),
-                ));
+            let resultado = Some(Mensaje::PublicacionConHeader(
+                topic.to_string(),
+                sid.to_string(),
+                reply_to.clone(),
+                headers,
+                payload,
+            ));
 
-                self.resetear_todo();
+            self.continuar_en_indice = *total_bytes + 2;
 
-                return resultado;
-            } else {
-                // Si no encontramos los headers antes, buscamos los headers
-                if self.bytes_pendientes.len() < *headers_bytes {
-                    return None;
-                }
+            self.resetear_todo();
 
-                self.header = Some(self.bytes_pendientes[..*headers_bytes].to_vec());
-                self.continuar_en_indice = *headers_bytes;
-                return self.proximo_mensaje();
-            }
+            return resultado;
         }
 
         // Si actualmente no se está parseando nada, buscamos la próxima línea
         if self.actual.is_none() {
-            let linea = self.proxima_linea()?;
+            let linea = match self.proxima_linea() {
+                Some(linea) if self.supera(self.max_control_line, linea.len()) => {
+                    return self.exceder(LimiteExcedido::LineaDeControl);
+                }
+                Some(linea) => linea,
+                // Todavía no llegó el salto de línea, pero ya no entra en el límite
+                None if self.supera(self.max_control_line, self.largo_linea_incompleta()) => {
+                    return self.exceder(LimiteExcedido::LineaDeControl);
+                }
+                None => return None,
+            };
 
             match self.parsear_linea(&linea) {
+                ResultadoLinea::Pub(_, _, total_bytes)
+                | ResultadoLinea::Hpub(_, _, _, total_bytes)
+                    if self.supera(self.max_payload, total_bytes) =>
+                {
+                    return self.exceder(LimiteExcedido::Payload);
+                }
                 ResultadoLinea::Hpub(subject, reply_to, header_bytes, total_bytes) => {
                     self.actual = Some(ResultadoLinea::Hpub(
                         subject,
@@ -212,6 +218,7 @@ impl Parseador {
                         header_bytes,
                         total_bytes,
                     ));
+                    return self.proximo_mensaje();
                 }
                 ResultadoLinea::MensajeIncorrecto => {
                     return Some(Mensaje::Error("Mensaje incorrecto".to_string()));
@@ -255,6 +262,7 @@ impl Parseador {
                         bytes_header,
                         bytes_contenido,
                     ));
+                    return self.proximo_mensaje();
                 }
                 ResultadoLinea::Msg(topico, id_suscripcion, responder_a, bytes_contenido) => {
                     self.actual = Some(ResultadoLinea::Msg(
@@ -272,6 +280,26 @@ impl Parseador {
         None
     }
 
+    /// Si `cantidad` supera el límite, en caso de que haya uno
+    fn supera(&self, limite: Option<usize>, cantidad: usize) -> bool {
+        limite.is_some_and(|limite| cantidad > limite)
+    }
+
+    /// Largo de la línea que se está recibiendo, sin el `\r` si ya llegó
+    fn largo_linea_incompleta(&self) -> usize {
+        self.bytes_pendientes.len() - usize::from(self.bytes_pendientes.ends_with(b"\r"))
+    }
+
+    /// Descarta los bytes pendientes y deja de parsear
+    fn exceder(&mut self, limite: LimiteExcedido) -> Option<Mensaje> {
+        self.limite_excedido = Some(limite);
+        self.bytes_pendientes.clear();
+        self.continuar_en_indice = 0;
+        self.actual = None;
+
+        Some(Mensaje::LimiteExcedido(limite))
+    }
+
     /// Parsea una línea y devuelve el tipo de mensaje que es
     fn parsear_linea(&self, linea: &str) -> ResultadoLinea {
         let palabras = linea
@@ -297,7 +325,7 @@ impl Parseador {
             return Self::linea_unsub(&palabras[1..]);
         }
 
-        if primera_palabra.eq("") {
+        if primera_palabra.is_empty() {
             return ResultadoLinea::StringVacio;
         }
 
@@ -373,41 +401,22 @@ impl Parseador {
 
     /// Devuelve si la hpublicación tiene reply_to o no.
     ///
+    /// `HPUB <subject> [reply-to] <#header bytes> <#total bytes>`, donde el total incluye los headers.
     /// Devuelve error si no se pudo parsear
     fn linea_hpub(palabras: &[String]) -> ResultadoLinea {
         // Buscamos si es de 3 o 4 para saber si tiene reply_to
-        if palabras.len() == 3 {
-            let bytes = match palabras[1].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-            let headers_bytes = match palabras[2].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-
-            return ResultadoLinea::Hpub(palabras[0].to_string(), None, headers_bytes, bytes);
-        }
-
-        if palabras.len() == 4 {
-            let bytes = match palabras[2].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-            let headers_bytes = match palabras[3].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-
-            return ResultadoLinea::Hpub(
-                palabras[0].to_string(),
-                Some(palabras[1].to_string()),
-                headers_bytes,
-                bytes,
-            );
+        let reply_to = match palabras.len() {
+            3 => None,
+            4 => Some(palabras[1].to_string()),
+            _ => return ResultadoLinea::MensajeIncorrecto,
+        };
+
+        match Self::bytes_headers_y_total(&palabras[palabras.len() - 2..]) {
+            Some((headers_bytes, bytes)) => {
+                ResultadoLinea::Hpub(palabras[0].to_string(), reply_to, headers_bytes, bytes)
+            }
+            None => ResultadoLinea::MensajeIncorrecto,
         }
-
-        ResultadoLinea::MensajeIncorrecto
     }
 
     /// Ve si la subscripción tiene queue group o no.
@@ -437,12 +446,16 @@ impl Parseador {
 
     /// Verifica que el unsub este correcto, si no devuelve error
     fn linea_unsub(palabras: &[String]) -> ResultadoLinea {
-        if palabras.len() != 1 {
+        if palabras.is_empty() || palabras.len() > 2 {
             return ResultadoLinea::MensajeIncorrecto;
         }
 
         let sid = &palabras[0];
-        let max_msgs = palabras.get(2).map(|s| s.parse::<u64>().unwrap());
+        let max_msgs = match palabras.get(1).map(|s| s.parse::<u64>()) {
+            Some(Ok(max_msgs)) => Some(max_msgs),
+            Some(Err(_)) => return ResultadoLinea::MensajeIncorrecto,
+            None => None,
+        };
 
         ResultadoLinea::Unsub(sid.to_string(), max_msgs)
     }
@@ -485,48 +498,57 @@ impl Parseador {
 
     /// Devuelve si el hmensaje tiene reply_to o no.
     ///
+    /// `HMSG <subject> <sid> [reply-to] <#header bytes> <#total bytes>`, donde el total incluye los headers.
     /// Devuelve error si no se pudo parsear
     fn linea_hmsg(palabras: &[String]) -> ResultadoLinea {
         // Buscamos si es de 4 o 5 para saber si tiene reply_to
-        if palabras.len() == 4 {
-            let bytes = match palabras[3].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-            let headers_bytes = match palabras[2].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-
-            return ResultadoLinea::Hmsg(
+        let reply_to = match palabras.len() {
+            4 => None,
+            5 => Some(palabras[2].to_string()),
+            _ => return ResultadoLinea::MensajeIncorrecto,
+        };
+
+        match Self::bytes_headers_y_total(&palabras[palabras.len() - 2..]) {
+            Some((headers_bytes, bytes)) => ResultadoLinea::Hmsg(
                 palabras[0].to_string(),
                 palabras[1].to_string(),
-                None,
+                reply_to,
                 headers_bytes,
                 bytes,
-            );
+            ),
+            None => ResultadoLinea::MensajeIncorrecto,
         }
+    }
 
-        if palabras.len() == 5 {
-            let bytes = match palabras[4].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
-            let headers_bytes = match palabras[3].parse() {
-                Ok(b) => b,
-                Err(_) => return ResultadoLinea::MensajeIncorrecto,
-            };
+    /// Parsea la cantidad de bytes de los headers y la cantidad total.
+    /// Los headers no pueden ser más largos que el total
+    fn bytes_headers_y_total(palabras: &[String]) -> Option<(usize, usize)> {
+        let headers_bytes = palabras.first()?.parse::<usize>().ok()?;
+        let bytes = palabras.get(1)?.parse::<usize>().ok()?;
 
-            return ResultadoLinea::Hmsg(
-                palabras[0].to_string(),
-                palabras[1].to_string(),
-                Some(palabras[2].to_string()),
-                headers_bytes,
-                bytes,
-            );
+        if headers_bytes > bytes {
+            return None;
         }
 
-        ResultadoLinea::MensajeIncorrecto
+        Some((headers_bytes, bytes))
+    }
+
+    /// Separa los headers del payload una vez que llegaron todos los bytes del mensaje
+    /// (incluyendo el salto de línea final)
+    fn headers_y_payload(
+        &self,
+        headers_bytes: usize,
+        total_bytes: usize,
+    ) -> Option<(Vec<u8>, Vec<u8>)> {
+        // No hay suficientes bytes para los headers, el payload y el salto de línea
+        if self.bytes_pendientes.len() < total_bytes + 2 {
+            return None;
+        }
+
+        Some((
+            self.bytes_pendientes[..headers_bytes].to_vec(),
+            self.bytes_pendientes[headers_bytes..total_bytes].to_vec(),
+        ))
     }
 
     /// Libera los bytes de la parte del mensaje que ya se parseó
@@ -544,13 +566,14 @@ impl Parseador {
     fn resetear_todo(&mut self) {
         self.resetear_bytes();
         self.actual = None;
-        self.header = None;
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::parseador::resultado_linea::ResultadoLinea;
+    use crate::parseador::{
+        limite::LimiteExcedido, mensaje::Mensaje, resultado_linea::ResultadoLinea,
+    };
 
     #[test]
     fn linea_sub() {
@@ -574,4 +597,106 @@ mod tests {
         let resultado = parser.parsear_linea("sub");
         assert_eq!(resultado, ResultadoLinea::MensajeIncorrecto);
     }
+
+    #[test]
+    fn linea_unsub() {
+        let parser = super::Parseador::new();
+        let resultado = parser.parsear_linea("unsub sid");
+        assert_eq!(resultado, ResultadoLinea::Unsub("sid".to_string(), None));
+
+        let resultado = parser.parsear_linea("UNSUB sid 1");
+        assert_eq!(resultado, ResultadoLinea::Unsub("sid".to_string(), Some(1)));
+
+        let resultado = parser.parsear_linea("unsub sid uno");
+        assert_eq!(resultado, ResultadoLinea::MensajeIncorrecto);
+
+        let resultado = parser.parsear_linea("unsub");
+        assert_eq!(resultado, ResultadoLinea::MensajeIncorrecto);
+    }
+
+    #[test]
+    fn linea_hpub() {
+        let parser = super::Parseador::new();
+        let resultado = parser.parsear_linea("HPUB subject 12 17");
+        assert_eq!(
+            resultado,
+            ResultadoLinea::Hpub("subject".to_string(), None, 12, 17)
+        );
+
+        let resultado = parser.parsear_linea("HPUB subject reply 12 17");
+        assert_eq!(
+            resultado,
+            ResultadoLinea::Hpub("subject".to_string(), Some("reply".to_string()), 12, 17)
+        );
+
+        let resultado = parser.parsear_linea("HPUB subject 17 12");
+        assert_eq!(resultado, ResultadoLinea::MensajeIncorrecto);
+    }
+
+    #[test]
+    fn payload_maximo() {
+        let mut parser = super::Parseador::con_limites(5, 1024);
+        parser.agregar_bytes(b"PUB a 5\r\nhola!\r\nPUB a 6\r\n");
+
+        assert!(matches!(
+            parser.proximo_mensaje(),
+            Some(Mensaje::Publicar(..))
+        ));
+        assert!(matches!(
+            parser.proximo_mensaje(),
+            Some(Mensaje::LimiteExcedido(LimiteExcedido::Payload))
+        ));
+
+        // Después del error no se lee nada más
+        parser.agregar_bytes(b"PING\r\n");
+        assert!(parser.proximo_mensaje().is_none());
+    }
+
+    #[test]
+    fn linea_de_control_maxima() {
+        let mut parser = super::Parseador::con_limites(1024, 10);
+        parser.agregar_bytes(b"SUB a 1\r\n");
+        assert!(matches!(
+            parser.proximo_mensaje(),
+            Some(Mensaje::Suscribir(..))
+        ));
+
+        // Sin salto de línea no se acumulan más bytes que el límite
+        parser.agregar_bytes(b"SUB abcdefghijk");
+        assert!(matches!(
+            parser.proximo_mensaje(),
+            Some(Mensaje::LimiteExcedido(LimiteExcedido::LineaDeControl))
+        ));
+
+        let mut parser = super::Parseador::con_limites(1024, 10);
+        parser.agregar_bytes(b"SUB abcdefg 1\r\n");
+        assert!(matches!(
+            parser.proximo_mensaje(),
+            Some(Mensaje::LimiteExcedido(LimiteExcedido::LineaDeControl))
+        ));
+    }
+
+    #[test]
+    fn mensaje_con_headers() {
+        let mut parser = super::Parseador::new();
+        parser.agregar_bytes(b"HMSG subject 1 reply 12 17\r\nNATS/1.0\r\n\r\nhola!");
+
+        // Todavía falta el salto de línea final
+        assert!(parser.proximo_mensaje().is_none());
+
+        parser.agregar_bytes(b"\r\nPING\r\n");
+
+        match parser.proximo_mensaje() {
+            Some(Mensaje::PublicacionConHeader(topico, sid, reply_to, headers, payload)) => {
+                assert_eq!(topico, "subject");
+                assert_eq!(sid, "1");
+                assert_eq!(reply_to, Some("reply".to_string()));
+                assert_eq!(headers, b"NATS/1.0\r\n\r\n");
+                assert_eq!(payload, b"hola!");
+            }
+            otro => panic!("Mensaje inesperado: {:?}", otro),
+        }
+
+        assert!(matches!(parser.proximo_mensaje(), Some(Mensaje::Ping())));
+    }
 }
@@ -51,7 +51,6 @@ impl ParametrosConectar {
 }
 
 #[cfg(test)]
-
 mod tests {
     use super::ParametrosConectar;
 
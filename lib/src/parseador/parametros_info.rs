@@ -21,7 +21,6 @@ impl ParametrosInfo {
 }
 
 #[cfg(test)]
-
 mod tests {
     use super::ParametrosInfo;
 
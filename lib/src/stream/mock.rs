@@ -29,6 +29,11 @@ impl Read for MockStream {
             self.bytes_read.extend_from_slice(&bytes);
         }
 
+        // Como un socket no bloqueante sin datos
+        if self.bytes_read.is_empty() {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+
         let len = std::cmp::min(buf.len(), self.bytes_read.len());
         buf[..len].copy_from_slice(&self.bytes_read[..len]);
         self.bytes_read = self.bytes_read.split_off(len);
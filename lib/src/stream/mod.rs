@@ -9,9 +9,21 @@ use std::{
 use native_tls::TlsStream;
 
 pub trait Stream: Read + Write + Send {
-    // Add methods here if you need more than what's provided by Read and Write
+    /// Socket TCP sobre el que está el stream, para poder esperar a que tenga
+    /// datos sin leerlo
+    fn socket(&self) -> Option<&TcpStream> {
+        None
+    }
 }
 
-impl Stream for TcpStream {}
+impl Stream for TcpStream {
+    fn socket(&self) -> Option<&TcpStream> {
+        Some(self)
+    }
+}
 
-impl Stream for TlsStream<TcpStream> {}
+impl Stream for TlsStream<TcpStream> {
+    fn socket(&self) -> Option<&TcpStream> {
+        Some(self.get_ref())
+    }
+}
@@ -69,6 +69,16 @@ impl HiloCliente {
         Ok(conectado)
     }
 
+    /// Si la suscripción ya se soltó pero todavía no se procesó su instrucción de
+    /// desuscribir, el mensaje se descarta
+    fn enviar_a_suscripcion(&mut self, id_suscripcion: &str, publicacion: Publicacion) {
+        if let Some(canal) = self.canales_subscripciones.get(id_suscripcion) {
+            if canal.send(publicacion).is_err() {
+                self.canales_subscripciones.remove(id_suscripcion);
+            }
+        }
+    }
+
     fn gestionar_nuevo_mensaje(&mut self, mensaje: Mensaje) -> std::io::Result<()> {
         match mensaje {
             // Ejemplo: MSG 1 4\r\nhola\r\n
@@ -80,11 +90,24 @@ impl HiloCliente {
                     subject: topico,
                 };
 
-                if let Some(canal) = self.canales_subscripciones.get(&id_suscripcion) {
-                    if let Err(e) = canal.send(publicacion) {
-                        return Err(std::io::Error::new(std::io::ErrorKind::Other, e));
-                    }
-                }
+                self.enviar_a_suscripcion(&id_suscripcion, publicacion);
+            }
+            // Ejemplo: HMSG 1 1 16 20\r\nNATS/1.0\r\nA: b\r\n\r\nhola\r\n
+            Mensaje::PublicacionConHeader(
+                topico,
+                id_suscripcion,
+                responder_a,
+                headers,
+                contenido,
+            ) => {
+                let publicacion = Publicacion {
+                    header: Some(headers),
+                    payload: contenido,
+                    reply_to: responder_a,
+                    subject: topico,
+                };
+
+                self.enviar_a_suscripcion(&id_suscripcion, publicacion);
             }
             // Ejemplo: INFO {"server_id":"a","version":"2.1.0","go":"go1.15.6","host":"...
             Mensaje::Info(parametros) => {
@@ -154,61 +177,37 @@ impl HiloCliente {
                     .write_all(format!("UNSUB {}\r\n", id_suscripcion).as_bytes())?;
             }
             Instruccion::Publicar(publicacion) => {
-                if let Some(reply_to) = publicacion.reply_to {
-                    if let Some(header) = &publicacion.header {
-                        self.stream.write_all(
-                            format!(
-                                "PUB {} {} {} {}\r\n",
-                                publicacion.subject,
-                                reply_to,
-                                header.len(),
-                                publicacion.payload.len()
-                            )
-                            .as_bytes(),
-                        )?;
-                        self.stream.write_all(header)?;
-                        self.stream.write_all(b"\r\n")?;
-                        self.stream.write_all(&publicacion.payload)?;
-                        self.stream.write_all(b"\r\n")?;
-                    } else {
-                        self.stream.write_all(
-                            format!(
-                                "PUB {} {} {}\r\n",
-                                publicacion.subject,
-                                reply_to,
-                                publicacion.payload.len()
-                            )
-                            .as_bytes(),
-                        )?;
-                        self.stream.write_all(&publicacion.payload)?;
-                        self.stream.write_all(b"\r\n")?;
-                    }
-                } else if let Some(header) = &publicacion.header {
+                let reply_to = match &publicacion.reply_to {
+                    Some(reply_to) => format!(" {}", reply_to),
+                    None => "".to_string(),
+                };
+
+                if let Some(header) = &publicacion.header {
+                    // HPUB <subject> [reply-to] <#header bytes> <#total bytes>
                     self.stream.write_all(
                         format!(
-                            "HPUB {} {} {}\r\n",
+                            "HPUB {}{} {} {}\r\n",
                             publicacion.subject,
+                            reply_to,
                             header.len(),
-                            publicacion.payload.len()
+                            header.len() + publicacion.payload.len()
                         )
                         .as_bytes(),
                     )?;
                     self.stream.write_all(header)?;
-                    self.stream.write_all(b"\r\n")?;
-                    self.stream.write_all(&publicacion.payload)?;
-                    self.stream.write_all(b"\r\n")?;
                 } else {
                     self.stream.write_all(
                         format!(
-                            "PUB {} {}\r\n",
+                            "PUB {}{} {}\r\n",
                             publicacion.subject,
+                            reply_to,
                             publicacion.payload.len()
                         )
                         .as_bytes(),
                     )?;
-                    self.stream.write_all(&publicacion.payload)?;
-                    self.stream.write_all(b"\r\n")?;
                 }
+                self.stream.write_all(&publicacion.payload)?;
+                self.stream.write_all(b"\r\n")?;
             }
             Instruccion::Desconectar => {
                 return Ok(false);
@@ -1,3 +1,5 @@
+pub const JS_API_INFO: &str = "$JS.API.INFO";
+
 pub fn js_api_stream_create(name: &str) -> String {
     format!("$JS.API.STREAM.CREATE.{}", name)
 }
@@ -12,3 +14,31 @@ pub fn js_api_consumer_next(stream_name: &str, consumer_name: &str) -> String {
         stream_name, consumer_name
     )
 }
+
+pub fn js_api_consumer_delete(stream_name: &str, consumer_name: &str) -> String {
+    format!("$JS.API.CONSUMER.DELETE.{}.{}", stream_name, consumer_name)
+}
+
+pub fn js_api_direct_get(stream_name: &str) -> String {
+    format!("$JS.API.DIRECT.GET.{}", stream_name)
+}
+
+pub fn js_api_stream_purge(stream_name: &str) -> String {
+    format!("$JS.API.STREAM.PURGE.{}", stream_name)
+}
+
+pub fn js_api_stream_msg_get(stream_name: &str) -> String {
+    format!("$JS.API.STREAM.MSG.GET.{}", stream_name)
+}
+
+pub fn js_api_stream_msg_delete(stream_name: &str) -> String {
+    format!("$JS.API.STREAM.MSG.DELETE.{}", stream_name)
+}
+
+pub fn js_api_stream_snapshot(stream_name: &str) -> String {
+    format!("$JS.API.STREAM.SNAPSHOT.{}", stream_name)
+}
+
+pub fn js_api_stream_restore(stream_name: &str) -> String {
+    format!("$JS.API.STREAM.RESTORE.{}", stream_name)
+}
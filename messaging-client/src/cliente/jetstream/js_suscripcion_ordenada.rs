@@ -0,0 +1,100 @@
+use std::{io, time::Duration};
+
+use lib::parseador::headers::Headers;
+
+use crate::cliente::{publicacion::Publicacion, suscripcion::Suscripcion};
+
+use super::{constantes::js_api_consumer_delete, JetStream};
+
+/// Suscripción a un consumer push creado solo para este cliente, que recibe los
+/// mensajes del stream en orden y sin ack. Los mensajes de control del servidor
+/// (latidos y control de flujo) se procesan acá y no se devuelven al leer
+pub struct JSSuscripcionOrdenada {
+    pub stream_nombre: String,
+    pub consumer_nombre: String,
+    pub js: JetStream,
+    pub suscripcion: Suscripcion,
+}
+
+impl JSSuscripcionOrdenada {
+    pub fn new(
+        js: JetStream,
+        stream_nombre: String,
+        consumer_nombre: String,
+        suscripcion: Suscripcion,
+    ) -> Self {
+        Self {
+            stream_nombre,
+            consumer_nombre,
+            js,
+            suscripcion,
+        }
+    }
+
+    pub fn leer(&self) -> io::Result<Publicacion> {
+        loop {
+            let publicacion = self.suscripcion.leer()?;
+            if let Some(publicacion) = self.procesar(publicacion)? {
+                return Ok(publicacion);
+            }
+        }
+    }
+
+    pub fn intentar_leer(&self) -> io::Result<Option<Publicacion>> {
+        while let Some(publicacion) = self.suscripcion.intentar_leer()? {
+            if let Some(publicacion) = self.procesar(publicacion)? {
+                return Ok(Some(publicacion));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn leer_con_limite_de_tiempo(&self, limite: Duration) -> io::Result<Option<Publicacion>> {
+        while let Some(publicacion) = self.suscripcion.leer_con_limite_de_tiempo(limite)? {
+            if let Some(publicacion) = self.procesar(publicacion)? {
+                return Ok(Some(publicacion));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Devuelve `None` si la publicación es un mensaje de control del servidor
+    fn procesar(&self, publicacion: Publicacion) -> io::Result<Option<Publicacion>> {
+        let estado = publicacion
+            .header
+            .as_deref()
+            .and_then(Headers::parsear)
+            .and_then(|headers| headers.estado);
+
+        if estado != Some(100) {
+            return Ok(Some(publicacion));
+        }
+
+        // Los pedidos de control de flujo tienen reply, los latidos no
+        if let Some(reply_to) = &publicacion.reply_to {
+            self.js.cliente.publicar(reply_to, b"", None)?;
+        }
+
+        Ok(None)
+    }
+}
+
+impl Drop for JSSuscripcionOrdenada {
+    fn drop(&mut self) {
+        let _ = self.js.cliente.publicar(
+            &js_api_consumer_delete(&self.stream_nombre, &self.consumer_nombre),
+            b"",
+            None,
+        );
+    }
+}
+
+impl Iterator for JSSuscripcionOrdenada {
+    type Item = io::Result<Publicacion>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.leer())
+    }
+}
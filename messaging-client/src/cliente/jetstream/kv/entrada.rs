@@ -0,0 +1,137 @@
+use lib::parseador::headers::Headers;
+
+use crate::cliente::publicacion::Publicacion;
+
+use super::{prefijo_topicos, HEADER_OPERACION};
+
+/// Qué representa una revisión de una clave
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperacionKV {
+    Put,
+    /// Marca de borrado, la clave conserva su historial
+    Delete,
+    /// Marca de borrado que eliminó las revisiones anteriores
+    Purge,
+}
+
+impl OperacionKV {
+    fn desde_headers(headers: Option<&Headers>) -> Self {
+        match headers.and_then(|headers| headers.obtener(HEADER_OPERACION)) {
+            Some("DEL") => OperacionKV::Delete,
+            Some("PURGE") => OperacionKV::Purge,
+            _ => OperacionKV::Put,
+        }
+    }
+}
+
+/// Una revisión de una clave de un bucket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntradaKV {
+    pub bucket: String,
+    pub clave: String,
+    pub valor: Vec<u8>,
+    /// Secuencia del mensaje en el stream del bucket
+    pub revision: u64,
+    pub operacion: OperacionKV,
+}
+
+impl EntradaKV {
+    /// Arma la entrada a partir de la respuesta de `$JS.API.DIRECT.GET`, que
+    /// indica el tópico y la secuencia en los headers
+    pub(super) fn desde_respuesta_directa(bucket: &str, publicacion: Publicacion) -> Option<Self> {
+        let headers = Headers::parsear(publicacion.header.as_deref()?)?;
+        let clave = headers
+            .obtener("Nats-Subject")?
+            .strip_prefix(&prefijo_topicos(bucket))?
+            .to_string();
+        let revision = headers.obtener("Nats-Sequence")?.parse().ok()?;
+
+        Some(Self {
+            bucket: bucket.to_string(),
+            clave,
+            valor: publicacion.payload,
+            revision,
+            operacion: OperacionKV::desde_headers(Some(&headers)),
+        })
+    }
+
+    /// Arma la entrada a partir de un mensaje entregado por un consumer. La
+    /// revisión es la secuencia del stream que viene en el tópico de ack
+    pub(super) fn desde_mensaje_consumer(bucket: &str, publicacion: Publicacion) -> Option<Self> {
+        // $JS.ACK.<stream>.<consumer>.<entregas>.<secuencia stream>...
+        let revision = publicacion
+            .reply_to
+            .as_deref()?
+            .split('.')
+            .nth(5)?
+            .parse()
+            .ok()?;
+        let clave = publicacion
+            .subject
+            .strip_prefix(&prefijo_topicos(bucket))?
+            .to_string();
+        let headers = publicacion.header.as_deref().and_then(Headers::parsear);
+
+        Some(Self {
+            bucket: bucket.to_string(),
+            clave,
+            valor: publicacion.payload,
+            revision,
+            operacion: OperacionKV::desde_headers(headers.as_ref()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lib::parseador::headers::Headers;
+
+    use crate::cliente::publicacion::Publicacion;
+
+    use super::{EntradaKV, OperacionKV};
+
+    #[test]
+    fn entrada_desde_mensaje_consumer() {
+        let mut headers = Headers::new();
+        headers.insertar("KV-Operation", "DEL");
+
+        let entrada = EntradaKV::desde_mensaje_consumer(
+            "drones",
+            Publicacion {
+                subject: "$KV.drones.dron.1".to_string(),
+                reply_to: Some("$JS.ACK.KV_drones.abc.1.42.3.1700000000.0".to_string()),
+                payload: Vec::new(),
+                header: Some(headers.serializar()),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(entrada.clave, "dron.1");
+        assert_eq!(entrada.revision, 42);
+        assert_eq!(entrada.operacion, OperacionKV::Delete);
+    }
+
+    #[test]
+    fn entrada_desde_respuesta_directa() {
+        let mut headers = Headers::new();
+        headers.insertar("Nats-Stream", "KV_drones");
+        headers.insertar("Nats-Subject", "$KV.drones.dron.1");
+        headers.insertar("Nats-Sequence", "7");
+
+        let entrada = EntradaKV::desde_respuesta_directa(
+            "drones",
+            Publicacion {
+                subject: "_INBOX.abc".to_string(),
+                reply_to: None,
+                payload: b"valor".to_vec(),
+                header: Some(headers.serializar()),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(entrada.clave, "dron.1");
+        assert_eq!(entrada.valor, b"valor");
+        assert_eq!(entrada.revision, 7);
+        assert_eq!(entrada.operacion, OperacionKV::Put);
+    }
+}
@@ -0,0 +1,348 @@
+use std::{io, time::Duration};
+
+use entrada::{EntradaKV, OperacionKV};
+use lib::{
+    jet_stream::{
+        consumer_config::DeliverPolicy,
+        stream_config::{DiscardPolicy, StreamConfig},
+    },
+    parseador::headers::Headers,
+};
+use observador::ObservadorKV;
+
+use super::{constantes::js_api_direct_get, JetStream};
+
+pub mod entrada;
+pub mod observador;
+
+/// Header que indica si el mensaje es un valor o una marca de borrado
+const HEADER_OPERACION: &str = "KV-Operation";
+
+/// Cuánto se espera la respuesta del servidor en cada operación
+const TIEMPO_LIMITE_KV: Duration = Duration::from_secs(5);
+
+/// Ventana de duplicados del stream de un bucket, salvo que el ttl sea menor
+const VENTANA_DUPLICADOS_KV: Duration = Duration::from_secs(120);
+
+/// Nombre del stream donde se guarda un bucket
+pub fn nombre_stream(bucket: &str) -> String {
+    format!("KV_{}", bucket)
+}
+
+/// Las claves del bucket son los tópicos `$KV.<bucket>.<clave>`
+fn prefijo_topicos(bucket: &str) -> String {
+    format!("$KV.{}.", bucket)
+}
+
+/// Configuración de un bucket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigKV {
+    /// Solo letras, números, `-` y `_`
+    pub bucket: String,
+    pub description: Option<String>,
+    /// Cuántas revisiones se guardan por clave
+    pub history: i64,
+    /// Tiempo que se conserva cada revisión, cero para siempre
+    pub ttl: Duration,
+    /// Tamaño máximo del bucket, cero para ilimitado
+    pub max_bytes: i64,
+    /// Tamaño máximo de un valor, cero para ilimitado
+    pub max_value_size: i32,
+}
+
+impl Default for ConfigKV {
+    fn default() -> Self {
+        Self {
+            bucket: String::new(),
+            description: None,
+            history: 1,
+            ttl: Duration::ZERO,
+            max_bytes: 0,
+            max_value_size: 0,
+        }
+    }
+}
+
+impl ConfigKV {
+    pub fn stream_config(&self) -> StreamConfig {
+        let duplicate_window = if !self.ttl.is_zero() && self.ttl < VENTANA_DUPLICADOS_KV {
+            self.ttl
+        } else {
+            VENTANA_DUPLICADOS_KV
+        };
+
+        StreamConfig {
+            name: nombre_stream(&self.bucket),
+            description: self.description.clone(),
+            subjects: vec![format!("{}>", prefijo_topicos(&self.bucket))],
+            max_msgs_per_subject: self.history.max(1),
+            max_bytes: self.max_bytes,
+            max_age: self.ttl,
+            max_msg_size: self.max_value_size,
+            max_consumers: -1,
+            discard: DiscardPolicy::New,
+            duplicate_window,
+            allow_direct: true,
+            allow_rollup_hdrs: true,
+            ..Default::default()
+        }
+    }
+
+    fn bucket_valido(&self) -> bool {
+        !self.bucket.is_empty()
+            && self
+                .bucket
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }
+}
+
+/// Almacenamiento clave-valor sobre un stream de JetStream. Cada clave es un
+/// tópico del stream y cada valor guardado es una revisión, identificada por
+/// su secuencia en el stream
+pub struct KeyValue {
+    js: JetStream,
+    bucket: String,
+    nombre_stream: String,
+}
+
+impl KeyValue {
+    pub(super) fn new(js: JetStream, bucket: &str) -> Self {
+        Self {
+            js,
+            bucket: bucket.to_string(),
+            nombre_stream: nombre_stream(bucket),
+        }
+    }
+
+    pub(super) fn crear(mut js: JetStream, config: &ConfigKV) -> io::Result<Self> {
+        if !config.bucket_valido() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Nombre de bucket inválido: {}", config.bucket),
+            ));
+        }
+
+        js.crear_stream(&config.stream_config())?;
+        Ok(Self::new(js, &config.bucket))
+    }
+
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// Último valor de la clave, `None` si no existe o se borró
+    pub fn get(&mut self, clave: &str) -> io::Result<Option<EntradaKV>> {
+        Ok(self
+            .ultima_entrada(clave)?
+            .filter(|entrada| entrada.operacion == OperacionKV::Put))
+    }
+
+    /// Guarda el valor y devuelve la revisión
+    pub fn put(&mut self, clave: &str, valor: &[u8]) -> io::Result<u64> {
+        self.publicar(clave, valor, Headers::new())
+    }
+
+    /// Guarda el valor solo si la clave no existe o está borrada
+    pub fn create(&mut self, clave: &str, valor: &[u8]) -> io::Result<u64> {
+        let error = match self.update(clave, valor, 0) {
+            Ok(revision) => return Ok(revision),
+            Err(error) if error.kind() == io::ErrorKind::Other => error,
+            Err(error) => return Err(error),
+        };
+
+        // Si la última revisión es una marca de borrado, la clave se puede volver a crear
+        match self.ultima_entrada(clave)? {
+            Some(entrada) if entrada.operacion != OperacionKV::Put => {
+                self.update(clave, valor, entrada.revision)
+            }
+            _ => Err(error),
+        }
+    }
+
+    /// Guarda el valor solo si la última revisión de la clave es `revision`
+    pub fn update(&mut self, clave: &str, valor: &[u8], revision: u64) -> io::Result<u64> {
+        let mut headers = Headers::new();
+        headers.insertar("Nats-Expected-Last-Subject-Sequence", &revision.to_string());
+        self.publicar(clave, valor, headers)
+    }
+
+    /// Borra la clave conservando su historial
+    pub fn delete(&mut self, clave: &str) -> io::Result<()> {
+        let mut headers = Headers::new();
+        headers.insertar(HEADER_OPERACION, "DEL");
+        self.publicar(clave, b"", headers)?;
+        Ok(())
+    }
+
+    /// Borra la clave y todas sus revisiones anteriores
+    pub fn purge(&mut self, clave: &str) -> io::Result<()> {
+        let mut headers = Headers::new();
+        headers.insertar(HEADER_OPERACION, "PURGE");
+        headers.insertar("Nats-Rollup", "sub");
+        self.publicar(clave, b"", headers)?;
+        Ok(())
+    }
+
+    /// Claves que tienen un valor
+    pub fn keys(&mut self) -> io::Result<Vec<String>> {
+        let filtro = format!("{}>", prefijo_topicos(&self.bucket));
+        let entradas = self.leer_entradas(&filtro, DeliverPolicy::LastPerSubject)?;
+
+        Ok(entradas
+            .into_iter()
+            .filter(|entrada| entrada.operacion == OperacionKV::Put)
+            .map(|entrada| entrada.clave)
+            .collect())
+    }
+
+    /// Revisiones guardadas de la clave, de la más vieja a la más nueva
+    pub fn history(&mut self, clave: &str) -> io::Result<Vec<EntradaKV>> {
+        let topico = self.topico(clave)?;
+        self.leer_entradas(&topico, DeliverPolicy::All)
+    }
+
+    /// Observa las claves que coinciden con `clave`, que puede tener comodines
+    pub fn watch(&mut self, clave: &str) -> io::Result<ObservadorKV> {
+        let filtro = format!("{}{}", prefijo_topicos(&self.bucket), clave);
+        let (suscripcion, _) = self.js.crear_suscripcion_ordenada(
+            &self.nombre_stream,
+            Some(&filtro),
+            DeliverPolicy::LastPerSubject,
+        )?;
+
+        Ok(ObservadorKV::new(self.bucket.clone(), suscripcion))
+    }
+
+    pub fn watch_all(&mut self) -> io::Result<ObservadorKV> {
+        self.watch(">")
+    }
+
+    /// Última revisión de la clave, aunque sea una marca de borrado
+    fn ultima_entrada(&mut self, clave: &str) -> io::Result<Option<EntradaKV>> {
+        let topico = format!(
+            "{}.{}",
+            js_api_direct_get(&self.nombre_stream),
+            self.topico(clave)?
+        );
+        let respuesta = self
+            .js
+            .cliente
+            .peticion_tiempo_limite(&topico, b"", TIEMPO_LIMITE_KV)?
+            .ok_or_else(|| sin_respuesta(&self.bucket))?;
+
+        let estado = respuesta
+            .header
+            .as_deref()
+            .and_then(Headers::parsear)
+            .and_then(|headers| headers.estado);
+
+        match estado {
+            Some(404) => Ok(None),
+            Some(estado) => Err(io::Error::other(format!(
+                "Error al leer la clave {}: {}",
+                clave, estado
+            ))),
+            None => Ok(EntradaKV::desde_respuesta_directa(&self.bucket, respuesta)),
+        }
+    }
+
+    fn publicar(&mut self, clave: &str, valor: &[u8], headers: Headers) -> io::Result<u64> {
+        let topico = self.topico(clave)?;
+        let ack = self.js.publicar_esperando_ack(
+            &topico,
+            valor,
+            Some(&headers.serializar()),
+            TIEMPO_LIMITE_KV,
+            0,
+        )?;
+        Ok(ack.seq)
+    }
+
+    /// Lee los mensajes guardados que entrega un consumer con el filtro y la
+    /// política indicados, sin esperar mensajes nuevos
+    fn leer_entradas(
+        &mut self,
+        filtro: &str,
+        deliver_policy: DeliverPolicy,
+    ) -> io::Result<Vec<EntradaKV>> {
+        let (suscripcion, pendientes) = self.js.crear_suscripcion_ordenada(
+            &self.nombre_stream,
+            Some(filtro),
+            deliver_policy,
+        )?;
+
+        let mut entradas = Vec::new();
+        for _ in 0..pendientes {
+            let publicacion = suscripcion
+                .leer_con_limite_de_tiempo(TIEMPO_LIMITE_KV)?
+                .ok_or_else(|| sin_respuesta(&self.bucket))?;
+
+            if let Some(entrada) = EntradaKV::desde_mensaje_consumer(&self.bucket, publicacion) {
+                entradas.push(entrada);
+            }
+        }
+
+        Ok(entradas)
+    }
+
+    fn topico(&self, clave: &str) -> io::Result<String> {
+        if !clave_valida(clave) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Clave inválida: {}", clave),
+            ));
+        }
+
+        Ok(format!("{}{}", prefijo_topicos(&self.bucket), clave))
+    }
+}
+
+/// Las claves pueden tener letras, números y `-/_=.`, pero no empezar ni terminar con punto
+fn clave_valida(clave: &str) -> bool {
+    !clave.is_empty()
+        && !clave.starts_with('.')
+        && !clave.ends_with('.')
+        && clave
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '/' | '_' | '=' | '.'))
+}
+
+fn sin_respuesta(bucket: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("No se recibió respuesta del bucket {}", bucket),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clave_valida, ConfigKV};
+
+    #[test]
+    fn claves_validas() {
+        assert!(clave_valida("dron.1"));
+        assert!(clave_valida("camaras/estado=activo"));
+        assert!(!clave_valida(""));
+        assert!(!clave_valida(".dron"));
+        assert!(!clave_valida("dron."));
+        assert!(!clave_valida("dron 1"));
+        assert!(!clave_valida("dron.*"));
+    }
+
+    #[test]
+    fn stream_de_un_bucket() {
+        let config = ConfigKV {
+            bucket: "drones".to_string(),
+            history: 5,
+            ..Default::default()
+        };
+
+        let stream = config.stream_config();
+        assert_eq!(stream.name, "KV_drones");
+        assert_eq!(stream.subjects, vec!["$KV.drones.>".to_string()]);
+        assert_eq!(stream.max_msgs_per_subject, 5);
+        assert!(stream.allow_direct);
+        assert!(stream.allow_rollup_hdrs);
+    }
+}
@@ -0,0 +1,58 @@
+use std::{io, time::Duration};
+
+use crate::cliente::jetstream::js_suscripcion_ordenada::JSSuscripcionOrdenada;
+
+use super::entrada::EntradaKV;
+
+/// Recibe los cambios de las claves de un bucket: primero el último valor de
+/// cada clave que coincide y después cada nueva revisión
+pub struct ObservadorKV {
+    bucket: String,
+    suscripcion: JSSuscripcionOrdenada,
+}
+
+impl ObservadorKV {
+    pub(super) fn new(bucket: String, suscripcion: JSSuscripcionOrdenada) -> Self {
+        Self {
+            bucket,
+            suscripcion,
+        }
+    }
+
+    pub fn leer(&self) -> io::Result<EntradaKV> {
+        loop {
+            let publicacion = self.suscripcion.leer()?;
+            if let Some(entrada) = EntradaKV::desde_mensaje_consumer(&self.bucket, publicacion) {
+                return Ok(entrada);
+            }
+        }
+    }
+
+    pub fn intentar_leer(&self) -> io::Result<Option<EntradaKV>> {
+        while let Some(publicacion) = self.suscripcion.intentar_leer()? {
+            if let Some(entrada) = EntradaKV::desde_mensaje_consumer(&self.bucket, publicacion) {
+                return Ok(Some(entrada));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn leer_con_limite_de_tiempo(&self, limite: Duration) -> io::Result<Option<EntradaKV>> {
+        while let Some(publicacion) = self.suscripcion.leer_con_limite_de_tiempo(limite)? {
+            if let Some(entrada) = EntradaKV::desde_mensaje_consumer(&self.bucket, publicacion) {
+                return Ok(Some(entrada));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl Iterator for ObservadorKV {
+    type Item = io::Result<EntradaKV>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.leer())
+    }
+}
@@ -1,16 +1,58 @@
-use std::{io, time::Duration};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
 
-use constantes::{js_api_consumer_create, js_api_consumer_next, js_api_stream_create};
+use constantes::{
+    js_api_consumer_create, js_api_consumer_next, js_api_direct_get, js_api_stream_create,
+    js_api_stream_msg_delete, js_api_stream_msg_get, js_api_stream_purge, js_api_stream_restore,
+    js_api_stream_snapshot, JS_API_INFO,
+};
 use js_suscripcion::JSSuscripcion;
+use js_suscripcion_ordenada::JSSuscripcionOrdenada;
+use kv::{ConfigKV, KeyValue};
 use lib::jet_stream::{
-    consumer_config::ConsumerConfig, crear_consumer_peticion::JSPeticionCrearConsumer,
+    api_info_response::JSApiInfoResponse,
+    consumer_config::{AckPolicy, ConsumerConfig, DeliverPolicy},
+    crear_consumer_peticion::JSPeticionCrearConsumer,
+    crear_consumer_respuesta::JSCrearConsumerRespuesta,
+    crear_stream_respuesta::JSCrearStreamRespuesta,
+    eliminar_mensaje_peticion::JSPeticionEliminarMensaje,
+    eliminar_mensaje_respuesta::JSEliminarMensajeRespuesta,
+    error::JSErrorRespuesta,
+    mensaje_guardado::JSMensajeGuardado,
+    obtener_mensaje_peticion::JSPeticionObtenerMensaje,
+    obtener_mensaje_respuesta::JSObtenerMensajeRespuesta,
+    pub_ack::JSPubAck,
+    purgar_stream_peticion::JSPeticionPurgarStream,
+    purgar_stream_respuesta::JSPurgarStreamRespuesta,
+    restaurar_stream_respuesta::JSRestaurarStreamRespuesta,
+    siguiente_mensaje_peticion::JSPeticionSiguienteMensaje,
+    snapshot_peticion::JSPeticionSnapshot,
+    snapshot_respuesta::JSSnapshotRespuesta,
     stream_config::StreamConfig,
 };
+use lib::parseador::headers::Headers;
+use object_store::{ConfigObjetos, ObjectStore};
 
 use super::{publicacion::Publicacion, suscripcion::Suscripcion, Cliente};
 
 pub mod constantes;
 pub mod js_suscripcion;
+pub mod js_suscripcion_ordenada;
+pub mod kv;
+pub mod object_store;
+
+/// Cada cuánto el servidor envía latidos a una suscripción ordenada sin mensajes
+const LATIDO_SUSCRIPCION_ORDENADA: Duration = Duration::from_secs(5);
+
+/// Cuánto se espera cada bloque de un snapshot, o su confirmación al restaurarlo
+const TIEMPO_LIMITE_SNAPSHOT: Duration = Duration::from_secs(10);
+
+/// Bytes de cada bloque que se envía al restaurar un snapshot
+const TAMANO_BLOQUE_RESTAURACION: usize = 128 * 1024;
 
 #[derive(Clone)]
 pub struct JetStream {
@@ -26,36 +68,447 @@ impl JetStream {
         self.cliente.publicar(subject, data, None)
     }
 
+    /// Publica un mensaje y espera la confirmación del stream que lo guardó.
+    /// Devuelve la secuencia asignada. Si no llega respuesta en `tiempo_limite`
+    /// se vuelve a publicar, hasta `reintentos` veces
+    pub fn publicar_con_ack(
+        &mut self,
+        subject: &str,
+        data: &[u8],
+        tiempo_limite: Duration,
+        reintentos: u32,
+    ) -> io::Result<u64> {
+        let ack = self.publicar_esperando_ack(subject, data, None, tiempo_limite, reintentos)?;
+        Ok(ack.seq)
+    }
+
+    /// Igual que `publicar_con_ack`, pero con un `Nats-Msg-Id`: si el stream ya
+    /// guardó un mensaje con ese id lo descarta y responde con `duplicate: true`.
+    /// Permite reintentar sin que el mensaje quede guardado dos veces
+    pub fn publicar_con_id(
+        &mut self,
+        subject: &str,
+        data: &[u8],
+        id_mensaje: &str,
+        tiempo_limite: Duration,
+        reintentos: u32,
+    ) -> io::Result<JSPubAck> {
+        let mut headers = Headers::new();
+        headers.insertar("Nats-Msg-Id", id_mensaje);
+
+        self.publicar_esperando_ack(
+            subject,
+            data,
+            Some(&headers.serializar()),
+            tiempo_limite,
+            reintentos,
+        )
+    }
+
+    fn publicar_esperando_ack(
+        &mut self,
+        subject: &str,
+        data: &[u8],
+        header: Option<&[u8]>,
+        tiempo_limite: Duration,
+        reintentos: u32,
+    ) -> io::Result<JSPubAck> {
+        for _ in 0..=reintentos {
+            let respuesta = match header {
+                Some(header) => self.cliente.peticion_tiempo_limite_con_header(
+                    subject,
+                    data,
+                    header,
+                    tiempo_limite,
+                )?,
+                None => self
+                    .cliente
+                    .peticion_tiempo_limite(subject, data, tiempo_limite)?,
+            };
+
+            if let Some(respuesta) = respuesta {
+                let json = String::from_utf8_lossy(&respuesta.payload);
+                return JSPubAck::from_json(&json).map_err(|_| {
+                    io::Error::other(format!("El stream rechazó el mensaje: {}", json))
+                });
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "Ningún stream confirmó el mensaje",
+        ))
+    }
+
+    /// Crea el stream. Si ya existe con la misma configuración no hace nada, y si
+    /// tiene otra devuelve el error del servidor
     pub fn crear_stream(&mut self, config: &StreamConfig) -> io::Result<()> {
-        let body = config
+        let body = config.to_json().map_err(io::Error::other)?;
+
+        let respuesta = self
+            .cliente
+            .peticion_tiempo_limite(
+                &js_api_stream_create(&config.name),
+                body.as_bytes(),
+                Duration::from_secs(5),
+            )?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "No se recibió respuesta al crear el stream",
+                )
+            })?;
+
+        let json = String::from_utf8_lossy(&respuesta.payload);
+        match JSCrearStreamRespuesta::from_json(&json) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(io::Error::other(format!(
+                "Error al crear el stream: {}",
+                json
+            ))),
+        }
+    }
+
+    /// Uso de JetStream de la cuenta (bytes guardados, streams, consumers y
+    /// peticiones a la API) y sus límites
+    pub fn informacion_cuenta(&mut self) -> io::Result<JSApiInfoResponse> {
+        let respuesta = self
+            .cliente
+            .peticion_tiempo_limite(JS_API_INFO, b"", Duration::from_secs(5))?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "No se recibió respuesta al pedir la información de la cuenta",
+                )
+            })?;
+
+        let json = String::from_utf8_lossy(&respuesta.payload);
+        JSApiInfoResponse::from_json(&json).map_err(|_| {
+            io::Error::other(format!(
+                "Error al pedir la información de la cuenta: {}",
+                json
+            ))
+        })
+    }
+
+    /// Elimina los mensajes del stream que indica la petición y devuelve cuántos se eliminaron
+    pub fn purgar_stream(
+        &mut self,
+        nombre_stream: &str,
+        peticion: &JSPeticionPurgarStream,
+    ) -> io::Result<u64> {
+        let body = peticion.to_json().map_err(io::Error::other)?;
+
+        let respuesta = self
+            .cliente
+            .peticion_tiempo_limite(
+                &js_api_stream_purge(nombre_stream),
+                body.as_bytes(),
+                Duration::from_secs(5),
+            )?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "No se recibió respuesta al purgar el stream",
+                )
+            })?;
+
+        let json = String::from_utf8_lossy(&respuesta.payload);
+        match JSPurgarStreamRespuesta::from_json(&json) {
+            Ok(respuesta) => Ok(respuesta.purged),
+            Err(_) => Err(io::Error::other(format!(
+                "Error al purgar el stream: {}",
+                json
+            ))),
+        }
+    }
+
+    /// Mensaje guardado en el stream que indica la petición, o `None` si no existe
+    pub fn obtener_mensaje(
+        &mut self,
+        nombre_stream: &str,
+        peticion: &JSPeticionObtenerMensaje,
+    ) -> io::Result<Option<JSMensajeGuardado>> {
+        let body = peticion.to_json().map_err(io::Error::other)?;
+
+        let respuesta = self
+            .cliente
+            .peticion_tiempo_limite(
+                &js_api_stream_msg_get(nombre_stream),
+                body.as_bytes(),
+                Duration::from_secs(5),
+            )?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "No se recibió respuesta al obtener el mensaje",
+                )
+            })?;
+
+        let json = String::from_utf8_lossy(&respuesta.payload);
+        if let Ok(respuesta) = JSObtenerMensajeRespuesta::from_json(&json) {
+            return Ok(Some(respuesta.message));
+        }
+
+        match JSErrorRespuesta::from_json(&json) {
+            Ok(respuesta) if respuesta.error.err_code == 10037 => Ok(None),
+            _ => Err(io::Error::other(format!(
+                "Error al obtener el mensaje: {}",
+                json
+            ))),
+        }
+    }
+
+    /// Igual que `obtener_mensaje`, pero el stream responde con el mensaje tal como
+    /// se publicó, con los headers `Nats-Subject`, `Nats-Sequence` y `Nats-Time-Stamp`.
+    /// El stream tiene que tener `allow_direct`
+    pub fn obtener_mensaje_directo(
+        &mut self,
+        nombre_stream: &str,
+        peticion: &JSPeticionObtenerMensaje,
+    ) -> io::Result<Option<Publicacion>> {
+        let body = peticion.to_json().map_err(io::Error::other)?;
+
+        let respuesta = self
+            .cliente
+            .peticion_tiempo_limite(
+                &js_api_direct_get(nombre_stream),
+                body.as_bytes(),
+                Duration::from_secs(5),
+            )?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "No se recibió respuesta al obtener el mensaje",
+                )
+            })?;
+
+        let estado = respuesta
+            .header
+            .as_deref()
+            .and_then(Headers::parsear)
+            .and_then(|headers| headers.estado);
+
+        match estado {
+            Some(404) => Ok(None),
+            Some(estado) => Err(io::Error::other(format!(
+                "Error al obtener el mensaje: {}",
+                estado
+            ))),
+            None => Ok(Some(respuesta)),
+        }
+    }
+
+    /// Elimina un mensaje del stream. Con `borrar` además se sobrescribe su
+    /// contenido en el disco del servidor
+    pub fn eliminar_mensaje(
+        &mut self,
+        nombre_stream: &str,
+        secuencia: u64,
+        borrar: bool,
+    ) -> io::Result<()> {
+        let body = JSPeticionEliminarMensaje::new(secuencia, borrar)
             .to_json()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            .map_err(io::Error::other)?;
 
-        self.cliente.peticion_tiempo_limite(
-            &js_api_stream_create(&config.name),
-            body.as_bytes(),
-            Duration::from_secs(5),
-        )?;
-        Ok(())
+        let respuesta = self
+            .cliente
+            .peticion_tiempo_limite(
+                &js_api_stream_msg_delete(nombre_stream),
+                body.as_bytes(),
+                Duration::from_secs(5),
+            )?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "No se recibió respuesta al eliminar el mensaje",
+                )
+            })?;
+
+        let json = String::from_utf8_lossy(&respuesta.payload);
+        match JSEliminarMensajeRespuesta::from_json(&json) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(io::Error::other(format!(
+                "Error al eliminar el mensaje: {}",
+                json
+            ))),
+        }
     }
 
+    /// Descarga un snapshot del stream (mensajes y, si se pide, estado de los
+    /// consumers) y lo guarda en `ruta`. Devuelve el estado del stream al momento
+    /// del snapshot
+    pub fn guardar_snapshot(
+        &mut self,
+        nombre_stream: &str,
+        ruta: &Path,
+        incluir_consumers: bool,
+    ) -> io::Result<JSSnapshotRespuesta> {
+        let inbox = self.cliente.nuevo_inbox();
+        let sub = self.cliente.suscribirse(&inbox, None)?;
+
+        let peticion = JSPeticionSnapshot {
+            no_consumers: !incluir_consumers,
+            ..JSPeticionSnapshot::new(&inbox)
+        };
+        let body = peticion.to_json().map_err(io::Error::other)?;
+
+        let respuesta = self
+            .cliente
+            .peticion_tiempo_limite(
+                &js_api_stream_snapshot(nombre_stream),
+                body.as_bytes(),
+                Duration::from_secs(5),
+            )?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "No se recibió respuesta al pedir el snapshot",
+                )
+            })?;
+
+        let json = String::from_utf8_lossy(&respuesta.payload);
+        let respuesta = JSSnapshotRespuesta::from_json(&json)
+            .map_err(|_| io::Error::other(format!("Error al pedir el snapshot: {}", json)))?;
+
+        let mut archivo = BufWriter::new(File::create(ruta)?);
+        loop {
+            let bloque = sub
+                .leer_con_limite_de_tiempo(TIEMPO_LIMITE_SNAPSHOT)?
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "El servidor dejó de enviar el snapshot",
+                    )
+                })?;
+
+            // El final del snapshot es un mensaje vacío
+            if bloque.payload.is_empty() {
+                break;
+            }
+
+            archivo.write_all(&bloque.payload)?;
+            if let Some(reply_to) = &bloque.reply_to {
+                self.cliente.publicar(reply_to, b"", None)?;
+            }
+        }
+        archivo.flush()?;
+
+        Ok(respuesta)
+    }
+
+    /// Crea el stream a partir de un snapshot guardado con `guardar_snapshot`.
+    /// El stream no puede existir, y puede tener otro nombre que el original
+    pub fn restaurar_snapshot(&mut self, nombre_stream: &str, ruta: &Path) -> io::Result<()> {
+        let mut archivo = BufReader::new(File::open(ruta)?);
+
+        let respuesta = self
+            .cliente
+            .peticion_tiempo_limite(
+                &js_api_stream_restore(nombre_stream),
+                b"",
+                Duration::from_secs(5),
+            )?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "No se recibió respuesta al restaurar el stream",
+                )
+            })?;
+
+        let json = String::from_utf8_lossy(&respuesta.payload);
+        let topico_bloques = JSRestaurarStreamRespuesta::from_json(&json)
+            .map_err(|_| io::Error::other(format!("Error al restaurar el stream: {}", json)))?
+            .deliver_subject;
+
+        let mut bloque = vec![0; TAMANO_BLOQUE_RESTAURACION];
+        loop {
+            let leidos = archivo.read(&mut bloque)?;
+
+            // El bloque vacío indica el final, y la respuesta es la del stream creado
+            let respuesta = self
+                .cliente
+                .peticion_tiempo_limite(&topico_bloques, &bloque[..leidos], TIEMPO_LIMITE_SNAPSHOT)?
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "El servidor no confirmó un bloque del snapshot",
+                    )
+                })?;
+
+            if leidos == 0 {
+                let json = String::from_utf8_lossy(&respuesta.payload);
+                return match JSCrearStreamRespuesta::from_json(&json) {
+                    Ok(_) => Ok(()),
+                    Err(_) => Err(io::Error::other(format!(
+                        "Error al restaurar el stream: {}",
+                        json
+                    ))),
+                };
+            }
+        }
+    }
+
+    /// Crea el stream del bucket si no existe
+    pub fn crear_kv(&self, config: &ConfigKV) -> io::Result<KeyValue> {
+        KeyValue::crear(self.clone(), config)
+    }
+
+    /// Bucket ya creado
+    pub fn kv(&self, bucket: &str) -> KeyValue {
+        KeyValue::new(self.clone(), bucket)
+    }
+
+    /// Crea el stream del object store si no existe
+    pub fn crear_object_store(&self, config: &ConfigObjetos) -> io::Result<ObjectStore> {
+        ObjectStore::crear(self.clone(), config)
+    }
+
+    /// Object store ya creado
+    pub fn object_store(&self, bucket: &str) -> ObjectStore {
+        ObjectStore::new(self.clone(), bucket)
+    }
+
+    /// Crea el consumer y devuelve su nombre. Si es efímero y no se indicó un
+    /// nombre, es el que le asignó el servidor
     pub fn crear_consumer(
         &mut self,
         nombre_stream: &str,
         config: ConsumerConfig,
-    ) -> io::Result<()> {
+    ) -> io::Result<String> {
+        Ok(self.crear_consumer_respuesta(nombre_stream, config)?.name)
+    }
+
+    fn crear_consumer_respuesta(
+        &mut self,
+        nombre_stream: &str,
+        config: ConsumerConfig,
+    ) -> io::Result<JSCrearConsumerRespuesta> {
         let peticion = JSPeticionCrearConsumer::new(config);
-        let body = peticion
-            .to_json()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let body = peticion.to_json().map_err(io::Error::other)?;
 
-        self.cliente.peticion_tiempo_limite(
-            &js_api_consumer_create(nombre_stream),
-            body.as_bytes(),
-            Duration::from_secs(5),
-        )?;
+        let respuesta = self
+            .cliente
+            .peticion_tiempo_limite(
+                &js_api_consumer_create(nombre_stream),
+                body.as_bytes(),
+                Duration::from_secs(5),
+            )?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "No se recibió respuesta al crear el consumer",
+                )
+            })?;
 
-        Ok(())
+        let json = String::from_utf8_lossy(&respuesta.payload);
+        match JSCrearConsumerRespuesta::from_json(&json) {
+            Ok(respuesta) => Ok(respuesta),
+            Err(_) => Err(io::Error::other(format!(
+                "Error al crear el consumer: {}",
+                json
+            ))),
+        }
     }
 
     pub fn suscribir_proximo_mensaje(
@@ -74,6 +527,54 @@ impl JetStream {
         Ok(sub)
     }
 
+    /// Pide hasta `batch` mensajes a un consumer pull y espera a lo sumo
+    /// `tiempo_limite`. Devuelve los mensajes recibidos hasta que se completa el
+    /// pedido, vence, o el servidor avisa que no hay más mensajes
+    pub fn fetch(
+        &mut self,
+        stream_name: &str,
+        consumer_name: &str,
+        batch: usize,
+        tiempo_limite: Duration,
+    ) -> io::Result<Vec<Publicacion>> {
+        let topico = js_api_consumer_next(stream_name, consumer_name);
+        let inbox = self.cliente.nuevo_inbox();
+        let sub = self.cliente.suscribirse(&inbox, None)?;
+
+        let mut peticion = JSPeticionSiguienteMensaje::new(batch);
+        peticion.expires = tiempo_limite;
+        let body = peticion.to_json().map_err(io::Error::other)?;
+        self.cliente
+            .publicar(&topico, body.as_bytes(), Some(&inbox))?;
+
+        let limite = Instant::now() + tiempo_limite;
+        let mut mensajes = Vec::new();
+
+        while mensajes.len() < batch {
+            let restante = limite.saturating_duration_since(Instant::now());
+            let publicacion = match sub.leer_con_limite_de_tiempo(restante)? {
+                Some(publicacion) => publicacion,
+                None => break,
+            };
+
+            let estado = publicacion
+                .header
+                .as_deref()
+                .and_then(Headers::parsear)
+                .and_then(|headers| headers.estado);
+
+            match estado {
+                // Latido del servidor, el pedido sigue activo
+                Some(100) => continue,
+                // 404 no hay mensajes, 408 venció el pedido, 409 el pedido no se puede completar
+                Some(_) => break,
+                None => mensajes.push(publicacion),
+            }
+        }
+
+        Ok(mensajes)
+    }
+
     pub fn suscribirse(
         &mut self,
         stream_name: &str,
@@ -86,6 +587,50 @@ impl JetStream {
         ))
     }
 
+    /// Crea un consumer push efímero, sin ack y con control de flujo, y se
+    /// suscribe a su tópico de entrega. El consumer se elimina al soltar la suscripción
+    pub fn suscribirse_ordenado(
+        &mut self,
+        stream_name: &str,
+        filtro: Option<&str>,
+    ) -> io::Result<JSSuscripcionOrdenada> {
+        let (suscripcion, _) =
+            self.crear_suscripcion_ordenada(stream_name, filtro, DeliverPolicy::All)?;
+        Ok(suscripcion)
+    }
+
+    /// Crea la suscripción ordenada con la política de entrega indicada. Devuelve
+    /// también cuántos mensajes guardados va a recibir antes de los nuevos
+    fn crear_suscripcion_ordenada(
+        &mut self,
+        stream_name: &str,
+        filtro: Option<&str>,
+        deliver_policy: DeliverPolicy,
+    ) -> io::Result<(JSSuscripcionOrdenada, u64)> {
+        let inbox = self.cliente.nuevo_inbox();
+
+        // La suscripción tiene que existir antes que el consumer para no perder mensajes
+        let sub = self.cliente.suscribirse(&inbox, None)?;
+
+        let respuesta = self.crear_consumer_respuesta(
+            stream_name,
+            ConsumerConfig {
+                filter_subject: filtro.map(|filtro| filtro.to_string()),
+                deliver_policy,
+                ack_policy: AckPolicy::None,
+                max_deliver: 1,
+                deliver_subject: Some(inbox),
+                flow_control: true,
+                idle_heartbeat: LATIDO_SUSCRIPCION_ORDENADA,
+                ..Default::default()
+            },
+        )?;
+
+        let suscripcion =
+            JSSuscripcionOrdenada::new(self.clone(), stream_name.to_string(), respuesta.name, sub);
+        Ok((suscripcion, respuesta.num_pending))
+    }
+
     pub fn ack(&self, publicacion: &Publicacion) -> io::Result<()> {
         if let Some(reply_to) = &publicacion.reply_to {
             self.cliente.publicar(reply_to, b"", None)?;
@@ -0,0 +1,101 @@
+use std::{
+    io::{self, Read},
+    mem,
+};
+
+use lib::jet_stream::objeto_info::ObjetoInfo;
+use sha2::{Digest, Sha256};
+
+use crate::cliente::jetstream::js_suscripcion_ordenada::JSSuscripcionOrdenada;
+
+use super::{digest, TIEMPO_LIMITE_OBJETOS};
+
+/// Lee el contenido de un objeto a medida que llegan sus chunks. Al terminar
+/// verifica que el contenido coincida con el digest del objeto
+pub struct LectorObjeto {
+    info: ObjetoInfo,
+    /// `None` si el objeto está vacío
+    suscripcion: Option<JSSuscripcionOrdenada>,
+    chunk: Vec<u8>,
+    posicion: usize,
+    chunks_restantes: u64,
+    hash: Sha256,
+    verificado: bool,
+}
+
+impl LectorObjeto {
+    pub(super) fn new(info: ObjetoInfo, suscripcion: Option<JSSuscripcionOrdenada>) -> Self {
+        Self {
+            chunks_restantes: info.chunks,
+            info,
+            suscripcion,
+            chunk: Vec::new(),
+            posicion: 0,
+            hash: Sha256::new(),
+            verificado: false,
+        }
+    }
+
+    pub fn info(&self) -> &ObjetoInfo {
+        &self.info
+    }
+
+    /// Espera el próximo chunk. Devuelve `false` si ya no quedan
+    fn leer_chunk(&mut self) -> io::Result<bool> {
+        let suscripcion = match &self.suscripcion {
+            Some(suscripcion) if self.chunks_restantes > 0 => suscripcion,
+            _ => return Ok(false),
+        };
+
+        let publicacion = suscripcion
+            .leer_con_limite_de_tiempo(TIEMPO_LIMITE_OBJETOS)?
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("No se recibieron los chunks de {}", self.info.name),
+                )
+            })?;
+
+        self.hash.update(&publicacion.payload);
+        self.chunk = publicacion.payload;
+        self.posicion = 0;
+        self.chunks_restantes -= 1;
+        Ok(true)
+    }
+
+    fn verificar(&mut self) -> io::Result<()> {
+        if self.verificado {
+            return Ok(());
+        }
+        self.verificado = true;
+
+        let hash = mem::take(&mut self.hash).finalize();
+        if digest(&hash) != self.info.digest {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "El contenido de {} no coincide con su digest",
+                    self.info.name
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+impl Read for LectorObjeto {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.posicion >= self.chunk.len() {
+            if !self.leer_chunk()? {
+                self.verificar()?;
+                return Ok(0);
+            }
+        }
+
+        let cantidad = buf.len().min(self.chunk.len() - self.posicion);
+        buf[..cantidad].copy_from_slice(&self.chunk[self.posicion..self.posicion + cantidad]);
+        self.posicion += cantidad;
+        Ok(cantidad)
+    }
+}
@@ -0,0 +1,392 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::URL_SAFE, Engine};
+use lector::LectorObjeto;
+use lib::{
+    jet_stream::{
+        consumer_config::DeliverPolicy,
+        objeto_info::ObjetoInfo,
+        purgar_stream_peticion::JSPeticionPurgarStream,
+        stream_config::{DiscardPolicy, StreamConfig},
+    },
+    parseador::headers::Headers,
+};
+use observador::{parsear_info, ObservadorObjetos};
+use sha2::{Digest, Sha256};
+
+use super::{constantes::js_api_direct_get, JetStream};
+
+pub mod lector;
+pub mod observador;
+
+/// Tamaño de los chunks en los que se divide cada objeto
+const TAMANO_CHUNK: usize = 128 * 1024;
+
+/// Cuánto se espera la respuesta del servidor en cada operación
+const TIEMPO_LIMITE_OBJETOS: Duration = Duration::from_secs(5);
+
+/// Nombre del stream donde se guarda un bucket
+pub fn nombre_stream(bucket: &str) -> String {
+    format!("OBJ_{}", bucket)
+}
+
+fn topico_chunks(bucket: &str, nuid: &str) -> String {
+    format!("$O.{}.C.{}", bucket, nuid)
+}
+
+/// El nombre del objeto se codifica para que pueda tener cualquier caracter
+fn topico_metadatos(bucket: &str, nombre: &str) -> String {
+    format!("$O.{}.M.{}", bucket, URL_SAFE.encode(nombre))
+}
+
+fn digest(hash: &[u8]) -> String {
+    format!("SHA-256={}", URL_SAFE.encode(hash))
+}
+
+/// Configuración de un bucket
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigObjetos {
+    /// Solo letras, números, `-` y `_`
+    pub bucket: String,
+    pub description: Option<String>,
+    /// Tiempo que se conserva cada objeto, cero para siempre
+    pub ttl: Duration,
+    /// Tamaño máximo del bucket, cero para ilimitado
+    pub max_bytes: i64,
+}
+
+impl ConfigObjetos {
+    pub fn stream_config(&self) -> StreamConfig {
+        StreamConfig {
+            name: nombre_stream(&self.bucket),
+            description: self.description.clone(),
+            subjects: vec![
+                format!("$O.{}.C.>", self.bucket),
+                format!("$O.{}.M.>", self.bucket),
+            ],
+            max_bytes: self.max_bytes,
+            max_age: self.ttl,
+            max_consumers: -1,
+            discard: DiscardPolicy::New,
+            allow_direct: true,
+            allow_rollup_hdrs: true,
+            ..Default::default()
+        }
+    }
+
+    fn bucket_valido(&self) -> bool {
+        !self.bucket.is_empty()
+            && self
+                .bucket
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    }
+}
+
+/// Datos con los que se guarda un objeto
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjetoMeta {
+    pub name: String,
+    pub description: Option<String>,
+    pub metadata: HashMap<String, String>,
+}
+
+impl From<&str> for ObjetoMeta {
+    fn from(nombre: &str) -> Self {
+        Self {
+            name: nombre.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Almacenamiento de objetos grandes sobre un stream de JetStream. El
+/// contenido se divide en chunks que se publican en un tópico propio del
+/// objeto, y al terminar se publica un mensaje con su información
+pub struct ObjectStore {
+    js: JetStream,
+    bucket: String,
+    nombre_stream: String,
+}
+
+impl ObjectStore {
+    pub(super) fn new(js: JetStream, bucket: &str) -> Self {
+        Self {
+            js,
+            bucket: bucket.to_string(),
+            nombre_stream: nombre_stream(bucket),
+        }
+    }
+
+    pub(super) fn crear(mut js: JetStream, config: &ConfigObjetos) -> io::Result<Self> {
+        if !config.bucket_valido() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Nombre de bucket inválido: {}", config.bucket),
+            ));
+        }
+
+        js.crear_stream(&config.stream_config())?;
+        Ok(Self::new(js, &config.bucket))
+    }
+
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    /// Guarda todo lo que se lea de `datos`. Si ya existía un objeto con el
+    /// mismo nombre, se reemplaza
+    pub fn put(
+        &mut self,
+        meta: impl Into<ObjetoMeta>,
+        datos: &mut impl Read,
+    ) -> io::Result<ObjetoInfo> {
+        let meta = meta.into();
+        if meta.name.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "El objeto no tiene nombre",
+            ));
+        }
+
+        let anterior = self.info(&meta.name)?;
+
+        let nuid = nuid::next().to_string();
+        let mut info = ObjetoInfo::new(&self.bucket, &meta.name, &nuid);
+        info.description = meta.description;
+        info.metadata = meta.metadata;
+
+        if let Err(e) = self.publicar_chunks(&mut info, datos) {
+            // No quedan chunks sin objeto
+            let _ = self.purgar_chunks(&nuid);
+            return Err(e);
+        }
+
+        self.publicar_info(&info)?;
+
+        if let Some(anterior) = anterior {
+            self.purgar_chunks(&anterior.nuid)?;
+        }
+
+        Ok(info)
+    }
+
+    /// Lector del contenido del objeto. Si no existe devuelve un error `NotFound`
+    pub fn get(&mut self, nombre: &str) -> io::Result<LectorObjeto> {
+        let info = self.info(nombre)?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No existe el objeto {}", nombre),
+            )
+        })?;
+
+        let suscripcion = if info.chunks > 0 {
+            let (suscripcion, _) = self.js.crear_suscripcion_ordenada(
+                &self.nombre_stream,
+                Some(&topico_chunks(&self.bucket, &info.nuid)),
+                DeliverPolicy::All,
+            )?;
+            Some(suscripcion)
+        } else {
+            None
+        };
+
+        Ok(LectorObjeto::new(info, suscripcion))
+    }
+
+    /// Información del objeto, `None` si no existe o se borró
+    pub fn info(&mut self, nombre: &str) -> io::Result<Option<ObjetoInfo>> {
+        let topico = format!(
+            "{}.{}",
+            js_api_direct_get(&self.nombre_stream),
+            topico_metadatos(&self.bucket, nombre)
+        );
+        let respuesta = self
+            .js
+            .cliente
+            .peticion_tiempo_limite(&topico, b"", TIEMPO_LIMITE_OBJETOS)?
+            .ok_or_else(|| sin_respuesta(&self.bucket))?;
+
+        let estado = respuesta
+            .header
+            .as_deref()
+            .and_then(Headers::parsear)
+            .and_then(|headers| headers.estado);
+
+        match estado {
+            Some(404) => Ok(None),
+            Some(estado) => Err(io::Error::other(format!(
+                "Error al leer el objeto {}: {}",
+                nombre, estado
+            ))),
+            None => Ok(parsear_info(respuesta).filter(|info| !info.deleted)),
+        }
+    }
+
+    /// Información de todos los objetos guardados
+    pub fn list(&mut self) -> io::Result<Vec<ObjetoInfo>> {
+        let (suscripcion, pendientes) = self.js.crear_suscripcion_ordenada(
+            &self.nombre_stream,
+            Some(&format!("$O.{}.M.>", self.bucket)),
+            DeliverPolicy::LastPerSubject,
+        )?;
+
+        let mut objetos = Vec::new();
+        for _ in 0..pendientes {
+            let publicacion = suscripcion
+                .leer_con_limite_de_tiempo(TIEMPO_LIMITE_OBJETOS)?
+                .ok_or_else(|| sin_respuesta(&self.bucket))?;
+
+            if let Some(info) = parsear_info(publicacion) {
+                if !info.deleted {
+                    objetos.push(info);
+                }
+            }
+        }
+
+        Ok(objetos)
+    }
+
+    /// Elimina el contenido del objeto y deja una marca de borrado
+    pub fn delete(&mut self, nombre: &str) -> io::Result<()> {
+        let info = self.info(nombre)?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No existe el objeto {}", nombre),
+            )
+        })?;
+
+        let mut borrado = ObjetoInfo::new(&self.bucket, &info.name, &info.nuid);
+        borrado.deleted = true;
+        self.publicar_info(&borrado)?;
+
+        self.purgar_chunks(&info.nuid)
+    }
+
+    /// Observa los cambios de los objetos del bucket
+    pub fn watch(&mut self) -> io::Result<ObservadorObjetos> {
+        let (suscripcion, _) = self.js.crear_suscripcion_ordenada(
+            &self.nombre_stream,
+            Some(&format!("$O.{}.M.>", self.bucket)),
+            DeliverPolicy::LastPerSubject,
+        )?;
+
+        Ok(ObservadorObjetos::new(suscripcion))
+    }
+
+    /// Publica el contenido en chunks y completa el tamaño, la cantidad de chunks y el digest
+    fn publicar_chunks(&mut self, info: &mut ObjetoInfo, datos: &mut impl Read) -> io::Result<()> {
+        let topico = topico_chunks(&self.bucket, &info.nuid);
+        let mut hash = Sha256::new();
+        let mut chunk = vec![0; TAMANO_CHUNK];
+
+        loop {
+            let leidos = leer_chunk(datos, &mut chunk)?;
+            if leidos == 0 {
+                break;
+            }
+
+            hash.update(&chunk[..leidos]);
+            self.js.publicar_esperando_ack(
+                &topico,
+                &chunk[..leidos],
+                None,
+                TIEMPO_LIMITE_OBJETOS,
+                0,
+            )?;
+
+            info.size += leidos as u64;
+            info.chunks += 1;
+        }
+
+        info.digest = digest(&hash.finalize());
+        Ok(())
+    }
+
+    /// Publica la información del objeto reemplazando la anterior
+    fn publicar_info(&mut self, info: &ObjetoInfo) -> io::Result<()> {
+        let json = info.to_json().map_err(io::Error::other)?;
+        let mut headers = Headers::new();
+        headers.insertar("Nats-Rollup", "sub");
+
+        self.js.publicar_esperando_ack(
+            &topico_metadatos(&self.bucket, &info.name),
+            json.as_bytes(),
+            Some(&headers.serializar()),
+            TIEMPO_LIMITE_OBJETOS,
+            0,
+        )?;
+        Ok(())
+    }
+
+    fn purgar_chunks(&mut self, nuid: &str) -> io::Result<()> {
+        let peticion = JSPeticionPurgarStream {
+            filter: Some(topico_chunks(&self.bucket, nuid)),
+            ..Default::default()
+        };
+        self.js.purgar_stream(&self.nombre_stream, &peticion)?;
+        Ok(())
+    }
+}
+
+/// Llena el chunk salvo que se terminen los datos. Devuelve cuántos bytes se leyeron
+fn leer_chunk(datos: &mut impl Read, chunk: &mut [u8]) -> io::Result<usize> {
+    let mut leidos = 0;
+    while leidos < chunk.len() {
+        match datos.read(&mut chunk[leidos..]) {
+            Ok(0) => break,
+            Ok(cantidad) => leidos += cantidad,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(leidos)
+}
+
+fn sin_respuesta(bucket: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("No se recibió respuesta del bucket {}", bucket),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use sha2::{Digest, Sha256};
+
+    use super::{digest, leer_chunk, topico_metadatos};
+
+    #[test]
+    fn digest_del_contenido() {
+        let hash = Sha256::digest(b"hola");
+        assert_eq!(
+            digest(&hash),
+            "SHA-256=siHZ27CDp_M0KNfCo8MZiuklYU1wIQ4ocWzKp81N23k="
+        );
+    }
+
+    #[test]
+    fn nombre_codificado_en_el_topico() {
+        assert_eq!(
+            topico_metadatos("camaras", "1/imagen.jpg"),
+            "$O.camaras.M.MS9pbWFnZW4uanBn"
+        );
+    }
+
+    #[test]
+    fn leer_chunk_completo() {
+        let mut datos: &[u8] = b"abcdefg";
+        let mut chunk = [0; 3];
+
+        assert_eq!(leer_chunk(&mut datos, &mut chunk).unwrap(), 3);
+        assert_eq!(&chunk, b"abc");
+        assert_eq!(leer_chunk(&mut datos, &mut chunk).unwrap(), 3);
+        assert_eq!(leer_chunk(&mut datos, &mut chunk).unwrap(), 1);
+        assert_eq!(leer_chunk(&mut datos, &mut chunk).unwrap(), 0);
+    }
+}
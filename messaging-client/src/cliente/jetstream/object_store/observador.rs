@@ -0,0 +1,59 @@
+use std::{io, time::Duration};
+
+use lib::jet_stream::objeto_info::ObjetoInfo;
+
+use crate::cliente::{
+    jetstream::js_suscripcion_ordenada::JSSuscripcionOrdenada, publicacion::Publicacion,
+};
+
+/// Recibe la información de los objetos de un bucket: primero la de cada
+/// objeto guardado y después cada vez que se guarda o se borra uno
+pub struct ObservadorObjetos {
+    suscripcion: JSSuscripcionOrdenada,
+}
+
+impl ObservadorObjetos {
+    pub(super) fn new(suscripcion: JSSuscripcionOrdenada) -> Self {
+        Self { suscripcion }
+    }
+
+    pub fn leer(&self) -> io::Result<ObjetoInfo> {
+        loop {
+            if let Some(info) = parsear_info(self.suscripcion.leer()?) {
+                return Ok(info);
+            }
+        }
+    }
+
+    pub fn intentar_leer(&self) -> io::Result<Option<ObjetoInfo>> {
+        while let Some(publicacion) = self.suscripcion.intentar_leer()? {
+            if let Some(info) = parsear_info(publicacion) {
+                return Ok(Some(info));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub fn leer_con_limite_de_tiempo(&self, limite: Duration) -> io::Result<Option<ObjetoInfo>> {
+        while let Some(publicacion) = self.suscripcion.leer_con_limite_de_tiempo(limite)? {
+            if let Some(info) = parsear_info(publicacion) {
+                return Ok(Some(info));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl Iterator for ObservadorObjetos {
+    type Item = io::Result<ObjetoInfo>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.leer())
+    }
+}
+
+pub(super) fn parsear_info(publicacion: Publicacion) -> Option<ObjetoInfo> {
+    ObjetoInfo::from_json(&String::from_utf8_lossy(&publicacion.payload)).ok()
+}
@@ -57,23 +57,16 @@ impl Cliente {
         let (tx, rx) = std::sync::mpsc::channel();
 
         if tls {
-            let connector = TlsConnector::new().map_err(|e| {
-                io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("Error al crear el conector TLS: {}", e),
-                )
-            })?;
+            let connector = TlsConnector::new()
+                .map_err(|e| io::Error::other(format!("Error al crear el conector TLS: {}", e)))?;
 
             let stream_clone = stream.try_clone()?;
 
             let host = direccion.split(':').next().unwrap_or("localhost");
 
-            let stream = connector.connect(host, stream).map_err(|e| {
-                io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("Error al conectar con TLS: {}", e),
-                )
-            })?;
+            let stream = connector
+                .connect(host, stream)
+                .map_err(|e| io::Error::other(format!("Error al conectar con TLS: {}", e)))?;
 
             stream_clone.set_nonblocking(true)?;
 
@@ -125,7 +118,7 @@ impl Cliente {
             .canal_instrucciones
             .send(Instruccion::Publicar(publicacion))
         {
-            return Err(io::Error::new(io::ErrorKind::Other, e.to_string()));
+            return Err(io::Error::other(e.to_string()));
         }
 
         Ok(())
@@ -149,7 +142,7 @@ impl Cliente {
             .canal_instrucciones
             .send(Instruccion::Publicar(publicacion))
         {
-            return Err(io::Error::new(io::ErrorKind::Other, e.to_string()));
+            return Err(io::Error::other(e.to_string()));
         }
 
         Ok(())
@@ -165,10 +158,7 @@ impl Cliente {
         {
             Ok(publicacion)
         } else {
-            Err(io::Error::new(
-                io::ErrorKind::Other,
-                "No se recibió respuesta".to_string(),
-            ))
+            Err(io::Error::other("No se recibió respuesta".to_string()))
         }
     }
 
@@ -192,10 +182,7 @@ impl Cliente {
         {
             Ok(publicacion)
         } else {
-            Err(io::Error::new(
-                io::ErrorKind::Other,
-                "No se recibió respuesta".to_string(),
-            ))
+            Err(io::Error::other("No se recibió respuesta".to_string()))
         }
     }
 
@@ -253,7 +240,7 @@ impl Cliente {
             queue_group: queue_group.map(|s| s.to_owned()),
             canal: tx,
         }) {
-            return Err(io::Error::new(io::ErrorKind::Other, e.to_string()));
+            return Err(io::Error::other(e.to_string()));
         }
 
         Ok(Suscripcion::new(canal_instrucciones, rx, id))
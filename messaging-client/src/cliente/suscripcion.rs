@@ -1,5 +1,5 @@
 use std::{
-    io::{self, ErrorKind},
+    io::{self},
     sync::mpsc::{Receiver, RecvTimeoutError, Sender, TryRecvError},
     time::Duration,
 };
@@ -31,10 +31,7 @@ impl Suscripcion {
     pub fn leer(&self) -> io::Result<Publicacion> {
         match self.canal_publicaciones.recv() {
             Ok(publicacion) => Ok(publicacion),
-            Err(_) => Err(io::Error::new(
-                ErrorKind::Other,
-                "El cliente está desconectado".to_string(),
-            )),
+            Err(_) => Err(io::Error::other("El cliente está desconectado".to_string())),
         }
     }
 
@@ -45,10 +42,7 @@ impl Suscripcion {
                 if let TryRecvError::Empty = e {
                     Ok(None)
                 } else {
-                    Err(io::Error::new(
-                        ErrorKind::Other,
-                        "El cliente está desconectado".to_string(),
-                    ))
+                    Err(io::Error::other("El cliente está desconectado".to_string()))
                 }
             }
         }
@@ -61,10 +55,7 @@ impl Suscripcion {
                 if let RecvTimeoutError::Timeout = e {
                     Ok(None)
                 } else {
-                    Err(io::Error::new(
-                        ErrorKind::Other,
-                        "El cliente está desconectado",
-                    ))
+                    Err(io::Error::other("El cliente está desconectado"))
                 }
             }
         }
@@ -0,0 +1,104 @@
+//! Compara la búsqueda de suscripciones por tópico recorriendo todas las
+//! suscripciones contra el árbol de tópicos, con y sin cache.
+//!
+//! `cargo bench --bench suscripciones`
+
+use std::{hint::black_box, time::Instant};
+
+use messaging_server::suscripciones::{
+    arbol::ArbolTopicos, suscripcion::Suscripcion, topico::Topico, Suscripciones,
+};
+
+const PUBLICACIONES: usize = 200_000;
+
+/// Cada dron escucha su propio inbox y sus comandos, y la central escucha
+/// todos los estados
+fn suscripciones(drones: usize) -> Vec<Suscripcion> {
+    let mut suscripciones = Vec::new();
+
+    for dron in 0..drones {
+        for (i, patron) in [
+            format!("_INBOX.dron{}.*", dron),
+            format!("comandos.dron{}", dron),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            suscripciones.push(Suscripcion::new(
+                0,
+                dron as u64,
+                Topico::new(patron).unwrap(),
+                i.to_string(),
+                None,
+            ));
+        }
+    }
+
+    suscripciones.push(Suscripcion::new(
+        0,
+        drones as u64,
+        Topico::new("estados.>".to_string()).unwrap(),
+        "central".to_string(),
+        None,
+    ));
+
+    suscripciones
+}
+
+fn topicos(drones: usize) -> Vec<String> {
+    (0..drones)
+        .flat_map(|dron| {
+            [
+                format!("_INBOX.dron{}.respuesta", dron),
+                format!("comandos.dron{}", dron),
+                format!("estados.dron{}.bateria", dron),
+            ]
+        })
+        .collect()
+}
+
+fn medir(nombre: &str, topicos: &[String], mut buscar: impl FnMut(&str) -> usize) {
+    let inicio = Instant::now();
+    let mut entregas = 0;
+
+    for topico in topicos.iter().cycle().take(PUBLICACIONES) {
+        entregas += buscar(black_box(topico));
+    }
+
+    let transcurrido = inicio.elapsed();
+    println!(
+        "  {:<20} {:>10.0} ns/publicación ({} entregas)",
+        nombre,
+        transcurrido.as_nanos() as f64 / PUBLICACIONES as f64,
+        entregas
+    );
+}
+
+fn main() {
+    for drones in [10, 100, 500, 1000] {
+        let lista = suscripciones(drones);
+        let topicos = topicos(drones);
+        println!("{} suscripciones:", lista.len());
+
+        medir("recorrido lineal", &topicos, |topico| {
+            lista
+                .iter()
+                .filter(|suscripcion| suscripcion.topico().test(topico))
+                .count()
+        });
+
+        let mut arbol = ArbolTopicos::new();
+        for suscripcion in &lista {
+            arbol.insertar(suscripcion.topico(), suscripcion.clone());
+        }
+        medir("árbol", &topicos, |topico| arbol.buscar(topico).len());
+
+        let mut indice = Suscripciones::new();
+        for suscripcion in &lista {
+            indice.suscribir(suscripcion.clone());
+        }
+        medir("árbol con cache", &topicos, |topico| {
+            indice.suscripciones_topico(topico).len()
+        });
+    }
+}
@@ -0,0 +1,43 @@
+use lib::configuracion::Configuracion;
+
+/// Límites del protocolo para los mensajes que envían los clientes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitesConexion {
+    /// Bytes que puede tener como máximo el payload de un `PUB` o `HPUB`
+    pub max_payload: usize,
+    /// Largo máximo de la línea de control de cada mensaje
+    pub max_control_line: usize,
+    /// Bytes que pueden quedar pendientes de enviar al cliente. Si los supera
+    /// se lo desconecta por ser un consumidor lento
+    pub max_pending: usize,
+}
+
+impl LimitesConexion {
+    /// Lee `max_payload`, `max_control_line` y `max_pending` de la configuración del servidor.
+    /// Los que no estén usan los valores por defecto
+    pub fn desde_configuracion(configuracion: &Configuracion) -> Self {
+        let por_defecto = Self::default();
+
+        Self {
+            max_payload: configuracion
+                .obtener::<usize>("max_payload")
+                .unwrap_or(por_defecto.max_payload),
+            max_control_line: configuracion
+                .obtener::<usize>("max_control_line")
+                .unwrap_or(por_defecto.max_control_line),
+            max_pending: configuracion
+                .obtener::<usize>("max_pending")
+                .unwrap_or(por_defecto.max_pending),
+        }
+    }
+}
+
+impl Default for LimitesConexion {
+    fn default() -> Self {
+        Self {
+            max_payload: 1048576,          // 1 MiB
+            max_control_line: 4096,        // 4 KiB
+            max_pending: 64 * 1024 * 1024, // 64 MiB
+        }
+    }
+}
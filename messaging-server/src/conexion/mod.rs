@@ -1,4 +1,5 @@
 pub mod id;
+pub mod limites;
 pub mod respuesta;
 pub mod tick_contexto;
 pub mod r#trait;
@@ -6,20 +7,36 @@ use lib::parseador::mensaje::formatear_mensaje_debug;
 use lib::parseador::parametros_info::ParametrosInfo;
 use lib::parseador::Parseador;
 use lib::{parseador::mensaje::Mensaje, stream::Stream};
+use mio::unix::SourceFd;
+use mio::{Interest, Registry, Token};
 use r#trait::Conexion;
+use std::collections::VecDeque;
+use std::os::fd::AsRawFd;
 use std::sync::Arc;
+use std::time::Duration;
 use std::{fmt::Debug, io};
 
 use chrono::{DateTime, Local};
 
 use crate::cuenta::Cuenta;
+use crate::estadisticas::Estadisticas;
+use crate::hilo::despertador::Despertador;
 use crate::{
     publicacion::{mensaje::PublicacionMensaje, Publicacion},
     registrador::Registrador,
     suscripciones::{suscripcion::Suscripcion, topico::Topico},
 };
 
-use self::{id::IdConexion, respuesta::Respuesta, tick_contexto::TickContexto};
+/// Cada cuánto se le envía un PING al cliente
+const INTERVALO_PING: Duration = Duration::from_secs(20);
+
+/// Bytes que se leen como máximo en un tick, para no atender a un solo cliente
+/// mientras el resto espera
+const MAXIMO_LECTURA_POR_TICK: usize = 256 * 1024;
+
+use self::{
+    id::IdConexion, limites::LimitesConexion, respuesta::Respuesta, tick_contexto::TickContexto,
+};
 pub struct ConexionDeCliente {
     /// El identificador de la conexión. Global y único0
     id: IdConexion,
@@ -43,6 +60,20 @@ pub struct ConexionDeCliente {
 
     /// Muestra o no +Ok y -ERR
     verbose: bool,
+
+    /// Límites de los mensajes que puede enviar el cliente
+    limites: LimitesConexion,
+
+    /// Bytes que todavía no se pudieron escribir al stream porque el cliente no
+    /// los lee lo suficientemente rápido. Se envían desde el principio, así que
+    /// quitar lo enviado no mueve el resto
+    pendientes: VecDeque<u8>,
+
+    /// Contadores del servidor
+    estadisticas: Arc<Estadisticas>,
+
+    /// Si quedaron bytes en el stream que no se leyeron en el último tick
+    lectura_pendiente: bool,
 }
 
 impl ConexionDeCliente {
@@ -51,17 +82,23 @@ impl ConexionDeCliente {
         stream: Box<dyn Stream>,
         registrador: Registrador,
         cuentas: Option<Arc<Vec<Cuenta>>>,
+        limites: LimitesConexion,
+        estadisticas: Arc<Estadisticas>,
     ) -> Self {
         let mut con = Self {
             id,
             stream,
-            parser: Parseador::new(),
+            parser: Parseador::con_limites(limites.max_payload, limites.max_control_line),
             registrador,
             tiempo_ultimo_ping: Local::now(),
             desconectado: false,
             autenticado: false,
             cuentas,
             verbose: true,
+            limites,
+            pendientes: VecDeque::new(),
+            estadisticas,
+            lectura_pendiente: false,
         };
 
         con.enviar_info();
@@ -74,7 +111,7 @@ impl ConexionDeCliente {
         let tiempo_actual = Local::now();
         let duracion_ultimo_ping = tiempo_actual.signed_duration_since(self.tiempo_ultimo_ping);
 
-        if duracion_ultimo_ping.num_seconds() >= 20 {
+        if duracion_ultimo_ping.num_seconds() >= INTERVALO_PING.as_secs() as i64 {
             self.tiempo_ultimo_ping = tiempo_actual;
             true
         } else {
@@ -82,44 +119,115 @@ impl ConexionDeCliente {
         }
     }
 
-    /// Lee los bytes del stream y los envía al parser
+    /// Lee los bytes del stream y los envía al parser, hasta que no haya más o se
+    /// lea el máximo de un tick. El hilo solo avisa cuando llegan bytes nuevos,
+    /// así que no pueden quedar bytes sin leer sin pedir otro tick
     fn leer_bytes(&mut self) {
         let mut buffer = [0; 32768]; // 32kib
-        match self.stream.read(&mut buffer) {
-            Ok(n) => {
-                if n == 0 {
-                    self.desconectado = true;
-                    return;
-                }
+        let mut leidos = 0;
+        self.lectura_pendiente = false;
 
-                // 2. Enviar bytes a parser y leer nuevos mensajes generados
-                self.parser.agregar_bytes(&buffer[..n]);
+        loop {
+            if leidos >= MAXIMO_LECTURA_POR_TICK {
+                self.lectura_pendiente = true;
+                return;
             }
-            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                // No hay datos para leer (no hay que hacer nada acá)
-            }
-            Err(e) => {
-                self.registrador
-                    .error(&format!("Error al leer del stream {}", e), Some(self.id));
-                self.registrador.error("Error al leer bytes", Some(self.id));
 
-                self.desconectado = true;
+            match self.stream.read(&mut buffer) {
+                Ok(n) => {
+                    if n == 0 {
+                        self.desconectado = true;
+                        return;
+                    }
+
+                    // 2. Enviar bytes a parser y leer nuevos mensajes generados
+                    self.parser.agregar_bytes(&buffer[..n]);
+                    leidos += n;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    // No hay datos para leer (no hay que hacer nada acá)
+                    return;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.registrador
+                        .error(&format!("Error al leer del stream {}", e), Some(self.id));
+                    self.registrador.error("Error al leer bytes", Some(self.id));
+
+                    self.desconectado = true;
+                    return;
+                }
             }
         }
     }
 
-    /// Escribir al stream
+    /// Agrega los bytes a los pendientes de enviar y envía todos los que se
+    /// puedan sin bloquear el hilo
     fn escribir_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
-        if let Err(e) = self.stream.write_all(bytes) {
-            self.registrador
-                .advertencia(&format!("Error al escribir al stream {}", e), Some(self.id));
-            self.desconectado = true;
-            return Err(e);
+        if self.desconectado {
+            return Err(io::Error::new(
+                io::ErrorKind::NotConnected,
+                "La conexión está cerrada",
+            ));
+        }
+
+        if self.pendientes.len() + bytes.len() > self.limites.max_pending {
+            self.desconectar_consumidor_lento();
+            return Err(io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "El cliente no lee los mensajes a tiempo",
+            ));
+        }
+
+        self.pendientes.extend(bytes);
+        self.enviar_pendientes()
+    }
+
+    /// Escribe al stream los bytes pendientes hasta que no acepte más
+    fn enviar_pendientes(&mut self) -> io::Result<()> {
+        while !self.pendientes.is_empty() {
+            // Si los bytes dan la vuelta al buffer, la segunda parte se envía en
+            // la siguiente vuelta
+            match self.stream.write(self.pendientes.as_slices().0) {
+                Ok(0) => {
+                    self.desconectado = true;
+                    return Err(io::ErrorKind::WriteZero.into());
+                }
+                Ok(n) => {
+                    self.pendientes.drain(..n);
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => {
+                    self.registrador
+                        .advertencia(&format!("Error al escribir al stream {}", e), Some(self.id));
+                    self.desconectado = true;
+                    return Err(e);
+                }
+            }
         }
 
         Ok(())
     }
 
+    /// Descarta lo que estaba pendiente, avisa al cliente y lo desconecta
+    fn desconectar_consumidor_lento(&mut self) {
+        self.registrador.advertencia(
+            &format!(
+                "Consumidor lento: más de {} bytes pendientes de enviar",
+                self.limites.max_pending
+            ),
+            Some(self.id),
+        );
+        self.estadisticas.registrar_consumidor_lento();
+
+        self.pendientes = Respuesta::Err(Some("'Slow Consumer'".to_string()))
+            .serializar()
+            .into();
+        let _ = self.enviar_pendientes();
+        self.desconectado = true;
+    }
+
     fn escribir_respuesta(&mut self, respuesta: &Respuesta) {
         let bytes = &respuesta.serializar();
         if self.escribir_bytes(bytes).is_err() {
@@ -148,7 +256,7 @@ impl ConexionDeCliente {
         let require_auth = self.cuentas.is_some();
         self.escribir_respuesta(&Respuesta::Info(ParametrosInfo {
             auth_required: Some(require_auth),
-            max_payload: Some(1048576),
+            max_payload: Some(self.limites.max_payload as u64),
         }));
     }
 
@@ -159,6 +267,15 @@ impl ConexionDeCliente {
                 Some(self.id),
             );
 
+            // Se envía aunque la conexión no sea verbose, porque se cierra
+            if let Mensaje::LimiteExcedido(limite) = mensaje {
+                self.registrador
+                    .advertencia(&format!("Límite excedido: {}", limite), Some(self.id));
+                self.escribir_respuesta(&Respuesta::Err(Some(limite.to_string())));
+                self.desconectado = true;
+                return;
+            }
+
             if !self.autenticado {
                 match mensaje {
                     Mensaje::Conectar(parametros) => {
@@ -224,8 +341,11 @@ impl ConexionDeCliente {
                         self.escribir_err(Some("Tópico de subscripción incorrecto".to_string()));
                     }
                 },
-                Mensaje::Desuscribir(id, _max_msgs) => {
-                    contexto.desuscribir(id);
+                Mensaje::Desuscribir(id, max_msgs) => {
+                    match max_msgs {
+                        Some(maximo) if maximo > 0 => contexto.desuscribir_despues(id, maximo),
+                        _ => contexto.desuscribir(id),
+                    }
                     self.escribir_ok(Some("unsub".to_string()));
                 }
                 Mensaje::Error(msg) => {
@@ -262,6 +382,11 @@ impl Conexion for ConexionDeCliente {
         if self.desconectado {
             return;
         }
+        // Lo que no se pudo enviar en ticks anteriores
+        if self.enviar_pendientes().is_err() {
+            return;
+        }
+
         // Si hace falta enviar un PING o no
         if self.enviar_ping() {
             _ = self.escribir_bytes(b"PING\r\n");
@@ -276,6 +401,11 @@ impl Conexion for ConexionDeCliente {
 
     /// Este método lo envia el Hilo cuando recibe un mensaje
     fn escribir_publicacion_mensaje(&mut self, mensaje: &PublicacionMensaje) {
+        // El hilo todavía no la eliminó
+        if self.desconectado {
+            return;
+        }
+
         self.registrador
             .info(&format!("MSG: {:?}", mensaje), Some(self.id));
 
@@ -288,6 +418,36 @@ impl Conexion for ConexionDeCliente {
     fn esta_conectado(&self) -> bool {
         !self.desconectado
     }
+
+    fn registrar(
+        &mut self,
+        registro: &Registry,
+        token: Token,
+        _despertador: &Despertador,
+    ) -> io::Result<()> {
+        // Se espera a que se pueda escribir para enviar los bytes pendientes
+        match self.stream.socket() {
+            Some(socket) => registro.register(
+                &mut SourceFd(&socket.as_raw_fd()),
+                token,
+                Interest::READABLE | Interest::WRITABLE,
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Hasta el próximo PING, salvo que hayan quedado bytes sin leer
+    fn intervalo_tick(&self) -> Option<Duration> {
+        if self.lectura_pendiente {
+            return Some(Duration::ZERO);
+        }
+
+        let desde_ultimo_ping = Local::now()
+            .signed_duration_since(self.tiempo_ultimo_ping)
+            .to_std()
+            .unwrap_or_default();
+        Some(INTERVALO_PING.saturating_sub(desde_ultimo_ping))
+    }
 }
 
 impl Debug for ConexionDeCliente {
@@ -302,14 +462,94 @@ impl Debug for ConexionDeCliente {
 
 #[cfg(test)]
 mod tests {
-    use std::sync::Arc;
+    use std::{
+        io::{self, Read, Write},
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
 
     use lib::{serializables::deserializar_vec, stream::mock_handler::MockHandler};
     use sha256::digest;
 
-    use crate::{conexion::r#trait::Conexion, registrador::Registrador};
+    use crate::{
+        conexion::r#trait::Conexion, estadisticas::Estadisticas, hilo::instruccion::Instruccion,
+        publicacion::mensaje::PublicacionMensaje, registrador::Registrador,
+    };
+
+    use super::{
+        limites::LimitesConexion, tick_contexto::TickContexto, ConexionDeCliente,
+        MAXIMO_LECTURA_POR_TICK,
+    };
+
+    /// Stream de un cliente que no lee nada mientras está bloqueado
+    #[derive(Clone)]
+    struct StreamLento {
+        bloqueado: Arc<AtomicBool>,
+        /// Bytes que acepta antes de bloquearse
+        disponibles: Arc<AtomicUsize>,
+        escritos: Arc<Mutex<Vec<u8>>>,
+    }
+
+    impl StreamLento {
+        fn bloqueado() -> Self {
+            Self {
+                bloqueado: Arc::new(AtomicBool::new(true)),
+                disponibles: Arc::new(AtomicUsize::new(usize::MAX)),
+                escritos: Arc::new(Mutex::new(Vec::new())),
+            }
+        }
+    }
 
-    use super::{tick_contexto::TickContexto, ConexionDeCliente};
+    impl lib::stream::Stream for StreamLento {}
+
+    impl Read for StreamLento {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::WouldBlock.into())
+        }
+    }
+
+    impl Write for StreamLento {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let disponibles = self.disponibles.load(Ordering::Relaxed);
+            if self.bloqueado.load(Ordering::Relaxed) || disponibles == 0 {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+
+            let n = buf.len().min(disponibles);
+            self.disponibles.store(disponibles - n, Ordering::Relaxed);
+            self.escritos.lock().unwrap().extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn conexion_lenta(
+        stream: &StreamLento,
+        max_pending: usize,
+        estadisticas: &Arc<Estadisticas>,
+    ) -> ConexionDeCliente {
+        ConexionDeCliente::new(
+            1,
+            Box::new(stream.clone()),
+            Registrador::new(Some(false)),
+            None,
+            LimitesConexion {
+                max_pending,
+                ..Default::default()
+            },
+            estadisticas.clone(),
+        )
+    }
+
+    fn mensaje() -> PublicacionMensaje {
+        PublicacionMensaje::new("1".to_string(), "a".to_string(), vec![b'x'; 50], None, None)
+    }
 
     #[test]
     fn probar_info() {
@@ -318,7 +558,14 @@ mod tests {
         let registrador = Registrador::new(Some(false));
 
         // Conexion representa el cliente del lado del servidor
-        ConexionDeCliente::new(1, Box::new(stream), registrador, None);
+        ConexionDeCliente::new(
+            1,
+            Box::new(stream),
+            registrador,
+            None,
+            LimitesConexion::default(),
+            Arc::new(Estadisticas::new()),
+        );
 
         assert!(control
             .intentar_recibir_string()
@@ -332,7 +579,14 @@ mod tests {
         let (mut mock, stream) = MockHandler::new();
         let registrador = Registrador::new(Some(false));
 
-        let mut con = ConexionDeCliente::new(1, Box::new(stream), registrador, None);
+        let mut con = ConexionDeCliente::new(
+            1,
+            Box::new(stream),
+            registrador,
+            None,
+            LimitesConexion::default(),
+            Arc::new(Estadisticas::new()),
+        );
 
         mock.escribir_bytes(b"CONNECT {}\r\n");
 
@@ -351,8 +605,14 @@ mod tests {
 
         let cuentas = deserializar_vec(format!("1,admin,{}", pass).as_bytes()).unwrap();
 
-        let mut con =
-            ConexionDeCliente::new(1, Box::new(stream), registrador, Some(Arc::new(cuentas)));
+        let mut con = ConexionDeCliente::new(
+            1,
+            Box::new(stream),
+            registrador,
+            Some(Arc::new(cuentas)),
+            LimitesConexion::default(),
+            Arc::new(Estadisticas::new()),
+        );
 
         mock.escribir_bytes(b"CONNECT {\"user\": \"admin\", \"pass\": \"1234\"}\r\n");
 
@@ -367,7 +627,14 @@ mod tests {
         let (mut mock, stream) = MockHandler::new();
         let registrador = Registrador::new(Some(false));
 
-        let mut con = ConexionDeCliente::new(1, Box::new(stream), registrador, None);
+        let mut con = ConexionDeCliente::new(
+            1,
+            Box::new(stream),
+            registrador,
+            None,
+            LimitesConexion::default(),
+            Arc::new(Estadisticas::new()),
+        );
         mock.escribir_bytes(b"CONNECT {\"user\": \"admin\", \"pass\": \"admin\"}\r\n");
 
         let mut contexto = TickContexto::new(0, 1);
@@ -388,7 +655,14 @@ mod tests {
         let (mut mock, stream) = MockHandler::new();
         let registrador = Registrador::new(Some(false));
 
-        let mut con = ConexionDeCliente::new(1, Box::new(stream), registrador, None);
+        let mut con = ConexionDeCliente::new(
+            1,
+            Box::new(stream),
+            registrador,
+            None,
+            LimitesConexion::default(),
+            Arc::new(Estadisticas::new()),
+        );
         mock.escribir_bytes(b"CONNECT {\"user\": \"admin\", \"pass\": \"admin\"}\r\n");
 
         let mut contexto = TickContexto::new(0, 1);
@@ -404,12 +678,53 @@ mod tests {
         assert_eq!(contexto.publicaciones()[0].payload, b"hola");
     }
 
+    #[test]
+    fn probar_lectura_pendiente() {
+        let (mut mock, stream) = MockHandler::new();
+
+        let mut con = ConexionDeCliente::new(
+            1,
+            Box::new(stream),
+            Registrador::new(Some(false)),
+            None,
+            LimitesConexion::default(),
+            Arc::new(Estadisticas::new()),
+        );
+        mock.escribir_bytes(b"CONNECT {}\r\n");
+        con.tick(&mut TickContexto::new(0, 1));
+
+        // Más bytes de los que se leen en un tick
+        let cantidad = MAXIMO_LECTURA_POR_TICK / 1000 + 100;
+        let publicacion = format!("PUB x 1000\r\n{}\r\n", "x".repeat(1000));
+        mock.escribir_bytes(publicacion.repeat(cantidad).as_bytes());
+
+        let mut contexto = TickContexto::new(0, 1);
+        con.tick(&mut contexto);
+        let primeras = contexto.publicaciones().len();
+
+        assert!(primeras < cantidad);
+        assert_eq!(con.intervalo_tick(), Some(Duration::ZERO));
+
+        let mut contexto = TickContexto::new(0, 1);
+        con.tick(&mut contexto);
+
+        assert_eq!(primeras + contexto.publicaciones().len(), cantidad);
+        assert!(con.intervalo_tick() > Some(Duration::ZERO));
+    }
+
     #[test]
     fn probar_desuscripcion() {
         let (mut mock, stream) = MockHandler::new();
         let registrador = Registrador::new(Some(false));
 
-        let mut con = ConexionDeCliente::new(1, Box::new(stream), registrador, None);
+        let mut con = ConexionDeCliente::new(
+            1,
+            Box::new(stream),
+            registrador,
+            None,
+            LimitesConexion::default(),
+            Arc::new(Estadisticas::new()),
+        );
         mock.escribir_bytes(b"CONNECT {\"user\": \"admin\", \"pass\": \"admin\"}\r\n");
 
         let mut contexto = TickContexto::new(0, 1);
@@ -423,4 +738,155 @@ mod tests {
         assert_eq!(contexto.desuscripciones().len(), 1);
         assert_eq!(contexto.desuscripciones()[0], "1");
     }
+
+    #[test]
+    fn probar_desuscripcion_con_maximo() {
+        let (mut mock, stream) = MockHandler::new();
+        let registrador = Registrador::new(Some(false));
+
+        let mut con = ConexionDeCliente::new(
+            1,
+            Box::new(stream),
+            registrador,
+            None,
+            LimitesConexion::default(),
+            Arc::new(Estadisticas::new()),
+        );
+        mock.escribir_bytes(b"CONNECT {\"user\": \"admin\", \"pass\": \"admin\"}\r\n");
+
+        let mut contexto = TickContexto::new(0, 1);
+        con.tick(&mut contexto);
+
+        mock.escribir_bytes(b"UNSUB 1 5\r\n");
+
+        let mut contexto = TickContexto::new(0, 1);
+        con.tick(&mut contexto);
+
+        assert!(contexto.desuscripciones().is_empty());
+        assert!(matches!(
+            contexto.instrucciones.as_slice(),
+            [Instruccion::DesuscribirDespues(1, id, 5)] if id == "1"
+        ));
+    }
+
+    #[test]
+    fn probar_payload_maximo() {
+        let (mut mock, stream) = MockHandler::new();
+        let registrador = Registrador::new(Some(false));
+        let limites = LimitesConexion {
+            max_payload: 4,
+            ..Default::default()
+        };
+
+        let mut con = ConexionDeCliente::new(
+            1,
+            Box::new(stream),
+            registrador,
+            None,
+            limites,
+            Arc::new(Estadisticas::new()),
+        );
+        mock.escribir_bytes(b"CONNECT {\"verbose\": false}\r\nPUB a 5\r\nhola!\r\n");
+
+        let mut contexto = TickContexto::new(0, 1);
+        con.tick(&mut contexto);
+
+        assert!(contexto.publicaciones().is_empty());
+        assert!(!con.esta_conectado());
+
+        let respuesta = mock.intentar_recibir_string().unwrap();
+        assert!(respuesta.contains("\"max_payload\":4"));
+        assert!(respuesta.ends_with("-ERR 'Maximum Payload Violation'\r\n"));
+    }
+
+    #[test]
+    fn probar_linea_de_control_maxima() {
+        let (mut mock, stream) = MockHandler::new();
+        let registrador = Registrador::new(Some(false));
+        let limites = LimitesConexion {
+            max_control_line: 64,
+            ..Default::default()
+        };
+
+        let mut con = ConexionDeCliente::new(
+            1,
+            Box::new(stream),
+            registrador,
+            None,
+            limites,
+            Arc::new(Estadisticas::new()),
+        );
+        let mut bytes = b"CONNECT {}\r\n".to_vec();
+        bytes.extend_from_slice(&[b'a'; 100]);
+        mock.escribir_bytes(&bytes);
+
+        let mut contexto = TickContexto::new(0, 1);
+        con.tick(&mut contexto);
+
+        assert!(!con.esta_conectado());
+        assert!(mock
+            .intentar_recibir_string()
+            .unwrap()
+            .ends_with("-ERR 'Maximum Control Line Exceeded'\r\n"));
+    }
+
+    #[test]
+    fn probar_envio_de_bytes_pendientes() {
+        let stream = StreamLento::bloqueado();
+        let estadisticas = Arc::new(Estadisticas::new());
+        let mut con = conexion_lenta(&stream, 1024, &estadisticas);
+        con.escribir_publicacion_mensaje(&mensaje());
+        assert!(stream.escritos.lock().unwrap().is_empty());
+
+        stream.bloqueado.store(false, Ordering::Relaxed);
+        con.tick(&mut TickContexto::new(0, 1));
+
+        let escritos = String::from_utf8(stream.escritos.lock().unwrap().clone()).unwrap();
+        assert!(escritos.starts_with("INFO "));
+        assert!(escritos.ends_with(&format!("MSG a 1 50\r\n{}\r\n", "x".repeat(50))));
+        assert!(con.esta_conectado());
+        assert_eq!(estadisticas.consumidores_lentos(), 0);
+    }
+
+    #[test]
+    fn probar_envio_parcial_en_orden() {
+        let stream = StreamLento::bloqueado();
+        let estadisticas = Arc::new(Estadisticas::new());
+        let mut con = conexion_lenta(&stream, 1024, &estadisticas);
+        stream.bloqueado.store(false, Ordering::Relaxed);
+
+        // El cliente lee de a poco mientras se siguen agregando mensajes, así
+        // los pendientes se vacían por delante y crecen por detrás
+        for _ in 0..10 {
+            stream.disponibles.store(37, Ordering::Relaxed);
+            con.escribir_publicacion_mensaje(&mensaje());
+            con.tick(&mut TickContexto::new(0, 1));
+        }
+        stream.disponibles.store(usize::MAX, Ordering::Relaxed);
+        con.tick(&mut TickContexto::new(0, 1));
+
+        let escritos = String::from_utf8(stream.escritos.lock().unwrap().clone()).unwrap();
+        let esperado = format!("MSG a 1 50\r\n{}\r\n", "x".repeat(50)).repeat(10);
+        assert!(escritos.starts_with("INFO "));
+        assert!(escritos.ends_with(&esperado));
+        assert!(con.esta_conectado());
+    }
+
+    #[test]
+    fn probar_consumidor_lento() {
+        let estadisticas = Arc::new(Estadisticas::new());
+        let mut con = conexion_lenta(&StreamLento::bloqueado(), 256, &estadisticas);
+        for _ in 0..3 {
+            con.escribir_publicacion_mensaje(&mensaje());
+        }
+        assert!(con.esta_conectado());
+
+        con.escribir_publicacion_mensaje(&mensaje());
+        assert!(!con.esta_conectado());
+        assert_eq!(estadisticas.consumidores_lentos(), 1);
+
+        // Una vez desconectado no se acumulan más bytes
+        con.escribir_publicacion_mensaje(&mensaje());
+        assert_eq!(estadisticas.consumidores_lentos(), 1);
+    }
 }
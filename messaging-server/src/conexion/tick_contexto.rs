@@ -34,6 +34,15 @@ impl TickContexto {
             .push(Instruccion::Desuscribir(self.id_conexion, id_suscripcion))
     }
 
+    /// Elimina la suscripción cuando haya recibido `maximo` mensajes en total
+    pub fn desuscribir_despues(&mut self, id_suscripcion: IdSuscripcion, maximo: u64) {
+        self.instrucciones.push(Instruccion::DesuscribirDespues(
+            self.id_conexion,
+            id_suscripcion,
+            maximo,
+        ))
+    }
+
     pub fn publicar(&mut self, publicacion: Publicacion) {
         self.instrucciones
             .push(Instruccion::NuevaPublicacion(publicacion))
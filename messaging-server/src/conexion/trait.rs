@@ -1,7 +1,14 @@
-use crate::publicacion::mensaje::PublicacionMensaje;
+use std::{io, time::Duration};
+
+use mio::{Registry, Token};
+
+use crate::{hilo::despertador::Despertador, publicacion::mensaje::PublicacionMensaje};
 
 use super::tick_contexto::TickContexto;
 
+/// Cada cuánto se hace tick de las conexiones que no dicen otra cosa
+pub const INTERVALO_TICK: Duration = Duration::from_millis(10);
+
 pub trait Conexion {
     fn obtener_id(&self) -> u64;
 
@@ -12,4 +19,21 @@ pub trait Conexion {
     fn esta_conectado(&self) -> bool;
 
     fn setear_id_conexion(&mut self, id_conexion: u64);
+
+    /// Se llama cuando la conexión llega a su hilo. Las que tienen un socket lo
+    /// registran con `token` para que el hilo haga tick cuando esté listo
+    fn registrar(
+        &mut self,
+        _registro: &Registry,
+        _token: Token,
+        _despertador: &Despertador,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Cuánto puede esperar el hilo para hacer el próximo tick si no hay
+    /// eventos de la conexión. `None` si solo hace falta cuando los haya
+    fn intervalo_tick(&self) -> Option<Duration> {
+        Some(INTERVALO_TICK)
+    }
 }
@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use lib::estadisticas::TOPICO_ESTADISTICAS;
+
+use crate::{
+    conexion::{id::IdConexion, r#trait::Conexion, tick_contexto::TickContexto},
+    publicacion::{mensaje::PublicacionMensaje, Publicacion},
+    registrador::Registrador,
+    suscripciones::{suscripcion::Suscripcion, topico::Topico},
+};
+
+use super::Estadisticas;
+
+/// Conexión interna del servidor que responde las peticiones de estadísticas
+pub struct ConexionEstadisticas {
+    id: IdConexion,
+    suscrito: bool,
+    estadisticas: Arc<Estadisticas>,
+    respuestas: Vec<Publicacion>,
+    registrador: Registrador,
+}
+
+impl ConexionEstadisticas {
+    pub fn new(id: IdConexion, estadisticas: Arc<Estadisticas>, registrador: Registrador) -> Self {
+        Self {
+            id,
+            suscrito: false,
+            estadisticas,
+            respuestas: Vec::new(),
+            registrador,
+        }
+    }
+}
+
+impl Conexion for ConexionEstadisticas {
+    fn obtener_id(&self) -> u64 {
+        self.id
+    }
+
+    fn setear_id_conexion(&mut self, id_conexion: u64) {
+        self.id = id_conexion;
+    }
+
+    fn tick(&mut self, contexto: &mut TickContexto) {
+        if !self.suscrito {
+            if let Ok(topico) = Topico::new(TOPICO_ESTADISTICAS.to_string()) {
+                contexto.suscribir(Suscripcion::new(
+                    contexto.id_hilo,
+                    self.id,
+                    topico,
+                    "estadisticas".to_string(),
+                    None,
+                ));
+            }
+            self.suscrito = true;
+        }
+
+        for respuesta in self.respuestas.drain(..) {
+            contexto.publicar(respuesta);
+        }
+    }
+
+    fn escribir_publicacion_mensaje(&mut self, mensaje: &PublicacionMensaje) {
+        let reply_to = match &mensaje.replay_to {
+            Some(reply_to) => reply_to,
+            None => return,
+        };
+
+        match self.estadisticas.resumen().to_json() {
+            Ok(json) => self.respuestas.push(Publicacion::new(
+                reply_to.to_string(),
+                json.into_bytes(),
+                None,
+                None,
+            )),
+            Err(e) => self.registrador.error(
+                &format!("Error al serializar las estadísticas: {}", e),
+                Some(self.id),
+            ),
+        }
+    }
+
+    fn esta_conectado(&self) -> bool {
+        true
+    }
+
+    /// Solo responde cuando le llega una petición
+    fn intervalo_tick(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
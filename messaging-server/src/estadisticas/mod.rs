@@ -0,0 +1,31 @@
+pub mod conexion;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use lib::estadisticas::EstadisticasServidor;
+
+/// Contadores del servidor, compartidos entre todas las conexiones
+#[derive(Debug, Default)]
+pub struct Estadisticas {
+    consumidores_lentos: AtomicU64,
+}
+
+impl Estadisticas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn registrar_consumidor_lento(&self) {
+        self.consumidores_lentos.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn consumidores_lentos(&self) -> u64 {
+        self.consumidores_lentos.load(Ordering::Relaxed)
+    }
+
+    pub fn resumen(&self) -> EstadisticasServidor {
+        EstadisticasServidor {
+            slow_consumers: self.consumidores_lentos(),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    io,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use mio::{Registry, Token, Waker};
+
+use crate::conexion::id::IdConexion;
+
+/// Token de los eventos con los que se despierta al hilo, que no son de ninguna conexión
+pub const TOKEN_DESPERTADOR: Token = Token(usize::MAX);
+
+/// Despierta a un hilo que está esperando eventos, desde cualquier otro hilo
+#[derive(Debug, Clone)]
+pub struct Despertador {
+    waker: Arc<Waker>,
+    /// Conexiones del hilo a las que se les pidió un tick
+    pedidas: Arc<Mutex<Vec<IdConexion>>>,
+}
+
+impl Despertador {
+    pub fn new(registro: &Registry) -> io::Result<Self> {
+        Ok(Self {
+            waker: Arc::new(Waker::new(registro, TOKEN_DESPERTADOR)?),
+            pedidas: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    pub fn despertar(&self) {
+        // Si falla, el hilo ya terminó
+        let _ = self.waker.wake();
+    }
+
+    /// Despierta al hilo para que haga tick de la conexión
+    pub fn pedir_tick(&self, id_conexion: IdConexion) {
+        if let Ok(mut pedidas) = self.pedidas.lock() {
+            pedidas.push(id_conexion);
+        }
+        self.despertar();
+    }
+
+    /// Conexiones a las que se les pidió un tick desde la última vez
+    pub fn tomar_pedidas(&self) -> Vec<IdConexion> {
+        match self.pedidas.lock() {
+            Ok(mut pedidas) => std::mem::take(&mut *pedidas),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+/// Permite pedir un tick de una conexión interna, como cuando se le envía algo
+/// por un canal, antes de saber en qué hilo va a estar. La conexión lo vincula
+/// con su hilo al registrarse, y hasta entonces avisar no hace nada
+#[derive(Debug, Clone, Default)]
+pub struct Aviso {
+    destino: Arc<OnceLock<(Despertador, IdConexion)>>,
+}
+
+impl Aviso {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn vincular(&self, despertador: &Despertador, id_conexion: IdConexion) {
+        let _ = self.destino.set((despertador.clone(), id_conexion));
+    }
+
+    pub fn avisar(&self) {
+        if let Some((despertador, id_conexion)) = self.destino.get() {
+            despertador.pedir_tick(*id_conexion);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use std::sync::mpsc::{SendError, Sender};
+
+use super::despertador::{Aviso, Despertador};
+
+/// Punta emisora de un canal hacia un hilo, que lo despierta cada vez que le
+/// envía algo para que no tenga que revisar el canal periódicamente
+#[derive(Debug)]
+pub struct Emisor<T> {
+    tx: Sender<T>,
+    despertador: Despertador,
+}
+
+impl<T> Emisor<T> {
+    pub fn new(tx: Sender<T>, despertador: Despertador) -> Self {
+        Self { tx, despertador }
+    }
+
+    pub fn send(&self, valor: T) -> Result<(), SendError<T>> {
+        self.tx.send(valor)?;
+        self.despertador.despertar();
+        Ok(())
+    }
+}
+
+impl<T> Clone for Emisor<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            despertador: self.despertador.clone(),
+        }
+    }
+}
+
+/// Punta emisora de un canal hacia una conexión interna, que le pide un tick a
+/// su hilo cada vez que le envía algo
+#[derive(Debug)]
+pub struct EmisorConexion<T> {
+    tx: Sender<T>,
+    aviso: Aviso,
+}
+
+impl<T> EmisorConexion<T> {
+    pub fn new(tx: Sender<T>, aviso: Aviso) -> Self {
+        Self { tx, aviso }
+    }
+
+    pub fn send(&self, valor: T) -> Result<(), SendError<T>> {
+        self.tx.send(valor)?;
+        self.aviso.avisar();
+        Ok(())
+    }
+}
+
+impl<T> Clone for EmisorConexion<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            aviso: self.aviso.clone(),
+        }
+    }
+}
@@ -11,6 +11,9 @@ pub enum Instruccion {
     Suscribir(Suscripcion),
     /// Eliminar una suscripción
     Desuscribir(IdConexion, IdSuscripcion),
+    /// Eliminar una suscripción cuando haya recibido esta cantidad de mensajes
+    /// (`UNSUB <sid> <max_msgs>`)
+    DesuscribirDespues(IdConexion, IdSuscripcion, u64),
     /// Publicar, excepto suscripciones de queue group
     Publicar(Publicacion),
     /// Enviar una publicación a una suscripción exacta
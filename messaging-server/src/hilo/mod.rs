@@ -1,27 +1,41 @@
+pub mod despertador;
+pub mod emisor;
 pub mod id;
 pub mod instruccion;
 
 use std::{
-    collections::HashMap,
-    sync::mpsc::{Receiver, Sender},
+    collections::{HashMap, HashSet},
+    io,
+    sync::mpsc::Receiver,
     thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
+use mio::{Events, Poll, Token};
+
 use crate::{
     conexion::{id::IdConexion, r#trait::Conexion, tick_contexto::TickContexto},
     publicacion::Publicacion,
     registrador::Registrador,
-    suscripciones::{suscripcion::Suscripcion, Suscripciones},
+    suscripciones::{id::IdSuscripcion, suscripcion::Suscripcion, Suscripciones},
+};
+
+use self::{
+    despertador::{Despertador, TOKEN_DESPERTADOR},
+    emisor::Emisor,
+    id::IdHilo,
+    instruccion::Instruccion,
 };
 
-use self::{id::IdHilo, instruccion::Instruccion};
+/// Cuántos eventos se leen como máximo cada vez que se espera
+const EVENTOS_POR_ESPERA: usize = 1024;
 
 pub struct Hilo {
     id: u64,
     /// Canal para **recibir** instrucciones de otros procesos
     canal_recibir_conexiones: Receiver<(IdConexion, Box<dyn Conexion + Send>)>,
     /// Canales a otros hilos para **enviar** instrucciones (ejemplo: publicar, suscribir, desuscribir, etc.)
-    canales_enviar_instrucciones: HashMap<IdHilo, Sender<Instruccion>>,
+    canales_enviar_instrucciones: HashMap<IdHilo, Emisor<Instruccion>>,
     /// Canal para **recibir** instrucciones de otros procesos
     canal_recibir_instrucciones: Receiver<Instruccion>,
     /// Suscripciones de este hilo
@@ -30,13 +44,25 @@ pub struct Hilo {
     registrador: Registrador,
     /// Conexiones de este hilo
     conexiones: HashMap<IdConexion, Box<dyn Conexion + Send>>,
+    /// Espera a que haya eventos en los sockets de las conexiones o a que lo despierten
+    poll: Poll,
+    /// Lo usan los otros hilos para despertar a este cuando le envían algo
+    despertador: Despertador,
+    /// Conexiones que tienen algo para hacer en el próximo tick
+    listas: HashSet<IdConexion>,
+    /// Momento en el que hay que hacer tick de cada conexión aunque no tenga eventos
+    vencimientos: HashMap<IdConexion, Instant>,
 }
 
 impl Hilo {
+    /// `despertador` tiene que estar creado con el registro de `poll`, y es el
+    /// que usan los emisores de los canales hacia este hilo
     pub fn new(
         id: u64,
+        poll: Poll,
+        despertador: Despertador,
         canal_recibir_conexiones: Receiver<(IdConexion, Box<dyn Conexion + Send>)>,
-        canales_enviar_instrucciones: HashMap<IdHilo, Sender<Instruccion>>,
+        canales_enviar_instrucciones: HashMap<IdHilo, Emisor<Instruccion>>,
         canal_recibir_instrucciones: Receiver<Instruccion>,
         registrador: Registrador,
     ) -> Self {
@@ -48,6 +74,10 @@ impl Hilo {
             registrador,
             suscripciones: Suscripciones::new(),
             conexiones: HashMap::new(),
+            poll,
+            despertador,
+            listas: HashSet::new(),
+            vencimientos: HashMap::new(),
         }
     }
 
@@ -62,13 +92,48 @@ impl Hilo {
     /// Punto inicial de ejecución del hilo, este nunca termina
     /// (al menos que ocurra un error fatal).
     pub fn inicio(&mut self) {
+        let mut eventos = Events::with_capacity(EVENTOS_POR_ESPERA);
+
         loop {
+            self.esperar_eventos(&mut eventos);
             self.tick();
-            // wait 500 microseconds
-            thread::sleep(std::time::Duration::from_micros(500));
         }
     }
 
+    /// Bloquea el hilo hasta que el socket de alguna conexión esté listo, otro
+    /// hilo lo despierte o venza el intervalo de alguna conexión
+    fn esperar_eventos(&mut self, eventos: &mut Events) {
+        let espera = self.tiempo_de_espera();
+
+        if let Err(e) = self.poll.poll(eventos, espera) {
+            if e.kind() != io::ErrorKind::Interrupted {
+                self.registrador
+                    .error(&format!("Error al esperar eventos: {}", e), None);
+            }
+        }
+
+        for evento in eventos.iter() {
+            if evento.token() != TOKEN_DESPERTADOR {
+                self.listas.insert(evento.token().0 as IdConexion);
+            }
+        }
+        self.listas.extend(self.despertador.tomar_pedidas());
+    }
+
+    /// Cuánto se puede esperar hasta que haya que hacer tick de alguna conexión.
+    /// `None` si no hace falta hasta que haya eventos
+    fn tiempo_de_espera(&self) -> Option<Duration> {
+        if !self.listas.is_empty() {
+            return Some(Duration::ZERO);
+        }
+
+        let ahora = Instant::now();
+        self.vencimientos
+            .values()
+            .min()
+            .map(|vencimiento| vencimiento.saturating_duration_since(ahora))
+    }
+
     /// Este método se ejecuta en cada ciclo del hilo.
     /// Se encarga de procesar las instrucciones recibidas y
     /// realizar las acciones correspondientes.
@@ -85,10 +150,21 @@ impl Hilo {
     // nivel de tipo "Informacion". Ademas, se insertan en las
     // conexiones del hilo el id de la conexion y la conexion
     pub fn recibir_conexiones(&mut self) {
-        while let Ok((id_conexion, conexion)) = self.canal_recibir_conexiones.try_recv() {
+        while let Ok((id_conexion, mut conexion)) = self.canal_recibir_conexiones.try_recv() {
             self.registrador
                 .info(&format!("Recibida conexión con id {}", id_conexion), None);
+
+            let token = Token(id_conexion as usize);
+            if let Err(e) = conexion.registrar(self.poll.registry(), token, &self.despertador) {
+                self.registrador.error(
+                    &format!("No se pudo registrar la conexión: {}", e),
+                    Some(id_conexion),
+                );
+                continue;
+            }
+
             self.conexiones.insert(id_conexion, conexion);
+            self.listas.insert(id_conexion);
         }
     }
 
@@ -119,6 +195,8 @@ impl Hilo {
     }
 
     pub fn recibir_publicacion(&mut self, publicacion: Publicacion) {
+        let mut agotadas = Vec::new();
+
         // Iterar sobre las suscripciones y enviar la publicación a cada una
         // Cabe destacar que solo itera en las suscripciones que coinciden con el tópico de la publicación
         for suscripcion in self.suscripciones.suscripciones_topico(&publicacion.topico) {
@@ -130,6 +208,10 @@ impl Hilo {
                 conexion.escribir_publicacion_mensaje(
                     &publicacion.mensaje(suscripcion.id().to_owned()),
                 );
+                self.listas.insert(*suscripcion.id_conexion());
+                if suscripcion.registrar_entrega() {
+                    agotadas.push((*suscripcion.id_conexion(), suscripcion.id().to_owned()));
+                }
             } else {
                 self.registrador.error(
                     "No se encontró una conexión que debería existir",
@@ -137,6 +219,10 @@ impl Hilo {
                 )
             }
         }
+
+        for (id_conexion, id_suscripcion) in agotadas {
+            self.eliminar_suscripcion(id_conexion, id_suscripcion);
+        }
     }
 
     pub fn recibir_publicacion_exacto(
@@ -144,19 +230,52 @@ impl Hilo {
         suscripcion: &Suscripcion,
         publicacion: Publicacion,
     ) {
+        // La instrucción pudo haberse enviado antes de que la suscripción
+        // recibiera todos sus mensajes
+        if suscripcion.entregas_restantes() == Some(0) {
+            return;
+        }
+
         if let Some(conexion) = self.conexiones.get_mut(suscripcion.id_conexion()) {
             conexion
                 .escribir_publicacion_mensaje(&publicacion.mensaje(suscripcion.id().to_owned()));
+            self.listas.insert(*suscripcion.id_conexion());
+            if suscripcion.registrar_entrega() {
+                self.eliminar_suscripcion(*suscripcion.id_conexion(), suscripcion.id().to_owned());
+            }
         }
     }
 
+    /// Elimina la suscripción de este hilo y de todos los demás
+    fn eliminar_suscripcion(&mut self, id_conexion: IdConexion, id_suscripcion: IdSuscripcion) {
+        self.suscripciones.desuscribir(id_conexion, &id_suscripcion);
+        self.enviar_instruccion(Instruccion::Desuscribir(id_conexion, id_suscripcion));
+    }
+
+    /// Hace tick de las conexiones que tuvieron eventos, recibieron mensajes o
+    /// a las que se les venció el intervalo, y procesa sus instrucciones
     pub fn tick_conexiones(&mut self) {
         let mut salidas = Vec::new();
+        let listas = std::mem::take(&mut self.listas);
+        let ahora = Instant::now();
 
         for (id_conexion, conexion) in self.conexiones.iter_mut() {
+            let vencida = self
+                .vencimientos
+                .get(id_conexion)
+                .is_some_and(|vencimiento| *vencimiento <= ahora);
+            if !vencida && !listas.contains(id_conexion) {
+                continue;
+            }
+
             let mut tick_salida = TickContexto::new(self.id, *id_conexion);
             conexion.tick(&mut tick_salida);
             salidas.push(tick_salida);
+
+            match conexion.intervalo_tick() {
+                Some(intervalo) => self.vencimientos.insert(*id_conexion, ahora + intervalo),
+                None => self.vencimientos.remove(id_conexion),
+            };
         }
 
         for salida in salidas {
@@ -188,11 +307,25 @@ impl Hilo {
                             Some(salida.id_conexion),
                         );
 
-                        self.suscripciones.desuscribir(id_conexion, &id_suscripcion);
-                        self.enviar_instruccion(Instruccion::Desuscribir(
-                            id_conexion,
-                            id_suscripcion,
-                        ));
+                        self.eliminar_suscripcion(id_conexion, id_suscripcion);
+                    }
+                    Instruccion::DesuscribirDespues(id_conexion, id_suscripcion, maximo) => {
+                        self.registrador.info(
+                            &format!(
+                                "Nueva desuscripcion: id_con={} id_sub={} max_msgs={}",
+                                &id_conexion, &id_suscripcion, maximo
+                            ),
+                            Some(salida.id_conexion),
+                        );
+
+                        // Si ya recibió esa cantidad de mensajes se elimina en el momento
+                        let agotada = self
+                            .suscripciones
+                            .suscripcion(id_conexion, &id_suscripcion)
+                            .is_some_and(|suscripcion| suscripcion.limitar_entregas(maximo));
+                        if agotada {
+                            self.eliminar_suscripcion(id_conexion, id_suscripcion);
+                        }
                     }
                     _ => {}
                 }
@@ -262,14 +395,182 @@ impl Hilo {
                 for suscripcion in self.suscripciones.suscripciones_conexion(id_conexion) {
                     suscripciones_eliminar.push((*id_conexion, suscripcion.id().to_owned()));
                 }
+                // Al cerrarse el socket se deja de esperar sus eventos
+                self.vencimientos.remove(id_conexion);
             }
 
             esta_conextado
         });
 
         for (id_conexion, id_suscripcion) in suscripciones_eliminar {
-            self.suscripciones.desuscribir(id_conexion, &id_suscripcion);
-            self.enviar_instruccion(Instruccion::Desuscribir(id_conexion, id_suscripcion));
+            self.eliminar_suscripcion(id_conexion, id_suscripcion);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc::{channel, Sender},
+            Arc,
+        },
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use mio::{Events, Poll, Registry, Token};
+
+    use crate::{
+        conexion::{id::IdConexion, r#trait::Conexion, tick_contexto::TickContexto},
+        publicacion::mensaje::PublicacionMensaje,
+        registrador::Registrador,
+    };
+
+    use super::{
+        despertador::{Aviso, Despertador},
+        emisor::Emisor,
+        Hilo,
+    };
+
+    /// Espera máxima por si el despertador no funciona, para que la prueba no
+    /// se quede bloqueada
+    const LIMITE_ESPERA: Duration = Duration::from_secs(5);
+
+    /// Conexión interna que cuenta sus ticks y solo los necesita cuando le avisan
+    struct ConexionPrueba {
+        ticks: Arc<AtomicUsize>,
+        aviso: Aviso,
+    }
+
+    impl Conexion for ConexionPrueba {
+        fn obtener_id(&self) -> u64 {
+            1
+        }
+
+        fn tick(&mut self, _salida: &mut TickContexto) {
+            self.ticks.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn escribir_publicacion_mensaje(&mut self, _mensaje: &PublicacionMensaje) {}
+
+        fn esta_conectado(&self) -> bool {
+            true
         }
+
+        fn setear_id_conexion(&mut self, _id_conexion: u64) {}
+
+        fn registrar(
+            &mut self,
+            _registro: &Registry,
+            token: Token,
+            despertador: &Despertador,
+        ) -> io::Result<()> {
+            self.aviso.vincular(despertador, token.0 as IdConexion);
+            Ok(())
+        }
+
+        fn intervalo_tick(&self) -> Option<Duration> {
+            None
+        }
+    }
+
+    type EmisorConexiones = Emisor<(IdConexion, Box<dyn Conexion + Send>)>;
+
+    fn hilo() -> (Hilo, EmisorConexiones, Sender<super::Instruccion>) {
+        let poll = Poll::new().unwrap();
+        let despertador = Despertador::new(poll.registry()).unwrap();
+        let (tx_conexiones, rx_conexiones) = channel();
+        let (tx_instrucciones, rx_instrucciones) = channel();
+
+        let hilo = Hilo::new(
+            0,
+            poll,
+            despertador.clone(),
+            rx_conexiones,
+            HashMap::new(),
+            rx_instrucciones,
+            Registrador::new(Some(false)),
+        );
+
+        (
+            hilo,
+            Emisor::new(tx_conexiones, despertador),
+            tx_instrucciones,
+        )
+    }
+
+    /// Espera como lo hace `Hilo::inicio` y devuelve cuánto tardó
+    fn esperar_y_tick(hilo: &mut Hilo) -> Duration {
+        let mut eventos = Events::with_capacity(8);
+        let inicio = Instant::now();
+        hilo.esperar_eventos(&mut eventos);
+        hilo.tick();
+        inicio.elapsed()
+    }
+
+    #[test]
+    fn tiempo_de_espera_hasta_el_primer_vencimiento() {
+        let (mut hilo, _tx_conexiones, _tx_instrucciones) = hilo();
+        assert_eq!(hilo.tiempo_de_espera(), None);
+
+        let ahora = Instant::now();
+        hilo.vencimientos.insert(1, ahora + Duration::from_secs(5));
+        hilo.vencimientos.insert(2, ahora + Duration::from_secs(1));
+
+        let espera = hilo.tiempo_de_espera().unwrap();
+        assert!(espera <= Duration::from_secs(1));
+        assert!(espera > Duration::from_millis(500));
+
+        hilo.vencimientos.insert(3, ahora - Duration::from_secs(1));
+        assert_eq!(hilo.tiempo_de_espera(), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn tiempo_de_espera_nulo_con_conexiones_listas() {
+        let (mut hilo, _tx_conexiones, _tx_instrucciones) = hilo();
+        hilo.vencimientos
+            .insert(1, Instant::now() + Duration::from_secs(5));
+        hilo.listas.insert(2);
+
+        assert_eq!(hilo.tiempo_de_espera(), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn emisor_despierta_al_hilo() {
+        let (mut hilo, tx_conexiones, _tx_instrucciones) = hilo();
+        // Conexión que no existe, solo para que la espera tenga un límite
+        hilo.vencimientos.insert(99, Instant::now() + LIMITE_ESPERA);
+
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let conexion = ConexionPrueba {
+            ticks: ticks.clone(),
+            aviso: Aviso::new(),
+        };
+        let aviso = conexion.aviso.clone();
+
+        let envio = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            tx_conexiones.send((1, Box::new(conexion))).unwrap();
+        });
+        let espera = esperar_y_tick(&mut hilo);
+        envio.join().unwrap();
+
+        assert!(espera < LIMITE_ESPERA);
+        assert_eq!(ticks.load(Ordering::Relaxed), 1);
+        assert!(!hilo.vencimientos.contains_key(&1));
+
+        let envio = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            aviso.avisar();
+        });
+        let espera = esperar_y_tick(&mut hilo);
+        envio.join().unwrap();
+
+        assert!(espera < LIMITE_ESPERA);
+        assert_eq!(ticks.load(Ordering::Relaxed), 2);
     }
 }
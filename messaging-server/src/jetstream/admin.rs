@@ -1,23 +1,89 @@
 use std::{
     collections::HashMap,
-    sync::mpsc::{channel, Receiver, Sender},
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
 use lib::jet_stream::{
     admin_nombres_streams_respuesta::JSNombresStreamsRespuesta,
-    api_info_response::JSApiInfoResponse, crear_stream_respuesta::JSCrearStreamRespuesta,
-    stream_config::StreamConfig, stream_info::StreamInfo,
+    api_info_response::JSApiInfoResponse,
+    crear_stream_respuesta::JSCrearStreamRespuesta,
+    error::{JSError, JSErrorRespuesta},
+    restaurar_stream_respuesta::JSRestaurarStreamRespuesta,
+    stream_config::{StorageType, StreamConfig},
+    stream_info::StreamInfo,
     stream_list_response::JetStreamStreamListResponse,
 };
+use mio::{Registry, Token};
 
 use crate::{
     conexion::{r#trait::Conexion, tick_contexto::TickContexto},
-    publicacion::Publicacion,
+    hilo::{
+        despertador::{Aviso, Despertador},
+        emisor::EmisorConexion,
+    },
+    publicacion::{mensaje::PublicacionMensaje, Publicacion},
     registrador::Registrador,
     suscripciones::{suscripcion::Suscripcion, topico::Topico},
 };
 
-use super::{actualizacion::ActualizacionJS, stream::JetStreamStream};
+use super::{
+    actualizacion::ActualizacionJS,
+    almacenamiento::{
+        archivo::AlmacenamientoArchivo, memoria::AlmacenamientoMemoria, Almacenamiento,
+    },
+    archivo_snapshot::desempaquetar,
+    cuenta::{Cuenta, LimitesCuenta, UsoStream},
+    stream::{topicos_validos, JetStreamStream},
+};
+
+/// Nombre del archivo donde se guarda la configuración de cada stream
+const ARCHIVO_CONFIG_STREAM: &str = "config.json";
+
+/// Peticiones que atiende cada stream, con el tipo de su respuesta. El admin
+/// también las recibe y responde `stream not found` si el stream no existe.
+/// El nombre del stream es el token del `*`
+const PETICIONES_A_STREAMS: [(&str, &str); 12] = [
+    ("$JS.API.STREAM.INFO.*", "stream_info_response"),
+    ("$JS.API.STREAM.UPDATE.*", "stream_update_response"),
+    ("$JS.API.STREAM.DELETE.*", "stream_delete_response"),
+    ("$JS.API.STREAM.PURGE.*", "stream_purge_response"),
+    ("$JS.API.STREAM.SNAPSHOT.*", "stream_snapshot_response"),
+    ("$JS.API.STREAM.MSG.GET.*", "stream_msg_get_response"),
+    ("$JS.API.STREAM.MSG.DELETE.*", "stream_msg_delete_response"),
+    ("$JS.API.CONSUMER.CREATE.*.>", "consumer_create_response"),
+    ("$JS.API.CONSUMER.LIST.*", "consumer_list_response"),
+    ("$JS.API.CONSUMER.NAMES.*", "consumer_names_response"),
+    ("$JS.API.CONSUMER.INFO.*.*", "consumer_info_response"),
+    ("$JS.API.CONSUMER.DELETE.*.*", "consumer_delete_response"),
+];
+
+/// Tiempo sin recibir bloques tras el cual se abandona una restauración
+const LIMITE_RESTAURACION: Duration = Duration::from_secs(10);
+
+/// Tamaño máximo de un snapshot a restaurar, aunque la cuenta no limite `max_storage`
+const MAXIMO_RESTAURACION: u64 = 1 << 30;
+
+/// Extensión del archivo donde se juntan los bloques de una restauración, junto
+/// al directorio del stream
+const EXTENSION_RESTAURACION: &str = "restauracion";
+
+/// Restauración de un stream en curso: se juntan los bloques del snapshot en un
+/// archivo hasta que llega el mensaje vacío que indica el final
+struct Restauracion {
+    /// Id de la suscripción al tópico por el que llegan los bloques
+    id_suscripcion: String,
+    ruta: PathBuf,
+    archivo: fs::File,
+    bytes: u64,
+    ultima_actividad: Instant,
+}
 
 pub struct JestStreamAdminConexion {
     id: u64,
@@ -26,8 +92,19 @@ pub struct JestStreamAdminConexion {
     respuestas: Vec<Publicacion>,
     streams: HashMap<String, StreamInfo>,
     rx_datos_js: Receiver<ActualizacionJS>,
-    tx_datos_js: Sender<ActualizacionJS>,
+    tx_datos_js: EmisorConexion<ActualizacionJS>,
+    /// Lo vincula con su hilo para que los streams puedan despertarlo
+    aviso: Aviso,
     registrador: Registrador,
+    /// Directorio donde se guardan los streams (uno por subdirectorio)
+    directorio: PathBuf,
+    /// Restauraciones en curso, por nombre del stream
+    restauraciones: HashMap<String, Restauracion>,
+    /// Tópicos que falta suscribir, con el id de la suscripción
+    suscripciones_pendientes: Vec<(String, String)>,
+    desuscripciones_pendientes: Vec<String>,
+    /// Uso y límites de la cuenta, compartidos con los streams
+    cuenta: Arc<Cuenta>,
 }
 
 impl JestStreamAdminConexion {
@@ -35,8 +112,11 @@ impl JestStreamAdminConexion {
         id: u64,
         tx_conexiones: Sender<Box<dyn Conexion + Send>>,
         registrador: Registrador,
+        directorio: PathBuf,
+        limites: LimitesCuenta,
     ) -> JestStreamAdminConexion {
         let (tx_datos_js, rx_datos_js) = channel();
+        let aviso = Aviso::new();
 
         JestStreamAdminConexion {
             preparado: false,
@@ -45,8 +125,14 @@ impl JestStreamAdminConexion {
             respuestas: Vec::new(),
             streams: HashMap::new(),
             rx_datos_js,
-            tx_datos_js,
+            tx_datos_js: EmisorConexion::new(tx_datos_js, aviso.clone()),
+            aviso,
             registrador,
+            directorio,
+            restauraciones: HashMap::new(),
+            suscripciones_pendientes: Vec::new(),
+            desuscripciones_pendientes: Vec::new(),
+            cuenta: Arc::new(Cuenta::new(limites)),
         }
     }
 
@@ -64,10 +150,41 @@ impl JestStreamAdminConexion {
         while let Ok(actualizacion) = self.rx_datos_js.try_recv() {
             match actualizacion {
                 ActualizacionJS::Stream(stream_info) => {
+                    let config_modificada = self
+                        .streams
+                        .get(&stream_info.config.name)
+                        .is_some_and(|anterior| anterior.config != stream_info.config);
+
+                    if config_modificada {
+                        if let Err(e) = self.guardar_config(&stream_info.config) {
+                            self.registrador.error(
+                                &format!(
+                                    "Error al guardar la configuración del stream {}: {}",
+                                    stream_info.config.name, e
+                                ),
+                                Some(self.id),
+                            );
+                        }
+                    }
+
+                    let bytes = stream_info.state.bytes;
+                    let (memoria, almacenamiento) = match stream_info.config.storage {
+                        StorageType::Memory => (bytes, 0),
+                        StorageType::File => (0, bytes),
+                    };
+                    self.cuenta.actualizar_uso(
+                        &stream_info.config.name,
+                        UsoStream {
+                            memoria,
+                            almacenamiento,
+                            consumers: stream_info.state.consumer_count,
+                        },
+                    );
                     self.streams
                         .insert(stream_info.config.name.clone(), stream_info);
                 }
                 ActualizacionJS::StreamEliminado(nombre) => {
+                    self.cuenta.quitar_stream(&nombre);
                     self.streams.remove(&nombre);
                 }
                 ActualizacionJS::Consumer(_) => {}
@@ -76,14 +193,420 @@ impl JestStreamAdminConexion {
         }
     }
 
-    fn crear_stream(&self, config: StreamConfig) {
+    /// Vuelve a crear los streams que quedaron guardados en disco
+    fn cargar_streams(&mut self) {
+        let entradas = match fs::read_dir(&self.directorio) {
+            Ok(entradas) => entradas,
+            Err(_) => return,
+        };
+
+        for entrada in entradas.flatten() {
+            // Restauraciones que quedaron a medias al apagarse el servidor
+            if entrada.path().extension().and_then(|e| e.to_str()) == Some(EXTENSION_RESTAURACION) {
+                let _ = fs::remove_file(entrada.path());
+                continue;
+            }
+
+            let ruta_config = entrada.path().join(ARCHIVO_CONFIG_STREAM);
+
+            let config = match fs::read_to_string(&ruta_config)
+                .ok()
+                .and_then(|json| StreamConfig::from_json(&json).ok())
+            {
+                Some(config) => config,
+                None => continue,
+            };
+
+            match self.crear_stream(config.clone()) {
+                Ok(()) => self.registrador.info(
+                    &format!("Stream {} cargado desde disco", config.name),
+                    Some(self.id),
+                ),
+                Err(e) => self.registrador.error(
+                    &format!("Error al cargar el stream {}: {}", config.name, e),
+                    Some(self.id),
+                ),
+            }
+        }
+    }
+
+    fn guardar_config(&self, config: &StreamConfig) -> io::Result<()> {
+        fs::write(
+            self.directorio
+                .join(&config.name)
+                .join(ARCHIVO_CONFIG_STREAM),
+            config.to_json().map_err(io::Error::other)?,
+        )
+    }
+
+    /// Valida la configuración y crea el stream. Si ya existe con la misma
+    /// configuración no se hace nada, y si tiene otra es un error
+    fn procesar_crear_stream(
+        &mut self,
+        topico: &str,
+        payload: &[u8],
+    ) -> Result<JSCrearStreamRespuesta, JSError> {
+        let config = StreamConfig::from_json(&String::from_utf8_lossy(payload))
+            .map_err(|_| JSError::peticion_invalida())?;
+
+        if topico.strip_prefix("$JS.API.STREAM.CREATE.") != Some(config.name.as_str()) {
+            return Err(JSError::nombre_stream_no_coincide());
+        }
+        if !config.nombre_valido() {
+            return Err(JSError::configuracion_stream_invalida(
+                "invalid stream name",
+            ));
+        }
+        if !config.origenes_validos() {
+            return Err(JSError::configuracion_stream_invalida(
+                "invalid mirror or sources",
+            ));
+        }
+        if !topicos_validos(&config) {
+            return Err(JSError::configuracion_stream_invalida("invalid subject"));
+        }
+
+        if self.restauraciones.contains_key(&config.name) {
+            return Err(JSError::nombre_stream_en_uso());
+        }
+        if let Some(existente) = self.streams.get(&config.name) {
+            if existente.config != config {
+                return Err(JSError::nombre_stream_en_uso());
+            }
+            return Ok(JSCrearStreamRespuesta::new(config, false));
+        }
+        if !self.admite_stream() {
+            return Err(JSError::maximo_streams_alcanzado());
+        }
+
+        self.crear_stream(config.clone()).map_err(|e| {
+            self.registrador.error(
+                &format!("Error al crear el stream {}: {}", config.name, e),
+                Some(self.id),
+            );
+            JSError::creacion_stream_fallida(&e.to_string())
+        })?;
+
+        Ok(JSCrearStreamRespuesta::new(config, true))
+    }
+
+    /// Si se puede crear un stream más sin superar `max_streams`. Las restauraciones
+    /// en curso cuentan como streams
+    fn admite_stream(&self) -> bool {
+        self.cuenta
+            .admite_stream(self.streams.len() + self.restauraciones.len())
+    }
+
+    /// Uso de la cuenta sumando el de todos los streams, con sus límites y las
+    /// peticiones recibidas
+    fn informacion_cuenta(&self) -> JSApiInfoResponse {
+        let uso = self.cuenta.uso_total(None);
+
+        JSApiInfoResponse {
+            memory: uso.memoria,
+            storage: uso.almacenamiento,
+            limits: self.cuenta.limites_api(),
+            api: self.cuenta.estadisticas_api(),
+            ..JSApiInfoResponse::new(self.streams.len() as u64, uso.consumers)
+        }
+    }
+
+    /// Responde a una petición a un stream que no existe. Si existe, la responde el stream
+    fn responder_stream_desconocido(&mut self, tipo: &str, mensaje: &PublicacionMensaje) {
+        let reply_to = match &mensaje.replay_to {
+            Some(reply_to) => reply_to,
+            None => return,
+        };
+
+        let patron = PETICIONES_A_STREAMS
+            .iter()
+            .find(|(_, tipo_peticion)| *tipo_peticion == tipo)
+            .map(|(patron, _)| *patron)
+            .unwrap_or_default();
+        let posicion = patron.split('.').position(|token| token == "*");
+        let nombre = posicion.and_then(|posicion| mensaje.topico.split('.').nth(posicion));
+
+        match nombre {
+            Some(nombre)
+                if self.streams.contains_key(nombre)
+                    || self.restauraciones.contains_key(nombre) => {}
+            _ => self.responder_error(
+                reply_to,
+                &format!("io.nats.jetstream.api.v1.{}", tipo),
+                JSError::stream_no_encontrado(),
+            ),
+        }
+    }
+
+    fn responder<E: std::fmt::Display>(&mut self, reply_to: &str, respuesta: Result<String, E>) {
+        match respuesta {
+            Ok(respuesta) => self.respuestas.push(Publicacion::new(
+                reply_to.to_string(),
+                respuesta.as_bytes().to_owned(),
+                None,
+                None,
+            )),
+            Err(e) => self.registrador.error(
+                &format!("Error al serializar respuesta: {}", e),
+                Some(self.id),
+            ),
+        }
+    }
+
+    fn responder_error(&mut self, reply_to: &str, tipo: &str, error: JSError) {
+        self.cuenta.registrar_error();
+        if let Ok(respuesta) = JSErrorRespuesta::new(tipo, error).to_json() {
+            self.respuestas.push(Publicacion::new(
+                reply_to.to_string(),
+                respuesta.as_bytes().to_owned(),
+                None,
+                None,
+            ));
+        }
+    }
+
+    /// Empieza a recibir el snapshot de un stream que todavía no existe y responde
+    /// el tópico al que hay que enviar los bloques
+    fn iniciar_restauracion(
+        &mut self,
+        topico: &str,
+    ) -> Result<JSRestaurarStreamRespuesta, JSError> {
+        let nombre = topico
+            .strip_prefix("$JS.API.STREAM.RESTORE.")
+            .unwrap_or_default();
+        let config = StreamConfig {
+            name: nombre.to_string(),
+            ..Default::default()
+        };
+        if !config.nombre_valido() {
+            return Err(JSError::peticion_invalida());
+        }
+        if self.streams.contains_key(nombre) || self.restauraciones.contains_key(nombre) {
+            return Err(JSError::stream_existente_al_restaurar());
+        }
+        if !self.admite_stream() {
+            return Err(JSError::maximo_streams_alcanzado());
+        }
+
+        let ruta = self
+            .directorio
+            .join(format!("{}.{}", nombre, EXTENSION_RESTAURACION));
+        let archivo = fs::create_dir_all(&self.directorio)
+            .and_then(|()| fs::File::create(&ruta))
+            .map_err(|e| JSError::restauracion_fallida(&e.to_string()))?;
+
+        let topico_bloques = format!("$JS.SNAPSHOT.RESTORE.{}.{}", nombre, nuid::next());
+        let id_suscripcion = format!("restaurar|{}", nombre);
+        self.suscripciones_pendientes
+            .push((topico_bloques.clone(), id_suscripcion.clone()));
+        self.restauraciones.insert(
+            nombre.to_string(),
+            Restauracion {
+                id_suscripcion,
+                ruta,
+                archivo,
+                bytes: 0,
+                ultima_actividad: Instant::now(),
+            },
+        );
+
+        Ok(JSRestaurarStreamRespuesta::new(topico_bloques))
+    }
+
+    /// Recibe un bloque del snapshot. Los bloques se confirman con una respuesta
+    /// vacía, y al final se crea el stream y se responde su configuración. Si el
+    /// snapshot no entra en la cuenta se abandona la restauración
+    fn recibir_bloque_restauracion(&mut self, nombre: &str, mensaje: &PublicacionMensaje) {
+        let restauracion = match self.restauraciones.get_mut(nombre) {
+            Some(restauracion) => restauracion,
+            None => return,
+        };
+
+        if !mensaje.payload.is_empty() {
+            let bytes = restauracion.bytes + mensaje.payload.len() as u64;
+            let resultado = if bytes > MAXIMO_RESTAURACION {
+                Err(JSError::restauracion_fallida(
+                    "snapshot exceeds maximum size",
+                ))
+            } else if !self.cuenta.admite_bytes(nombre, 0, bytes) {
+                Err(JSError::almacenamiento_insuficiente())
+            } else {
+                restauracion
+                    .archivo
+                    .write_all(&mensaje.payload)
+                    .map_err(|e| JSError::restauracion_fallida(&e.to_string()))
+            };
+
+            match resultado {
+                Ok(()) => {
+                    restauracion.bytes = bytes;
+                    restauracion.ultima_actividad = Instant::now();
+                    if let Some(reply_to) = &mensaje.replay_to {
+                        self.respuestas.push(Publicacion::new(
+                            reply_to.to_string(),
+                            Vec::new(),
+                            None,
+                            None,
+                        ));
+                    }
+                }
+                Err(error) => {
+                    self.abandonar_restauracion(nombre);
+                    self.registrador.error(
+                        &format!(
+                            "Restauración del stream {} abandonada: {}",
+                            nombre, error.description
+                        ),
+                        Some(self.id),
+                    );
+                    if let Some(reply_to) = &mensaje.replay_to {
+                        self.responder_error(
+                            reply_to,
+                            "io.nats.jetstream.api.v1.stream_restore_response",
+                            error,
+                        );
+                    }
+                }
+            }
+            return;
+        }
+
+        let restauracion = match self.restauraciones.remove(nombre) {
+            Some(restauracion) => restauracion,
+            None => return,
+        };
+        self.desuscripciones_pendientes
+            .push(restauracion.id_suscripcion);
+
+        let resultado = self.restaurar_stream(nombre, &restauracion.ruta);
+        let _ = fs::remove_file(&restauracion.ruta);
+        match &resultado {
+            Ok(_) => self.registrador.info(
+                &format!("Stream {} restaurado desde un snapshot", nombre),
+                Some(self.id),
+            ),
+            Err(error) => self.registrador.error(
+                &format!(
+                    "Error al restaurar el stream {}: {}",
+                    nombre, error.description
+                ),
+                Some(self.id),
+            ),
+        }
+
+        if let Some(reply_to) = &mensaje.replay_to {
+            match resultado {
+                Ok(config) => {
+                    let respuesta = JSCrearStreamRespuesta::new(config, true).to_json();
+                    self.responder(reply_to, respuesta);
+                }
+                Err(error) => self.responder_error(
+                    reply_to,
+                    "io.nats.jetstream.api.v1.stream_restore_response",
+                    error,
+                ),
+            }
+        }
+    }
+
+    /// Escribe los archivos del snapshot en el directorio del stream y lo crea con
+    /// la configuración que traía, con el nombre pedido
+    fn restaurar_stream(&mut self, nombre: &str, snapshot: &Path) -> Result<StreamConfig, JSError> {
+        if self.streams.contains_key(nombre) {
+            return Err(JSError::stream_existente_al_restaurar());
+        }
+
+        let directorio_stream = self.directorio.join(nombre);
+        if directorio_stream.exists() {
+            return Err(JSError::restauracion_fallida(
+                "stream directory already exists",
+            ));
+        }
+
+        let resultado = fs::File::open(snapshot)
+            .and_then(|mut archivo| desempaquetar(&mut archivo, &directorio_stream))
+            .and_then(|()| fs::read_to_string(directorio_stream.join(ARCHIVO_CONFIG_STREAM)))
+            .and_then(|json| StreamConfig::from_json(&json).map_err(io::Error::other))
+            .and_then(|config| {
+                let config = StreamConfig {
+                    name: nombre.to_string(),
+                    ..config
+                };
+                self.crear_stream(config.clone())?;
+                Ok(config)
+            });
+
+        resultado.map_err(|e| {
+            let _ = fs::remove_dir_all(&directorio_stream);
+            JSError::restauracion_fallida(&e.to_string())
+        })
+    }
+
+    /// Abandona las restauraciones de clientes que dejaron de enviar bloques
+    fn expirar_restauraciones(&mut self) {
+        let vencidas = self
+            .restauraciones
+            .iter()
+            .filter(|(_, restauracion)| {
+                restauracion.ultima_actividad.elapsed() >= LIMITE_RESTAURACION
+            })
+            .map(|(nombre, _)| nombre.clone())
+            .collect::<Vec<String>>();
+
+        for nombre in vencidas {
+            self.abandonar_restauracion(&nombre);
+            self.registrador.advertencia(
+                &format!(
+                    "Restauración del stream {} abandonada: no llegaron más bloques",
+                    nombre
+                ),
+                Some(self.id),
+            );
+        }
+    }
+
+    /// Deja de recibir los bloques de una restauración y borra los que llegaron
+    fn abandonar_restauracion(&mut self, nombre: &str) {
+        if let Some(restauracion) = self.restauraciones.remove(nombre) {
+            self.desuscripciones_pendientes
+                .push(restauracion.id_suscripcion);
+            let _ = fs::remove_file(&restauracion.ruta);
+        }
+    }
+
+    fn crear_stream(&mut self, config: StreamConfig) -> io::Result<()> {
+        let directorio_stream = self.directorio.join(&config.name);
+
+        // La configuración y los consumers siempre van en el directorio del stream,
+        // pero un stream en memoria vuelve a empezar vacío al reiniciar el servidor
+        let almacenamiento: Box<dyn Almacenamiento> = match config.storage {
+            StorageType::File => Box::new(AlmacenamientoArchivo::abrir(&directorio_stream)?),
+            StorageType::Memory => Box::new(AlmacenamientoMemoria::importar(&directorio_stream)?),
+        };
+        self.guardar_config(&config)?;
+
+        // Se registra de inmediato para que no se pueda crear dos veces
+        // antes de que el stream envíe su primera actualización
+        self.streams.insert(
+            config.name.clone(),
+            StreamInfo {
+                config: config.clone(),
+                ..Default::default()
+            },
+        );
+
         let stream = JetStreamStream::new(
             config,
+            almacenamiento,
+            directorio_stream,
             self.tx_datos_js.clone(),
             self.tx_conexiones.clone(),
             self.registrador.clone(),
+            self.cuenta.clone(),
         );
         let _ = self.tx_conexiones.send(Box::new(stream));
+
+        Ok(())
     }
 }
 
@@ -98,13 +621,30 @@ impl Conexion for JestStreamAdminConexion {
 
     fn tick(&mut self, contexto: &mut TickContexto) {
         if !self.preparado {
+            self.suscribir(contexto, "$JS.API.>", "api");
             self.suscribir(contexto, "$JS.API.INFO", "info");
             self.suscribir(contexto, "$JS.API.STREAM.CREATE.*", "stream.crear");
             self.suscribir(contexto, "$JS.API.STREAM.LIST", "stream.listar");
             self.suscribir(contexto, "$JS.API.STREAM.NAMES", "stream.nombres");
+            self.suscribir(contexto, "$JS.API.STREAM.RESTORE.*", "stream.restaurar");
+            for (patron, tipo) in PETICIONES_A_STREAMS {
+                self.suscribir(contexto, patron, &format!("stream_desconocido|{}", tipo));
+            }
+            self.cargar_streams();
             self.preparado = true;
         }
 
+        self.expirar_restauraciones();
+
+        // Las suscripciones van antes que las respuestas: el cliente envía los
+        // bloques de una restauración apenas recibe el tópico
+        for (topico, id_suscripcion) in std::mem::take(&mut self.suscripciones_pendientes) {
+            self.suscribir(contexto, &topico, &id_suscripcion);
+        }
+        for id_suscripcion in self.desuscripciones_pendientes.drain(..) {
+            contexto.desuscribir(id_suscripcion);
+        }
+
         for respuesta in self.respuestas.drain(..) {
             contexto.publicar(respuesta);
         }
@@ -117,42 +657,37 @@ impl Conexion for JestStreamAdminConexion {
         mensaje: &crate::publicacion::mensaje::PublicacionMensaje,
     ) {
         match mensaje.sid.as_str() {
+            "api" => self.cuenta.registrar_peticion(),
             "info" => {
                 // Si hay reply_to, es una respuesta a una petición
                 if let Some(reply_to) = &mensaje.replay_to {
-                    if let Ok(info) =
-                        JSApiInfoResponse::new(self.streams.values().len() as i32, 0).to_json()
-                    {
-                        self.respuestas.push(Publicacion::new(
-                            reply_to.to_string(),
-                            info.as_bytes().to_owned(),
-                            None,
-                            None,
-                        ))
-                    }
+                    let info = self.informacion_cuenta().to_json();
+                    self.responder(reply_to, info);
                 }
             }
             "stream.crear" => {
-                if let Ok(config) =
-                    StreamConfig::from_json(&String::from_utf8_lossy(&mensaje.payload))
-                {
-                    let mut creado = true;
-                    if self.streams.contains_key(&config.name) {
-                        creado = false;
-                    } else {
-                        self.crear_stream(config.clone());
-                    }
+                let resultado = self.procesar_crear_stream(&mensaje.topico, &mensaje.payload);
 
-                    if let Some(reply_to) = &mensaje.replay_to {
-                        if let Ok(respuesta) = JSCrearStreamRespuesta::new(config, creado).to_json()
-                        {
-                            self.respuestas.push(Publicacion::new(
-                                reply_to.to_string(),
-                                respuesta.as_bytes().to_owned(),
-                                None,
-                                None,
-                            ));
-                        }
+                if let Some(reply_to) = &mensaje.replay_to {
+                    match resultado {
+                        Ok(respuesta) => self.responder(reply_to, respuesta.to_json()),
+                        Err(error) => self.responder_error(
+                            reply_to,
+                            "io.nats.jetstream.api.v1.stream_create_response",
+                            error,
+                        ),
+                    }
+                }
+            }
+            "stream.restaurar" => {
+                if let Some(reply_to) = &mensaje.replay_to {
+                    match self.iniciar_restauracion(&mensaje.topico) {
+                        Ok(respuesta) => self.responder(reply_to, respuesta.to_json()),
+                        Err(error) => self.responder_error(
+                            reply_to,
+                            "io.nats.jetstream.api.v1.stream_restore_response",
+                            error,
+                        ),
                     }
                 }
             }
@@ -197,9 +732,257 @@ impl Conexion for JestStreamAdminConexion {
             }
             _ => {}
         }
+
+        if let Some(nombre) = mensaje.sid.strip_prefix("restaurar|") {
+            self.recibir_bloque_restauracion(nombre, mensaje);
+        } else if let Some(tipo) = mensaje.sid.strip_prefix("stream_desconocido|") {
+            self.responder_stream_desconocido(tipo, mensaje);
+        }
     }
 
     fn esta_conectado(&self) -> bool {
         true
     }
+
+    /// Los streams avisan al hilo cuando le envían actualizaciones
+    fn registrar(
+        &mut self,
+        _registro: &Registry,
+        _token: Token,
+        despertador: &Despertador,
+    ) -> io::Result<()> {
+        self.aviso.vincular(despertador, self.id);
+        Ok(())
+    }
+
+    /// Solo hace falta un tick sin mensajes para abandonar la restauración que
+    /// vence primero
+    fn intervalo_tick(&self) -> Option<Duration> {
+        self.restauraciones
+            .values()
+            .map(|restauracion| {
+                LIMITE_RESTAURACION.saturating_sub(restauracion.ultima_actividad.elapsed())
+            })
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::{channel, Receiver};
+
+    use lib::jet_stream::{
+        crear_stream_respuesta::JSCrearStreamRespuesta,
+        eliminar_mensaje_peticion::JSPeticionEliminarMensaje,
+        error::{JSError, JSErrorRespuesta},
+        restaurar_stream_respuesta::JSRestaurarStreamRespuesta,
+        stream_config::{StorageType, StreamConfig},
+        stream_list_response::JetStreamStreamListResponse,
+    };
+
+    use crate::{
+        conexion::{r#trait::Conexion, tick_contexto::TickContexto},
+        jetstream::{archivo_snapshot::empaquetar, cuenta::LimitesCuenta},
+        publicacion::mensaje::PublicacionMensaje,
+        registrador::Registrador,
+    };
+
+    use super::JestStreamAdminConexion;
+
+    const RESPUESTA: &str = "_INBOX.respuesta";
+
+    fn admin(nombre: &str) -> (JestStreamAdminConexion, Receiver<Box<dyn Conexion + Send>>) {
+        admin_con_limites(nombre, LimitesCuenta::default())
+    }
+
+    fn admin_con_limites(
+        nombre: &str,
+        limites: LimitesCuenta,
+    ) -> (JestStreamAdminConexion, Receiver<Box<dyn Conexion + Send>>) {
+        let (tx_conexiones, rx_conexiones) = channel();
+        let directorio = std::env::temp_dir().join(format!("admin_prueba_{}", nombre));
+        let _ = std::fs::remove_dir_all(&directorio);
+
+        let mut admin = JestStreamAdminConexion::new(
+            0,
+            tx_conexiones,
+            Registrador::new(Some(false)),
+            directorio,
+            limites,
+        );
+        admin.tick(&mut TickContexto::new(0, 0));
+
+        (admin, rx_conexiones)
+    }
+
+    fn enviar(conexion: &mut dyn Conexion, sid: &str, topico: &str, payload: &[u8]) -> Vec<String> {
+        conexion.escribir_publicacion_mensaje(&PublicacionMensaje::new(
+            sid.to_string(),
+            topico.to_string(),
+            payload.to_vec(),
+            None,
+            Some(RESPUESTA.to_string()),
+        ));
+
+        let mut contexto = TickContexto::new(0, 0);
+        conexion.tick(&mut contexto);
+        contexto
+            .publicaciones()
+            .into_iter()
+            .filter(|publicacion| publicacion.topico == RESPUESTA)
+            .map(|publicacion| String::from_utf8_lossy(&publicacion.payload).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn listar_streams_informa_el_estado() {
+        let (mut admin, rx_conexiones) = admin("listar");
+
+        let config = StreamConfig {
+            name: "listado".to_string(),
+            subjects: vec!["listado.>".to_string()],
+            storage: StorageType::Memory,
+            ..Default::default()
+        };
+        enviar(
+            &mut admin,
+            "stream.crear",
+            "$JS.API.STREAM.CREATE.listado",
+            config.to_json().unwrap().as_bytes(),
+        );
+
+        let mut stream = rx_conexiones.try_recv().unwrap();
+        stream.tick(&mut TickContexto::new(0, 0));
+        for topico in ["listado.a", "listado.b", "listado.a"] {
+            enviar(
+                stream.as_mut(),
+                &format!("mensaje|{}", topico),
+                topico,
+                b"x",
+            );
+        }
+        let peticion = JSPeticionEliminarMensaje::new(2, false).to_json().unwrap();
+        enviar(
+            stream.as_mut(),
+            "eliminar_mensaje",
+            "$JS.API.STREAM.MSG.DELETE.listado",
+            peticion.as_bytes(),
+        );
+
+        // El admin recibe el estado en el tick y lo informa en el siguiente
+        admin.tick(&mut TickContexto::new(0, 0));
+        let respuestas = enviar(&mut admin, "stream.listar", "$JS.API.STREAM.LIST", b"");
+        let listado = JetStreamStreamListResponse::from_json(&respuestas[0]).unwrap();
+
+        assert_eq!(listado.total, 1);
+        let info = &listado.streams[0];
+        assert_eq!(info.config.name, config.name);
+        assert_eq!(info.config.subjects, config.subjects);
+        assert_eq!(info.state.messages, 2);
+        assert_eq!(info.state.first_seq, 1);
+        assert_eq!(info.state.last_seq, 3);
+        assert_eq!(info.state.num_deleted, 1);
+        assert_eq!(info.state.num_subjects, 1);
+        assert_eq!(info.state.subjects, None);
+    }
+
+    /// Pide restaurar el stream y devuelve el tópico al que hay que enviar los bloques
+    fn iniciar_restauracion(admin: &mut JestStreamAdminConexion, nombre: &str) -> String {
+        let respuestas = enviar(
+            admin,
+            "stream.restaurar",
+            &format!("$JS.API.STREAM.RESTORE.{}", nombre),
+            b"",
+        );
+        JSRestaurarStreamRespuesta::from_json(&respuestas[0])
+            .unwrap()
+            .deliver_subject
+    }
+
+    #[test]
+    fn restaurar_junta_los_bloques_en_disco() {
+        let (mut admin, rx_conexiones) = admin("restaurar");
+
+        let origen = std::env::temp_dir().join("admin_prueba_restaurar_origen");
+        let _ = std::fs::remove_dir_all(&origen);
+        std::fs::create_dir_all(&origen).unwrap();
+        let config = StreamConfig {
+            name: "original".to_string(),
+            subjects: vec!["restaurado.>".to_string()],
+            ..Default::default()
+        };
+        std::fs::write(origen.join("config.json"), config.to_json().unwrap()).unwrap();
+        let snapshot = empaquetar(&origen, &[]).unwrap();
+        std::fs::remove_dir_all(&origen).unwrap();
+
+        let topico = iniciar_restauracion(&mut admin, "restaurado");
+        let spool = admin.restauraciones["restaurado"].ruta.clone();
+        for bloque in snapshot.chunks(16) {
+            let respuestas = enviar(&mut admin, "restaurar|restaurado", &topico, bloque);
+            assert_eq!(respuestas, vec![String::new()]);
+        }
+        assert_eq!(
+            std::fs::metadata(&spool).unwrap().len(),
+            snapshot.len() as u64
+        );
+
+        let respuestas = enviar(&mut admin, "restaurar|restaurado", &topico, b"");
+        let creado = JSCrearStreamRespuesta::from_json(&respuestas[0]).unwrap();
+        assert_eq!(creado.config.name, "restaurado");
+        assert_eq!(creado.config.subjects, config.subjects);
+        assert!(!spool.exists());
+        assert!(rx_conexiones.try_recv().is_ok());
+    }
+
+    #[test]
+    fn restaurar_rechaza_snapshots_que_no_entran_en_la_cuenta() {
+        let (mut admin, _rx_conexiones) = admin_con_limites(
+            "restaurar_limite",
+            LimitesCuenta {
+                max_storage: 20,
+                ..Default::default()
+            },
+        );
+
+        let topico = iniciar_restauracion(&mut admin, "grande");
+        let spool = admin.restauraciones["grande"].ruta.clone();
+        enviar(&mut admin, "restaurar|grande", &topico, &[0; 15]);
+
+        let respuestas = enviar(&mut admin, "restaurar|grande", &topico, &[0; 15]);
+        let respuesta = JSErrorRespuesta::from_json(&respuestas[0]).unwrap();
+        assert_eq!(
+            respuesta.r#type,
+            "io.nats.jetstream.api.v1.stream_restore_response"
+        );
+        assert_eq!(respuesta.error, JSError::almacenamiento_insuficiente());
+        assert!(admin.restauraciones.is_empty());
+        assert!(!spool.exists());
+
+        // Los bloques que llegan después ya no tienen restauración
+        assert!(enviar(&mut admin, "restaurar|grande", &topico, &[0; 5]).is_empty());
+    }
+
+    #[test]
+    fn crear_stream_rechaza_topicos_invalidos() {
+        let (mut admin, rx_conexiones) = admin("topicos_invalidos");
+
+        let config = StreamConfig {
+            name: "invalido".to_string(),
+            subjects: vec!["valido.a".to_string(), "invalido.>.a".to_string()],
+            ..Default::default()
+        };
+        let respuestas = enviar(
+            &mut admin,
+            "stream.crear",
+            "$JS.API.STREAM.CREATE.invalido",
+            config.to_json().unwrap().as_bytes(),
+        );
+
+        assert_eq!(
+            JSErrorRespuesta::from_json(&respuestas[0]).unwrap().error,
+            JSError::configuracion_stream_invalida("invalid subject")
+        );
+        assert!(admin.streams.is_empty());
+        assert!(rx_conexiones.try_recv().is_err());
+    }
 }
@@ -0,0 +1,555 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{mensaje::MensajeAlmacenado, segmento::Segmento, Almacenamiento};
+
+/// Cuando el segmento actual supera este tamaño se empieza uno nuevo
+const TAMANO_MAXIMO_SEGMENTO: u64 = 4 * 1024 * 1024;
+
+/// Archivo con las secuencias de los mensajes eliminados que todavía están dentro de un segmento
+const ARCHIVO_ELIMINADOS: &str = "eliminados.idx";
+
+/// Datos de cada mensaje que se mantienen en memoria para no tener que leer el disco
+#[derive(Debug, Clone)]
+struct EntradaIndice {
+    segmento: u64,
+    offset: u64,
+    bytes: u64,
+    tiempo: i64,
+    topico: String,
+}
+
+/// Almacenamiento de los mensajes de un stream en disco.
+///
+/// Los mensajes se guardan en segmentos append-only dentro del directorio del stream
+/// y se indexan por número de secuencia. Al abrir el almacenamiento se reconstruye
+/// el índice en memoria a partir de los segmentos existentes.
+///
+/// Como los segmentos no se reescriben, al eliminar un mensaje se anota su secuencia
+/// en `eliminados.idx`. Cuando un segmento queda sin mensajes se borran sus archivos.
+pub struct AlmacenamientoArchivo {
+    directorio: PathBuf,
+    segmentos: BTreeMap<u64, Segmento>,
+    indice: BTreeMap<u64, EntradaIndice>,
+    ultima_secuencia: u64,
+    /// Momento en el que se guardó el último mensaje, aunque ya se haya eliminado
+    ultimo_tiempo: i64,
+    bytes: u64,
+    /// Secuencias de los mensajes guardados en cada tópico
+    topicos: HashMap<String, BTreeSet<u64>>,
+    /// Secuencias eliminadas cuyos segmentos todavía existen
+    eliminados: BTreeSet<u64>,
+    archivo_eliminados: File,
+}
+
+impl AlmacenamientoArchivo {
+    pub fn abrir(directorio: &Path) -> io::Result<Self> {
+        fs::create_dir_all(directorio)?;
+
+        let ruta_eliminados = directorio.join(ARCHIVO_ELIMINADOS);
+        let eliminados = leer_eliminados(&ruta_eliminados)?;
+
+        let mut almacenamiento = Self {
+            directorio: directorio.to_path_buf(),
+            segmentos: BTreeMap::new(),
+            indice: BTreeMap::new(),
+            ultima_secuencia: 0,
+            ultimo_tiempo: 0,
+            bytes: 0,
+            topicos: HashMap::new(),
+            eliminados: BTreeSet::new(),
+            archivo_eliminados: abrir_append(&ruta_eliminados)?,
+        };
+
+        for entrada in fs::read_dir(directorio)? {
+            let ruta = entrada?.path();
+
+            if ruta.extension().and_then(|e| e.to_str()) != Some("seg") {
+                continue;
+            }
+
+            let primera_secuencia = match ruta
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse::<u64>().ok())
+            {
+                Some(secuencia) => secuencia,
+                None => continue,
+            };
+
+            let (segmento, mensajes) = Segmento::abrir(directorio, primera_secuencia)?;
+
+            for (offset, mensaje) in mensajes {
+                if mensaje.secuencia >= almacenamiento.ultima_secuencia {
+                    almacenamiento.ultima_secuencia = mensaje.secuencia;
+                    almacenamiento.ultimo_tiempo = mensaje.tiempo;
+                }
+
+                if eliminados.contains(&mensaje.secuencia) {
+                    almacenamiento.eliminados.insert(mensaje.secuencia);
+                    continue;
+                }
+
+                almacenamiento.indexar(&mensaje, primera_secuencia, offset);
+            }
+
+            almacenamiento.ultima_secuencia = almacenamiento
+                .ultima_secuencia
+                .max(primera_secuencia.saturating_sub(1));
+            almacenamiento.segmentos.insert(primera_secuencia, segmento);
+        }
+
+        // Se borran los segmentos que quedaron vacíos y se descartan las
+        // secuencias eliminadas que ya no pertenecen a ningún segmento
+        let ids_segmentos = almacenamiento
+            .segmentos
+            .keys()
+            .copied()
+            .collect::<Vec<u64>>();
+        for id_segmento in ids_segmentos {
+            almacenamiento.liberar_segmento_si_vacio(id_segmento)?;
+        }
+        almacenamiento.guardar_eliminados()?;
+
+        Ok(almacenamiento)
+    }
+
+    fn indexar(&mut self, mensaje: &MensajeAlmacenado, segmento: u64, offset: u64) {
+        self.bytes += mensaje.bytes();
+        self.topicos
+            .entry(mensaje.topico.clone())
+            .or_default()
+            .insert(mensaje.secuencia);
+        self.indice.insert(
+            mensaje.secuencia,
+            EntradaIndice {
+                segmento,
+                offset,
+                bytes: mensaje.bytes(),
+                tiempo: mensaje.tiempo,
+                topico: mensaje.topico.clone(),
+            },
+        );
+    }
+
+    fn segmento_para_escribir(&mut self, secuencia: u64) -> io::Result<&mut Segmento> {
+        let necesita_nuevo = match self.segmentos.values().next_back() {
+            Some(segmento) => segmento.tamano() >= TAMANO_MAXIMO_SEGMENTO,
+            None => true,
+        };
+
+        if necesita_nuevo {
+            let segmento = Segmento::crear(&self.directorio, secuencia)?;
+            self.segmentos.insert(secuencia, segmento);
+        }
+
+        self.segmentos
+            .values_mut()
+            .next_back()
+            .ok_or_else(|| io::Error::other("No hay segmentos"))
+    }
+
+    /// Borra el segmento si ya no le quedan mensajes y devuelve si lo hizo.
+    ///
+    /// Si es el último segmento, antes se crea uno vacío que empieza en la próxima
+    /// secuencia para no perder la numeración al reiniciar.
+    fn liberar_segmento_si_vacio(&mut self, id_segmento: u64) -> io::Result<bool> {
+        let fin = self
+            .segmentos
+            .range(id_segmento + 1..)
+            .next()
+            .map(|(id, _)| *id);
+
+        let tiene_mensajes = match fin {
+            Some(fin) => self.indice.range(id_segmento..fin).next().is_some(),
+            None => self.indice.range(id_segmento..).next().is_some(),
+        };
+
+        if tiene_mensajes {
+            return Ok(false);
+        }
+
+        if fin.is_none() {
+            let proxima = self.ultima_secuencia + 1;
+            if proxima == id_segmento {
+                return Ok(false);
+            }
+            let segmento = Segmento::crear(&self.directorio, proxima)?;
+            self.segmentos.insert(proxima, segmento);
+        }
+
+        if let Some(segmento) = self.segmentos.remove(&id_segmento) {
+            segmento.eliminar()?;
+        }
+
+        match fin {
+            Some(fin) => self.eliminados.retain(|s| *s < id_segmento || *s >= fin),
+            None => self.eliminados.retain(|s| *s < id_segmento),
+        }
+
+        Ok(true)
+    }
+
+    /// Reescribe `eliminados.idx` con las secuencias eliminadas que siguen en algún segmento
+    fn guardar_eliminados(&mut self) -> io::Result<()> {
+        let ruta = self.directorio.join(ARCHIVO_ELIMINADOS);
+
+        let mut bytes = Vec::with_capacity(self.eliminados.len() * 8);
+        for secuencia in &self.eliminados {
+            bytes.extend_from_slice(&secuencia.to_le_bytes());
+        }
+        fs::write(&ruta, bytes)?;
+
+        self.archivo_eliminados = abrir_append(&ruta)?;
+        Ok(())
+    }
+}
+
+impl Almacenamiento for AlmacenamientoArchivo {
+    fn agregar_mensaje(&mut self, mensaje: &MensajeAlmacenado) -> io::Result<()> {
+        if mensaje.secuencia <= self.ultima_secuencia {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "La secuencia {} no es posterior a la última guardada ({})",
+                    mensaje.secuencia, self.ultima_secuencia
+                ),
+            ));
+        }
+
+        let segmento = self.segmento_para_escribir(mensaje.secuencia)?;
+        let offset = segmento.agregar(mensaje)?;
+        let id_segmento = segmento.primera_secuencia;
+
+        self.indexar(mensaje, id_segmento, offset);
+        self.ultima_secuencia = mensaje.secuencia;
+        self.ultimo_tiempo = mensaje.tiempo;
+
+        Ok(())
+    }
+
+    fn obtener(&self, secuencia: u64) -> io::Result<Option<MensajeAlmacenado>> {
+        let entrada = match self.indice.get(&secuencia) {
+            Some(entrada) => entrada,
+            None => return Ok(None),
+        };
+
+        match self.segmentos.get(&entrada.segmento) {
+            Some(segmento) => segmento.leer(entrada.offset).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn eliminar(&mut self, secuencia: u64) -> io::Result<bool> {
+        let entrada = match self.indice.remove(&secuencia) {
+            Some(entrada) => entrada,
+            None => return Ok(false),
+        };
+
+        self.bytes -= entrada.bytes;
+        if let Some(secuencias) = self.topicos.get_mut(&entrada.topico) {
+            secuencias.remove(&secuencia);
+            if secuencias.is_empty() {
+                self.topicos.remove(&entrada.topico);
+            }
+        }
+        self.eliminados.insert(secuencia);
+        self.archivo_eliminados
+            .write_all(&secuencia.to_le_bytes())?;
+
+        if self.liberar_segmento_si_vacio(entrada.segmento)? {
+            self.guardar_eliminados()?;
+        }
+
+        Ok(true)
+    }
+
+    /// Sobrescribe el contenido del mensaje en disco con datos aleatorios antes
+    /// de eliminarlo, para que no se pueda recuperar leyendo los archivos
+    fn borrar(&mut self, secuencia: u64) -> io::Result<bool> {
+        let entrada = match self.indice.get(&secuencia) {
+            Some(entrada) => entrada,
+            None => return Ok(false),
+        };
+
+        if let Some(segmento) = self.segmentos.get(&entrada.segmento) {
+            let mensaje = segmento.leer(entrada.offset)?;
+            segmento.sobrescribir(entrada.offset, &mensaje.con_datos_aleatorios())?;
+        }
+
+        self.eliminar(secuencia)
+    }
+
+    fn secuencias(&self) -> Vec<u64> {
+        self.indice.keys().copied().collect()
+    }
+
+    fn siguiente_secuencia(&self, desde: u64) -> Option<u64> {
+        self.indice
+            .range(desde..)
+            .next()
+            .map(|(secuencia, _)| *secuencia)
+    }
+
+    fn primera_secuencia(&self) -> Option<u64> {
+        self.indice.keys().next().copied()
+    }
+
+    fn ultima_secuencia(&self) -> u64 {
+        self.ultima_secuencia
+    }
+
+    fn tiempo(&self, secuencia: u64) -> Option<i64> {
+        self.indice.get(&secuencia).map(|entrada| entrada.tiempo)
+    }
+
+    fn topico(&self, secuencia: u64) -> Option<&str> {
+        self.indice
+            .get(&secuencia)
+            .map(|entrada| entrada.topico.as_str())
+    }
+
+    fn ultimo_tiempo(&self) -> i64 {
+        self.ultimo_tiempo
+    }
+
+    fn topicos(&self) -> HashMap<&str, u64> {
+        self.topicos
+            .iter()
+            .map(|(topico, secuencias)| (topico.as_str(), secuencias.len() as u64))
+            .collect()
+    }
+
+    fn secuencias_del_topico(&self, topico: &str) -> Vec<u64> {
+        self.topicos
+            .get(topico)
+            .map(|secuencias| secuencias.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    fn siguiente_secuencia_del_topico(&self, topico: &str, desde: u64) -> Option<u64> {
+        self.topicos
+            .get(topico)
+            .and_then(|secuencias| secuencias.range(desde..).next().copied())
+    }
+
+    fn ultima_secuencia_del_topico(&self, topico: &str) -> Option<u64> {
+        self.topicos
+            .get(topico)
+            .and_then(|secuencias| secuencias.last().copied())
+    }
+
+    fn cantidad(&self) -> u64 {
+        self.indice.len() as u64
+    }
+
+    fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Elimina todos los archivos del stream
+    fn destruir(&mut self) -> io::Result<()> {
+        self.segmentos.clear();
+        self.indice.clear();
+        self.eliminados.clear();
+        self.topicos.clear();
+        self.bytes = 0;
+        fs::remove_dir_all(&self.directorio)
+    }
+}
+
+fn abrir_append(ruta: &Path) -> io::Result<File> {
+    OpenOptions::new().append(true).create(true).open(ruta)
+}
+
+fn leer_eliminados(ruta: &Path) -> io::Result<BTreeSet<u64>> {
+    let bytes = match fs::read(ruta) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(BTreeSet::new()),
+        Err(e) => return Err(e),
+    };
+
+    // Si la última entrada quedó incompleta se ignora
+    Ok(bytes
+        .chunks_exact(8)
+        .map(|entrada| u64::from_le_bytes(entrada.try_into().unwrap_or_default()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{Almacenamiento, AlmacenamientoArchivo};
+
+    fn directorio_prueba(nombre: &str) -> PathBuf {
+        let directorio = std::env::temp_dir().join(format!("almacenamiento_{}", nombre));
+        let _ = std::fs::remove_dir_all(&directorio);
+        directorio
+    }
+
+    #[test]
+    fn reabrir_conserva_mensajes() {
+        let directorio = directorio_prueba("reabrir_conserva_mensajes");
+
+        {
+            let mut almacenamiento = AlmacenamientoArchivo::abrir(&directorio).unwrap();
+            almacenamiento
+                .agregar("a".to_string(), None, b"uno".to_vec())
+                .unwrap();
+            almacenamiento
+                .agregar(
+                    "b".to_string(),
+                    Some(b"NATS/1.0\r\n\r\n".to_vec()),
+                    b"dos".to_vec(),
+                )
+                .unwrap();
+        }
+
+        let mut almacenamiento = AlmacenamientoArchivo::abrir(&directorio).unwrap();
+
+        assert_eq!(almacenamiento.secuencias(), vec![1, 2]);
+        assert_eq!(almacenamiento.obtener(2).unwrap().unwrap().payload, b"dos");
+        assert!(almacenamiento.obtener(2).unwrap().unwrap().header.is_some());
+
+        let mensaje = almacenamiento
+            .agregar("c".to_string(), None, b"tres".to_vec())
+            .unwrap();
+        assert_eq!(mensaje.secuencia, 3);
+
+        almacenamiento.destruir().unwrap();
+    }
+
+    #[test]
+    fn reabrir_descarta_mensaje_incompleto() {
+        let directorio = directorio_prueba("reabrir_descarta_mensaje_incompleto");
+
+        {
+            let mut almacenamiento = AlmacenamientoArchivo::abrir(&directorio).unwrap();
+            almacenamiento
+                .agregar("a".to_string(), None, b"uno".to_vec())
+                .unwrap();
+        }
+
+        // Simula un corte mientras se escribía un segundo mensaje
+        let ruta = directorio.join(format!("{:020}.seg", 1));
+        let mut datos = std::fs::read(&ruta).unwrap();
+        datos.extend_from_slice(&[40, 0, 0, 0, 2, 0]);
+        std::fs::write(&ruta, datos).unwrap();
+
+        let mut almacenamiento = AlmacenamientoArchivo::abrir(&directorio).unwrap();
+        assert_eq!(almacenamiento.secuencias(), vec![1]);
+
+        let mensaje = almacenamiento
+            .agregar("b".to_string(), None, b"dos".to_vec())
+            .unwrap();
+        assert_eq!(mensaje.secuencia, 2);
+        assert_eq!(almacenamiento.obtener(2).unwrap(), Some(mensaje));
+
+        almacenamiento.destruir().unwrap();
+    }
+
+    #[test]
+    fn eliminar_persiste_al_reabrir() {
+        let directorio = directorio_prueba("eliminar_persiste_al_reabrir");
+
+        {
+            let mut almacenamiento = AlmacenamientoArchivo::abrir(&directorio).unwrap();
+            for payload in ["uno", "dos", "tres"] {
+                almacenamiento
+                    .agregar("a".to_string(), None, payload.as_bytes().to_vec())
+                    .unwrap();
+            }
+
+            assert!(almacenamiento.eliminar(2).unwrap());
+            assert!(!almacenamiento.eliminar(2).unwrap());
+            assert_eq!(almacenamiento.cantidad(), 2);
+            assert_eq!(almacenamiento.bytes(), 1 + 3 + 1 + 4);
+        }
+
+        let mut almacenamiento = AlmacenamientoArchivo::abrir(&directorio).unwrap();
+        assert_eq!(almacenamiento.secuencias(), vec![1, 3]);
+        assert_eq!(almacenamiento.obtener(2).unwrap(), None);
+        assert_eq!(almacenamiento.topicos().get("a"), Some(&2));
+        assert_eq!(almacenamiento.secuencias_del_topico("a"), vec![1, 3]);
+        assert_eq!(almacenamiento.ultima_secuencia_del_topico("a"), Some(3));
+        assert_eq!(almacenamiento.primera_secuencia(), Some(1));
+
+        almacenamiento.destruir().unwrap();
+    }
+
+    #[test]
+    fn eliminar_todo_conserva_numeracion() {
+        let directorio = directorio_prueba("eliminar_todo_conserva_numeracion");
+
+        {
+            let mut almacenamiento = AlmacenamientoArchivo::abrir(&directorio).unwrap();
+            almacenamiento
+                .agregar("a".to_string(), None, b"uno".to_vec())
+                .unwrap();
+            almacenamiento
+                .agregar("a".to_string(), None, b"dos".to_vec())
+                .unwrap();
+
+            almacenamiento.eliminar(1).unwrap();
+            almacenamiento.eliminar(2).unwrap();
+
+            // El segmento vacío se borra
+            assert!(!directorio.join(format!("{:020}.seg", 1)).exists());
+        }
+
+        let mut almacenamiento = AlmacenamientoArchivo::abrir(&directorio).unwrap();
+        assert_eq!(almacenamiento.cantidad(), 0);
+        assert_eq!(almacenamiento.primera_secuencia(), None);
+        assert_eq!(almacenamiento.ultima_secuencia(), 2);
+
+        let mensaje = almacenamiento
+            .agregar("a".to_string(), None, b"tres".to_vec())
+            .unwrap();
+        assert_eq!(mensaje.secuencia, 3);
+
+        almacenamiento.destruir().unwrap();
+    }
+
+    #[test]
+    fn borrar_sobrescribe_el_contenido() {
+        let directorio = directorio_prueba("borrar_sobrescribe_el_contenido");
+
+        {
+            let mut almacenamiento = AlmacenamientoArchivo::abrir(&directorio).unwrap();
+            almacenamiento
+                .agregar(
+                    "claves.secreta".to_string(),
+                    None,
+                    b"contrasena-1234".to_vec(),
+                )
+                .unwrap();
+            almacenamiento
+                .agregar("a".to_string(), None, b"visible".to_vec())
+                .unwrap();
+
+            assert!(almacenamiento.borrar(1).unwrap());
+            assert!(!almacenamiento.borrar(1).unwrap());
+
+            let datos = std::fs::read(directorio.join(format!("{:020}.seg", 1))).unwrap();
+            let contiene = |buscado: &[u8]| datos.windows(buscado.len()).any(|v| v == buscado);
+            assert!(!contiene(b"contrasena-1234"));
+            assert!(!contiene(b"claves.secreta"));
+            assert!(contiene(b"visible"));
+        }
+
+        // El segmento sigue siendo legible
+        let mut almacenamiento = AlmacenamientoArchivo::abrir(&directorio).unwrap();
+        assert_eq!(almacenamiento.secuencias(), vec![2]);
+        assert_eq!(
+            almacenamiento.obtener(2).unwrap().unwrap().payload,
+            b"visible"
+        );
+
+        almacenamiento.destruir().unwrap();
+    }
+}
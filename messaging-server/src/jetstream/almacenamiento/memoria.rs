@@ -0,0 +1,250 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fs, io,
+    path::Path,
+};
+
+use super::{
+    archivo::AlmacenamientoArchivo,
+    mensaje::MensajeAlmacenado,
+    segmento::{nombre_datos, registro},
+    Almacenamiento,
+};
+
+/// Almacenamiento de los mensajes de un stream en memoria, para streams con
+/// mucho movimiento cuyos mensajes no hace falta conservar al reiniciar el servidor
+#[derive(Debug, Default)]
+pub struct AlmacenamientoMemoria {
+    mensajes: BTreeMap<u64, MensajeAlmacenado>,
+    ultima_secuencia: u64,
+    /// Momento en el que se guardó el último mensaje, aunque ya se haya eliminado
+    ultimo_tiempo: i64,
+    bytes: u64,
+    /// Secuencias de los mensajes guardados en cada tópico
+    topicos: HashMap<String, BTreeSet<u64>>,
+}
+
+impl AlmacenamientoMemoria {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Carga los mensajes que haya en los segmentos de `directorio`, como los de
+    /// un snapshot restaurado, y borra esos archivos porque un stream en memoria
+    /// no guarda mensajes en disco
+    pub fn importar(directorio: &Path) -> io::Result<Self> {
+        let mut almacenamiento = Self::new();
+
+        {
+            let archivo = AlmacenamientoArchivo::abrir(directorio)?;
+            for secuencia in archivo.secuencias() {
+                if let Some(mensaje) = archivo.obtener(secuencia)? {
+                    almacenamiento.agregar_mensaje(&mensaje)?;
+                }
+            }
+            almacenamiento.ultima_secuencia = archivo.ultima_secuencia();
+            almacenamiento.ultimo_tiempo = archivo.ultimo_tiempo();
+        }
+
+        for entrada in fs::read_dir(directorio)? {
+            let ruta = entrada?.path();
+            if matches!(
+                ruta.extension().and_then(|e| e.to_str()),
+                Some("seg") | Some("idx")
+            ) {
+                fs::remove_file(ruta)?;
+            }
+        }
+
+        Ok(almacenamiento)
+    }
+}
+
+impl Almacenamiento for AlmacenamientoMemoria {
+    fn agregar_mensaje(&mut self, mensaje: &MensajeAlmacenado) -> io::Result<()> {
+        if mensaje.secuencia <= self.ultima_secuencia {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "La secuencia {} no es posterior a la última guardada ({})",
+                    mensaje.secuencia, self.ultima_secuencia
+                ),
+            ));
+        }
+
+        self.bytes += mensaje.bytes();
+        self.topicos
+            .entry(mensaje.topico.clone())
+            .or_default()
+            .insert(mensaje.secuencia);
+        self.mensajes.insert(mensaje.secuencia, mensaje.clone());
+        self.ultima_secuencia = mensaje.secuencia;
+        self.ultimo_tiempo = mensaje.tiempo;
+
+        Ok(())
+    }
+
+    fn obtener(&self, secuencia: u64) -> io::Result<Option<MensajeAlmacenado>> {
+        Ok(self.mensajes.get(&secuencia).cloned())
+    }
+
+    fn eliminar(&mut self, secuencia: u64) -> io::Result<bool> {
+        let mensaje = match self.mensajes.remove(&secuencia) {
+            Some(mensaje) => mensaje,
+            None => return Ok(false),
+        };
+
+        self.bytes -= mensaje.bytes();
+        if let Some(secuencias) = self.topicos.get_mut(&mensaje.topico) {
+            secuencias.remove(&secuencia);
+            if secuencias.is_empty() {
+                self.topicos.remove(&mensaje.topico);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// En memoria no queda ninguna copia que sobrescribir
+    fn borrar(&mut self, secuencia: u64) -> io::Result<bool> {
+        self.eliminar(secuencia)
+    }
+
+    fn secuencias(&self) -> Vec<u64> {
+        self.mensajes.keys().copied().collect()
+    }
+
+    fn siguiente_secuencia(&self, desde: u64) -> Option<u64> {
+        self.mensajes
+            .range(desde..)
+            .next()
+            .map(|(secuencia, _)| *secuencia)
+    }
+
+    fn primera_secuencia(&self) -> Option<u64> {
+        self.mensajes.keys().next().copied()
+    }
+
+    fn ultima_secuencia(&self) -> u64 {
+        self.ultima_secuencia
+    }
+
+    fn tiempo(&self, secuencia: u64) -> Option<i64> {
+        self.mensajes.get(&secuencia).map(|mensaje| mensaje.tiempo)
+    }
+
+    fn topico(&self, secuencia: u64) -> Option<&str> {
+        self.mensajes
+            .get(&secuencia)
+            .map(|mensaje| mensaje.topico.as_str())
+    }
+
+    fn ultimo_tiempo(&self) -> i64 {
+        self.ultimo_tiempo
+    }
+
+    fn topicos(&self) -> HashMap<&str, u64> {
+        self.topicos
+            .iter()
+            .map(|(topico, secuencias)| (topico.as_str(), secuencias.len() as u64))
+            .collect()
+    }
+
+    fn secuencias_del_topico(&self, topico: &str) -> Vec<u64> {
+        self.topicos
+            .get(topico)
+            .map(|secuencias| secuencias.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    fn siguiente_secuencia_del_topico(&self, topico: &str, desde: u64) -> Option<u64> {
+        self.topicos
+            .get(topico)
+            .and_then(|secuencias| secuencias.range(desde..).next().copied())
+    }
+
+    fn ultima_secuencia_del_topico(&self, topico: &str) -> Option<u64> {
+        self.topicos
+            .get(topico)
+            .and_then(|secuencias| secuencias.last().copied())
+    }
+
+    fn cantidad(&self) -> u64 {
+        self.mensajes.len() as u64
+    }
+
+    fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    fn destruir(&mut self) -> io::Result<()> {
+        self.mensajes.clear();
+        self.topicos.clear();
+        self.bytes = 0;
+        Ok(())
+    }
+
+    /// Los mensajes van en un segmento con el mismo formato que los del
+    /// almacenamiento en disco, seguido de uno vacío que conserva la numeración
+    fn archivos_snapshot(&self) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let mut archivos = Vec::new();
+
+        if let Some(primera_secuencia) = self.primera_secuencia() {
+            let datos = self.mensajes.values().flat_map(registro).collect();
+            archivos.push((nombre_datos(primera_secuencia), datos));
+        }
+        if self.ultima_secuencia > 0 {
+            archivos.push((nombre_datos(self.ultima_secuencia + 1), Vec::new()));
+        }
+
+        Ok(archivos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{Almacenamiento, AlmacenamientoMemoria};
+
+    #[test]
+    fn importar_los_archivos_del_snapshot() {
+        let directorio = std::env::temp_dir().join("almacenamiento_memoria_importar");
+        let _ = fs::remove_dir_all(&directorio);
+        fs::create_dir_all(&directorio).unwrap();
+        fs::write(directorio.join("config.json"), b"{}").unwrap();
+
+        let mut memoria = AlmacenamientoMemoria::new();
+        for payload in ["uno", "dos", "tres", "cuatro"] {
+            memoria
+                .agregar("a".to_string(), None, payload.as_bytes().to_vec())
+                .unwrap();
+        }
+        memoria.eliminar(2).unwrap();
+        memoria.eliminar(4).unwrap();
+        for (ruta, datos) in memoria.archivos_snapshot().unwrap() {
+            fs::write(directorio.join(ruta), datos).unwrap();
+        }
+
+        let mut importado = AlmacenamientoMemoria::importar(&directorio).unwrap();
+        assert_eq!(importado.secuencias(), vec![1, 3]);
+        assert_eq!(importado.obtener(3).unwrap(), memoria.obtener(3).unwrap());
+        assert_eq!(importado.ultima_secuencia(), 4);
+        assert_eq!(
+            importado
+                .agregar("a".to_string(), None, b"cinco".to_vec())
+                .unwrap()
+                .secuencia,
+            5
+        );
+
+        // Solo queda lo que no son mensajes
+        let archivos: Vec<_> = fs::read_dir(&directorio)
+            .unwrap()
+            .map(|entrada| entrada.unwrap().file_name())
+            .collect();
+        assert_eq!(archivos, vec!["config.json"]);
+
+        fs::remove_dir_all(directorio).unwrap();
+    }
+}
@@ -0,0 +1,130 @@
+use std::io;
+
+use chrono::Utc;
+use rand::{distributions::Alphanumeric, Rng};
+
+/// Un mensaje guardado en el almacenamiento de un stream.
+///
+/// Cada mensaje tiene un número de secuencia único dentro del stream
+/// y el momento en el que se guardó (en nanosegundos desde epoch)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MensajeAlmacenado {
+    pub secuencia: u64,
+    pub topico: String,
+    pub header: Option<Vec<u8>>,
+    pub payload: Vec<u8>,
+    pub tiempo: i64,
+}
+
+impl MensajeAlmacenado {
+    pub fn new(secuencia: u64, topico: String, header: Option<Vec<u8>>, payload: Vec<u8>) -> Self {
+        Self {
+            secuencia,
+            topico,
+            header,
+            payload,
+            tiempo: Utc::now().timestamp_nanos_opt().unwrap_or(0),
+        }
+    }
+
+    /// Cantidad de bytes que ocupa el mensaje (tópico, header y payload)
+    pub fn bytes(&self) -> u64 {
+        (self.topico.len() + self.header.as_ref().map_or(0, |h| h.len()) + self.payload.len())
+            as u64
+    }
+
+    /// Copia del mensaje con el tópico, los headers y el payload reemplazados por
+    /// datos aleatorios del mismo largo, que ocupa los mismos bytes al serializarla
+    pub fn con_datos_aleatorios(&self) -> Self {
+        let mut rng = rand::thread_rng();
+        let topico = (&mut rng)
+            .sample_iter(Alphanumeric)
+            .take(self.topico.len())
+            .map(char::from)
+            .collect();
+        let header = self
+            .header
+            .as_ref()
+            .map(|header| (0..header.len()).map(|_| rng.gen()).collect());
+        let payload = (0..self.payload.len()).map(|_| rng.gen()).collect();
+
+        Self {
+            secuencia: self.secuencia,
+            topico,
+            header,
+            payload,
+            tiempo: self.tiempo,
+        }
+    }
+
+    /// Formato binario:
+    /// `[secuencia u64][tiempo i64][len topico u32][topico][tiene header u8][len header u32][header][len payload u32][payload]`
+    pub fn serializar(&self) -> Vec<u8> {
+        let header = self.header.as_deref().unwrap_or_default();
+
+        let mut bytes = Vec::with_capacity(29 + self.bytes() as usize);
+        bytes.extend_from_slice(&self.secuencia.to_le_bytes());
+        bytes.extend_from_slice(&self.tiempo.to_le_bytes());
+        bytes.extend_from_slice(&(self.topico.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(self.topico.as_bytes());
+        bytes.push(self.header.is_some() as u8);
+        bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(header);
+        bytes.extend_from_slice(&(self.payload.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    pub fn deserializar(bytes: &[u8]) -> io::Result<Self> {
+        let mut lector = LectorBytes { bytes, posicion: 0 };
+
+        let secuencia = u64::from_le_bytes(lector.leer_arreglo()?);
+        let tiempo = i64::from_le_bytes(lector.leer_arreglo()?);
+
+        let largo_topico = u32::from_le_bytes(lector.leer_arreglo()?) as usize;
+        let topico = String::from_utf8(lector.leer(largo_topico)?.to_vec())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let tiene_header = lector.leer(1)?[0] == 1;
+        let largo_header = u32::from_le_bytes(lector.leer_arreglo()?) as usize;
+        let header = lector.leer(largo_header)?.to_vec();
+
+        let largo_payload = u32::from_le_bytes(lector.leer_arreglo()?) as usize;
+        let payload = lector.leer(largo_payload)?.to_vec();
+
+        Ok(Self {
+            secuencia,
+            topico,
+            header: if tiene_header { Some(header) } else { None },
+            payload,
+            tiempo,
+        })
+    }
+}
+
+struct LectorBytes<'a> {
+    bytes: &'a [u8],
+    posicion: usize,
+}
+
+impl<'a> LectorBytes<'a> {
+    fn leer(&mut self, cantidad: usize) -> io::Result<&'a [u8]> {
+        let fin = self.posicion + cantidad;
+        if fin > self.bytes.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Mensaje almacenado incompleto",
+            ));
+        }
+
+        let resultado = &self.bytes[self.posicion..fin];
+        self.posicion = fin;
+        Ok(resultado)
+    }
+
+    fn leer_arreglo<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut arreglo = [0; N];
+        arreglo.copy_from_slice(self.leer(N)?);
+        Ok(arreglo)
+    }
+}
@@ -0,0 +1,235 @@
+use std::{
+    collections::HashMap,
+    io,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use mensaje::MensajeAlmacenado;
+
+pub mod archivo;
+pub mod memoria;
+pub mod mensaje;
+mod segmento;
+
+/// Mensajes guardados por un stream, indexados por número de secuencia.
+///
+/// Las secuencias son crecientes y no se reutilizan aunque se eliminen los
+/// mensajes, y cada tópico lleva la cuenta de las secuencias que tiene guardadas
+pub trait Almacenamiento: Send {
+    /// Guarda un nuevo mensaje y le asigna el próximo número de secuencia
+    fn agregar(
+        &mut self,
+        topico: String,
+        header: Option<Vec<u8>>,
+        payload: Vec<u8>,
+    ) -> io::Result<MensajeAlmacenado> {
+        let mensaje = MensajeAlmacenado::new(self.ultima_secuencia() + 1, topico, header, payload);
+        self.agregar_mensaje(&mensaje)?;
+        Ok(mensaje)
+    }
+
+    /// Guarda un mensaje que ya tiene secuencia y tiempo, como los que copia un
+    /// espejo de su origen. La secuencia tiene que ser mayor a la última guardada
+    fn agregar_mensaje(&mut self, mensaje: &MensajeAlmacenado) -> io::Result<()>;
+
+    /// Devuelve el mensaje con la secuencia indicada, si existe
+    fn obtener(&self, secuencia: u64) -> io::Result<Option<MensajeAlmacenado>>;
+
+    /// Elimina el mensaje con la secuencia indicada. Devuelve `false` si no existía
+    fn eliminar(&mut self, secuencia: u64) -> io::Result<bool>;
+
+    /// Elimina el mensaje sin que quede ninguna copia de su contenido que se
+    /// pueda recuperar. Devuelve `false` si no existía
+    fn borrar(&mut self, secuencia: u64) -> io::Result<bool>;
+
+    /// Secuencias de todos los mensajes guardados, en orden
+    fn secuencias(&self) -> Vec<u64>;
+
+    /// Secuencia del primer mensaje guardado a partir de `desde`
+    fn siguiente_secuencia(&self, desde: u64) -> Option<u64>;
+
+    /// Secuencia del mensaje más antiguo que sigue guardado
+    fn primera_secuencia(&self) -> Option<u64>;
+
+    fn ultima_secuencia(&self) -> u64;
+
+    /// Momento en el que se guardó el mensaje (en nanosegundos desde epoch)
+    fn tiempo(&self, secuencia: u64) -> Option<i64>;
+
+    /// Tópico en el que se publicó el mensaje
+    fn topico(&self, secuencia: u64) -> Option<&str>;
+
+    /// Momento en el que se guardó el último mensaje del stream, aunque ya se haya eliminado
+    fn ultimo_tiempo(&self) -> i64;
+
+    /// Cantidad de mensajes guardados por tópico
+    fn topicos(&self) -> HashMap<&str, u64>;
+
+    /// Secuencias de los mensajes guardados en el tópico, en orden
+    fn secuencias_del_topico(&self, topico: &str) -> Vec<u64>;
+
+    /// Secuencia del primer mensaje guardado en el tópico a partir de `desde`
+    fn siguiente_secuencia_del_topico(&self, topico: &str, desde: u64) -> Option<u64>;
+
+    /// Secuencia del último mensaje guardado en el tópico
+    fn ultima_secuencia_del_topico(&self, topico: &str) -> Option<u64>;
+
+    /// Cantidad de mensajes guardados
+    fn cantidad(&self) -> u64;
+
+    /// Suma de los bytes de todos los mensajes guardados
+    fn bytes(&self) -> u64;
+
+    /// Elimina todos los mensajes y lo que se haya guardado en disco
+    fn destruir(&mut self) -> io::Result<()>;
+
+    /// Archivos con los mensajes que hay que agregar al snapshot del stream, con
+    /// su ruta relativa al directorio. Los que guardan en disco no necesitan
+    /// ninguno porque sus archivos ya están en el directorio
+    fn archivos_snapshot(&self) -> io::Result<Vec<(String, Vec<u8>)>> {
+        Ok(Vec::new())
+    }
+}
+
+/// Almacenamiento de un stream compartido con sus consumers, que leen de él los
+/// mensajes a medida que los entregan. Solo el stream lo modifica
+#[derive(Clone)]
+pub struct AlmacenamientoCompartido(Arc<Mutex<Box<dyn Almacenamiento>>>);
+
+impl AlmacenamientoCompartido {
+    pub fn new(almacenamiento: Box<dyn Almacenamiento>) -> Self {
+        Self(Arc::new(Mutex::new(almacenamiento)))
+    }
+
+    /// Toma el almacenamiento hasta soltar el guard. Si un hilo entró en pánico
+    /// mientras lo tenía se sigue usando igual, porque cada operación lo deja consistente
+    pub fn bloquear(&self) -> MutexGuard<'_, Box<dyn Almacenamiento>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Pruebas que tiene que cumplir cualquier almacenamiento, en disco o en memoria
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{
+        archivo::AlmacenamientoArchivo, memoria::AlmacenamientoMemoria, mensaje::MensajeAlmacenado,
+        Almacenamiento,
+    };
+
+    fn directorio_prueba(nombre: &str) -> PathBuf {
+        let directorio = std::env::temp_dir().join(format!("almacenamiento_comun_{}", nombre));
+        let _ = std::fs::remove_dir_all(&directorio);
+        directorio
+    }
+
+    /// Ejecuta la prueba sobre un almacenamiento de cada tipo
+    fn en_ambos(nombre: &str, prueba: fn(&mut dyn Almacenamiento)) {
+        let mut memoria = AlmacenamientoMemoria::new();
+        prueba(&mut memoria);
+
+        let directorio = directorio_prueba(nombre);
+        let mut archivo = AlmacenamientoArchivo::abrir(&directorio).unwrap();
+        prueba(&mut archivo);
+        let _ = std::fs::remove_dir_all(directorio);
+    }
+
+    #[test]
+    fn agregar_y_obtener() {
+        en_ambos("agregar_y_obtener", |almacenamiento| {
+            let mensaje = almacenamiento
+                .agregar("a.b".to_string(), None, b"hola".to_vec())
+                .unwrap();
+            let con_header = almacenamiento
+                .agregar(
+                    "a.c".to_string(),
+                    Some(b"NATS/1.0\r\n\r\n".to_vec()),
+                    b"chau".to_vec(),
+                )
+                .unwrap();
+
+            assert_eq!(mensaje.secuencia, 1);
+            assert_eq!(con_header.secuencia, 2);
+            assert_eq!(almacenamiento.obtener(1).unwrap(), Some(mensaje.clone()));
+            assert_eq!(almacenamiento.obtener(2).unwrap(), Some(con_header));
+            assert_eq!(almacenamiento.obtener(3).unwrap(), None);
+            assert_eq!(almacenamiento.tiempo(1), Some(mensaje.tiempo));
+            assert_eq!(almacenamiento.topico(2), Some("a.c"));
+            assert_eq!(almacenamiento.cantidad(), 2);
+            assert_eq!(almacenamiento.bytes(), 3 + 4 + 3 + 12 + 4);
+        });
+    }
+
+    #[test]
+    fn eliminar_conserva_numeracion_y_topicos() {
+        en_ambos("eliminar_conserva_numeracion", |almacenamiento| {
+            for (topico, payload) in [("a", "uno"), ("b", "dos"), ("a", "tres")] {
+                almacenamiento
+                    .agregar(topico.to_string(), None, payload.as_bytes().to_vec())
+                    .unwrap();
+            }
+
+            assert!(almacenamiento.eliminar(2).unwrap());
+            assert!(!almacenamiento.eliminar(2).unwrap());
+            assert_eq!(almacenamiento.secuencias(), vec![1, 3]);
+            assert_eq!(almacenamiento.siguiente_secuencia(2), Some(3));
+            assert_eq!(almacenamiento.siguiente_secuencia(4), None);
+            assert_eq!(almacenamiento.bytes(), 1 + 3 + 1 + 4);
+            assert_eq!(almacenamiento.topicos().get("a"), Some(&2));
+            assert_eq!(almacenamiento.topicos().get("b"), None);
+            assert_eq!(almacenamiento.secuencias_del_topico("a"), vec![1, 3]);
+            assert_eq!(
+                almacenamiento.siguiente_secuencia_del_topico("a", 2),
+                Some(3)
+            );
+            assert_eq!(almacenamiento.ultima_secuencia_del_topico("a"), Some(3));
+
+            let ultimo_tiempo = almacenamiento.ultimo_tiempo();
+            assert!(almacenamiento.borrar(3).unwrap());
+            assert!(almacenamiento.eliminar(1).unwrap());
+            assert_eq!(almacenamiento.cantidad(), 0);
+            assert_eq!(almacenamiento.primera_secuencia(), None);
+            assert_eq!(almacenamiento.ultima_secuencia(), 3);
+            assert_eq!(almacenamiento.ultimo_tiempo(), ultimo_tiempo);
+
+            let mensaje = almacenamiento
+                .agregar("a".to_string(), None, b"cuatro".to_vec())
+                .unwrap();
+            assert_eq!(mensaje.secuencia, 4);
+        });
+    }
+
+    #[test]
+    fn agregar_con_secuencia_propia() {
+        en_ambos("agregar_con_secuencia_propia", |almacenamiento| {
+            let mut mensaje = MensajeAlmacenado::new(5, "a".to_string(), None, b"uno".to_vec());
+            mensaje.tiempo = 1000;
+            almacenamiento.agregar_mensaje(&mensaje).unwrap();
+
+            // No se puede volver atrás en la numeración
+            mensaje.secuencia = 3;
+            assert!(almacenamiento.agregar_mensaje(&mensaje).is_err());
+            mensaje.secuencia = 5;
+            assert!(almacenamiento.agregar_mensaje(&mensaje).is_err());
+
+            assert_eq!(almacenamiento.secuencias(), vec![5]);
+            assert_eq!(almacenamiento.tiempo(5), Some(1000));
+            assert_eq!(almacenamiento.ultimo_tiempo(), 1000);
+        });
+    }
+
+    #[test]
+    fn destruir_elimina_los_mensajes() {
+        en_ambos("destruir_elimina_los_mensajes", |almacenamiento| {
+            almacenamiento
+                .agregar("a".to_string(), None, b"uno".to_vec())
+                .unwrap();
+
+            almacenamiento.destruir().unwrap();
+            assert_eq!(almacenamiento.cantidad(), 0);
+            assert_eq!(almacenamiento.bytes(), 0);
+            assert!(almacenamiento.topicos().is_empty());
+        });
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use super::mensaje::MensajeAlmacenado;
+
+/// Bytes de cada entrada del índice: `[secuencia u64][offset u64]`
+const BYTES_ENTRADA_INDICE: usize = 16;
+
+/// Un segmento es un par de archivos append-only:
+/// - `<primera secuencia>.seg` con los mensajes, cada uno precedido por su largo (`u32`)
+/// - `<primera secuencia>.idx` con la posición de cada mensaje dentro del `.seg`
+pub struct Segmento {
+    pub primera_secuencia: u64,
+    ruta_datos: PathBuf,
+    ruta_indice: PathBuf,
+    datos: File,
+    indice: File,
+    tamano: u64,
+}
+
+impl Segmento {
+    pub fn crear(directorio: &Path, primera_secuencia: u64) -> io::Result<Self> {
+        let ruta_datos = directorio.join(nombre_datos(primera_secuencia));
+        let ruta_indice = directorio.join(format!("{:020}.idx", primera_secuencia));
+
+        let datos = abrir_append(&ruta_datos)?;
+        let indice = abrir_append(&ruta_indice)?;
+        let tamano = datos.metadata()?.len();
+
+        Ok(Self {
+            primera_secuencia,
+            ruta_datos,
+            ruta_indice,
+            datos,
+            indice,
+            tamano,
+        })
+    }
+
+    /// Abre un segmento existente y devuelve los mensajes que contiene junto con su posición.
+    ///
+    /// Si el índice no coincide con los datos (por ejemplo, porque el servidor se cortó
+    /// mientras escribía) se reconstruye recorriendo el `.seg` y se descarta cualquier
+    /// mensaje incompleto al final del archivo.
+    pub fn abrir(
+        directorio: &Path,
+        primera_secuencia: u64,
+    ) -> io::Result<(Self, Vec<(u64, MensajeAlmacenado)>)> {
+        let mut segmento = Self::crear(directorio, primera_secuencia)?;
+
+        let mensajes = match segmento.leer_desde_indice() {
+            Ok(mensajes) => mensajes,
+            Err(_) => segmento.reconstruir_indice()?,
+        };
+
+        Ok((segmento, mensajes))
+    }
+
+    pub fn tamano(&self) -> u64 {
+        self.tamano
+    }
+
+    /// Agrega un mensaje al final del segmento y devuelve su posición
+    pub fn agregar(&mut self, mensaje: &MensajeAlmacenado) -> io::Result<u64> {
+        let offset = self.tamano;
+        let registro = registro(mensaje);
+        self.datos.write_all(&registro)?;
+
+        let mut entrada = [0; BYTES_ENTRADA_INDICE];
+        entrada[..8].copy_from_slice(&mensaje.secuencia.to_le_bytes());
+        entrada[8..].copy_from_slice(&offset.to_le_bytes());
+        self.indice.write_all(&entrada)?;
+
+        self.tamano += registro.len() as u64;
+
+        Ok(offset)
+    }
+
+    /// Lee el mensaje que empieza en `offset`
+    pub fn leer(&self, offset: u64) -> io::Result<MensajeAlmacenado> {
+        let (mensaje, _) = leer_registro(&self.datos, offset)?;
+        Ok(mensaje)
+    }
+
+    /// Reemplaza el mensaje que empieza en `offset` por otro que ocupa los mismos
+    /// bytes, sin mover el resto del segmento
+    pub fn sobrescribir(&self, offset: u64, mensaje: &MensajeAlmacenado) -> io::Result<()> {
+        let (_, largo) = leer_registro(&self.datos, offset)?;
+        let bytes = mensaje.serializar();
+        if 4 + bytes.len() as u64 != largo {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "El mensaje nuevo no ocupa lo mismo que el anterior",
+            ));
+        }
+
+        // Los datos se abren en modo append, así que se escribe con otro descriptor
+        let mut archivo = OpenOptions::new().write(true).open(&self.ruta_datos)?;
+        archivo.seek(SeekFrom::Start(offset + 4))?;
+        archivo.write_all(&bytes)?;
+        archivo.sync_data()
+    }
+
+    /// Borra los archivos del segmento
+    pub fn eliminar(self) -> io::Result<()> {
+        fs::remove_file(&self.ruta_datos)?;
+        fs::remove_file(&self.ruta_indice)
+    }
+
+    fn leer_desde_indice(&mut self) -> io::Result<Vec<(u64, MensajeAlmacenado)>> {
+        let bytes_indice = fs::read(&self.ruta_indice)?;
+
+        if bytes_indice.len() % BYTES_ENTRADA_INDICE != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Índice de segmento corrupto",
+            ));
+        }
+
+        let mut mensajes = Vec::new();
+        let mut fin = 0;
+
+        for entrada in bytes_indice.chunks(BYTES_ENTRADA_INDICE) {
+            let secuencia = u64::from_le_bytes(entrada[..8].try_into().unwrap_or_default());
+            let offset = u64::from_le_bytes(entrada[8..].try_into().unwrap_or_default());
+
+            let (mensaje, largo) = leer_registro(&self.datos, offset)?;
+            if mensaje.secuencia != secuencia {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Índice de segmento corrupto",
+                ));
+            }
+
+            fin = offset + largo;
+            mensajes.push((offset, mensaje));
+        }
+
+        if fin != self.tamano {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "El índice no coincide con los datos del segmento",
+            ));
+        }
+
+        Ok(mensajes)
+    }
+
+    fn reconstruir_indice(&mut self) -> io::Result<Vec<(u64, MensajeAlmacenado)>> {
+        let mut mensajes = Vec::new();
+        let mut offset = 0;
+
+        while offset < self.tamano {
+            match leer_registro(&self.datos, offset) {
+                Ok((mensaje, largo)) => {
+                    mensajes.push((offset, mensaje));
+                    offset += largo;
+                }
+                Err(_) => break,
+            }
+        }
+
+        // Se descarta lo que haya quedado incompleto al final
+        self.datos.set_len(offset)?;
+        self.tamano = offset;
+
+        let mut indice = Vec::with_capacity(mensajes.len() * BYTES_ENTRADA_INDICE);
+        for (offset, mensaje) in &mensajes {
+            indice.extend_from_slice(&mensaje.secuencia.to_le_bytes());
+            indice.extend_from_slice(&offset.to_le_bytes());
+        }
+        fs::write(&self.ruta_indice, indice)?;
+        self.indice = abrir_append(&self.ruta_indice)?;
+
+        Ok(mensajes)
+    }
+}
+
+/// Nombre del archivo de datos del segmento que empieza en `primera_secuencia`
+pub fn nombre_datos(primera_secuencia: u64) -> String {
+    format!("{:020}.seg", primera_secuencia)
+}
+
+/// Registro con el que se guarda un mensaje en el `.seg`: `[largo u32][mensaje]`
+pub fn registro(mensaje: &MensajeAlmacenado) -> Vec<u8> {
+    let bytes = mensaje.serializar();
+
+    let mut registro = Vec::with_capacity(4 + bytes.len());
+    registro.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    registro.extend_from_slice(&bytes);
+    registro
+}
+
+fn abrir_append(ruta: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(ruta)
+}
+
+/// Lee un registro (`[largo u32][mensaje]`) y devuelve el mensaje junto con la cantidad de bytes que ocupa
+fn leer_registro(mut archivo: &File, offset: u64) -> io::Result<(MensajeAlmacenado, u64)> {
+    archivo.seek(SeekFrom::Start(offset))?;
+
+    let mut largo = [0; 4];
+    archivo.read_exact(&mut largo)?;
+    let largo = u32::from_le_bytes(largo) as usize;
+
+    if offset + 4 + largo as u64 > archivo.metadata()?.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "Registro incompleto",
+        ));
+    }
+
+    let mut bytes = vec![0; largo];
+    archivo.read_exact(&mut bytes)?;
+
+    Ok((MensajeAlmacenado::deserializar(&bytes)?, 4 + largo as u64))
+}
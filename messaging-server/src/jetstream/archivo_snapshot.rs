@@ -0,0 +1,200 @@
+use std::{
+    fs,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Component, Path},
+};
+
+/// Comienzo de todo archivo de snapshot, con la versión del formato
+const MAGIA: &[u8] = b"JSSNAP1\n";
+
+/// Empaqueta los archivos del directorio de un stream en un solo archivo. Tiene
+/// una entrada por archivo, `[largo ruta u16][ruta][largo datos u64][datos]`,
+/// con la ruta relativa al directorio y separada por `/`. No se incluyen los
+/// subdirectorios de `omitidos`
+pub fn empaquetar(directorio: &Path, omitidos: &[&str]) -> io::Result<Vec<u8>> {
+    let mut archivo = MAGIA.to_vec();
+    agregar_directorio(&mut archivo, directorio, "", omitidos)?;
+    Ok(archivo)
+}
+
+fn agregar_directorio(
+    archivo: &mut Vec<u8>,
+    directorio: &Path,
+    prefijo: &str,
+    omitidos: &[&str],
+) -> io::Result<()> {
+    let mut entradas = fs::read_dir(directorio)?.collect::<io::Result<Vec<_>>>()?;
+    entradas.sort_by_key(|entrada| entrada.file_name());
+
+    for entrada in entradas {
+        let nombre = entrada.file_name().to_string_lossy().to_string();
+        let ruta = format!("{}{}", prefijo, nombre);
+
+        if entrada.file_type()?.is_dir() {
+            if prefijo.is_empty() && omitidos.contains(&nombre.as_str()) {
+                continue;
+            }
+            agregar_directorio(archivo, &entrada.path(), &format!("{}/", ruta), omitidos)?;
+            continue;
+        }
+
+        agregar_archivo(archivo, &ruta, &fs::read(entrada.path())?);
+    }
+
+    Ok(())
+}
+
+/// Agrega al snapshot un archivo que no está en el directorio del stream
+pub fn agregar_archivo(archivo: &mut Vec<u8>, ruta: &str, datos: &[u8]) {
+    archivo.extend_from_slice(&(ruta.len() as u16).to_le_bytes());
+    archivo.extend_from_slice(ruta.as_bytes());
+    archivo.extend_from_slice(&(datos.len() as u64).to_le_bytes());
+    archivo.extend_from_slice(datos);
+}
+
+/// Escribe en `directorio` los archivos de un snapshot. Si el snapshot está
+/// incompleto o tiene rutas fuera del directorio no se escribe nada. Los datos
+/// se copian por partes, sin cargar todo el snapshot en memoria
+pub fn desempaquetar<R: Read + Seek>(archivo: &mut R, directorio: &Path) -> io::Result<()> {
+    let entradas = leer_entradas(archivo)?;
+
+    for (ruta, inicio, largo) in entradas {
+        let destino = directorio.join(ruta);
+        if let Some(padre) = destino.parent() {
+            fs::create_dir_all(padre)?;
+        }
+        archivo.seek(SeekFrom::Start(inicio))?;
+        io::copy(&mut archivo.take(largo), &mut fs::File::create(destino)?)?;
+    }
+
+    Ok(())
+}
+
+/// Ruta, posición y largo de los datos de cada entrada
+fn leer_entradas<R: Read + Seek>(archivo: &mut R) -> io::Result<Vec<(String, u64, u64)>> {
+    let invalido = |descripcion: &str| io::Error::new(io::ErrorKind::InvalidData, descripcion);
+
+    let total = archivo.seek(SeekFrom::End(0))?;
+    archivo.seek(SeekFrom::Start(0))?;
+
+    let mut magia = [0; MAGIA.len()];
+    if leer(archivo, &mut magia).is_err() || magia != MAGIA {
+        return Err(invalido("No es un snapshot de stream"));
+    }
+
+    let mut posicion = MAGIA.len() as u64;
+    let mut entradas = Vec::new();
+
+    while posicion < total {
+        let mut largo_ruta = [0; 2];
+        leer(archivo, &mut largo_ruta)?;
+        let mut ruta = vec![0; u16::from_le_bytes(largo_ruta) as usize];
+        leer(archivo, &mut ruta)?;
+        let mut largo_datos = [0; 8];
+        leer(archivo, &mut largo_datos)?;
+        let largo_datos = u64::from_le_bytes(largo_datos);
+
+        posicion += (largo_ruta.len() + ruta.len() + 8) as u64;
+        if total - posicion < largo_datos {
+            return Err(incompleto());
+        }
+
+        let ruta = String::from_utf8(ruta).map_err(|_| invalido("Ruta inválida"))?;
+        let relativa = !ruta.is_empty()
+            && Path::new(&ruta)
+                .components()
+                .all(|componente| matches!(componente, Component::Normal(_)));
+        if !relativa {
+            return Err(invalido("Ruta fuera del directorio del stream"));
+        }
+
+        entradas.push((ruta, posicion, largo_datos));
+        posicion += largo_datos;
+        archivo.seek(SeekFrom::Start(posicion))?;
+    }
+
+    Ok(entradas)
+}
+
+fn leer<R: Read>(archivo: &mut R, destino: &mut [u8]) -> io::Result<()> {
+    archivo.read_exact(destino).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => incompleto(),
+        _ => e,
+    })
+}
+
+fn incompleto() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "Snapshot incompleto")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Cursor, path::PathBuf};
+
+    use super::{desempaquetar, empaquetar, MAGIA};
+
+    fn directorio_prueba(nombre: &str) -> PathBuf {
+        let directorio = std::env::temp_dir().join(format!("snapshot_{}", nombre));
+        let _ = fs::remove_dir_all(&directorio);
+        directorio
+    }
+
+    #[test]
+    fn empaquetar_y_desempaquetar() {
+        let origen = directorio_prueba("origen");
+        fs::create_dir_all(origen.join("consumers")).unwrap();
+        fs::write(origen.join("config.json"), b"{}").unwrap();
+        fs::write(origen.join("00000000000000000001.seg"), [0, 1, 2, 3]).unwrap();
+        fs::write(origen.join("consumers").join("c.json"), b"estado").unwrap();
+
+        let destino = directorio_prueba("destino");
+        desempaquetar(
+            &mut Cursor::new(empaquetar(&origen, &[]).unwrap()),
+            &destino,
+        )
+        .unwrap();
+        assert_eq!(fs::read(destino.join("config.json")).unwrap(), b"{}");
+        assert_eq!(
+            fs::read(destino.join("00000000000000000001.seg")).unwrap(),
+            [0, 1, 2, 3]
+        );
+        assert_eq!(
+            fs::read(destino.join("consumers").join("c.json")).unwrap(),
+            b"estado"
+        );
+
+        let sin_consumers = directorio_prueba("sin_consumers");
+        desempaquetar(
+            &mut Cursor::new(empaquetar(&origen, &["consumers"]).unwrap()),
+            &sin_consumers,
+        )
+        .unwrap();
+        assert!(sin_consumers.join("config.json").exists());
+        assert!(!sin_consumers.join("consumers").exists());
+
+        for directorio in [origen, destino, sin_consumers] {
+            fs::remove_dir_all(directorio).unwrap();
+        }
+    }
+
+    #[test]
+    fn rechaza_snapshots_invalidos() {
+        let destino = directorio_prueba("invalidos");
+
+        let mut fuera = MAGIA.to_vec();
+        fuera.extend_from_slice(&5u16.to_le_bytes());
+        fuera.extend_from_slice(b"../x1");
+        fuera.extend_from_slice(&0u64.to_le_bytes());
+        assert!(desempaquetar(&mut Cursor::new(fuera), &destino).is_err());
+
+        let mut incompleto = MAGIA.to_vec();
+        incompleto.extend_from_slice(&1u16.to_le_bytes());
+        incompleto.extend_from_slice(b"a");
+        incompleto.extend_from_slice(&10u64.to_le_bytes());
+        incompleto.extend_from_slice(b"abc");
+        assert!(desempaquetar(&mut Cursor::new(incompleto), &destino).is_err());
+
+        assert!(desempaquetar(&mut Cursor::new(b"otra cosa"), &destino).is_err());
+        assert!(!destino.exists());
+    }
+}
@@ -1,59 +1,356 @@
-use std::sync::mpsc::{Receiver, Sender};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fs, io,
+    path::PathBuf,
+    sync::mpsc::{Receiver, TryRecvError},
+    time::{Duration, Instant},
+};
 
 use chrono::Utc;
 use lib::jet_stream::{
-    consumer_config::ConsumerConfig, consumer_info::ConsumerInfo,
+    consumer_config::{AckPolicy, ConsumerConfig, ReplayPolicy},
+    consumer_estado::{ConsumerEstado, EntregaPendiente},
+    consumer_info::{ConsumerInfo, SecuenciaInfo},
     consumer_info_respuesta::JSConsumerInfoRespuesta,
+    eliminar_consumer_respuesta::JSEliminarConsumerRespuesta,
+    siguiente_mensaje_peticion::JSPeticionSiguienteMensaje,
 };
+use lib::parseador::headers::Headers;
+
+use mio::{Registry, Token};
 
 use crate::{
     conexion::{r#trait::Conexion, tick_contexto::TickContexto},
+    hilo::{
+        despertador::{Aviso, Despertador},
+        emisor::EmisorConexion,
+    },
     publicacion::Publicacion,
     registrador::Registrador,
     suscripciones::{suscripcion::Suscripcion, topico::Topico},
 };
 
-use super::actualizacion::ActualizacionJS;
+use super::{
+    actualizacion::ActualizacionJS,
+    almacenamiento::{mensaje::MensajeAlmacenado, AlmacenamientoCompartido},
+    cursor::Cursor,
+    evento_stream::{EnlaceStream, EventoStream},
+};
+
+/// Un mensaje entregado que todavía espera su ack
+struct Entrega {
+    mensaje: MensajeAlmacenado,
+    /// Cantidad de veces que se entregó el mensaje
+    entregas: u64,
+    /// Secuencia del consumer asignada en la última entrega
+    secuencia_consumer: u64,
+    /// Si no llega el ack antes de este momento el mensaje se vuelve a entregar
+    vencimiento: Instant,
+}
+
+/// Bytes que se entregan a un consumer push con control de flujo antes de pedir
+/// confirmación al cliente
+const BYTES_POR_CONTROL_DE_FLUJO: u64 = 256 * 1024;
+
+/// Tiempo mínimo entre dos escrituras del estado guardado. Los cambios de ese
+/// intervalo se escriben juntos, y al soltar el consumer se escribe lo que falte
+const INTERVALO_GUARDADO: Duration = Duration::from_secs(1);
+
+/// Estado de la entrega de mensajes de un consumer push
+struct EntregaPush {
+    ultimo_envio: Instant,
+    /// Tópico del último pedido de control de flujo que el cliente todavía no respondió
+    control_de_flujo_pendiente: Option<String>,
+    pedidos_control_de_flujo: u64,
+    bytes_desde_control_de_flujo: u64,
+    /// Bits que todavía se pueden enviar según `rate_limit_bps`
+    presupuesto_bits: f64,
+    ultima_recarga: Instant,
+}
+
+impl EntregaPush {
+    fn new() -> Self {
+        let ahora = Instant::now();
+
+        Self {
+            ultimo_envio: ahora,
+            control_de_flujo_pendiente: None,
+            pedidos_control_de_flujo: 0,
+            bytes_desde_control_de_flujo: 0,
+            presupuesto_bits: 0.,
+            ultima_recarga: ahora,
+        }
+    }
+
+    /// Suma los bits que corresponden al tiempo transcurrido, como máximo un segundo de envío
+    fn recargar_presupuesto(&mut self, rate_limit_bps: u64, ahora: Instant) {
+        let transcurrido = ahora.duration_since(self.ultima_recarga).as_secs_f64();
+        self.presupuesto_bits = (self.presupuesto_bits + transcurrido * rate_limit_bps as f64)
+            .min(rate_limit_bps as f64);
+        self.ultima_recarga = ahora;
+    }
+}
+
+/// Un pedido de mensajes de un cliente pull que todavía no se completó
+struct PedidoPendiente {
+    reply_to: String,
+    /// Mensajes que todavía se pueden entregar
+    restantes: usize,
+    /// Bytes que todavía se pueden entregar, si hay límite
+    bytes_restantes: Option<u64>,
+    no_wait: bool,
+    /// Si el pedido no se completa antes de este momento se responde `408 Request Timeout`
+    vencimiento: Option<Instant>,
+    latido: Option<Duration>,
+    ultimo_envio: Instant,
+}
+
+impl PedidoPendiente {
+    fn new(reply_to: String, peticion: JSPeticionSiguienteMensaje) -> Self {
+        let ahora = Instant::now();
+
+        Self {
+            reply_to,
+            restantes: peticion.batch.max(1),
+            bytes_restantes: (peticion.max_bytes > 0).then_some(peticion.max_bytes),
+            no_wait: peticion.no_wait,
+            vencimiento: (!peticion.expires.is_zero()).then(|| ahora + peticion.expires),
+            latido: (!peticion.idle_heartbeat.is_zero()).then_some(peticion.idle_heartbeat),
+            ultimo_envio: ahora,
+        }
+    }
+}
+
+/// Respuestas que se pueden enviar al tópico de ack de un mensaje
+#[derive(Debug, PartialEq)]
+enum TipoAck {
+    /// `+ACK` o cuerpo vacío
+    Ack,
+    /// `-NAK`, con una espera opcional antes de volver a entregar el mensaje
+    Nak(Option<Duration>),
+    /// `+WPI`, el mensaje se sigue procesando y se reinicia la espera del ack
+    EnProgreso,
+    /// `+TERM`, el mensaje no se tiene que volver a entregar
+    Terminar,
+}
 
 pub struct JetStreamConsumer {
     id_conexion: u64,
     nombre_stream: String,
     config: ConsumerConfig,
+    /// Archivo donde se guarda el estado del consumer para recuperarlo al
+    /// reiniciar. Los consumers efímeros no se guardan
+    ruta_estado: Option<PathBuf>,
     eliminado: bool,
+    /// Se pidió eliminar el consumer, se elimina al terminar el próximo tick
+    /// para que llegue a enviar la respuesta
+    eliminacion_pedida: bool,
+    /// Último pedido, ack o respuesta de control de flujo recibido
+    ultima_actividad: Instant,
     preparado: bool,
-    tx_actualizaciones_js: Sender<ActualizacionJS>,
+    tx_actualizaciones_js: EmisorConexion<ActualizacionJS>,
     respuestas: Vec<Publicacion>,
-    mensaje_pendiente: Option<Publicacion>,
-    rx_mensajes: Receiver<Publicacion>,
-    topico_ack_mensaje_pendiente: String,
+    /// Almacenamiento del stream, del que se leen los mensajes a medida que se entregan
+    almacenamiento: AlmacenamientoCompartido,
+    /// Hasta dónde se leyeron los mensajes del stream
+    cursor: Cursor,
+    /// Próximo mensaje nuevo a entregar, ya leído del almacenamiento
+    proximo: Option<MensajeAlmacenado>,
+    /// Mensajes entregados que esperan ack, por secuencia del stream
+    pendientes: BTreeMap<u64, Entrega>,
+    /// Secuencias de mensajes pendientes que hay que volver a entregar
+    reentregas: BTreeSet<u64>,
+    /// Últimas secuencias entregadas
+    entregados: SecuenciaInfo,
+    rx_mensajes: Receiver<EventoStream>,
+    /// Lo vincula con su hilo para que el stream pueda despertarlo
+    aviso: Aviso,
     registrador: Registrador,
-    reply_to_pendiente: Option<String>,
+    /// Pedidos de mensajes esperando respuesta, en el orden en que llegaron
+    pedidos: VecDeque<PedidoPendiente>,
+    push: EntregaPush,
+    /// Con `replay_policy` original: cuándo se entregó el primer mensaje y el tiempo en que se guardó
+    inicio_reproduccion: Option<(Instant, i64)>,
+    creado: String,
+    /// Si cambió algo que el stream tiene que saber desde la última actualización enviada
+    estado_modificado: bool,
+    /// Si cambiaron las entregas o los acks desde la última vez que se guardó el estado
+    cambios_sin_guardar: bool,
+    ultimo_guardado: Option<Instant>,
 }
 
 impl JetStreamConsumer {
     pub fn new(
         config: ConsumerConfig,
         nombre_stream: String,
-        tx_actualizaciones_js: Sender<ActualizacionJS>,
-        rx_mensajes: Receiver<Publicacion>,
+        ruta_estado: Option<PathBuf>,
+        tx_actualizaciones_js: EmisorConexion<ActualizacionJS>,
+        enlace: EnlaceStream,
+        cursor: Cursor,
         registrador: Registrador,
     ) -> Self {
         JetStreamConsumer {
             nombre_stream,
             id_conexion: 0,
             config,
+            ruta_estado,
             eliminado: false,
+            eliminacion_pedida: false,
+            ultima_actividad: Instant::now(),
             preparado: false,
             tx_actualizaciones_js,
             respuestas: Vec::new(),
-            mensaje_pendiente: None,
-            rx_mensajes,
-            topico_ack_mensaje_pendiente: "".to_string(),
+            almacenamiento: enlace.almacenamiento,
+            cursor,
+            proximo: None,
+            pendientes: BTreeMap::new(),
+            reentregas: BTreeSet::new(),
+            entregados: SecuenciaInfo::default(),
+            rx_mensajes: enlace.rx_eventos,
+            aviso: enlace.aviso,
             registrador,
-            reply_to_pendiente: None,
+            pedidos: VecDeque::new(),
+            push: EntregaPush::new(),
+            inicio_reproduccion: None,
+            creado: Utc::now().to_rfc3339(),
+            estado_modificado: false,
+            cambios_sin_guardar: true,
+            ultimo_guardado: None,
         }
     }
 
+    /// Recupera las entregas de un estado guardado. Los mensajes pendientes de
+    /// ack se vuelven a entregar si el ack no llega a tiempo
+    pub fn restaurar(
+        &mut self,
+        estado: ConsumerEstado,
+        mensajes_pendientes: Vec<MensajeAlmacenado>,
+    ) {
+        let ahora = Instant::now();
+
+        self.creado = estado.created;
+        self.entregados = estado.delivered;
+
+        for mensaje in mensajes_pendientes {
+            if let Some(pendiente) = estado
+                .pending
+                .iter()
+                .find(|pendiente| pendiente.stream_seq == mensaje.secuencia)
+            {
+                self.pendientes.insert(
+                    mensaje.secuencia,
+                    Entrega {
+                        mensaje,
+                        entregas: pendiente.deliveries,
+                        secuencia_consumer: pendiente.consumer_seq,
+                        vencimiento: ahora + self.config.espera_ack(pendiente.deliveries),
+                    },
+                );
+            }
+        }
+    }
+
+    fn estado_guardado(&self) -> ConsumerEstado {
+        ConsumerEstado {
+            config: self.config.clone(),
+            created: self.creado.clone(),
+            delivered: self.entregados.clone(),
+            pending: self
+                .pendientes
+                .iter()
+                .map(|(secuencia, entrega)| EntregaPendiente {
+                    stream_seq: *secuencia,
+                    consumer_seq: entrega.secuencia_consumer,
+                    deliveries: entrega.entregas,
+                })
+                .collect(),
+        }
+    }
+
+    /// Si hay cambios sin guardar y ya pasó `INTERVALO_GUARDADO` desde la última escritura
+    fn toca_guardar(&self) -> bool {
+        self.cambios_sin_guardar
+            && !self.eliminacion_pedida
+            && self
+                .ultimo_guardado
+                .is_none_or(|ultimo| ultimo.elapsed() >= INTERVALO_GUARDADO)
+    }
+
+    /// Escribe el estado en un archivo temporal y lo renombra, para que un corte
+    /// no deje el archivo a medio escribir
+    fn guardar_estado(&mut self) {
+        self.cambios_sin_guardar = false;
+        self.ultimo_guardado = Some(Instant::now());
+
+        let ruta_estado = match &self.ruta_estado {
+            Some(ruta_estado) => ruta_estado,
+            None => return,
+        };
+
+        let resultado = self
+            .estado_guardado()
+            .to_json()
+            .map_err(std::io::Error::other)
+            .and_then(|json| {
+                if let Some(directorio) = ruta_estado.parent() {
+                    fs::create_dir_all(directorio)?;
+                }
+                let temporal = ruta_estado.with_extension("tmp");
+                fs::write(&temporal, json)?;
+                fs::rename(&temporal, ruta_estado)
+            });
+
+        if let Err(e) = resultado {
+            self.registrador.error(
+                &format!(
+                    "Error al guardar el estado del consumer {}: {}",
+                    self.config.nombre(),
+                    e
+                ),
+                Some(self.id_conexion),
+            );
+        }
+    }
+
+    /// Borra el estado guardado y avisa al stream que el consumer ya no existe
+    fn eliminar(&mut self) {
+        self.eliminado = true;
+
+        if let Some(ruta_estado) = &self.ruta_estado {
+            // Si se eliminó el stream, el archivo ya se borró con su directorio
+            match fs::remove_file(ruta_estado) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    self.registrador.advertencia(
+                        &format!(
+                            "No se pudo borrar el estado del consumer {}: {}",
+                            self.config.nombre(),
+                            e
+                        ),
+                        Some(self.id_conexion),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        let _ = self
+            .tx_actualizaciones_js
+            .send(ActualizacionJS::ConsumerEliminado(
+                self.config.nombre().to_string(),
+            ));
+    }
+
+    /// Un consumer efímero con `inactive_threshold` se elimina si pasa ese tiempo
+    /// sin pedidos, acks ni respuestas de control de flujo
+    fn esta_inactivo(&self) -> bool {
+        let limite = self.config.inactive_threshold;
+
+        !self.config.es_durable()
+            && !limite.is_zero()
+            && self.pedidos.is_empty()
+            && self.ultima_actividad.elapsed() >= limite
+    }
+
     fn suscribir(&self, contexto: &mut TickContexto, topico: &str, sid: &str) {
         contexto.suscribir(Suscripcion::new(
             contexto.id_hilo,
@@ -67,20 +364,538 @@ impl JetStreamConsumer {
     fn enviar_actualizacion_de_estado(&self) {
         let _ = self
             .tx_actualizaciones_js
-            .send(ActualizacionJS::Consumer(ConsumerInfo {
-                config: self.config.clone(),
-                created: Utc::now().to_rfc3339(),
-                ts: Utc::now().to_rfc3339(),
-            }));
+            .send(ActualizacionJS::Consumer(self.info()));
     }
 
-    fn responder_mensaje_pendiente(&mut self, reply_to: &str, mensaje: &Publicacion) {
+    fn info(&self) -> ConsumerInfo {
+        let ack_floor = match self.pendientes.iter().next() {
+            Some((secuencia_stream, _)) => SecuenciaInfo {
+                consumer_seq: self
+                    .pendientes
+                    .values()
+                    .map(|entrega| entrega.secuencia_consumer)
+                    .min()
+                    .unwrap_or_default()
+                    .saturating_sub(1),
+                stream_seq: secuencia_stream.saturating_sub(1),
+            },
+            None => self.entregados.clone(),
+        };
+
+        ConsumerInfo {
+            config: self.config.clone(),
+            created: self.creado.clone(),
+            ts: Utc::now().to_rfc3339(),
+            delivered: self.entregados.clone(),
+            ack_floor,
+            num_ack_pending: self.pendientes.len() as u64,
+            num_redelivered: self
+                .pendientes
+                .values()
+                .filter(|entrega| entrega.entregas > 1)
+                .count() as u64,
+            num_waiting: self.pedidos.len() as u64,
+            num_pending: self.sin_entregar(),
+        }
+    }
+
+    /// Mensajes que el consumer todavía no entregó por primera vez
+    fn sin_entregar(&self) -> u64 {
+        self.cursor.restantes() + self.proximo.is_some() as u64
+    }
+
+    /// Recibe los avisos del stream y, si no hay un mensaje nuevo preparado, lee
+    /// el próximo del almacenamiento. Los avisos se reciben con el almacenamiento
+    /// bloqueado para que lo que se lea ya esté contado en el cursor
+    fn recibir_eventos_stream(&mut self) {
+        let almacenamiento = self.almacenamiento.clone();
+        let almacenamiento = almacenamiento.bloquear();
+
+        loop {
+            let evento = match self.rx_mensajes.try_recv() {
+                Ok(evento) => evento,
+                Err(TryRecvError::Empty) => break,
+                // El stream se eliminó, y sus consumers con él
+                Err(TryRecvError::Disconnected) => {
+                    self.eliminacion_pedida = true;
+                    break;
+                }
+            };
+
+            match evento {
+                EventoStream::Nuevo => self.cursor.agregado(),
+                EventoStream::Eliminado(secuencia) => {
+                    if self
+                        .proximo
+                        .as_ref()
+                        .is_some_and(|mensaje| mensaje.secuencia == secuencia)
+                    {
+                        self.proximo = None;
+                    } else {
+                        self.cursor.eliminado(secuencia);
+                    }
+                    self.reentregas.remove(&secuencia);
+                    if self.pendientes.remove(&secuencia).is_some() {
+                        self.cambios_sin_guardar = true;
+                    }
+                }
+            }
+            self.estado_modificado = true;
+        }
+
+        while self.proximo.is_none() {
+            match self.cursor.siguiente(&**almacenamiento, &self.config) {
+                Ok(Some(mensaje)) => self.proximo = Some(mensaje),
+                Ok(None) => break,
+                Err(e) => self.registrador.error(
+                    &format!(
+                        "Error al leer un mensaje para el consumer {}: {}",
+                        self.config.nombre(),
+                        e
+                    ),
+                    Some(self.id_conexion),
+                ),
+            }
+        }
+    }
+
+    /// Marca para reentregar los mensajes cuyo ack no llegó a tiempo. Los que ya
+    /// se entregaron `max_deliver` veces se descartan
+    fn revisar_vencimientos(&mut self) {
+        let ahora = Instant::now();
+        let mut descartados = Vec::new();
+
+        for (secuencia, entrega) in self.pendientes.iter() {
+            if entrega.vencimiento > ahora || self.reentregas.contains(secuencia) {
+                continue;
+            }
+
+            if self.entregas_agotadas(entrega.entregas) {
+                descartados.push(*secuencia);
+            } else {
+                self.reentregas.insert(*secuencia);
+            }
+        }
+
+        for secuencia in descartados {
+            self.descartar(secuencia);
+        }
+    }
+
+    fn entregas_agotadas(&self, entregas: u64) -> bool {
+        self.config.max_deliver > 0 && entregas >= self.config.max_deliver as u64
+    }
+
+    /// Deja de esperar el ack de un mensaje que ya no se puede volver a entregar
+    fn descartar(&mut self, secuencia: u64) {
+        self.registrador.advertencia(
+            &format!(
+                "Mensaje {} descartado por superar max_deliver en consumer {}",
+                secuencia,
+                self.config.nombre()
+            ),
+            Some(self.id_conexion),
+        );
+        self.pendientes.remove(&secuencia);
+        self.reentregas.remove(&secuencia);
+        self.estado_modificado = true;
+        self.cambios_sin_guardar = true;
+    }
+
+    /// Tamaño del mensaje que devolvería `proxima_entrega`, si hay alguno para entregar
+    fn tamano_proxima_entrega(&self) -> Option<u64> {
+        if let Some(entrega) = self
+            .reentregas
+            .first()
+            .and_then(|secuencia| self.pendientes.get(secuencia))
+        {
+            return Some(entrega.mensaje.bytes());
+        }
+
+        let max_ack_pending = self.config.max_ack_pending;
+        if max_ack_pending > 0 && self.pendientes.len() >= max_ack_pending as usize {
+            return None;
+        }
+
+        self.proximo
+            .as_ref()
+            .filter(|mensaje| self.mensaje_listo(mensaje))
+            .map(|mensaje| mensaje.bytes())
+    }
+
+    /// Momento en que se puede entregar el primer mensaje nuevo con `replay_policy`
+    /// original, si todavía no llegó
+    fn proxima_reproduccion(&self) -> Option<Instant> {
+        if self.config.replay_policy != ReplayPolicy::Original {
+            return None;
+        }
+
+        let (inicio, tiempo_inicial) = self.inicio_reproduccion?;
+        let mensaje = self.proximo.as_ref()?;
+        let diferencia = mensaje.tiempo.saturating_sub(tiempo_inicial).max(0) as u64;
+        Some(inicio + Duration::from_nanos(diferencia)).filter(|listo| *listo > Instant::now())
+    }
+
+    /// Momento en que el presupuesto de `rate_limit_bps` vuelve a permitir
+    /// entregar, si se agotó y hay algo para entregar
+    fn proxima_recarga(&self) -> Option<Instant> {
+        let rate_limit_bps = self.config.rate_limit_bps;
+        if rate_limit_bps == 0
+            || self.push.presupuesto_bits > 0.
+            || self.push.control_de_flujo_pendiente.is_some()
+            || self.tamano_proxima_entrega().is_none()
+        {
+            return None;
+        }
+
+        let faltante = 1. - self.push.presupuesto_bits;
+        Some(self.push.ultima_recarga + Duration::from_secs_f64(faltante / rate_limit_bps as f64))
+    }
+
+    /// Primer momento en que hay que hacer algo aunque no lleguen mensajes:
+    /// reentregar por `ack_wait`, vencer pedidos o enviarles latidos, enviar
+    /// latidos push, seguir entregando con `rate_limit_bps` o `replay_policy`,
+    /// eliminar el consumer por inactividad o guardar el estado
+    fn proximo_vencimiento(&self) -> Option<Instant> {
+        let acks = self
+            .pendientes
+            .iter()
+            .filter(|(secuencia, _)| !self.reentregas.contains(secuencia))
+            .map(|(_, entrega)| entrega.vencimiento);
+
+        let pedidos = self.pedidos.iter().flat_map(|pedido| {
+            [
+                pedido.vencimiento,
+                pedido.latido.map(|latido| pedido.ultimo_envio + latido),
+            ]
+        });
+
+        let latido_push = (self.config.es_push() && !self.config.idle_heartbeat.is_zero())
+            .then(|| self.push.ultimo_envio + self.config.idle_heartbeat);
+
+        let limite = self.config.inactive_threshold;
+        let inactividad =
+            (!self.config.es_durable() && !limite.is_zero() && self.pedidos.is_empty())
+                .then(|| self.ultima_actividad + limite);
+
+        let guardado = self
+            .ultimo_guardado
+            .filter(|_| self.cambios_sin_guardar)
+            .map(|ultimo| ultimo + INTERVALO_GUARDADO);
+
+        acks.chain(pedidos.flatten())
+            .chain(
+                [
+                    latido_push,
+                    self.proxima_recarga(),
+                    self.proxima_reproduccion(),
+                    inactividad,
+                    guardado,
+                ]
+                .into_iter()
+                .flatten(),
+            )
+            .min()
+    }
+
+    /// Con `replay_policy` original un mensaje nuevo se entrega recién cuando pasó,
+    /// desde la primera entrega, el mismo tiempo que había entre ellos al guardarse
+    fn mensaje_listo(&self, mensaje: &MensajeAlmacenado) -> bool {
+        if self.config.replay_policy != ReplayPolicy::Original {
+            return true;
+        }
+
+        match self.inicio_reproduccion {
+            Some((inicio, tiempo_inicial)) => {
+                let diferencia = mensaje.tiempo.saturating_sub(tiempo_inicial).max(0) as u64;
+                inicio + Duration::from_nanos(diferencia) <= Instant::now()
+            }
+            None => true,
+        }
+    }
+
+    /// Entrega los mensajes disponibles a los pedidos pendientes, en orden, y
+    /// responde los pedidos que vencieron o que no se pueden completar
+    fn atender_pedidos(&mut self) {
+        let ahora = Instant::now();
+
+        for mut pedido in std::mem::take(&mut self.pedidos) {
+            if pedido
+                .vencimiento
+                .is_some_and(|vencimiento| vencimiento <= ahora)
+            {
+                self.responder_estado(&pedido.reply_to, 408, "Request Timeout");
+                continue;
+            }
+
+            let mut terminado = false;
+            while pedido.restantes > 0 {
+                let tamano = match self.tamano_proxima_entrega() {
+                    Some(tamano) => tamano,
+                    None => break,
+                };
+
+                if pedido.bytes_restantes.is_some_and(|bytes| tamano > bytes) {
+                    self.responder_estado(&pedido.reply_to, 409, "Message Size Exceeds MaxBytes");
+                    terminado = true;
+                    break;
+                }
+
+                if let Some((mensaje, entregas)) = self.proxima_entrega() {
+                    self.entregar(&pedido.reply_to, &mensaje, entregas);
+                    pedido.restantes -= 1;
+                    pedido.bytes_restantes = pedido.bytes_restantes.map(|b| b - tamano);
+                    pedido.ultimo_envio = ahora;
+                }
+            }
+
+            if terminado || pedido.restantes == 0 {
+                continue;
+            }
+
+            if pedido.no_wait {
+                self.responder_estado(&pedido.reply_to, 404, "No Messages");
+                continue;
+            }
+
+            if pedido
+                .latido
+                .is_some_and(|latido| pedido.ultimo_envio + latido <= ahora)
+            {
+                self.responder_estado(&pedido.reply_to, 100, "Idle Heartbeat");
+                pedido.ultimo_envio = ahora;
+            }
+
+            self.pedidos.push_back(pedido);
+        }
+    }
+
+    /// Publica en `deliver_subject` los mensajes disponibles, respetando el
+    /// control de flujo y `rate_limit_bps`, y envía latidos si no hubo mensajes
+    fn entregar_push(&mut self) {
+        let deliver_subject = match &self.config.deliver_subject {
+            Some(deliver_subject) => deliver_subject.clone(),
+            None => return,
+        };
+
+        let ahora = Instant::now();
+        let rate_limit_bps = self.config.rate_limit_bps;
+        if rate_limit_bps > 0 {
+            self.push.recargar_presupuesto(rate_limit_bps, ahora);
+        }
+
+        while self.push.control_de_flujo_pendiente.is_none()
+            && (rate_limit_bps == 0 || self.push.presupuesto_bits > 0.)
+        {
+            let (mensaje, entregas) = match self.proxima_entrega() {
+                Some(entrega) => entrega,
+                None => break,
+            };
+
+            let tamano = mensaje.bytes();
+            self.entregar(&deliver_subject, &mensaje, entregas);
+            self.push.ultimo_envio = ahora;
+            self.push.presupuesto_bits -= (tamano * 8) as f64;
+            self.push.bytes_desde_control_de_flujo += tamano;
+
+            if self.config.flow_control
+                && self.push.bytes_desde_control_de_flujo >= BYTES_POR_CONTROL_DE_FLUJO
+            {
+                self.pedir_control_de_flujo(&deliver_subject);
+            }
+        }
+
+        let latido = self.config.idle_heartbeat;
+        if !latido.is_zero() && self.push.ultimo_envio + latido <= ahora {
+            let mut headers = Headers::con_estado(100, "Idle Heartbeat");
+            headers.insertar(
+                "Nats-Last-Consumer",
+                &self.entregados.consumer_seq.to_string(),
+            );
+            headers.insertar("Nats-Last-Stream", &self.entregados.stream_seq.to_string());
+            if let Some(control) = &self.push.control_de_flujo_pendiente {
+                headers.insertar("Nats-Consumer-Stalled", control);
+            }
+
+            self.publicar_estado(&deliver_subject, headers, None);
+            self.push.ultimo_envio = ahora;
+        }
+    }
+
+    /// Deja de entregar mensajes hasta que el cliente responda al tópico del pedido
+    fn pedir_control_de_flujo(&mut self, deliver_subject: &str) {
+        self.push.pedidos_control_de_flujo += 1;
+        let topico = format!(
+            "$JS.FC.{}.{}.{}",
+            self.nombre_stream,
+            self.config.nombre(),
+            self.push.pedidos_control_de_flujo
+        );
+
+        self.publicar_estado(
+            deliver_subject,
+            Headers::con_estado(100, "FlowControl Request"),
+            Some(topico.clone()),
+        );
+        self.push.control_de_flujo_pendiente = Some(topico);
+        self.push.bytes_desde_control_de_flujo = 0;
+    }
+
+    fn procesar_control_de_flujo(&mut self, topico: &str) {
+        if self.push.control_de_flujo_pendiente.as_deref() == Some(topico) {
+            self.push.control_de_flujo_pendiente = None;
+        }
+    }
+
+    /// Envía un mensaje vacío con un estado en los headers (por ejemplo `404 No Messages`)
+    fn responder_estado(&mut self, reply_to: &str, estado: u16, descripcion: &str) {
+        self.publicar_estado(reply_to, Headers::con_estado(estado, descripcion), None);
+    }
+
+    fn publicar_estado(&mut self, topico: &str, headers: Headers, reply_to: Option<String>) {
         self.respuestas.push(Publicacion::new(
-            reply_to.to_string(),
-            mensaje.payload.clone(),
-            mensaje.header.clone(),
-            Some(self.topico_ack_mensaje_pendiente.clone()),
+            topico.to_string(),
+            Vec::new(),
+            Some(headers.serializar()),
+            reply_to,
         ));
+        self.estado_modificado = true;
+    }
+
+    /// Elige el próximo mensaje a entregar: primero los que hay que reentregar y
+    /// después los nuevos, si no se superó `max_ack_pending`. Al entregar uno
+    /// nuevo se lee el siguiente del almacenamiento
+    fn proxima_entrega(&mut self) -> Option<(MensajeAlmacenado, u64)> {
+        let ahora = Instant::now();
+
+        if let Some(secuencia) = self.reentregas.pop_first() {
+            if let Some(entrega) = self.pendientes.get_mut(&secuencia) {
+                self.entregados.consumer_seq += 1;
+                entrega.entregas += 1;
+                entrega.secuencia_consumer = self.entregados.consumer_seq;
+                entrega.vencimiento = ahora + self.config.espera_ack(entrega.entregas);
+                self.cambios_sin_guardar = true;
+                return Some((entrega.mensaje.clone(), entrega.entregas));
+            }
+        }
+
+        let max_ack_pending = self.config.max_ack_pending;
+        if max_ack_pending > 0 && self.pendientes.len() >= max_ack_pending as usize {
+            return None;
+        }
+
+        if !self
+            .proximo
+            .as_ref()
+            .is_some_and(|mensaje| self.mensaje_listo(mensaje))
+        {
+            return None;
+        }
+
+        let mensaje = self.proximo.take()?;
+        if self.config.replay_policy == ReplayPolicy::Original && self.inicio_reproduccion.is_none()
+        {
+            self.inicio_reproduccion = Some((ahora, mensaje.tiempo));
+        }
+
+        self.entregados.consumer_seq += 1;
+        self.entregados.stream_seq = self.entregados.stream_seq.max(mensaje.secuencia);
+        self.cambios_sin_guardar = true;
+
+        if self.config.ack_policy != AckPolicy::None {
+            self.pendientes.insert(
+                mensaje.secuencia,
+                Entrega {
+                    mensaje: mensaje.clone(),
+                    entregas: 1,
+                    secuencia_consumer: self.entregados.consumer_seq,
+                    vencimiento: ahora + self.config.espera_ack(1),
+                },
+            );
+        }
+
+        self.recibir_eventos_stream();
+        Some((mensaje, 1))
+    }
+
+    fn entregar(&mut self, reply_to: &str, mensaje: &MensajeAlmacenado, entregas: u64) {
+        // $JS.ACK.<stream>.<consumer>.<entregas>.<secuencia stream>.<secuencia consumer>.<tiempo>.<pendientes>
+        let topico_ack = format!(
+            "$JS.ACK.{}.{}.{}.{}.{}.{}.{}",
+            self.nombre_stream,
+            self.config.nombre(),
+            entregas,
+            mensaje.secuencia,
+            self.entregados.consumer_seq,
+            mensaje.tiempo,
+            self.sin_entregar()
+        );
+
+        self.respuestas.push(
+            Publicacion::new(
+                reply_to.to_string(),
+                mensaje.payload.clone(),
+                mensaje.header.clone(),
+                Some(topico_ack),
+            )
+            .con_topico_original(mensaje.topico.clone()),
+        );
+        self.estado_modificado = true;
+    }
+
+    fn procesar_ack(&mut self, topico: &str, payload: &[u8]) {
+        let prefijo = format!("$JS.ACK.{}.{}.", self.nombre_stream, self.config.nombre());
+
+        let secuencia = match topico
+            .strip_prefix(&prefijo)
+            .and_then(|resto| resto.split('.').nth(1))
+            .and_then(|secuencia| secuencia.parse::<u64>().ok())
+        {
+            Some(secuencia) => secuencia,
+            None => return,
+        };
+
+        match parsear_ack(payload) {
+            TipoAck::Ack => match self.config.ack_policy {
+                AckPolicy::All => {
+                    self.pendientes.retain(|s, _| *s > secuencia);
+                    self.reentregas.retain(|s| *s > secuencia);
+                }
+                AckPolicy::Explicit => {
+                    self.pendientes.remove(&secuencia);
+                    self.reentregas.remove(&secuencia);
+                }
+                AckPolicy::None => {}
+            },
+            TipoAck::Nak(Some(espera)) => {
+                if let Some(entrega) = self.pendientes.get_mut(&secuencia) {
+                    entrega.vencimiento = Instant::now() + espera;
+                    self.reentregas.remove(&secuencia);
+                }
+            }
+            TipoAck::Nak(None) => {
+                if let Some(entrega) = self.pendientes.get(&secuencia) {
+                    if self.entregas_agotadas(entrega.entregas) {
+                        self.descartar(secuencia);
+                    } else {
+                        self.reentregas.insert(secuencia);
+                    }
+                }
+            }
+            TipoAck::EnProgreso => {
+                if let Some(entrega) = self.pendientes.get_mut(&secuencia) {
+                    entrega.vencimiento = Instant::now() + self.config.espera_ack(entrega.entregas);
+                    self.reentregas.remove(&secuencia);
+                }
+            }
+            TipoAck::Terminar => {
+                self.pendientes.remove(&secuencia);
+                self.reentregas.remove(&secuencia);
+            }
+        }
+
+        self.estado_modificado = true;
+        self.cambios_sin_guardar = true;
     }
 }
 
@@ -99,7 +914,8 @@ impl Conexion for JetStreamConsumer {
                 contexto,
                 &format!(
                     "$JS.API.CONSUMER.INFO.{}.{}",
-                    self.nombre_stream, self.config.durable_name
+                    self.nombre_stream,
+                    self.config.nombre()
                 ),
                 "info",
             );
@@ -107,7 +923,8 @@ impl Conexion for JetStreamConsumer {
                 contexto,
                 &format!(
                     "$JS.API.CONSUMER.DELETE.{}.{}",
-                    self.nombre_stream, self.config.durable_name
+                    self.nombre_stream,
+                    self.config.nombre()
                 ),
                 "eliminar",
             );
@@ -115,46 +932,67 @@ impl Conexion for JetStreamConsumer {
                 contexto,
                 &format!(
                     "$JS.API.CONSUMER.MSG.NEXT.{}.{}",
-                    self.nombre_stream, self.config.durable_name
+                    self.nombre_stream,
+                    self.config.nombre()
                 ),
                 "mensaje_siguiente",
             );
             self.suscribir(
                 contexto,
-                &format!(
-                    "$JS.ACK.{}.{}.*",
-                    self.nombre_stream, self.config.durable_name
-                ),
+                &format!("$JS.ACK.{}.{}.>", self.nombre_stream, self.config.nombre()),
                 "ack",
             );
+            if self.config.es_push() {
+                self.suscribir(
+                    contexto,
+                    &format!("$JS.FC.{}.{}.*", self.nombre_stream, self.config.nombre()),
+                    "control_de_flujo",
+                );
+            }
 
             self.enviar_actualizacion_de_estado();
 
             self.registrador.info(
-                &format!("JetStreamConsumer {} preparado", self.config.durable_name),
+                &format!("JetStreamConsumer {} preparado", self.config.nombre()),
                 Some(self.obtener_id()),
             );
 
             self.preparado = true;
         }
 
-        if self.mensaje_pendiente.is_none() {
-            if let Ok(mensaje) = self.rx_mensajes.try_recv() {
-                self.mensaje_pendiente = Some(mensaje);
-            }
-        }
+        self.recibir_eventos_stream();
+        self.revisar_vencimientos();
 
-        let mp = self.mensaje_pendiente.clone();
+        self.atender_pedidos();
+        self.entregar_push();
 
-        if let Some(m) = &mp {
-            if let Some(reply_to) = &self.reply_to_pendiente.take() {
-                self.responder_mensaje_pendiente(reply_to, m);
-            }
+        if self.estado_modificado {
+            self.enviar_actualizacion_de_estado();
+            self.estado_modificado = false;
+        }
+
+        if self.toca_guardar() {
+            self.guardar_estado();
         }
 
         for respuesta in self.respuestas.drain(..) {
             contexto.publicar(respuesta);
         }
+
+        if self.esta_inactivo() {
+            self.registrador.info(
+                &format!(
+                    "Consumer efímero {} eliminado por inactividad",
+                    self.config.nombre()
+                ),
+                Some(self.id_conexion),
+            );
+            self.eliminacion_pedida = true;
+        }
+
+        if self.eliminacion_pedida && !self.eliminado {
+            self.eliminar();
+        }
     }
 
     fn escribir_publicacion_mensaje(
@@ -164,9 +1002,7 @@ impl Conexion for JetStreamConsumer {
         match mensaje.sid.as_str() {
             "info" => {
                 if let Some(reply_to) = &mensaje.replay_to {
-                    if let Ok(respuesta) =
-                        JSConsumerInfoRespuesta::new(self.config.clone()).to_json()
-                    {
+                    if let Ok(respuesta) = JSConsumerInfoRespuesta::new(self.info()).to_json() {
                         self.respuestas.push(Publicacion::new(
                             reply_to.to_string(),
                             respuesta.as_bytes().to_owned(),
@@ -177,35 +1013,43 @@ impl Conexion for JetStreamConsumer {
                 }
             }
             "eliminar" => {
-                self.eliminado = true;
-                let _ = self
-                    .tx_actualizaciones_js
-                    .send(ActualizacionJS::ConsumerEliminado(
-                        self.config.durable_name.clone(),
-                    ));
+                if let Some(reply_to) = &mensaje.replay_to {
+                    if let Ok(respuesta) = JSEliminarConsumerRespuesta::new().to_json() {
+                        self.respuestas.push(Publicacion::new(
+                            reply_to.to_string(),
+                            respuesta.as_bytes().to_owned(),
+                            None,
+                            None,
+                        ));
+                    }
+                }
+                self.eliminacion_pedida = true;
             }
             "mensaje_siguiente" => {
+                self.ultima_actividad = Instant::now();
                 if let Some(reply_to) = &mensaje.replay_to {
-                    self.reply_to_pendiente = Some(reply_to.to_string());
-
-                    if self.topico_ack_mensaje_pendiente.is_empty() {
-                        self.topico_ack_mensaje_pendiente = format!(
-                            "$JS.ACK.{}.{}.{}",
-                            self.nombre_stream,
-                            self.config.durable_name,
-                            nuid::next()
-                        );
+                    if self.config.es_push() {
+                        self.responder_estado(reply_to, 409, "Consumer is push based");
+                        return;
+                    }
+
+                    match JSPeticionSiguienteMensaje::parsear(&mensaje.payload) {
+                        Ok(peticion) => {
+                            self.pedidos
+                                .push_back(PedidoPendiente::new(reply_to.to_string(), peticion));
+                            self.estado_modificado = true;
+                        }
+                        Err(_) => self.responder_estado(reply_to, 400, "Bad Request"),
                     }
                 }
             }
             "ack" => {
-                if self.mensaje_pendiente.is_some()
-                    && self.topico_ack_mensaje_pendiente.eq(&mensaje.topico)
-                {
-                    self.mensaje_pendiente = None;
-                    self.reply_to_pendiente = None;
-                    self.topico_ack_mensaje_pendiente = "".to_string();
-                }
+                self.ultima_actividad = Instant::now();
+                self.procesar_ack(&mensaje.topico, &mensaje.payload);
+            }
+            "control_de_flujo" => {
+                self.ultima_actividad = Instant::now();
+                self.procesar_control_de_flujo(&mensaje.topico);
             }
             _ => {}
         }
@@ -214,4 +1058,480 @@ impl Conexion for JetStreamConsumer {
     fn esta_conectado(&self) -> bool {
         !self.eliminado
     }
+
+    /// El stream avisa al hilo cuando le envía mensajes
+    fn registrar(
+        &mut self,
+        _registro: &Registry,
+        _token: Token,
+        despertador: &Despertador,
+    ) -> io::Result<()> {
+        self.aviso.vincular(despertador, self.id_conexion);
+        Ok(())
+    }
+
+    fn intervalo_tick(&self) -> Option<Duration> {
+        self.proximo_vencimiento()
+            .map(|vencimiento| vencimiento.saturating_duration_since(Instant::now()))
+    }
+}
+
+impl Drop for JetStreamConsumer {
+    /// Escribe los cambios que todavía esperaban el intervalo de guardado
+    fn drop(&mut self) {
+        if self.cambios_sin_guardar && !self.eliminado && !self.eliminacion_pedida {
+            self.guardar_estado();
+        }
+    }
+}
+
+fn parsear_ack(payload: &[u8]) -> TipoAck {
+    let texto = String::from_utf8_lossy(payload);
+    let texto = texto.trim();
+
+    if let Some(resto) = texto.strip_prefix("-NAK") {
+        // -NAK {"delay": <nanosegundos>}
+        let espera = resto
+            .split_once("\"delay\"")
+            .and_then(|(_, valor)| valor.split_once(':'))
+            .map(|(_, valor)| {
+                valor
+                    .trim()
+                    .chars()
+                    .take_while(|c| c.is_ascii_digit())
+                    .collect::<String>()
+            })
+            .and_then(|nanos| nanos.parse::<u64>().ok())
+            .map(Duration::from_nanos);
+        return TipoAck::Nak(espera);
+    }
+
+    if texto.starts_with("+WPI") {
+        return TipoAck::EnProgreso;
+    }
+
+    if texto.starts_with("+TERM") {
+        return TipoAck::Terminar;
+    }
+
+    TipoAck::Ack
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        path::PathBuf,
+        sync::mpsc::{channel, Receiver, Sender},
+        time::Duration,
+    };
+
+    use lib::jet_stream::{
+        consumer_config::{AckPolicy, ConsumerConfig},
+        consumer_estado::ConsumerEstado,
+        consumer_info::ConsumerInfo,
+    };
+
+    use crate::{
+        conexion::{r#trait::Conexion, tick_contexto::TickContexto},
+        hilo::{despertador::Aviso, emisor::EmisorConexion},
+        jetstream::{
+            actualizacion::ActualizacionJS,
+            almacenamiento::{
+                memoria::AlmacenamientoMemoria, mensaje::MensajeAlmacenado,
+                AlmacenamientoCompartido,
+            },
+            cursor::Cursor,
+            evento_stream::{EnlaceStream, EventoStream},
+        },
+        publicacion::{mensaje::PublicacionMensaje, Publicacion},
+        registrador::Registrador,
+    };
+
+    use super::{parsear_ack, JetStreamConsumer, TipoAck, INTERVALO_GUARDADO};
+
+    const ENTREGAS: &str = "entregas";
+
+    struct Prueba {
+        consumer: JetStreamConsumer,
+        almacenamiento: AlmacenamientoCompartido,
+        tx_mensajes: Sender<EventoStream>,
+        rx_actualizaciones: Receiver<ActualizacionJS>,
+    }
+
+    /// Consumer push que entrega en `ENTREGAS`, sin estado guardado, de un
+    /// stream vacío en memoria
+    fn prueba(config: ConsumerConfig) -> Prueba {
+        let almacenamiento = AlmacenamientoCompartido::new(Box::new(AlmacenamientoMemoria::new()));
+        prueba_guardada(config, None, almacenamiento, None)
+    }
+
+    /// Como lo crea el stream: si hay un estado guardado sigue desde el último
+    /// mensaje entregado y recupera los pendientes de ack
+    fn prueba_guardada(
+        config: ConsumerConfig,
+        ruta_estado: Option<PathBuf>,
+        almacenamiento: AlmacenamientoCompartido,
+        estado: Option<ConsumerEstado>,
+    ) -> Prueba {
+        let (tx_actualizaciones, rx_actualizaciones) = channel();
+        let (tx_mensajes, rx_mensajes) = channel();
+
+        let config = ConsumerConfig {
+            durable_name: "consumer".to_string(),
+            deliver_subject: Some(ENTREGAS.to_string()),
+            ..config
+        };
+        let cursor = match &estado {
+            Some(estado) => Cursor::desde(
+                &**almacenamiento.bloquear(),
+                &config,
+                estado.delivered.stream_seq + 1,
+            ),
+            None => Cursor::nuevo(&**almacenamiento.bloquear(), &config),
+        };
+
+        let mut consumer = JetStreamConsumer::new(
+            config,
+            "stream".to_string(),
+            ruta_estado,
+            EmisorConexion::new(tx_actualizaciones, Aviso::new()),
+            EnlaceStream {
+                almacenamiento: almacenamiento.clone(),
+                rx_eventos: rx_mensajes,
+                aviso: Aviso::new(),
+            },
+            cursor,
+            Registrador::new(Some(false)),
+        );
+
+        if let Some(estado) = estado {
+            let pendientes = estado
+                .pending
+                .iter()
+                .filter_map(|pendiente| {
+                    almacenamiento
+                        .bloquear()
+                        .obtener(pendiente.stream_seq)
+                        .unwrap()
+                })
+                .collect();
+            consumer.restaurar(estado, pendientes);
+        }
+
+        Prueba {
+            consumer,
+            almacenamiento,
+            tx_mensajes,
+            rx_actualizaciones,
+        }
+    }
+
+    impl Prueba {
+        /// Guarda un mensaje en el stream y se lo avisa al consumer
+        fn publicar(&self, secuencia: u64) {
+            let mut almacenamiento = self.almacenamiento.bloquear();
+            almacenamiento.agregar_mensaje(&mensaje(secuencia)).unwrap();
+            self.tx_mensajes.send(EventoStream::Nuevo).unwrap();
+        }
+
+        fn eliminar(&self, secuencia: u64) {
+            let mut almacenamiento = self.almacenamiento.bloquear();
+            almacenamiento.eliminar(secuencia).unwrap();
+            self.tx_mensajes
+                .send(EventoStream::Eliminado(secuencia))
+                .unwrap();
+        }
+
+        /// Mensajes entregados en el tick
+        fn tick(&mut self) -> Vec<Publicacion> {
+            let mut contexto = TickContexto::new(0, 0);
+            self.consumer.tick(&mut contexto);
+            contexto
+                .publicaciones()
+                .into_iter()
+                .filter(|publicacion| publicacion.topico == ENTREGAS)
+                .collect()
+        }
+
+        /// Responde al tópico de ack de una entrega
+        fn responder(&mut self, entrega: &Publicacion, payload: &[u8]) {
+            self.consumer
+                .escribir_publicacion_mensaje(&PublicacionMensaje::new(
+                    "ack".to_string(),
+                    entrega.replay_to.clone().unwrap(),
+                    payload.to_vec(),
+                    None,
+                    None,
+                ));
+        }
+
+        /// Última información que el consumer envió al stream
+        fn info(&self) -> ConsumerInfo {
+            self.rx_actualizaciones
+                .try_iter()
+                .filter_map(|actualizacion| match actualizacion {
+                    ActualizacionJS::Consumer(info) => Some(info),
+                    _ => None,
+                })
+                .last()
+                .unwrap()
+        }
+    }
+
+    fn mensaje(secuencia: u64) -> MensajeAlmacenado {
+        MensajeAlmacenado::new(secuencia, "a".to_string(), None, b"x".to_vec())
+    }
+
+    /// Cantidad de entregas y secuencia del stream, según el tópico de ack
+    fn entregas_y_secuencia(entrega: &Publicacion) -> (u64, u64) {
+        let topico = entrega.replay_to.as_deref().unwrap();
+        let campos = topico.split('.').collect::<Vec<_>>();
+        (campos[4].parse().unwrap(), campos[5].parse().unwrap())
+    }
+
+    #[test]
+    fn parsear_tipos_de_ack() {
+        assert_eq!(parsear_ack(b""), TipoAck::Ack);
+        assert_eq!(parsear_ack(b"+ACK"), TipoAck::Ack);
+        assert_eq!(parsear_ack(b"-NAK"), TipoAck::Nak(None));
+        assert_eq!(
+            parsear_ack(b"-NAK {\"delay\": 2000000000}"),
+            TipoAck::Nak(Some(Duration::from_secs(2)))
+        );
+        assert_eq!(parsear_ack(b"+WPI"), TipoAck::EnProgreso);
+        assert_eq!(parsear_ack(b"+TERM razon"), TipoAck::Terminar);
+    }
+
+    #[test]
+    fn reentrega_si_no_llega_el_ack() {
+        let mut prueba = prueba(ConsumerConfig {
+            ack_wait: Duration::from_millis(20),
+            ..Default::default()
+        });
+        prueba.publicar(1);
+
+        let entregas = prueba.tick();
+        assert_eq!(entregas.len(), 1);
+        assert_eq!(entregas_y_secuencia(&entregas[0]), (1, 1));
+        assert!(prueba.tick().is_empty());
+
+        std::thread::sleep(Duration::from_millis(30));
+        let entregas = prueba.tick();
+        assert_eq!(entregas.len(), 1);
+        assert_eq!(entregas_y_secuencia(&entregas[0]), (2, 1));
+
+        prueba.responder(&entregas[0], b"+ACK");
+        prueba.tick();
+        let info = prueba.info();
+        assert_eq!(info.num_ack_pending, 0);
+        assert_eq!(info.delivered.consumer_seq, 2);
+        assert_eq!(info.ack_floor.stream_seq, 1);
+    }
+
+    #[test]
+    fn max_deliver_por_vencimiento() {
+        let mut prueba = prueba(ConsumerConfig {
+            ack_wait: Duration::from_millis(10),
+            max_deliver: 2,
+            ..Default::default()
+        });
+        prueba.publicar(1);
+
+        assert_eq!(prueba.tick().len(), 1);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(prueba.tick().len(), 1);
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(prueba.tick().is_empty());
+
+        assert_eq!(prueba.info().num_ack_pending, 0);
+    }
+
+    #[test]
+    fn max_deliver_por_nak() {
+        let mut prueba = prueba(ConsumerConfig {
+            ack_wait: Duration::from_secs(30),
+            max_deliver: 2,
+            ..Default::default()
+        });
+        prueba.publicar(1);
+
+        let entregas = prueba.tick();
+        prueba.responder(&entregas[0], b"-NAK");
+        let entregas = prueba.tick();
+        assert_eq!(entregas.len(), 1);
+        assert_eq!(entregas_y_secuencia(&entregas[0]), (2, 1));
+
+        prueba.responder(&entregas[0], b"-NAK");
+        assert!(prueba.tick().is_empty());
+        assert!(prueba.tick().is_empty());
+        assert_eq!(prueba.info().num_ack_pending, 0);
+    }
+
+    #[test]
+    fn max_ack_pending_limita_las_entregas() {
+        let mut prueba = prueba(ConsumerConfig {
+            max_ack_pending: 2,
+            ..Default::default()
+        });
+        for secuencia in 1..=3 {
+            prueba.publicar(secuencia);
+        }
+
+        let entregas = prueba.tick();
+        assert_eq!(entregas.len(), 2);
+        assert!(prueba.tick().is_empty());
+        assert_eq!(prueba.info().num_pending, 1);
+
+        prueba.responder(&entregas[0], b"+ACK");
+        let entregas = prueba.tick();
+        assert_eq!(entregas.len(), 1);
+        assert_eq!(entregas_y_secuencia(&entregas[0]), (1, 3));
+    }
+
+    #[test]
+    fn lee_del_almacenamiento_solo_lo_que_entrega() {
+        let mut prueba = prueba(ConsumerConfig {
+            max_ack_pending: 2,
+            ..Default::default()
+        });
+        for secuencia in 1..=100 {
+            prueba.publicar(secuencia);
+        }
+
+        assert_eq!(prueba.tick().len(), 2);
+        // Además de los que esperan ack solo tiene leído el próximo
+        assert_eq!(prueba.consumer.pendientes.len(), 2);
+        assert_eq!(
+            prueba.consumer.proximo.as_ref().map(|m| m.secuencia),
+            Some(3)
+        );
+        assert_eq!(prueba.info().num_pending, 98);
+    }
+
+    #[test]
+    fn no_entrega_los_mensajes_eliminados() {
+        let mut prueba = prueba(ConsumerConfig {
+            max_ack_pending: 1,
+            ..Default::default()
+        });
+        for secuencia in 1..=3 {
+            prueba.publicar(secuencia);
+        }
+
+        let entregas = prueba.tick();
+        assert_eq!(entregas.len(), 1);
+        prueba.eliminar(2);
+        prueba.eliminar(3);
+        prueba.publicar(4);
+
+        prueba.responder(&entregas[0], b"+ACK");
+        let entregas = prueba.tick();
+        assert_eq!(entregas.len(), 1);
+        assert_eq!(entregas_y_secuencia(&entregas[0]), (1, 4));
+        assert_eq!(prueba.info().num_pending, 0);
+    }
+
+    #[test]
+    fn ack_all_confirma_las_secuencias_anteriores() {
+        let mut prueba = prueba(ConsumerConfig {
+            ack_policy: AckPolicy::All,
+            ..Default::default()
+        });
+        for secuencia in 1..=3 {
+            prueba.publicar(secuencia);
+        }
+
+        let entregas = prueba.tick();
+        assert_eq!(entregas.len(), 3);
+
+        prueba.responder(&entregas[1], b"+ACK");
+        prueba.tick();
+        let info = prueba.info();
+        assert_eq!(info.num_ack_pending, 1);
+        assert_eq!(info.ack_floor.stream_seq, 2);
+        assert_eq!(info.ack_floor.consumer_seq, 2);
+    }
+
+    #[test]
+    fn restaurar_estado_guardado() {
+        let ruta = std::env::temp_dir().join("consumer_prueba_restaurar.json");
+        let _ = fs::remove_file(&ruta);
+
+        let almacenamiento = AlmacenamientoCompartido::new(Box::new(AlmacenamientoMemoria::new()));
+        let mut prueba = prueba_guardada(
+            ConsumerConfig::default(),
+            Some(ruta.clone()),
+            almacenamiento.clone(),
+            None,
+        );
+        for secuencia in 1..=3 {
+            prueba.publicar(secuencia);
+        }
+        let entregas = prueba.tick();
+        prueba.responder(&entregas[0], b"+ACK");
+        prueba.responder(&entregas[1], b"-NAK");
+        prueba.tick();
+        let info = prueba.info();
+
+        // Los cambios posteriores al primer guardado se escriben al soltar el consumer
+        let guardado = ConsumerEstado::from_json(&fs::read_to_string(&ruta).unwrap()).unwrap();
+        assert_eq!(guardado.pending.len(), 3);
+        drop(prueba);
+        let estado = ConsumerEstado::from_json(&fs::read_to_string(&ruta).unwrap()).unwrap();
+        assert_eq!(estado.pending.len(), 2);
+
+        let mut restaurado = prueba_guardada(
+            estado.config.clone(),
+            Some(ruta.clone()),
+            almacenamiento,
+            Some(estado),
+        );
+        assert!(restaurado.tick().is_empty());
+        let info_restaurada = restaurado.info();
+        let _ = fs::remove_file(&ruta);
+
+        assert_eq!(info_restaurada.delivered, info.delivered);
+        assert_eq!(info_restaurada.delivered.consumer_seq, 4);
+        assert_eq!(info_restaurada.delivered.stream_seq, 3);
+        assert_eq!(info_restaurada.ack_floor, info.ack_floor);
+        assert_eq!(info_restaurada.ack_floor.stream_seq, 1);
+        assert_eq!(info_restaurada.ack_floor.consumer_seq, 2);
+        assert_eq!(info_restaurada.num_ack_pending, 2);
+        assert_eq!(info_restaurada.num_redelivered, 1);
+        assert_eq!(info_restaurada.created, info.created);
+    }
+
+    #[test]
+    fn intervalo_tick_hasta_el_proximo_vencimiento() {
+        let mut prueba = prueba(ConsumerConfig {
+            ack_wait: Duration::from_secs(30),
+            ..Default::default()
+        });
+        prueba.tick();
+        assert_eq!(prueba.consumer.intervalo_tick(), None);
+
+        // La entrega queda sin guardar hasta que pase el intervalo de guardado
+        prueba.publicar(1);
+        assert_eq!(prueba.tick().len(), 1);
+        assert!(prueba.consumer.intervalo_tick().unwrap() <= INTERVALO_GUARDADO);
+
+        prueba.consumer.guardar_estado();
+        let intervalo = prueba.consumer.intervalo_tick().unwrap();
+        assert!(intervalo <= Duration::from_secs(30));
+        assert!(intervalo > Duration::from_secs(29));
+    }
+
+    #[test]
+    fn intervalo_tick_hasta_el_proximo_latido() {
+        let mut prueba = prueba(ConsumerConfig {
+            idle_heartbeat: Duration::from_secs(2),
+            ..Default::default()
+        });
+        prueba.tick();
+
+        let intervalo = prueba.consumer.intervalo_tick().unwrap();
+        assert!(intervalo <= Duration::from_secs(2));
+        assert!(intervalo > Duration::from_secs(1));
+    }
 }
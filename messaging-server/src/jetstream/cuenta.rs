@@ -0,0 +1,213 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use lib::{
+    configuracion::Configuracion,
+    jet_stream::api_info_response::{JSApiInfoApi, JSApiInfoLimits},
+};
+
+/// Límites de JetStream para toda la cuenta. Un valor negativo es sin límite
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LimitesCuenta {
+    pub max_streams: i64,
+    pub max_consumers: i64,
+    /// Bytes que pueden guardar en disco entre todos los streams
+    pub max_storage: i64,
+}
+
+impl LimitesCuenta {
+    /// Lee `max_streams`, `max_consumers` y `max_storage` de la configuración del
+    /// servidor. Los que no estén no tienen límite
+    pub fn desde_configuracion(configuracion: &Configuracion) -> Self {
+        Self {
+            max_streams: configuracion.obtener::<i64>("max_streams").unwrap_or(-1),
+            max_consumers: configuracion.obtener::<i64>("max_consumers").unwrap_or(-1),
+            max_storage: configuracion.obtener::<i64>("max_storage").unwrap_or(-1),
+        }
+    }
+}
+
+impl Default for LimitesCuenta {
+    fn default() -> Self {
+        Self {
+            max_streams: -1,
+            max_consumers: -1,
+            max_storage: -1,
+        }
+    }
+}
+
+/// Lo que ocupa un stream, según la última actualización de estado que envió
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UsoStream {
+    /// Bytes guardados en memoria
+    pub memoria: u64,
+    /// Bytes guardados en disco
+    pub almacenamiento: u64,
+    pub consumers: u64,
+}
+
+/// Uso de JetStream de la cuenta, compartido entre el admin y los streams. El
+/// admin lo actualiza con el estado que le envía cada stream, y los streams lo
+/// consultan para no superar los límites de la cuenta
+#[derive(Debug, Default)]
+pub struct Cuenta {
+    limites: LimitesCuenta,
+    peticiones: AtomicU64,
+    errores: AtomicU64,
+    uso_streams: Mutex<HashMap<String, UsoStream>>,
+}
+
+impl Cuenta {
+    pub fn new(limites: LimitesCuenta) -> Self {
+        Self {
+            limites,
+            ..Default::default()
+        }
+    }
+
+    pub fn registrar_peticion(&self) {
+        self.peticiones.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn registrar_error(&self) {
+        self.errores.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn actualizar_uso(&self, stream: &str, uso: UsoStream) {
+        if let Ok(mut uso_streams) = self.uso_streams.lock() {
+            uso_streams.insert(stream.to_string(), uso);
+        }
+    }
+
+    pub fn quitar_stream(&self, stream: &str) {
+        if let Ok(mut uso_streams) = self.uso_streams.lock() {
+            uso_streams.remove(stream);
+        }
+    }
+
+    /// Suma de lo que ocupan los streams, sin contar `excluido`
+    pub fn uso_total(&self, excluido: Option<&str>) -> UsoStream {
+        let uso_streams = match self.uso_streams.lock() {
+            Ok(uso_streams) => uso_streams,
+            Err(_) => return UsoStream::default(),
+        };
+
+        uso_streams
+            .iter()
+            .filter(|(nombre, _)| Some(nombre.as_str()) != excluido)
+            .fold(UsoStream::default(), |total, (_, uso)| UsoStream {
+                memoria: total.memoria + uso.memoria,
+                almacenamiento: total.almacenamiento + uso.almacenamiento,
+                consumers: total.consumers + uso.consumers,
+            })
+    }
+
+    pub fn admite_stream(&self, streams: usize) -> bool {
+        dentro_del_limite(self.limites.max_streams, streams as u64 + 1)
+    }
+
+    /// Si el stream puede tener un consumer más. Se usa el uso actual del stream
+    /// porque el que tiene la cuenta puede estar desactualizado
+    pub fn admite_consumer(&self, stream: &str, consumers_stream: u64) -> bool {
+        let otros = self.uso_total(Some(stream)).consumers;
+        dentro_del_limite(self.limites.max_consumers, otros + consumers_stream + 1)
+    }
+
+    /// Si el stream puede guardar `bytes` más en disco, con el mismo criterio que
+    /// `admite_consumer`
+    pub fn admite_bytes(&self, stream: &str, bytes_stream: u64, bytes: u64) -> bool {
+        let otros = self.uso_total(Some(stream)).almacenamiento;
+        dentro_del_limite(self.limites.max_storage, otros + bytes_stream + bytes)
+    }
+
+    pub fn limites_api(&self) -> JSApiInfoLimits {
+        JSApiInfoLimits {
+            max_streams: self.limites.max_streams,
+            max_consumers: self.limites.max_consumers,
+            max_storage: self.limites.max_storage,
+            ..JSApiInfoLimits::new()
+        }
+    }
+
+    pub fn estadisticas_api(&self) -> JSApiInfoApi {
+        JSApiInfoApi {
+            total: self.peticiones.load(Ordering::Relaxed),
+            errors: self.errores.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn dentro_del_limite(limite: i64, uso: u64) -> bool {
+    limite < 0 || uso <= limite as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cuenta, LimitesCuenta, UsoStream};
+
+    #[test]
+    fn limites_de_la_cuenta() {
+        let cuenta = Cuenta::new(LimitesCuenta {
+            max_streams: 2,
+            max_consumers: 3,
+            max_storage: 100,
+        });
+        cuenta.actualizar_uso(
+            "a",
+            UsoStream {
+                memoria: 500,
+                almacenamiento: 60,
+                consumers: 2,
+            },
+        );
+        cuenta.actualizar_uso(
+            "b",
+            UsoStream {
+                memoria: 0,
+                almacenamiento: 30,
+                consumers: 0,
+            },
+        );
+
+        assert!(cuenta.admite_stream(1));
+        assert!(!cuenta.admite_stream(2));
+
+        assert!(cuenta.admite_consumer("b", 0));
+        assert!(!cuenta.admite_consumer("b", 1));
+        // El uso del stream que pregunta reemplaza al que tenía la cuenta
+        assert!(cuenta.admite_consumer("a", 1));
+
+        assert!(cuenta.admite_bytes("b", 30, 10));
+        assert!(!cuenta.admite_bytes("b", 30, 11));
+
+        cuenta.quitar_stream("a");
+        assert_eq!(
+            cuenta.uso_total(None),
+            UsoStream {
+                memoria: 0,
+                almacenamiento: 30,
+                consumers: 0
+            }
+        );
+    }
+
+    #[test]
+    fn sin_limites_por_defecto() {
+        let cuenta = Cuenta::new(LimitesCuenta::default());
+        cuenta.registrar_peticion();
+        cuenta.registrar_peticion();
+        cuenta.registrar_error();
+
+        assert!(cuenta.admite_stream(1000));
+        assert!(cuenta.admite_bytes("a", u32::MAX as u64, u32::MAX as u64));
+        assert_eq!(cuenta.estadisticas_api().total, 2);
+        assert_eq!(cuenta.estadisticas_api().errors, 1);
+        assert_eq!(cuenta.limites_api().max_storage, -1);
+    }
+}
@@ -0,0 +1,253 @@
+use std::{collections::VecDeque, io, iter};
+
+use lib::jet_stream::consumer_config::{ConsumerConfig, DeliverPolicy};
+
+use super::{
+    almacenamiento::{mensaje::MensajeAlmacenado, Almacenamiento},
+    stream::consumer_aceptar_topico,
+};
+
+/// Mensajes del stream que un consumer todavía no entregó por primera vez.
+///
+/// No guarda los mensajes sino hasta qué secuencia llegó: el consumer los lee
+/// del almacenamiento a medida que los entrega, así que en memoria solo tiene
+/// los que esperan ack
+#[derive(Debug)]
+pub struct Cursor {
+    /// Secuencias sueltas que se entregan antes de seguir desde `proxima`, como
+    /// las que elige `last_per_subject` al crear el consumer
+    elegidas: VecDeque<u64>,
+    /// Primera secuencia del almacenamiento que todavía no se leyó
+    proxima: u64,
+    /// Mensajes que acepta el consumer y todavía no se leyeron
+    restantes: u64,
+}
+
+impl Cursor {
+    /// Posición de un consumer nuevo según su `deliver_policy`
+    pub fn nuevo(almacenamiento: &dyn Almacenamiento, config: &ConsumerConfig) -> Self {
+        let siguiente = almacenamiento.ultima_secuencia() + 1;
+
+        match config.deliver_policy {
+            DeliverPolicy::All => Self::desde(almacenamiento, config, 0),
+            DeliverPolicy::New => Self::desde(almacenamiento, config, siguiente),
+            DeliverPolicy::ByStartSequence => {
+                let inicio = config.opt_start_seq.unwrap_or_default();
+                Self::desde(almacenamiento, config, inicio)
+            }
+            DeliverPolicy::ByStartTime => {
+                let inicio = config.tiempo_inicio().unwrap_or_default();
+                let desde = secuencias(almacenamiento, 0)
+                    .find(|secuencia| {
+                        almacenamiento
+                            .tiempo(*secuencia)
+                            .is_some_and(|tiempo| tiempo >= inicio)
+                    })
+                    .unwrap_or(siguiente);
+                Self::desde(almacenamiento, config, desde)
+            }
+            DeliverPolicy::Last => {
+                let ultimas = ultimas_por_topico(almacenamiento, config);
+                Self::con_elegidas(ultimas.last().into_iter().copied().collect(), siguiente)
+            }
+            DeliverPolicy::LastPerSubject => {
+                Self::con_elegidas(ultimas_por_topico(almacenamiento, config), siguiente)
+            }
+        }
+    }
+
+    /// Sigue con todos los mensajes que acepta el consumer a partir de `proxima`,
+    /// como al recuperar un consumer guardado
+    pub fn desde(
+        almacenamiento: &dyn Almacenamiento,
+        config: &ConsumerConfig,
+        proxima: u64,
+    ) -> Self {
+        let restantes = secuencias(almacenamiento, proxima)
+            .filter(|secuencia| acepta(almacenamiento, config, *secuencia))
+            .count() as u64;
+
+        Self {
+            elegidas: VecDeque::new(),
+            proxima,
+            restantes,
+        }
+    }
+
+    fn con_elegidas(elegidas: Vec<u64>, proxima: u64) -> Self {
+        Self {
+            restantes: elegidas.len() as u64,
+            elegidas: elegidas.into(),
+            proxima,
+        }
+    }
+
+    pub fn restantes(&self) -> u64 {
+        self.restantes
+    }
+
+    /// Lee el próximo mensaje que acepta el consumer y avanza. Si no se puede leer
+    /// se avanza igual, para no trabarse en un mensaje dañado
+    pub fn siguiente(
+        &mut self,
+        almacenamiento: &dyn Almacenamiento,
+        config: &ConsumerConfig,
+    ) -> io::Result<Option<MensajeAlmacenado>> {
+        loop {
+            let secuencia = match self.elegidas.pop_front() {
+                Some(secuencia) => secuencia,
+                None => match secuencias(almacenamiento, self.proxima)
+                    .find(|secuencia| acepta(almacenamiento, config, *secuencia))
+                {
+                    Some(secuencia) => {
+                        self.proxima = secuencia + 1;
+                        secuencia
+                    }
+                    None => {
+                        // Lo revisado no se vuelve a recorrer
+                        self.proxima = self.proxima.max(almacenamiento.ultima_secuencia() + 1);
+                        return Ok(None);
+                    }
+                },
+            };
+
+            self.restantes = self.restantes.saturating_sub(1);
+            if let Some(mensaje) = almacenamiento.obtener(secuencia)? {
+                return Ok(Some(mensaje));
+            }
+        }
+    }
+
+    /// Se guardó un mensaje nuevo que acepta el consumer
+    pub fn agregado(&mut self) {
+        self.restantes += 1;
+    }
+
+    /// Se eliminó un mensaje que acepta el consumer. Solo cuenta si todavía no se leyó
+    pub fn eliminado(&mut self, secuencia: u64) {
+        let pendiente = match self.elegidas.iter().position(|s| *s == secuencia) {
+            Some(posicion) => {
+                self.elegidas.remove(posicion);
+                true
+            }
+            None => secuencia >= self.proxima,
+        };
+
+        if pendiente {
+            self.restantes = self.restantes.saturating_sub(1);
+        }
+    }
+}
+
+/// Secuencias guardadas a partir de `desde`, en orden
+fn secuencias(almacenamiento: &dyn Almacenamiento, desde: u64) -> impl Iterator<Item = u64> + '_ {
+    iter::successors(almacenamiento.siguiente_secuencia(desde), |secuencia| {
+        almacenamiento.siguiente_secuencia(secuencia + 1)
+    })
+}
+
+fn acepta(almacenamiento: &dyn Almacenamiento, config: &ConsumerConfig, secuencia: u64) -> bool {
+    almacenamiento
+        .topico(secuencia)
+        .is_some_and(|topico| consumer_aceptar_topico(config, topico))
+}
+
+/// Último mensaje de cada tópico que acepta el consumer, en orden
+fn ultimas_por_topico(almacenamiento: &dyn Almacenamiento, config: &ConsumerConfig) -> Vec<u64> {
+    let mut ultimas: Vec<u64> = almacenamiento
+        .topicos()
+        .into_keys()
+        .filter(|topico| consumer_aceptar_topico(config, topico))
+        .filter_map(|topico| almacenamiento.ultima_secuencia_del_topico(topico))
+        .collect();
+    ultimas.sort_unstable();
+    ultimas
+}
+
+#[cfg(test)]
+mod tests {
+    use lib::jet_stream::consumer_config::{ConsumerConfig, DeliverPolicy};
+
+    use crate::jetstream::almacenamiento::{memoria::AlmacenamientoMemoria, Almacenamiento};
+
+    use super::Cursor;
+
+    /// Almacenamiento con un mensaje en cada tópico, en orden
+    fn almacenamiento(topicos: &[&str]) -> AlmacenamientoMemoria {
+        let mut almacenamiento = AlmacenamientoMemoria::new();
+        for topico in topicos {
+            almacenamiento
+                .agregar(topico.to_string(), None, b"x".to_vec())
+                .unwrap();
+        }
+        almacenamiento
+    }
+
+    /// Secuencias que entrega el cursor hasta quedarse sin mensajes
+    fn leer_todo(
+        cursor: &mut Cursor,
+        almacenamiento: &AlmacenamientoMemoria,
+        config: &ConsumerConfig,
+    ) -> Vec<u64> {
+        std::iter::from_fn(|| cursor.siguiente(almacenamiento, config).unwrap())
+            .map(|mensaje| mensaje.secuencia)
+            .collect()
+    }
+
+    #[test]
+    fn lee_solo_los_mensajes_del_filtro() {
+        let almacenamiento = almacenamiento(&["a", "b", "a", "b"]);
+        let config = ConsumerConfig {
+            filter_subject: Some("b".to_string()),
+            ..Default::default()
+        };
+
+        let mut cursor = Cursor::nuevo(&almacenamiento, &config);
+        assert_eq!(cursor.restantes(), 2);
+
+        let mensaje = cursor.siguiente(&almacenamiento, &config).unwrap().unwrap();
+        assert_eq!(mensaje.secuencia, 2);
+        assert_eq!(cursor.restantes(), 1);
+        assert_eq!(leer_todo(&mut cursor, &almacenamiento, &config), vec![4]);
+        assert_eq!(cursor.restantes(), 0);
+    }
+
+    #[test]
+    fn last_per_subject_sigue_con_los_nuevos() {
+        let mut almacenamiento = almacenamiento(&["a", "b", "a", "c", "b"]);
+        let config = ConsumerConfig {
+            deliver_policy: DeliverPolicy::LastPerSubject,
+            ..Default::default()
+        };
+
+        let mut cursor = Cursor::nuevo(&almacenamiento, &config);
+        assert_eq!(cursor.restantes(), 3);
+
+        almacenamiento
+            .agregar("a".to_string(), None, b"x".to_vec())
+            .unwrap();
+        cursor.agregado();
+        assert_eq!(
+            leer_todo(&mut cursor, &almacenamiento, &config),
+            vec![3, 4, 5, 6]
+        );
+    }
+
+    #[test]
+    fn eliminados_sin_leer_dejan_de_contar() {
+        let mut almacenamiento = almacenamiento(&["a", "a", "a", "a"]);
+        let config = ConsumerConfig::default();
+
+        let mut cursor = Cursor::desde(&almacenamiento, &config, 3);
+        assert_eq!(cursor.restantes(), 2);
+
+        // Ya se había leído antes de eliminarse
+        almacenamiento.eliminar(1).unwrap();
+        cursor.eliminado(1);
+        almacenamiento.eliminar(4).unwrap();
+        cursor.eliminado(4);
+        assert_eq!(cursor.restantes(), 1);
+        assert_eq!(leer_todo(&mut cursor, &almacenamiento, &config), vec![3]);
+        assert_eq!(cursor.restantes(), 0);
+    }
+}
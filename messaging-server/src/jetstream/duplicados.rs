@@ -0,0 +1,57 @@
+use std::collections::{HashMap, VecDeque};
+
+/// `Nats-Msg-Id` de los mensajes guardados por un stream dentro de su ventana
+/// de duplicados, con la secuencia que se les asignó
+#[derive(Debug, Default)]
+pub struct IdsRecientes {
+    secuencias: HashMap<String, u64>,
+    /// Los mismos ids en el orden en que se guardaron, con el tiempo en nanosegundos
+    por_tiempo: VecDeque<(i64, String)>,
+}
+
+impl IdsRecientes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Secuencia del mensaje guardado con este id, si sigue dentro de la ventana
+    pub fn buscar(&self, id: &str) -> Option<u64> {
+        self.secuencias.get(id).copied()
+    }
+
+    pub fn registrar(&mut self, id: String, secuencia: u64, tiempo: i64) {
+        self.secuencias.insert(id.clone(), secuencia);
+        self.por_tiempo.push_back((tiempo, id));
+    }
+
+    /// Olvida los ids guardados antes de `limite`
+    pub fn expirar(&mut self, limite: i64) {
+        while let Some((tiempo, _)) = self.por_tiempo.front() {
+            if *tiempo > limite {
+                break;
+            }
+
+            if let Some((_, id)) = self.por_tiempo.pop_front() {
+                self.secuencias.remove(&id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IdsRecientes;
+
+    #[test]
+    fn expirar_olvida_ids_viejos() {
+        let mut ids = IdsRecientes::new();
+        ids.registrar("a".to_string(), 1, 100);
+        ids.registrar("b".to_string(), 2, 200);
+
+        assert_eq!(ids.buscar("a"), Some(1));
+
+        ids.expirar(150);
+        assert_eq!(ids.buscar("a"), None);
+        assert_eq!(ids.buscar("b"), Some(2));
+    }
+}
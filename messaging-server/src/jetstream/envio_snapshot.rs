@@ -0,0 +1,149 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    conexion::{r#trait::Conexion, tick_contexto::TickContexto},
+    publicacion::{mensaje::PublicacionMensaje, Publicacion},
+    registrador::Registrador,
+    suscripciones::{suscripcion::Suscripcion, topico::Topico},
+};
+
+/// Bytes de cada bloque si la petición no indica `chunk_size`
+const TAMANO_BLOQUE_DEFECTO: usize = 128 * 1024;
+
+/// Los bloques tienen que entrar en un mensaje
+const TAMANO_BLOQUE_MAXIMO: usize = 512 * 1024;
+
+/// Bloques que se envían sin que el cliente los haya confirmado
+const VENTANA_BLOQUES: usize = 4;
+
+/// Tiempo sin confirmaciones del cliente tras el cual se abandona el envío
+const LIMITE_SIN_CONFIRMACION: Duration = Duration::from_secs(10);
+
+/// Envía el snapshot de un stream en bloques al tópico que indicó el cliente.
+/// Cada bloque lleva un `reply_to` al que el cliente responde para recibir más
+/// (control de flujo), y el final se indica con un mensaje vacío
+pub struct EnvioSnapshot {
+    id_conexion: u64,
+    preparado: bool,
+    terminado: bool,
+    nombre_stream: String,
+    topico_entrega: String,
+    topico_confirmacion: String,
+    datos: Vec<u8>,
+    tamano_bloque: usize,
+    /// Bytes de `datos` que ya se enviaron
+    enviados: usize,
+    /// Bloques enviados que el cliente todavía no confirmó
+    sin_confirmar: usize,
+    ultima_confirmacion: Instant,
+    registrador: Registrador,
+}
+
+impl EnvioSnapshot {
+    pub fn new(
+        nombre_stream: &str,
+        topico_entrega: String,
+        tamano_bloque: usize,
+        datos: Vec<u8>,
+        registrador: Registrador,
+    ) -> Self {
+        let tamano_bloque = match tamano_bloque {
+            0 => TAMANO_BLOQUE_DEFECTO,
+            tamano => tamano.min(TAMANO_BLOQUE_MAXIMO),
+        };
+
+        Self {
+            id_conexion: 0,
+            preparado: false,
+            terminado: false,
+            nombre_stream: nombre_stream.to_string(),
+            topico_entrega,
+            topico_confirmacion: format!("$JS.SNAPSHOT.ACK.{}.{}", nombre_stream, nuid::next()),
+            datos,
+            tamano_bloque,
+            enviados: 0,
+            sin_confirmar: 0,
+            ultima_confirmacion: Instant::now(),
+            registrador,
+        }
+    }
+}
+
+impl Conexion for EnvioSnapshot {
+    fn obtener_id(&self) -> u64 {
+        self.id_conexion
+    }
+
+    fn setear_id_conexion(&mut self, id_conexion: u64) {
+        self.id_conexion = id_conexion;
+    }
+
+    fn tick(&mut self, contexto: &mut TickContexto) {
+        if !self.preparado {
+            contexto.suscribir(Suscripcion::new(
+                contexto.id_hilo,
+                self.id_conexion,
+                Topico::new(self.topico_confirmacion.clone()).unwrap(),
+                "confirmacion".to_string(),
+                None,
+            ));
+            self.preparado = true;
+        }
+
+        while self.sin_confirmar < VENTANA_BLOQUES && self.enviados < self.datos.len() {
+            let fin = (self.enviados + self.tamano_bloque).min(self.datos.len());
+            contexto.publicar(Publicacion::new(
+                self.topico_entrega.clone(),
+                self.datos[self.enviados..fin].to_vec(),
+                None,
+                Some(self.topico_confirmacion.clone()),
+            ));
+            self.enviados = fin;
+            self.sin_confirmar += 1;
+        }
+
+        if self.enviados == self.datos.len() && self.sin_confirmar == 0 {
+            contexto.publicar(Publicacion::new(
+                self.topico_entrega.clone(),
+                Vec::new(),
+                None,
+                None,
+            ));
+            self.registrador.info(
+                &format!(
+                    "Snapshot del stream {} enviado ({} bytes)",
+                    self.nombre_stream,
+                    self.datos.len()
+                ),
+                Some(self.id_conexion),
+            );
+            self.terminado = true;
+        } else if self.ultima_confirmacion.elapsed() >= LIMITE_SIN_CONFIRMACION {
+            self.registrador.advertencia(
+                &format!(
+                    "Snapshot del stream {} abandonado: el cliente no confirmó los bloques",
+                    self.nombre_stream
+                ),
+                Some(self.id_conexion),
+            );
+            self.terminado = true;
+        }
+    }
+
+    fn escribir_publicacion_mensaje(&mut self, mensaje: &PublicacionMensaje) {
+        if mensaje.sid == "confirmacion" {
+            self.sin_confirmar = self.sin_confirmar.saturating_sub(1);
+            self.ultima_confirmacion = Instant::now();
+        }
+    }
+
+    fn esta_conectado(&self) -> bool {
+        !self.terminado
+    }
+
+    /// Las confirmaciones llegan como mensajes, así que sin ellas solo hace
+    /// falta un tick para abandonar el envío
+    fn intervalo_tick(&self) -> Option<Duration> {
+        Some(LIMITE_SIN_CONFIRMACION.saturating_sub(self.ultima_confirmacion.elapsed()))
+    }
+}
@@ -0,0 +1,27 @@
+use std::sync::mpsc::Receiver;
+
+use crate::hilo::despertador::Aviso;
+
+use super::almacenamiento::AlmacenamientoCompartido;
+
+/// Lo que el stream le avisa a cada uno de sus consumers sobre los mensajes de
+/// los tópicos que aceptan.
+///
+/// El stream los envía con el almacenamiento bloqueado, así el consumer que los
+/// recibe antes de leer del almacenamiento ya sabe de todo lo que va a encontrar
+#[derive(Debug)]
+pub enum EventoStream {
+    /// Se guardó un mensaje nuevo
+    Nuevo,
+    /// El mensaje con esta secuencia se eliminó del stream y ya no se debe entregar
+    Eliminado(u64),
+}
+
+/// Lo que un consumer recibe de su stream al crearse
+pub struct EnlaceStream {
+    /// Almacenamiento del stream, del que el consumer lee los mensajes que entrega
+    pub almacenamiento: AlmacenamientoCompartido,
+    pub rx_eventos: Receiver<EventoStream>,
+    /// Lo vincula con su hilo para que el stream pueda despertarlo
+    pub aviso: Aviso,
+}
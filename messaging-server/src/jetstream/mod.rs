@@ -1,4 +1,12 @@
 mod actualizacion;
 pub mod admin;
+pub mod almacenamiento;
+mod archivo_snapshot;
 pub mod consumer;
+pub mod cuenta;
+mod cursor;
+mod duplicados;
+mod envio_snapshot;
+mod evento_stream;
+mod origen;
 pub mod stream;
@@ -0,0 +1,160 @@
+use std::time::{Duration, Instant};
+
+use lib::jet_stream::{
+    consumer_config::{AckPolicy, ConsumerConfig, DeliverPolicy},
+    stream_source::StreamSource,
+};
+
+/// Cada cuánto el consumer del origen envía un latido si no tiene mensajes
+const LATIDO_ORIGEN: Duration = Duration::from_secs(1);
+
+/// Tiempo sin mensajes ni latidos tras el cual se da por perdido el consumer
+const LIMITE_SIN_ACTIVIDAD: Duration = Duration::from_secs(3);
+
+/// Tiempo que se espera la respuesta del origen antes de volver a pedir el consumer
+const ESPERA_CREACION: Duration = Duration::from_secs(2);
+
+/// Stream del mismo servidor del que se copian mensajes. En el origen se crea un
+/// consumer push efímero que entrega los mensajes en un tópico propio del stream
+/// que copia, y se vuelve a crear si deja de enviar latidos
+pub struct Origen {
+    pub config: StreamSource,
+    /// Última secuencia del origen que se copió
+    pub ultima_secuencia: u64,
+    /// Consumer que entrega los mensajes, desde que el origen confirmó que lo creó
+    pub consumer: Option<String>,
+    ultima_actividad: Instant,
+    /// Cuándo se pidió crear el consumer por última vez
+    ultimo_pedido: Option<Instant>,
+}
+
+impl Origen {
+    pub fn new(config: StreamSource, ultima_secuencia: u64) -> Self {
+        Self {
+            config,
+            ultima_secuencia,
+            consumer: None,
+            ultima_actividad: Instant::now(),
+            ultimo_pedido: None,
+        }
+    }
+
+    pub fn registrar_actividad(&mut self) {
+        self.ultima_actividad = Instant::now();
+    }
+
+    /// Si hay que pedir al origen un consumer nuevo: todavía no respondió al último
+    /// pedido a tiempo, o el consumer que había dejó de enviar latidos
+    pub fn necesita_consumer(&self) -> bool {
+        match (&self.consumer, self.ultimo_pedido) {
+            (Some(_), _) => self.ultima_actividad.elapsed() >= LIMITE_SIN_ACTIVIDAD,
+            (None, Some(ultimo_pedido)) => ultimo_pedido.elapsed() >= ESPERA_CREACION,
+            (None, None) => true,
+        }
+    }
+
+    /// Cuánto falta para que `necesita_consumer` sea verdadero si no hay actividad
+    pub fn espera(&self) -> Duration {
+        match (&self.consumer, self.ultimo_pedido) {
+            (Some(_), _) => LIMITE_SIN_ACTIVIDAD.saturating_sub(self.ultima_actividad.elapsed()),
+            (None, Some(ultimo_pedido)) => ESPERA_CREACION.saturating_sub(ultimo_pedido.elapsed()),
+            (None, None) => Duration::ZERO,
+        }
+    }
+
+    /// Configuración del consumer a pedir, que sigue desde el último mensaje copiado.
+    /// Devuelve también el consumer anterior, que hay que eliminar
+    pub fn pedir_consumer(&mut self, deliver_subject: String) -> (ConsumerConfig, Option<String>) {
+        self.ultimo_pedido = Some(Instant::now());
+
+        let inicio = if self.ultima_secuencia > 0 {
+            self.ultima_secuencia + 1
+        } else {
+            self.config.opt_start_seq.unwrap_or(1).max(1)
+        };
+
+        let config = ConsumerConfig {
+            filter_subject: self.config.filter_subject.clone(),
+            deliver_policy: DeliverPolicy::ByStartSequence,
+            opt_start_seq: Some(inicio),
+            ack_policy: AckPolicy::None,
+            max_deliver: 1,
+            deliver_subject: Some(deliver_subject),
+            flow_control: true,
+            idle_heartbeat: LATIDO_ORIGEN,
+            ..Default::default()
+        };
+
+        (config, self.consumer.take())
+    }
+
+    /// Se pierde el consumer actual para que se pida otro con la configuración nueva.
+    /// Devuelve el consumer anterior, que hay que eliminar
+    pub fn actualizar(&mut self, config: StreamSource) -> Option<String> {
+        self.config = config;
+        self.ultimo_pedido = None;
+        self.consumer.take()
+    }
+}
+
+/// Tópico en el que el consumer del origen entrega los mensajes al stream
+pub fn topico_entrega(stream: &str, origen: &str) -> String {
+    format!("$JS.S.{}.{}", stream, origen)
+}
+
+/// Tópico al que el origen responde el pedido de creación del consumer
+pub fn topico_respuesta_consumer(stream: &str, origen: &str) -> String {
+    format!("$JS.S.{}.{}.consumer", stream, origen)
+}
+
+/// Secuencia en el origen y momento en el que se guardó un mensaje entregado,
+/// que vienen en su tópico de ack:
+/// `$JS.ACK.<stream>.<consumer>.<entregas>.<secuencia stream>.<secuencia consumer>.<tiempo>.<pendientes>`
+pub fn datos_entrega(topico_ack: &str) -> Option<(u64, i64)> {
+    let tokens: Vec<&str> = topico_ack.strip_prefix("$JS.ACK.")?.split('.').collect();
+
+    let secuencia = tokens.get(3)?.parse().ok()?;
+    let tiempo = tokens.get(5)?.parse().ok()?;
+    Some((secuencia, tiempo))
+}
+
+#[cfg(test)]
+mod tests {
+    use lib::jet_stream::{consumer_config::DeliverPolicy, stream_source::StreamSource};
+
+    use super::{datos_entrega, Origen};
+
+    #[test]
+    fn datos_del_topico_de_ack() {
+        assert_eq!(
+            datos_entrega("$JS.ACK.incidentes.abc.1.42.7.1700000000000000000.3"),
+            Some((42, 1700000000000000000))
+        );
+        assert_eq!(datos_entrega("$JS.ACK.incidentes.abc.1"), None);
+        assert_eq!(datos_entrega("_INBOX.abc"), None);
+    }
+
+    #[test]
+    fn consumer_sigue_desde_la_ultima_secuencia_copiada() {
+        let mut origen = Origen::new(
+            StreamSource {
+                opt_start_seq: Some(10),
+                ..StreamSource::new("incidentes")
+            },
+            0,
+        );
+        assert!(origen.necesita_consumer());
+
+        let (config, anterior) = origen.pedir_consumer("$JS.S.a.incidentes".to_string());
+        assert_eq!(config.deliver_policy, DeliverPolicy::ByStartSequence);
+        assert_eq!(config.opt_start_seq, Some(10));
+        assert_eq!(anterior, None);
+        assert!(!origen.necesita_consumer());
+
+        origen.consumer = Some("abc".to_string());
+        origen.ultima_secuencia = 25;
+        let (config, anterior) = origen.pedir_consumer("$JS.S.a.incidentes".to_string());
+        assert_eq!(config.opt_start_seq, Some(26));
+        assert_eq!(anterior, Some("abc".to_string()));
+    }
+}
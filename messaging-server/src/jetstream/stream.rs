@@ -1,76 +1,203 @@
 use std::{
-    collections::HashMap,
-    sync::mpsc::{channel, Receiver, Sender},
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::PathBuf,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use lib::jet_stream::{
-    consumer_config::ConsumerConfig, consumer_info::ConsumerInfo,
+    actualizar_stream_respuesta::JSActualizarStreamRespuesta,
+    consumer_config::ConsumerConfig,
+    consumer_estado::ConsumerEstado,
+    consumer_info::ConsumerInfo,
     consumer_list_respuesta::JetStreamConsumerListaRespuesta,
     crear_consumer_peticion::JSPeticionCrearConsumer,
     crear_consumer_respuesta::JSCrearConsumerRespuesta,
-    nombres_consumers_respuesta::JSNombresConsumersRespuesta, stream_config::StreamConfig,
-    stream_info::StreamInfo, stream_info_respuesta::JSStreamInfoRespuesta,
+    eliminar_mensaje_peticion::JSPeticionEliminarMensaje,
+    eliminar_mensaje_respuesta::JSEliminarMensajeRespuesta,
+    eliminar_stream_respuesta::JSEliminarStreamRespuesta,
+    error::{JSError, JSErrorRespuesta},
+    mensaje_guardado::JSMensajeGuardado,
+    nombres_consumers_respuesta::JSNombresConsumersRespuesta,
+    obtener_mensaje_peticion::JSPeticionObtenerMensaje,
+    obtener_mensaje_respuesta::JSObtenerMensajeRespuesta,
+    pub_ack::JSPubAck,
+    purgar_stream_peticion::JSPeticionPurgarStream,
+    purgar_stream_respuesta::JSPurgarStreamRespuesta,
+    snapshot_peticion::JSPeticionSnapshot,
+    snapshot_respuesta::JSSnapshotRespuesta,
+    stream_config::{DiscardPolicy, StorageType, StreamConfig},
+    stream_info::StreamInfo,
+    stream_info_peticion::JSPeticionStreamInfo,
+    stream_info_respuesta::JSStreamInfoRespuesta,
+    stream_source::StreamSource,
     stream_state::JetStreamStreamState,
 };
+use lib::parseador::headers::Headers;
+use mio::{Registry, Token};
 
 use crate::{
     conexion::{r#trait::Conexion, tick_contexto::TickContexto},
-    publicacion::Publicacion,
+    hilo::{
+        despertador::{Aviso, Despertador},
+        emisor::EmisorConexion,
+    },
+    publicacion::{mensaje::PublicacionMensaje, Publicacion},
     registrador::Registrador,
     suscripciones::{suscripcion::Suscripcion, topico::Topico},
 };
 
-use super::{actualizacion::ActualizacionJS, consumer::JetStreamConsumer};
+use super::{
+    actualizacion::ActualizacionJS,
+    almacenamiento::{mensaje::MensajeAlmacenado, Almacenamiento, AlmacenamientoCompartido},
+    archivo_snapshot::{agregar_archivo, empaquetar},
+    consumer::JetStreamConsumer,
+    cuenta::Cuenta,
+    cursor::Cursor,
+    duplicados::IdsRecientes,
+    envio_snapshot::EnvioSnapshot,
+    evento_stream::{EnlaceStream, EventoStream},
+    origen::{datos_entrega, topico_entrega, topico_respuesta_consumer, Origen},
+};
+
+/// Subdirectorio del stream donde se guarda el estado de cada consumer
+const DIRECTORIO_CONSUMERS: &str = "consumers";
+
+/// Tiempo sin actividad tras el cual se elimina un consumer pull efímero que no indica `inactive_threshold`
+const INACTIVIDAD_EFIMEROS: Duration = Duration::from_secs(5);
+
+/// Header con el que el publicador identifica un mensaje para descartar duplicados
+const HEADER_ID_MENSAJE: &str = "Nats-Msg-Id";
+
+/// Header con la secuencia que tiene que tener el último mensaje del tópico para
+/// que se guarde el nuevo. Cero si el tópico no tiene que tener mensajes
+const HEADER_ULTIMA_SECUENCIA_DEL_TOPICO: &str = "Nats-Expected-Last-Subject-Sequence";
+
+/// Header que pide eliminar los mensajes anteriores del tópico (`sub`) o de todo el stream (`all`)
+const HEADER_ROLLUP: &str = "Nats-Rollup";
+
+/// Header de los mensajes copiados de una fuente, con el nombre del stream de
+/// origen y la secuencia que tenían allí: `<stream> <secuencia>`
+const HEADER_FUENTE: &str = "Nats-Stream-Source";
+
+/// Cada cuánto se buscan mensajes que superaron `max_age`
+const INTERVALO_EXPIRACION: Duration = Duration::from_secs(1);
+
+/// Mensajes que se eliminan al guardar uno con el header `Nats-Rollup`
+enum Rollup {
+    /// Los anteriores del mismo tópico
+    Topico,
+    /// Todos los anteriores del stream
+    Todo,
+}
 
 pub struct JetStreamStream {
     id_conexion: u64,
     config: StreamConfig,
     eliminado: bool,
+    /// Se pidió eliminar el stream, se elimina al terminar el próximo tick para
+    /// que lleguen a enviarse los pedidos pendientes a otros streams
+    eliminacion_pedida: bool,
     preparado: bool,
     tx_conexiones: Sender<Box<dyn Conexion + Send>>,
-    tx_actualizaciones_js: Sender<ActualizacionJS>,
+    tx_actualizaciones_js: EmisorConexion<ActualizacionJS>,
     rx_actualizaciones_js_consumers: Receiver<ActualizacionJS>,
-    tx_actualizaciones_js_consumers: Sender<ActualizacionJS>,
+    tx_actualizaciones_js_consumers: EmisorConexion<ActualizacionJS>,
+    /// Lo vincula con su hilo para que los consumers puedan despertarlo
+    aviso: Aviso,
     respuestas: Vec<Publicacion>,
     consumers: HashMap<String, ConsumerInfo>,
-    consumers_transmisores: HashMap<String, Sender<Publicacion>>,
+    /// Canal y configuración de cada consumer, desde que se crea hasta que se
+    /// elimina. Con el aviso se le pide un tick al hilo del consumer cuando se le
+    /// envía algo
+    consumers_transmisores: HashMap<String, (ConsumerConfig, Sender<EventoStream>, Aviso)>,
     registrador: Registrador,
+    /// Mensajes guardados por el stream, en disco o en memoria según `storage`.
+    /// Los consumers leen de acá los mensajes que entregan
+    almacenamiento: AlmacenamientoCompartido,
+    /// Directorio con la configuración y los consumers del stream
+    directorio: PathBuf,
+    ultima_expiracion: Instant,
+    /// Ids de los mensajes guardados dentro de `duplicate_window`
+    ids_recientes: IdsRecientes,
+    creado: String,
+    /// Streams de los que se copian mensajes (`mirror` o `sources`), por nombre
+    origenes: HashMap<String, Origen>,
+    /// Tópicos que falta suscribir, con el id de la suscripción
+    suscripciones_pendientes: Vec<(String, String)>,
+    /// Ids de las suscripciones a tópicos que se quitaron de la configuración
+    desuscripciones_pendientes: Vec<String>,
+    /// Si cambió algo que el admin tiene que saber desde la última actualización enviada
+    estado_modificado: bool,
+    /// Uso y límites de la cuenta, compartidos con el admin y el resto de los streams
+    cuenta: Arc<Cuenta>,
 }
 
 impl JetStreamStream {
     pub fn new(
         config: StreamConfig,
-        tx_actualizaciones_js: Sender<ActualizacionJS>,
+        almacenamiento: Box<dyn Almacenamiento>,
+        directorio: PathBuf,
+        tx_actualizaciones_js: EmisorConexion<ActualizacionJS>,
         tx_conexiones: Sender<Box<dyn Conexion + Send>>,
         registrador: Registrador,
+        cuenta: Arc<Cuenta>,
     ) -> Self {
         let (tx_actualizaciones_js_consumers, rx_actualizaciones_js_consumers) = channel();
+        let aviso = Aviso::new();
 
         JetStreamStream {
             tx_conexiones,
             id_conexion: 0,
             config,
             eliminado: false,
+            eliminacion_pedida: false,
             preparado: false,
             tx_actualizaciones_js,
             respuestas: Vec::new(),
             rx_actualizaciones_js_consumers,
-            tx_actualizaciones_js_consumers,
+            tx_actualizaciones_js_consumers: EmisorConexion::new(
+                tx_actualizaciones_js_consumers,
+                aviso.clone(),
+            ),
+            aviso,
             consumers: HashMap::new(),
             consumers_transmisores: HashMap::new(),
             registrador,
+            almacenamiento: AlmacenamientoCompartido::new(almacenamiento),
+            directorio,
+            ultima_expiracion: Instant::now(),
+            ids_recientes: IdsRecientes::new(),
+            creado: Utc::now().to_rfc3339(),
+            origenes: HashMap::new(),
+            suscripciones_pendientes: Vec::new(),
+            desuscripciones_pendientes: Vec::new(),
+            estado_modificado: false,
+            cuenta,
         }
     }
 
+    /// Un tópico inválido solo puede venir de una configuración guardada antes de
+    /// que se validaran, así que se registra y se sigue con los demás
     fn suscribir(&self, contexto: &mut TickContexto, topico: &str, sid: &str) {
-        contexto.suscribir(Suscripcion::new(
-            contexto.id_hilo,
-            self.id_conexion,
-            Topico::new(topico.to_string()).unwrap(),
-            sid.to_string(),
-            None,
-        ));
+        match Topico::new(topico.to_string()) {
+            Ok(topico) => contexto.suscribir(Suscripcion::new(
+                contexto.id_hilo,
+                self.id_conexion,
+                topico,
+                sid.to_string(),
+                None,
+            )),
+            Err(e) => self.registrador.error(
+                &format!("No se pudo suscribir al tópico {}: {}", topico, e),
+                Some(self.id_conexion),
+            ),
+        }
     }
 
     fn enviar_actualizacion_de_estado(&self) {
@@ -78,48 +205,1232 @@ impl JetStreamStream {
             .tx_actualizaciones_js
             .send(ActualizacionJS::Stream(StreamInfo {
                 config: self.config.clone(),
-                created: Utc::now().to_rfc3339(),
-                state: JetStreamStreamState::new(),
+                created: self.creado.clone(),
+                state: self.estado(None),
                 ts: Utc::now().to_rfc3339(),
             }));
     }
 
+    /// Estado actual del stream. Si se indica un filtro, se incluye la cantidad
+    /// de mensajes de cada tópico que coincida con él
+    fn estado(&self, filtro_topicos: Option<&str>) -> JetStreamStreamState {
+        let almacenamiento = self.almacenamiento.bloquear();
+        let ultima_secuencia = almacenamiento.ultima_secuencia();
+        let primera_secuencia = almacenamiento
+            .primera_secuencia()
+            .unwrap_or(ultima_secuencia + 1);
+        let mensajes = almacenamiento.cantidad();
+        let topicos = almacenamiento.topicos();
+
+        let first_ts = almacenamiento.tiempo(primera_secuencia).unwrap_or_default();
+
+        let subjects = filtro_topicos
+            .and_then(|filtro| Topico::new(filtro.to_string()).ok())
+            .map(|filtro| {
+                topicos
+                    .iter()
+                    .filter(|(topico, _)| filtro.test(topico))
+                    .map(|(topico, cantidad)| (topico.to_string(), *cantidad))
+                    .collect()
+            });
+
+        JetStreamStreamState {
+            messages: mensajes,
+            bytes: almacenamiento.bytes(),
+            first_seq: primera_secuencia,
+            first_ts: formatear_tiempo(first_ts),
+            last_seq: ultima_secuencia,
+            last_ts: formatear_tiempo(almacenamiento.ultimo_tiempo()),
+            consumer_count: self.consumers_transmisores.len() as u64,
+            num_deleted: (ultima_secuencia + 1 - primera_secuencia).saturating_sub(mensajes),
+            num_subjects: topicos.len() as u64,
+            subjects,
+        }
+    }
+
     fn recibir_actualizaciones_js_consumers(&mut self) {
         while let Ok(actualizacion) = self.rx_actualizaciones_js_consumers.try_recv() {
             match actualizacion {
                 ActualizacionJS::Consumer(consumer_info) => {
                     self.consumers
-                        .insert(consumer_info.config.durable_name.clone(), consumer_info);
+                        .insert(consumer_info.config.nombre().to_string(), consumer_info);
                 }
-                ActualizacionJS::ConsumerEliminado(durable_name) => {
-                    self.consumers.remove(&durable_name);
-                    self.consumers_transmisores.remove(&durable_name);
+                ActualizacionJS::ConsumerEliminado(nombre) => {
+                    self.consumers.remove(&nombre);
+                    self.consumers_transmisores.remove(&nombre);
+                    self.estado_modificado = true;
                 }
                 _ => {}
             }
         }
     }
 
-    fn crear_consumer(&mut self, config: ConsumerConfig) {
+    fn leer_mensaje(&self, secuencia: u64) -> Option<MensajeAlmacenado> {
+        let resultado = self.almacenamiento.bloquear().obtener(secuencia);
+        match resultado {
+            Ok(mensaje) => mensaje,
+            Err(e) => {
+                self.registrador.error(
+                    &format!("Error al leer el mensaje {}: {}", secuencia, e),
+                    Some(self.obtener_id()),
+                );
+                None
+            }
+        }
+    }
+
+    fn ruta_estado_consumer(&self, nombre: &str) -> PathBuf {
+        self.directorio
+            .join(DIRECTORIO_CONSUMERS)
+            .join(format!("{}.json", nombre))
+    }
+
+    /// Vuelve a crear los consumers que quedaron guardados en disco
+    fn cargar_consumers(&mut self) {
+        let directorio = self.directorio.join(DIRECTORIO_CONSUMERS);
+        let entradas = match fs::read_dir(directorio) {
+            Ok(entradas) => entradas,
+            Err(_) => return,
+        };
+
+        for entrada in entradas.flatten() {
+            let ruta = entrada.path();
+            if ruta.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+
+            match fs::read_to_string(&ruta)
+                .ok()
+                .and_then(|json| ConsumerEstado::from_json(&json).ok())
+            {
+                Some(estado) => {
+                    self.crear_consumer(estado.config.clone(), Some(estado));
+                }
+                None => self.registrador.error(
+                    &format!("No se pudo leer el estado del consumer en {:?}", ruta),
+                    Some(self.obtener_id()),
+                ),
+            }
+        }
+    }
+
+    /// Completa la configuración pedida con el nombre y el filtro del tópico
+    /// (`$JS.API.CONSUMER.CREATE.<stream>[.<consumer>[.<filtro>]]`) y crea el
+    /// consumer si no existe
+    fn procesar_crear_consumer(
+        &mut self,
+        topico: &str,
+        payload: &[u8],
+    ) -> Result<JSCrearConsumerRespuesta, JSError> {
+        let mut config = JSPeticionCrearConsumer::from_json(&String::from_utf8_lossy(payload))
+            .map_err(|_| JSError::peticion_invalida())?
+            .config;
+
+        let prefijo = format!("$JS.API.CONSUMER.CREATE.{}.", self.config.name);
+        let (nombre, filtro) = match topico.strip_prefix(&prefijo) {
+            Some(resto) => match resto.split_once('.') {
+                Some((nombre, filtro)) => (Some(nombre), Some(filtro)),
+                None => (Some(resto), None),
+            },
+            None => (None, None),
+        };
+
+        if let Some(nombre) = nombre {
+            if config.nombre().is_empty() {
+                config.name = Some(nombre.to_string());
+            } else if config.nombre() != nombre {
+                return Err(JSError::peticion_invalida());
+            }
+        }
+
+        if let Some(filtro) = filtro {
+            match &config.filter_subject {
+                Some(filter_subject) if filter_subject != filtro => {
+                    return Err(JSError::peticion_invalida());
+                }
+                _ => config.filter_subject = Some(filtro.to_string()),
+            }
+        }
+
+        if !config.es_durable() {
+            if config.name.is_none() {
+                config.name = Some(nuid::next().to_string());
+            }
+            if config.inactive_threshold.is_zero() && !config.es_push() {
+                config.inactive_threshold = INACTIVIDAD_EFIMEROS;
+            }
+        }
+
+        if !config.es_valida() {
+            return Err(JSError::peticion_invalida());
+        }
+
+        if let Some((existente, _, _)) = self.consumers_transmisores.get(config.nombre()) {
+            if *existente != config {
+                return Err(JSError::nombre_consumer_en_uso());
+            }
+            let pendientes = self
+                .consumers
+                .get(config.nombre())
+                .map_or(0, |info| info.num_pending);
+            return Ok(JSCrearConsumerRespuesta::new(config, false, pendientes));
+        }
+
+        let max_consumers = self.config.max_consumers;
+        if max_consumers > 0 && self.consumers_transmisores.len() >= max_consumers as usize {
+            return Err(JSError::maximo_consumers_alcanzado());
+        }
+        if !self
+            .cuenta
+            .admite_consumer(&self.config.name, self.consumers_transmisores.len() as u64)
+        {
+            return Err(JSError::maximo_consumers_alcanzado());
+        }
+
+        let pendientes = self.crear_consumer(config.clone(), None);
+        Ok(JSCrearConsumerRespuesta::new(config, true, pendientes))
+    }
+
+    /// Crea el consumer, que va a leer del almacenamiento los mensajes guardados
+    /// que le corresponden. Si se recupera un estado guardado, sigue desde el
+    /// último mensaje que entregó. Devuelve la cantidad de mensajes que tiene para entregar
+    fn crear_consumer(&mut self, config: ConsumerConfig, estado: Option<ConsumerEstado>) -> u64 {
         let (tx, rx) = channel();
+        let aviso = Aviso::new();
 
-        self.consumers_transmisores
-            .insert(config.durable_name.clone(), tx);
+        let cursor = {
+            let almacenamiento = self.almacenamiento.bloquear();
+            match &estado {
+                Some(estado) => {
+                    Cursor::desde(&**almacenamiento, &config, estado.delivered.stream_seq + 1)
+                }
+                None => Cursor::nuevo(&**almacenamiento, &config),
+            }
+        };
+        let pendientes = cursor.restantes();
+
+        self.consumers_transmisores.insert(
+            config.nombre().to_string(),
+            (config.clone(), tx, aviso.clone()),
+        );
+        self.estado_modificado = true;
 
         self.registrador.info(
             &format!("Consumer creado: {:?}", config),
             Some(self.obtener_id()),
         );
 
-        let consumer = JetStreamConsumer::new(
+        // En un stream en memoria los mensajes no sobreviven a un reinicio, así
+        // que tampoco se guarda hasta dónde los entregó cada consumer
+        let ruta_estado = (config.es_durable() && self.config.storage == StorageType::File)
+            .then(|| self.ruta_estado_consumer(config.nombre()));
+        let enlace = EnlaceStream {
+            almacenamiento: self.almacenamiento.clone(),
+            rx_eventos: rx,
+            aviso,
+        };
+        let mut consumer = JetStreamConsumer::new(
             config,
             self.config.name.clone(),
+            ruta_estado,
             self.tx_actualizaciones_js_consumers.clone(),
-            rx,
+            enlace,
+            cursor,
             self.registrador.clone(),
         );
 
+        if let Some(estado) = estado {
+            let pendientes = estado
+                .pending
+                .iter()
+                .filter_map(|pendiente| self.leer_mensaje(pendiente.stream_seq))
+                .collect();
+            consumer.restaurar(estado, pendientes);
+        }
+
         let _ = self.tx_conexiones.send(Box::new(consumer));
+        pendientes
+    }
+
+    /// Guarda el mensaje y, si se publicó con reply, responde con la secuencia
+    /// asignada o con el error por el que no se guardó. Un mensaje con un
+    /// `Nats-Msg-Id` ya guardado dentro de la ventana de duplicados se descarta
+    fn guardar_mensaje(&mut self, mensaje: &PublicacionMensaje) {
+        self.expirar_ids_recientes();
+
+        let id_mensaje = id_mensaje(&mensaje.header);
+        if let Some(secuencia) = id_mensaje
+            .as_ref()
+            .and_then(|id| self.ids_recientes.buscar(id))
+        {
+            if let Some(reply_to) = &mensaje.replay_to {
+                self.responder(
+                    reply_to,
+                    JSPubAck::new(self.config.name.clone(), secuencia, true).to_json(),
+                );
+            }
+            return;
+        }
+
+        let rollup = match self
+            .validar_headers(mensaje)
+            .and_then(|rollup| self.validar_limites(mensaje).map(|()| rollup))
+        {
+            Ok(rollup) => rollup,
+            Err(error) => {
+                self.registrador.advertencia(
+                    &format!("Mensaje rechazado por el stream: {}", error.description),
+                    Some(self.obtener_id()),
+                );
+                if let Some(reply_to) = &mensaje.replay_to {
+                    self.responder_error(
+                        reply_to,
+                        "io.nats.jetstream.api.v1.pub_ack_response",
+                        error,
+                    );
+                }
+                return;
+            }
+        };
+
+        let reply_to = mensaje.replay_to.clone();
+        let resultado = {
+            let mut almacenamiento = self.almacenamiento.bloquear();
+            let resultado = almacenamiento.agregar(
+                mensaje.topico.clone(),
+                mensaje.header.clone(),
+                mensaje.payload.clone(),
+            );
+            if let Ok(mensaje) = &resultado {
+                self.distribuir_mensaje(mensaje);
+            }
+            resultado
+        };
+        let mensaje = match resultado {
+            Ok(mensaje) => mensaje,
+            Err(e) => {
+                self.registrador.error(
+                    &format!("Error al guardar mensaje en el stream: {}", e),
+                    Some(self.obtener_id()),
+                );
+                if let Some(reply_to) = &reply_to {
+                    self.responder_error(
+                        reply_to,
+                        "io.nats.jetstream.api.v1.pub_ack_response",
+                        JSError::almacenamiento_fallido(&e.to_string()),
+                    );
+                }
+                return;
+            }
+        };
+
+        if let Some(id) = id_mensaje {
+            self.ids_recientes
+                .registrar(id, mensaje.secuencia, mensaje.tiempo);
+        }
+
+        self.estado_modificado = true;
+        match rollup {
+            Some(Rollup::Topico) => self.eliminar_anteriores(&mensaje, true),
+            Some(Rollup::Todo) => self.eliminar_anteriores(&mensaje, false),
+            None => {}
+        }
+        self.limitar_mensajes_del_topico(&mensaje.topico);
+        self.descartar_mensajes_viejos();
+
+        if let Some(reply_to) = &reply_to {
+            self.responder(
+                reply_to,
+                JSPubAck::new(self.config.name.clone(), mensaje.secuencia, false).to_json(),
+            );
+        }
+    }
+
+    /// Avisa del mensaje recién guardado a los consumers que aceptan su tópico.
+    /// Se llama con el almacenamiento bloqueado, ver `EventoStream`
+    fn distribuir_mensaje(&self, mensaje: &MensajeAlmacenado) {
+        for (nombre_consumer, (config, tx_consumer, aviso)) in self.consumers_transmisores.iter() {
+            if !consumer_aceptar_topico(config, &mensaje.topico) {
+                continue;
+            }
+
+            if tx_consumer.send(EventoStream::Nuevo).is_err() {
+                self.registrador.error(
+                    &format!("Error al enviar mensaje a consumer {}", nombre_consumer),
+                    Some(self.obtener_id()),
+                );
+            }
+            aviso.avisar();
+        }
+    }
+
+    /// Guarda un mensaje copiado por un espejo, con la secuencia y el tiempo que
+    /// tenía en el origen
+    fn guardar_mensaje_espejo(&mut self, mensaje: MensajeAlmacenado) {
+        let resultado = {
+            let mut almacenamiento = self.almacenamiento.bloquear();
+            let resultado = almacenamiento.agregar_mensaje(&mensaje);
+            if resultado.is_ok() {
+                self.distribuir_mensaje(&mensaje);
+            }
+            resultado
+        };
+        if let Err(e) = resultado {
+            self.registrador.error(
+                &format!("Error al guardar mensaje del espejo: {}", e),
+                Some(self.obtener_id()),
+            );
+            return;
+        }
+
+        self.estado_modificado = true;
+        self.limitar_mensajes_del_topico(&mensaje.topico);
+        self.descartar_mensajes_viejos();
+    }
+
+    /// Guarda un mensaje copiado de una fuente como si se hubiera publicado en
+    /// este stream, indicando de dónde viene. Se quitan los headers que le piden
+    /// condiciones al stream de origen, que ya se cumplieron al guardarlo allí
+    fn guardar_mensaje_de_fuente(
+        &mut self,
+        origen: &str,
+        secuencia: u64,
+        mensaje: &PublicacionMensaje,
+    ) {
+        let mut headers = Headers::new();
+        if let Some(originales) = mensaje.header.as_deref().and_then(Headers::parsear) {
+            for (clave, valor) in originales.valores() {
+                let condicion = [
+                    HEADER_ULTIMA_SECUENCIA_DEL_TOPICO,
+                    HEADER_ROLLUP,
+                    HEADER_FUENTE,
+                ]
+                .iter()
+                .any(|header| clave.eq_ignore_ascii_case(header));
+                if !condicion {
+                    headers.insertar(clave, valor);
+                }
+            }
+        }
+        headers.insertar(HEADER_FUENTE, &format!("{} {}", origen, secuencia));
+
+        self.guardar_mensaje(&PublicacionMensaje::new(
+            mensaje.sid.clone(),
+            mensaje.topico.clone(),
+            mensaje.payload.clone(),
+            Some(headers.serializar()),
+            None,
+        ));
+    }
+
+    /// Crea los orígenes de la configuración. Cada uno sigue desde la última
+    /// secuencia que se copió: la del propio stream si es un espejo, o la que
+    /// indica el último mensaje que vino de cada fuente
+    fn cargar_origenes(&mut self) {
+        let mut ultimas = HashMap::new();
+
+        if let Some(mirror) = &self.config.mirror {
+            let ultima = self.almacenamiento.bloquear().ultima_secuencia();
+            ultimas.insert(mirror.name.clone(), ultima);
+        } else {
+            let mut buscadas: HashSet<&str> = self
+                .config
+                .sources
+                .iter()
+                .map(|source| source.name.as_str())
+                .collect();
+
+            let secuencias = self.almacenamiento.bloquear().secuencias();
+            for secuencia in secuencias.into_iter().rev() {
+                if buscadas.is_empty() {
+                    break;
+                }
+
+                let fuente = self
+                    .leer_mensaje(secuencia)
+                    .and_then(|mensaje| fuente_del_mensaje(&mensaje.header));
+                if let Some((origen, secuencia_origen)) = fuente {
+                    if buscadas.remove(origen.as_str()) {
+                        ultimas.insert(origen, secuencia_origen);
+                    }
+                }
+            }
+        }
+
+        let origenes: Vec<StreamSource> = self.config.origenes().cloned().collect();
+        for origen in origenes {
+            let ultima = ultimas.get(&origen.name).copied().unwrap_or(0);
+            self.agregar_origen(origen, ultima);
+        }
+    }
+
+    fn agregar_origen(&mut self, config: StreamSource, ultima_secuencia: u64) {
+        let nombre = config.name.clone();
+
+        self.suscripciones_pendientes.push((
+            topico_entrega(&self.config.name, &nombre),
+            format!("origen|{}", nombre),
+        ));
+        self.suscripciones_pendientes.push((
+            topico_respuesta_consumer(&self.config.name, &nombre),
+            format!("origen_consumer|{}", nombre),
+        ));
+
+        self.origenes
+            .insert(nombre, Origen::new(config, ultima_secuencia));
+    }
+
+    fn quitar_origen(&mut self, nombre: &str) {
+        if let Some(origen) = self.origenes.remove(nombre) {
+            if let Some(consumer) = origen.consumer {
+                self.respuestas
+                    .push(pedido_eliminar_consumer(nombre, &consumer));
+            }
+            self.desuscripciones_pendientes
+                .push(format!("origen|{}", nombre));
+            self.desuscripciones_pendientes
+                .push(format!("origen_consumer|{}", nombre));
+        }
+    }
+
+    /// Agrega, quita o reinicia los orígenes según los de la configuración actual
+    fn sincronizar_origenes(&mut self) {
+        let configurados: Vec<StreamSource> = self.config.origenes().cloned().collect();
+
+        let sobrantes: Vec<String> = self
+            .origenes
+            .keys()
+            .filter(|nombre| !configurados.iter().any(|config| config.name == **nombre))
+            .cloned()
+            .collect();
+        for nombre in sobrantes {
+            self.quitar_origen(&nombre);
+        }
+
+        for config in configurados {
+            match self.origenes.get_mut(&config.name) {
+                Some(origen) if origen.config != config => {
+                    if let Some(anterior) = origen.actualizar(config.clone()) {
+                        self.respuestas
+                            .push(pedido_eliminar_consumer(&config.name, &anterior));
+                    }
+                }
+                Some(_) => {}
+                None => self.agregar_origen(config, 0),
+            }
+        }
+    }
+
+    /// Pide un consumer a los orígenes que todavía no tienen uno o que perdieron el suyo
+    fn revisar_origenes(&mut self) {
+        for (nombre, origen) in self.origenes.iter_mut() {
+            if !origen.necesita_consumer() {
+                continue;
+            }
+
+            let (config, anterior) =
+                origen.pedir_consumer(topico_entrega(&self.config.name, nombre));
+            if let Some(anterior) = anterior {
+                self.registrador.advertencia(
+                    &format!(
+                        "El consumer {} del stream {} dejó de responder, se crea otro",
+                        anterior, nombre
+                    ),
+                    Some(self.id_conexion),
+                );
+                self.respuestas
+                    .push(pedido_eliminar_consumer(nombre, &anterior));
+            }
+
+            match JSPeticionCrearConsumer::new(config).to_json() {
+                Ok(body) => self.respuestas.push(Publicacion::new(
+                    format!("$JS.API.CONSUMER.CREATE.{}", nombre),
+                    body.into_bytes(),
+                    None,
+                    Some(topico_respuesta_consumer(&self.config.name, nombre)),
+                )),
+                Err(e) => self.registrador.error(
+                    &format!("Error al serializar el consumer para {}: {}", nombre, e),
+                    Some(self.id_conexion),
+                ),
+            }
+        }
+    }
+
+    /// Respuesta del origen al pedido de creación del consumer
+    fn consumer_de_origen_creado(&mut self, nombre: &str, payload: &[u8]) {
+        let origen = match self.origenes.get_mut(nombre) {
+            Some(origen) => origen,
+            None => return,
+        };
+
+        let json = String::from_utf8_lossy(payload);
+        let respuesta = match JSCrearConsumerRespuesta::from_json(&json) {
+            Ok(respuesta) => respuesta,
+            Err(_) => {
+                self.registrador.advertencia(
+                    &format!(
+                        "El stream {} no pudo crear el consumer para copiar sus mensajes: {}",
+                        nombre, json
+                    ),
+                    Some(self.id_conexion),
+                );
+                return;
+            }
+        };
+
+        match &origen.consumer {
+            // Respuesta a un pedido anterior que tardó en llegar
+            Some(actual) if *actual != respuesta.name => {
+                self.respuestas
+                    .push(pedido_eliminar_consumer(nombre, &respuesta.name));
+            }
+            _ => {
+                origen.consumer = Some(respuesta.name);
+                origen.registrar_actividad();
+            }
+        }
+    }
+
+    /// Mensaje, latido o pedido de control de flujo del consumer de un origen
+    fn recibir_de_origen(&mut self, nombre: &str, mensaje: &PublicacionMensaje) {
+        let es_espejo = self.config.mirror.is_some();
+        let origen = match self.origenes.get_mut(nombre) {
+            Some(origen) => origen,
+            None => return,
+        };
+        origen.registrar_actividad();
+
+        let estado = mensaje
+            .header
+            .as_deref()
+            .and_then(Headers::parsear)
+            .and_then(|headers| headers.estado);
+        if estado.is_some() {
+            // El control de flujo se responde para seguir recibiendo mensajes
+            if let Some(reply_to) = &mensaje.replay_to {
+                self.respuestas.push(Publicacion::new(
+                    reply_to.to_string(),
+                    Vec::new(),
+                    None,
+                    None,
+                ));
+            }
+            return;
+        }
+
+        let (secuencia, tiempo) = match mensaje.replay_to.as_deref().and_then(datos_entrega) {
+            Some(datos) => datos,
+            None => return,
+        };
+
+        // Un consumer reemplazado puede volver a entregar mensajes ya copiados
+        if secuencia <= origen.ultima_secuencia {
+            return;
+        }
+        origen.ultima_secuencia = secuencia;
+
+        if es_espejo {
+            self.guardar_mensaje_espejo(MensajeAlmacenado {
+                secuencia,
+                topico: mensaje.topico.clone(),
+                header: mensaje.header.clone(),
+                payload: mensaje.payload.clone(),
+                tiempo,
+            });
+        } else {
+            self.guardar_mensaje_de_fuente(nombre, secuencia, mensaje);
+        }
+    }
+
+    /// Verifica las condiciones que el publicador pide con headers. Devuelve el
+    /// rollup pedido, si hay uno
+    fn validar_headers(&self, mensaje: &PublicacionMensaje) -> Result<Option<Rollup>, JSError> {
+        let headers = match mensaje.header.as_deref().and_then(Headers::parsear) {
+            Some(headers) => headers,
+            None => return Ok(None),
+        };
+
+        if let Some(esperada) = headers.obtener(HEADER_ULTIMA_SECUENCIA_DEL_TOPICO) {
+            let esperada = esperada
+                .parse::<u64>()
+                .map_err(|_| JSError::peticion_invalida())?;
+            let ultima = self
+                .almacenamiento
+                .bloquear()
+                .ultima_secuencia_del_topico(&mensaje.topico)
+                .unwrap_or(0);
+            if ultima != esperada {
+                return Err(JSError::ultima_secuencia_incorrecta(ultima));
+            }
+        }
+
+        let rollup = match headers.obtener(HEADER_ROLLUP) {
+            Some("sub") => Rollup::Topico,
+            Some("all") => Rollup::Todo,
+            Some(_) => return Err(JSError::peticion_invalida()),
+            None => return Ok(None),
+        };
+
+        if !self.config.allow_rollup_hdrs {
+            return Err(JSError::rollup_no_permitido());
+        }
+
+        Ok(Some(rollup))
+    }
+
+    /// Verifica que el mensaje entre en el stream sin superar sus límites.
+    ///
+    /// Con la política `discard: old` solo se rechazan los mensajes demasiado grandes
+    /// o que no entran en el `max_storage` de la cuenta, porque el resto de los
+    /// límites se cumple descartando mensajes viejos.
+    fn validar_limites(&self, mensaje: &PublicacionMensaje) -> Result<(), JSError> {
+        let tamano = mensaje.header.as_ref().map_or(0, |h| h.len()) + mensaje.payload.len();
+        if self.config.max_msg_size > 0 && tamano > self.config.max_msg_size as usize {
+            return Err(JSError::mensaje_excede_maximo());
+        }
+
+        let almacenamiento = self.almacenamiento.bloquear();
+        let bytes = (mensaje.topico.len()
+            + mensaje.header.as_ref().map_or(0, |h| h.len())
+            + mensaje.payload.len()) as u64;
+        if self.config.storage == StorageType::File
+            && !self
+                .cuenta
+                .admite_bytes(&self.config.name, almacenamiento.bytes(), bytes)
+        {
+            return Err(JSError::almacenamiento_insuficiente());
+        }
+
+        if self.config.discard == DiscardPolicy::New {
+            if self.config.max_msgs > 0 && almacenamiento.cantidad() >= self.config.max_msgs as u64
+            {
+                return Err(JSError::maximo_mensajes_excedido());
+            }
+
+            if self.config.max_bytes > 0
+                && almacenamiento.bytes() + bytes > self.config.max_bytes as u64
+            {
+                return Err(JSError::maximo_bytes_excedido());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Elimina los mensajes más antiguos hasta que el stream vuelva a estar dentro de
+    /// `max_msgs` y `max_bytes`
+    fn descartar_mensajes_viejos(&mut self) {
+        while self.supera_limites() {
+            let primera = self.almacenamiento.bloquear().primera_secuencia();
+            match primera {
+                Some(secuencia) => {
+                    self.eliminar_mensaje(secuencia);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Elimina los mensajes anteriores al indicado, solo de su tópico o de todo el stream
+    fn eliminar_anteriores(&mut self, mensaje: &MensajeAlmacenado, solo_topico: bool) {
+        let anteriores = if solo_topico {
+            self.almacenamiento
+                .bloquear()
+                .secuencias_del_topico(&mensaje.topico)
+        } else {
+            self.almacenamiento.bloquear().secuencias()
+        };
+
+        for secuencia in anteriores {
+            if secuencia < mensaje.secuencia {
+                self.eliminar_mensaje(secuencia);
+            }
+        }
+    }
+
+    /// Elimina los mensajes más antiguos del tópico que superen `max_msgs_per_subject`
+    fn limitar_mensajes_del_topico(&mut self, topico: &str) {
+        if self.config.max_msgs_per_subject <= 0 {
+            return;
+        }
+
+        let secuencias = self.almacenamiento.bloquear().secuencias_del_topico(topico);
+        let sobrantes = secuencias
+            .len()
+            .saturating_sub(self.config.max_msgs_per_subject as usize);
+        for secuencia in secuencias.into_iter().take(sobrantes) {
+            self.eliminar_mensaje(secuencia);
+        }
+    }
+
+    fn limitar_mensajes_de_todos_los_topicos(&mut self) {
+        let topicos: Vec<String> = self
+            .almacenamiento
+            .bloquear()
+            .topicos()
+            .into_keys()
+            .map(|topico| topico.to_string())
+            .collect();
+
+        for topico in topicos {
+            self.limitar_mensajes_del_topico(&topico);
+        }
+    }
+
+    fn supera_limites(&self) -> bool {
+        let almacenamiento = self.almacenamiento.bloquear();
+        (self.config.max_msgs > 0 && almacenamiento.cantidad() > self.config.max_msgs as u64)
+            || (self.config.max_bytes > 0 && almacenamiento.bytes() > self.config.max_bytes as u64)
+    }
+
+    /// Olvida los ids de los mensajes que salieron de la ventana de duplicados
+    fn expirar_ids_recientes(&mut self) {
+        let limite = Utc::now().timestamp_nanos_opt().unwrap_or(0)
+            - self.config.ventana_duplicados().as_nanos() as i64;
+        self.ids_recientes.expirar(limite);
+    }
+
+    /// Recupera los ids de los mensajes guardados dentro de la ventana de duplicados
+    fn cargar_ids_recientes(&mut self) {
+        let limite = Utc::now().timestamp_nanos_opt().unwrap_or(0)
+            - self.config.ventana_duplicados().as_nanos() as i64;
+
+        let recientes: Vec<u64> = {
+            let almacenamiento = self.almacenamiento.bloquear();
+            almacenamiento
+                .secuencias()
+                .into_iter()
+                .rev()
+                .take_while(|secuencia| {
+                    almacenamiento
+                        .tiempo(*secuencia)
+                        .is_some_and(|tiempo| tiempo > limite)
+                })
+                .collect()
+        };
+
+        for secuencia in recientes.into_iter().rev() {
+            if let Some(mensaje) = self.leer_mensaje(secuencia) {
+                if let Some(id) = id_mensaje(&mensaje.header) {
+                    self.ids_recientes
+                        .registrar(id, mensaje.secuencia, mensaje.tiempo);
+                }
+            }
+        }
+    }
+
+    /// Cuánto falta para que venza el primer mensaje por `max_age`, sin revisar
+    /// más seguido que cada `INTERVALO_EXPIRACION`
+    fn espera_expiracion(&self) -> Option<Duration> {
+        if self.config.max_age.is_zero() {
+            return None;
+        }
+
+        let almacenamiento = self.almacenamiento.bloquear();
+        let tiempo = almacenamiento
+            .primera_secuencia()
+            .and_then(|secuencia| almacenamiento.tiempo(secuencia))?;
+        let vencimiento = tiempo.saturating_add(self.config.max_age.as_nanos() as i64);
+        let restante = vencimiento - Utc::now().timestamp_nanos_opt().unwrap_or(0);
+
+        Some(
+            Duration::from_nanos(restante.max(0) as u64)
+                .max(INTERVALO_EXPIRACION.saturating_sub(self.ultima_expiracion.elapsed())),
+        )
+    }
+
+    /// Elimina los mensajes que llevan guardados más de `max_age`
+    fn expirar_mensajes(&mut self) {
+        if self.config.max_age.is_zero() {
+            return;
+        }
+
+        let limite =
+            Utc::now().timestamp_nanos_opt().unwrap_or(0) - self.config.max_age.as_nanos() as i64;
+
+        loop {
+            let vencida = {
+                let almacenamiento = self.almacenamiento.bloquear();
+                almacenamiento.primera_secuencia().filter(|secuencia| {
+                    almacenamiento
+                        .tiempo(*secuencia)
+                        .is_some_and(|tiempo| tiempo <= limite)
+                })
+            };
+            match vencida {
+                Some(secuencia) => {
+                    self.eliminar_mensaje(secuencia);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Elimina el mensaje del almacenamiento y avisa a los consumers. Devuelve si existía
+    fn eliminar_mensaje(&mut self, secuencia: u64) -> bool {
+        match self.quitar_mensaje(secuencia, false) {
+            Ok(eliminado) => eliminado,
+            Err(e) => {
+                self.registrador.error(
+                    &format!("Error al eliminar el mensaje {}: {}", secuencia, e),
+                    Some(self.obtener_id()),
+                );
+                false
+            }
+        }
+    }
+
+    /// Elimina el mensaje, sobrescribiendo su contenido si se pide `borrar`, y
+    /// avisa a los consumers que aceptan su tópico. Devuelve si existía
+    fn quitar_mensaje(&mut self, secuencia: u64, borrar: bool) -> io::Result<bool> {
+        let mut almacenamiento = self.almacenamiento.bloquear();
+        let topico = match almacenamiento.topico(secuencia) {
+            Some(topico) => topico.to_string(),
+            None => return Ok(false),
+        };
+
+        if borrar {
+            almacenamiento.borrar(secuencia)?;
+        } else {
+            almacenamiento.eliminar(secuencia)?;
+        }
+
+        // Con el almacenamiento todavía bloqueado, ver `EventoStream`
+        for (config, tx_consumer, aviso) in self.consumers_transmisores.values() {
+            if consumer_aceptar_topico(config, &topico) {
+                let _ = tx_consumer.send(EventoStream::Eliminado(secuencia));
+                aviso.avisar();
+            }
+        }
+
+        self.estado_modificado = true;
+        Ok(true)
+    }
+
+    /// Aplica una nueva configuración al stream. El nombre no se puede cambiar
+    fn actualizar(&mut self, config: StreamConfig) -> Result<(), JSError> {
+        if config.name != self.config.name {
+            return Err(JSError::nombre_stream_no_coincide());
+        }
+        if config.mirror != self.config.mirror {
+            return Err(JSError::espejo_no_actualizable());
+        }
+        if config.storage != self.config.storage {
+            return Err(JSError::configuracion_stream_invalida(
+                "stream configuration update can not change storage type",
+            ));
+        }
+        if !config.origenes_validos() || !topicos_validos(&config) {
+            return Err(JSError::peticion_invalida());
+        }
+
+        let topicos_anteriores = self.config.subjects.clone();
+        self.config = config;
+        self.sincronizar_origenes();
+
+        for topico in topicos_anteriores.iter() {
+            if !self.config.subjects.contains(topico) {
+                self.desuscripciones_pendientes
+                    .push(format!("mensaje|{}", topico));
+            }
+        }
+        for topico in self.config.subjects.iter() {
+            if !topicos_anteriores.contains(topico) {
+                self.suscripciones_pendientes
+                    .push((topico.clone(), format!("mensaje|{}", topico)));
+            }
+        }
+
+        self.expirar_mensajes();
+        self.limitar_mensajes_de_todos_los_topicos();
+        self.descartar_mensajes_viejos();
+        self.estado_modificado = true;
+
+        self.registrador.info(
+            &format!("Stream actualizado: {:?}", self.config),
+            Some(self.obtener_id()),
+        );
+
+        Ok(())
+    }
+
+    /// Elimina los mensajes indicados por la petición y devuelve cuántos se eliminaron
+    fn purgar(&mut self, peticion: &JSPeticionPurgarStream) -> Result<u64, JSError> {
+        if peticion.seq.is_some() && peticion.keep.is_some() {
+            return Err(JSError::peticion_invalida());
+        }
+
+        let filtro = match &peticion.filter {
+            Some(filtro) => match Topico::new(filtro.clone()) {
+                Ok(topico) => Some(topico),
+                Err(_) => return Err(JSError::peticion_invalida()),
+            },
+            None => None,
+        };
+
+        let mut secuencias = {
+            let almacenamiento = self.almacenamiento.bloquear();
+            almacenamiento
+                .secuencias()
+                .into_iter()
+                .filter(|secuencia| match &filtro {
+                    Some(filtro) => almacenamiento
+                        .topico(*secuencia)
+                        .is_some_and(|topico| filtro.test(topico)),
+                    None => true,
+                })
+                .collect::<Vec<u64>>()
+        };
+
+        if let Some(hasta) = peticion.seq {
+            secuencias.retain(|secuencia| *secuencia < hasta);
+        }
+        if let Some(conservar) = peticion.keep {
+            let cantidad = secuencias.len().saturating_sub(conservar as usize);
+            secuencias.truncate(cantidad);
+        }
+
+        let mut purgados = 0;
+        for secuencia in secuencias {
+            if self.eliminar_mensaje(secuencia) {
+                purgados += 1;
+            }
+        }
+
+        self.registrador.info(
+            &format!("Stream purgado: {} mensajes eliminados", purgados),
+            Some(self.obtener_id()),
+        );
+
+        Ok(purgados)
+    }
+
+    /// Tópicos guardados que coinciden con el filtro, que puede tener comodines
+    fn topicos_del_filtro(&self, filtro: &str) -> Result<Vec<String>, JSError> {
+        if !filtro.contains(['*', '>']) {
+            return Ok(vec![filtro.to_string()]);
+        }
+
+        let filtro = Topico::new(filtro.to_string()).map_err(|_| JSError::peticion_invalida())?;
+        Ok(self
+            .almacenamiento
+            .bloquear()
+            .topicos()
+            .into_keys()
+            .filter(|topico| filtro.test(topico))
+            .map(|topico| topico.to_string())
+            .collect())
+    }
+
+    /// Busca el mensaje que indica la petición. Es un error pedir a la vez por
+    /// último y por siguiente del tópico, o no indicar nada
+    fn buscar_mensaje(
+        &self,
+        peticion: &JSPeticionObtenerMensaje,
+    ) -> Result<Option<MensajeAlmacenado>, JSError> {
+        let secuencia = match (peticion.seq, &peticion.last_by_subj, &peticion.next_by_subj) {
+            (Some(secuencia), None, None) => Some(secuencia),
+            (None, Some(filtro), None) => self
+                .topicos_del_filtro(filtro)?
+                .iter()
+                .filter_map(|topico| {
+                    self.almacenamiento
+                        .bloquear()
+                        .ultima_secuencia_del_topico(topico)
+                })
+                .max(),
+            (desde, None, Some(filtro)) => {
+                let desde = desde.unwrap_or(0);
+                self.topicos_del_filtro(filtro)?
+                    .iter()
+                    .filter_map(|topico| {
+                        self.almacenamiento
+                            .bloquear()
+                            .siguiente_secuencia_del_topico(topico, desde)
+                    })
+                    .min()
+            }
+            _ => return Err(JSError::peticion_invalida()),
+        };
+
+        Ok(secuencia.and_then(|secuencia| self.leer_mensaje(secuencia)))
+    }
+
+    /// Responde a `$JS.API.STREAM.MSG.GET.<stream>` con el mensaje dentro del JSON
+    fn obtener_mensaje(&self, payload: &[u8]) -> Result<JSObtenerMensajeRespuesta, JSError> {
+        let peticion = JSPeticionObtenerMensaje::from_json(&String::from_utf8_lossy(payload))
+            .map_err(|_| JSError::peticion_invalida())?;
+
+        let mensaje = self
+            .buscar_mensaje(&peticion)?
+            .ok_or_else(JSError::mensaje_no_encontrado)?;
+
+        Ok(JSObtenerMensajeRespuesta::new(JSMensajeGuardado::new(
+            mensaje.topico,
+            mensaje.secuencia,
+            mensaje.header.as_deref(),
+            &mensaje.payload,
+            formatear_tiempo(mensaje.tiempo),
+        )))
+    }
+
+    /// Responde a `$JS.API.DIRECT.GET.<stream>[.<tópico>]` con el mensaje que pide
+    /// el cuerpo, o con el último del tópico si viene en el tópico de la petición.
+    /// El mensaje se envía tal como se guardó, con headers que indican de dónde viene
+    fn obtener_directo(&mut self, topico: &str, payload: &[u8], reply_to: &str) {
+        let prefijo = format!("$JS.API.DIRECT.GET.{}.", self.config.name);
+        let peticion = match topico.strip_prefix(&prefijo) {
+            Some(topico) => Ok(JSPeticionObtenerMensaje::ultimo_del_topico(topico)),
+            None => JSPeticionObtenerMensaje::from_json(&String::from_utf8_lossy(payload))
+                .map_err(|_| JSError::peticion_invalida()),
+        };
+
+        let mensaje = match peticion.and_then(|peticion| self.buscar_mensaje(&peticion)) {
+            Ok(Some(mensaje)) => mensaje,
+            Ok(None) => {
+                self.responder_estado(reply_to, 404, "Message Not Found");
+                return;
+            }
+            Err(_) => {
+                self.responder_estado(reply_to, 400, "Bad Request");
+                return;
+            }
+        };
+
+        let mut headers = Headers::new();
+        headers.insertar("Nats-Stream", &self.config.name);
+        headers.insertar("Nats-Subject", &mensaje.topico);
+        headers.insertar("Nats-Sequence", &mensaje.secuencia.to_string());
+        headers.insertar("Nats-Time-Stamp", &formatear_tiempo(mensaje.tiempo));
+        if let Some(originales) = mensaje.header.as_deref().and_then(Headers::parsear) {
+            for (clave, valor) in originales.valores() {
+                headers.insertar(clave, valor);
+            }
+        }
+
+        self.respuestas.push(Publicacion::new(
+            reply_to.to_string(),
+            mensaje.payload,
+            Some(headers.serializar()),
+            None,
+        ));
+    }
+
+    /// Responde a `$JS.API.STREAM.MSG.DELETE.<stream>`
+    fn procesar_eliminar_mensaje(&mut self, payload: &[u8]) -> Result<(), JSError> {
+        let peticion = JSPeticionEliminarMensaje::from_json(&String::from_utf8_lossy(payload))
+            .map_err(|_| JSError::peticion_invalida())?;
+
+        match self.quitar_mensaje(peticion.seq, !peticion.no_erase) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(JSError::eliminacion_mensaje_fallida("no message found")),
+            Err(e) => {
+                self.registrador.error(
+                    &format!("Error al eliminar el mensaje {}: {}", peticion.seq, e),
+                    Some(self.obtener_id()),
+                );
+                Err(JSError::eliminacion_mensaje_fallida(&e.to_string()))
+            }
+        }
+    }
+
+    /// Empaqueta el directorio del stream y empieza a enviarlo al tópico que indica
+    /// la petición. Como el stream es el único que escribe en su almacenamiento,
+    /// el snapshot es consistente aunque sigan llegando mensajes
+    fn iniciar_snapshot(&mut self, payload: &[u8]) -> Result<JSSnapshotRespuesta, JSError> {
+        let peticion = JSPeticionSnapshot::from_json(&String::from_utf8_lossy(payload))
+            .map_err(|_| JSError::peticion_invalida())?;
+        if peticion.deliver_subject.is_empty() {
+            return Err(JSError::peticion_invalida());
+        }
+
+        let omitidos: &[&str] = if peticion.no_consumers {
+            &[DIRECTORIO_CONSUMERS]
+        } else {
+            &[]
+        };
+        let mut datos = empaquetar(&self.directorio, omitidos)
+            .map_err(|e| JSError::snapshot_fallido(&e.to_string()))?;
+        let archivos = self
+            .almacenamiento
+            .bloquear()
+            .archivos_snapshot()
+            .map_err(|e| JSError::snapshot_fallido(&e.to_string()))?;
+        for (ruta, contenido) in archivos {
+            agregar_archivo(&mut datos, &ruta, &contenido);
+        }
+
+        let envio = EnvioSnapshot::new(
+            &self.config.name,
+            peticion.deliver_subject,
+            peticion.chunk_size,
+            datos,
+            self.registrador.clone(),
+        );
+        let _ = self.tx_conexiones.send(Box::new(envio));
+
+        Ok(JSSnapshotRespuesta::new(
+            self.config.clone(),
+            self.estado(None),
+        ))
+    }
+
+    /// Responde a una petición a un consumer que no existe. Si existe, la responde el consumer
+    fn responder_consumer_desconocido(&mut self, tipo: &str, mensaje: &PublicacionMensaje) {
+        let reply_to = match &mensaje.replay_to {
+            Some(reply_to) => reply_to,
+            None => return,
+        };
+
+        let nombre = mensaje.topico.rsplit('.').next().unwrap_or_default();
+        if !self.consumers_transmisores.contains_key(nombre) {
+            self.responder_error(
+                reply_to,
+                &format!("io.nats.jetstream.api.v1.{}", tipo),
+                JSError::consumer_no_encontrado(),
+            );
+        }
+    }
+
+    /// Responde con un mensaje sin cuerpo que solo tiene el estado en los headers
+    fn responder_estado(&mut self, reply_to: &str, estado: u16, descripcion: &str) {
+        self.respuestas.push(Publicacion::new(
+            reply_to.to_string(),
+            Vec::new(),
+            Some(Headers::con_estado(estado, descripcion).serializar()),
+            None,
+        ));
+    }
+
+    fn responder<E: std::fmt::Display>(&mut self, reply_to: &str, respuesta: Result<String, E>) {
+        match respuesta {
+            Ok(respuesta) => self.respuestas.push(Publicacion::new(
+                reply_to.to_string(),
+                respuesta.as_bytes().to_owned(),
+                None,
+                None,
+            )),
+            Err(e) => self.registrador.error(
+                &format!("Error al serializar respuesta: {}", e),
+                Some(self.obtener_id()),
+            ),
+        }
+    }
+
+    fn responder_error(&mut self, reply_to: &str, tipo: &str, error: JSError) {
+        // Los errores al publicar no son de peticiones a la API
+        if tipo != "io.nats.jetstream.api.v1.pub_ack_response" {
+            self.cuenta.registrar_error();
+        }
+        if let Ok(respuesta) = JSErrorRespuesta::new(tipo, error).to_json() {
+            self.respuestas.push(Publicacion::new(
+                reply_to.to_string(),
+                respuesta.as_bytes().to_owned(),
+                None,
+                None,
+            ));
+        }
     }
 }
 
@@ -133,6 +1444,22 @@ impl Conexion for JetStreamStream {
     }
 
     fn tick(&mut self, contexto: &mut crate::conexion::tick_contexto::TickContexto) {
+        if self.eliminacion_pedida {
+            for respuesta in self.respuestas.drain(..) {
+                contexto.publicar(respuesta);
+            }
+            // Al soltar los canales, los consumers ven en su próximo tick que el
+            // stream ya no existe y se eliminan
+            for (_, tx_consumer, aviso) in
+                std::mem::take(&mut self.consumers_transmisores).into_values()
+            {
+                drop(tx_consumer);
+                aviso.avisar();
+            }
+            self.eliminado = true;
+            return;
+        }
+
         if !self.preparado {
             self.suscribir(
                 contexto,
@@ -154,11 +1481,36 @@ impl Conexion for JetStreamStream {
                 &format!("$JS.API.STREAM.PURGE.{}", self.config.name),
                 "purgar",
             );
+            self.suscribir(
+                contexto,
+                &format!("$JS.API.STREAM.SNAPSHOT.{}", self.config.name),
+                "snapshot",
+            );
+            self.suscribir(
+                contexto,
+                &format!("$JS.API.STREAM.MSG.GET.{}", self.config.name),
+                "obtener_mensaje",
+            );
+            self.suscribir(
+                contexto,
+                &format!("$JS.API.STREAM.MSG.DELETE.{}", self.config.name),
+                "eliminar_mensaje",
+            );
             self.suscribir(
                 contexto,
                 &format!("$JS.API.CONSUMER.CREATE.{}.>", self.config.name),
                 "crear_consumer",
             );
+            self.suscribir(
+                contexto,
+                &format!("$JS.API.CONSUMER.INFO.{}.*", self.config.name),
+                "consumer_desconocido|consumer_info_response",
+            );
+            self.suscribir(
+                contexto,
+                &format!("$JS.API.CONSUMER.DELETE.{}.*", self.config.name),
+                "consumer_desconocido|consumer_delete_response",
+            );
             self.suscribir(
                 contexto,
                 &format!("$JS.API.CONSUMER.LIST.{}", self.config.name),
@@ -170,16 +1522,49 @@ impl Conexion for JetStreamStream {
                 "nombres_consumer",
             );
 
+            self.suscribir(
+                contexto,
+                &format!("$JS.API.DIRECT.GET.{}.>", self.config.name),
+                "obtener_directo",
+            );
+
             for topico in self.config.subjects.iter() {
                 self.suscribir(contexto, topico, &format!("mensaje|{}", topico));
             }
 
+            self.cargar_consumers();
+            self.cargar_ids_recientes();
+            self.cargar_origenes();
+
+            // Los límites pueden haberse superado mientras el servidor estaba apagado
+            self.expirar_mensajes();
+            self.limitar_mensajes_de_todos_los_topicos();
+            self.descartar_mensajes_viejos();
+
             self.enviar_actualizacion_de_estado();
 
             self.preparado = true;
         }
 
+        for (topico, id_suscripcion) in std::mem::take(&mut self.suscripciones_pendientes) {
+            self.suscribir(contexto, &topico, &id_suscripcion);
+        }
+        for id_suscripcion in self.desuscripciones_pendientes.drain(..) {
+            contexto.desuscribir(id_suscripcion);
+        }
+
         self.recibir_actualizaciones_js_consumers();
+        self.revisar_origenes();
+
+        if self.ultima_expiracion.elapsed() >= INTERVALO_EXPIRACION {
+            self.expirar_mensajes();
+            self.ultima_expiracion = Instant::now();
+        }
+
+        if self.estado_modificado {
+            self.enviar_actualizacion_de_estado();
+            self.estado_modificado = false;
+        }
 
         for respuesta in self.respuestas.drain(..) {
             contexto.publicar(respuesta);
@@ -190,13 +1575,39 @@ impl Conexion for JetStreamStream {
         &mut self,
         mensaje: &crate::publicacion::mensaje::PublicacionMensaje,
     ) {
+        if self.eliminacion_pedida {
+            return;
+        }
+
         match mensaje.sid.as_str() {
             "info" => {
                 if let Some(reply_to) = &mensaje.replay_to {
-                    if let Ok(respuesta) =
-                        JSStreamInfoRespuesta::new(self.config.clone(), JetStreamStreamState::new())
-                            .to_json()
-                    {
+                    // Sin cuerpo se devuelve la información sin filtro de tópicos
+                    let peticion = if mensaje.payload.is_empty() {
+                        JSPeticionStreamInfo::default()
+                    } else {
+                        match JSPeticionStreamInfo::from_json(&String::from_utf8_lossy(
+                            &mensaje.payload,
+                        )) {
+                            Ok(peticion) => peticion,
+                            Err(_) => {
+                                self.responder_error(
+                                    reply_to,
+                                    "io.nats.jetstream.api.v1.stream_info_response",
+                                    JSError::peticion_invalida(),
+                                );
+                                return;
+                            }
+                        }
+                    };
+
+                    let mut respuesta = JSStreamInfoRespuesta::new(
+                        self.config.clone(),
+                        self.estado(peticion.subjects_filter.as_deref()),
+                    );
+                    respuesta.created = self.creado.clone();
+
+                    if let Ok(respuesta) = respuesta.to_json() {
                         self.respuestas.push(Publicacion::new(
                             reply_to.to_string(),
                             respuesta.as_bytes().to_owned(),
@@ -207,40 +1618,147 @@ impl Conexion for JetStreamStream {
                 }
             }
             "eliminar" => {
-                self.eliminado = true;
+                self.eliminacion_pedida = true;
+                let origenes: Vec<String> = self.origenes.keys().cloned().collect();
+                for origen in origenes {
+                    self.quitar_origen(&origen);
+                }
+                let resultado =
+                    self.almacenamiento.bloquear().destruir().and_then(
+                        |()| match fs::remove_dir_all(&self.directorio) {
+                            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                            _ => Ok(()),
+                        },
+                    );
+                if let Err(e) = &resultado {
+                    self.registrador.error(
+                        &format!("Error al eliminar los archivos del stream: {}", e),
+                        Some(self.obtener_id()),
+                    );
+                }
                 let _ = self
                     .tx_actualizaciones_js
                     .send(ActualizacionJS::StreamEliminado(self.config.name.clone()));
+
+                if let Some(reply_to) = &mensaje.replay_to {
+                    match resultado {
+                        Ok(()) => {
+                            let respuesta = JSEliminarStreamRespuesta::new().to_json();
+                            self.responder(reply_to, respuesta);
+                        }
+                        Err(e) => self.responder_error(
+                            reply_to,
+                            "io.nats.jetstream.api.v1.stream_delete_response",
+                            JSError::eliminacion_stream_fallida(&e.to_string()),
+                        ),
+                    }
+                }
             }
-            "actualizar" => {}
-            "purgar" => {}
-            "crear_consumer" => {
-                if let Ok(datos) =
-                    JSPeticionCrearConsumer::from_json(&String::from_utf8_lossy(&mensaje.payload))
-                {
-                    let mut creado = true;
-                    if self.consumers.contains_key(&datos.config.durable_name) {
-                        creado = false;
-                    } else {
-                        self.crear_consumer(datos.config.clone());
+            "actualizar" => {
+                let resultado =
+                    match StreamConfig::from_json(&String::from_utf8_lossy(&mensaje.payload)) {
+                        Ok(config) => self.actualizar(config),
+                        Err(_) => Err(JSError::peticion_invalida()),
+                    };
+
+                if let Some(reply_to) = &mensaje.replay_to {
+                    match resultado {
+                        Ok(()) => {
+                            let respuesta = JSActualizarStreamRespuesta::new(
+                                self.config.clone(),
+                                self.creado.clone(),
+                                self.estado(None),
+                            )
+                            .to_json();
+                            self.responder(reply_to, respuesta);
+                        }
+                        Err(error) => self.responder_error(
+                            reply_to,
+                            "io.nats.jetstream.api.v1.stream_update_response",
+                            error,
+                        ),
+                    }
+                }
+            }
+            "purgar" => {
+                // Sin cuerpo se purga todo el stream
+                let resultado = if mensaje.payload.is_empty() {
+                    self.purgar(&JSPeticionPurgarStream::default())
+                } else {
+                    match JSPeticionPurgarStream::from_json(&String::from_utf8_lossy(
+                        &mensaje.payload,
+                    )) {
+                        Ok(peticion) => self.purgar(&peticion),
+                        Err(_) => Err(JSError::peticion_invalida()),
+                    }
+                };
+
+                if let Some(reply_to) = &mensaje.replay_to {
+                    match resultado {
+                        Ok(purgados) => {
+                            let respuesta = JSPurgarStreamRespuesta::new(purgados).to_json();
+                            self.responder(reply_to, respuesta);
+                        }
+                        Err(error) => self.responder_error(
+                            reply_to,
+                            "io.nats.jetstream.api.v1.stream_purge_response",
+                            error,
+                        ),
+                    }
+                }
+            }
+            "snapshot" => {
+                if let Some(reply_to) = &mensaje.replay_to {
+                    match self.iniciar_snapshot(&mensaje.payload) {
+                        Ok(respuesta) => self.responder(reply_to, respuesta.to_json()),
+                        Err(error) => self.responder_error(
+                            reply_to,
+                            "io.nats.jetstream.api.v1.stream_snapshot_response",
+                            error,
+                        ),
+                    }
+                }
+            }
+            "obtener_mensaje" => {
+                if let Some(reply_to) = &mensaje.replay_to {
+                    match self.obtener_mensaje(&mensaje.payload) {
+                        Ok(respuesta) => self.responder(reply_to, respuesta.to_json()),
+                        Err(error) => self.responder_error(
+                            reply_to,
+                            "io.nats.jetstream.api.v1.stream_msg_get_response",
+                            error,
+                        ),
                     }
+                }
+            }
+            "eliminar_mensaje" => {
+                let resultado = self.procesar_eliminar_mensaje(&mensaje.payload);
 
-                    if let Some(reply_to) = &mensaje.replay_to {
-                        if let Ok(respuesta) =
-                            JSCrearConsumerRespuesta::new(datos.config, creado).to_json()
-                        {
-                            self.respuestas.push(Publicacion::new(
-                                reply_to.to_string(),
-                                respuesta.as_bytes().to_owned(),
-                                None,
-                                None,
-                            ));
-                        } else {
-                            self.registrador.error(
-                                "Error al serializar respuesta de crear consumer",
-                                Some(self.obtener_id()),
-                            );
+                if let Some(reply_to) = &mensaje.replay_to {
+                    match resultado {
+                        Ok(()) => {
+                            let respuesta = JSEliminarMensajeRespuesta::new().to_json();
+                            self.responder(reply_to, respuesta);
                         }
+                        Err(error) => self.responder_error(
+                            reply_to,
+                            "io.nats.jetstream.api.v1.stream_msg_delete_response",
+                            error,
+                        ),
+                    }
+                }
+            }
+            "crear_consumer" => {
+                let resultado = self.procesar_crear_consumer(&mensaje.topico, &mensaje.payload);
+
+                if let Some(reply_to) = &mensaje.replay_to {
+                    match resultado {
+                        Ok(respuesta) => self.responder(reply_to, respuesta.to_json()),
+                        Err(error) => self.responder_error(
+                            reply_to,
+                            "io.nats.jetstream.api.v1.consumer_create_response",
+                            error,
+                        ),
                     }
                 }
             }
@@ -288,43 +1806,54 @@ impl Conexion for JetStreamStream {
                     }
                 }
             }
+            "obtener_directo" => {
+                if let Some(reply_to) = &mensaje.replay_to {
+                    if self.config.allow_direct {
+                        self.obtener_directo(&mensaje.topico, &mensaje.payload, reply_to);
+                    } else {
+                        // Igual que si nadie atendiera el tópico, para que el
+                        // cliente no espere hasta vencer la petición
+                        self.responder_estado(reply_to, 503, "No Responders");
+                    }
+                }
+            }
             _ => {}
         }
 
         if mensaje.sid.starts_with("mensaje|") {
-            for (nombre_consumer, tx_consumer) in self.consumers_transmisores.iter() {
-                if let Some(consumer) = self.consumers.get(nombre_consumer) {
-                    if !consumer_aceptar_topico(&consumer.config, &mensaje.topico) {
-                        continue;
-                    }
-
-                    if tx_consumer
-                        .send(Publicacion::new(
-                            mensaje.topico.clone(),
-                            mensaje.payload.clone(),
-                            mensaje.header.clone(),
-                            mensaje.replay_to.clone(),
-                        ))
-                        .is_err()
-                    {
-                        self.registrador.error(
-                            &format!("Error al enviar mensaje a consumer {}", nombre_consumer),
-                            Some(self.obtener_id()),
-                        );
-                    }
-                } else {
-                    self.registrador.error(
-                        &format!("Consumer {} no encontrado", nombre_consumer),
-                        Some(self.obtener_id()),
-                    );
-                }
-            }
+            self.guardar_mensaje(mensaje);
+        } else if let Some(origen) = mensaje.sid.strip_prefix("origen|") {
+            self.recibir_de_origen(origen, mensaje);
+        } else if let Some(origen) = mensaje.sid.strip_prefix("origen_consumer|") {
+            self.consumer_de_origen_creado(origen, &mensaje.payload);
+        } else if let Some(tipo) = mensaje.sid.strip_prefix("consumer_desconocido|") {
+            self.responder_consumer_desconocido(tipo, mensaje);
         }
     }
 
     fn esta_conectado(&self) -> bool {
         !self.eliminado
     }
+
+    /// Los consumers avisan al hilo cuando le envían actualizaciones
+    fn registrar(
+        &mut self,
+        _registro: &Registry,
+        _token: Token,
+        despertador: &Despertador,
+    ) -> io::Result<()> {
+        self.aviso.vincular(despertador, self.id_conexion);
+        Ok(())
+    }
+
+    /// Sin mensajes solo hace falta un tick para expirar mensajes por `max_age`
+    /// o para volver a pedir el consumer de un origen que no responde
+    fn intervalo_tick(&self) -> Option<Duration> {
+        self.espera_expiracion()
+            .into_iter()
+            .chain(self.origenes.values().map(Origen::espera))
+            .min()
+    }
 }
 
 pub fn consumer_aceptar_topico(config: &ConsumerConfig, topico: &str) -> bool {
@@ -349,3 +1878,656 @@ pub fn consumer_aceptar_topico(config: &ConsumerConfig, topico: &str) -> bool {
 
     false
 }
+
+/// Si todos los tópicos del stream son patrones válidos para suscribirse
+pub fn topicos_validos(config: &StreamConfig) -> bool {
+    config
+        .subjects
+        .iter()
+        .all(|topico| Topico::new(topico.to_string()).is_ok())
+}
+
+/// Valor del header `Nats-Msg-Id` del mensaje, si lo tiene
+fn id_mensaje(header: &Option<Vec<u8>>) -> Option<String> {
+    let headers = Headers::parsear(header.as_deref()?)?;
+    headers.obtener(HEADER_ID_MENSAJE).map(|id| id.to_string())
+}
+
+/// Stream y secuencia de origen de un mensaje copiado de una fuente
+fn fuente_del_mensaje(header: &Option<Vec<u8>>) -> Option<(String, u64)> {
+    let headers = Headers::parsear(header.as_deref()?)?;
+    let (origen, secuencia) = headers.obtener(HEADER_FUENTE)?.split_once(' ')?;
+    Some((origen.to_string(), secuencia.parse().ok()?))
+}
+
+/// Pedido para eliminar un consumer de otro stream, sin esperar respuesta
+fn pedido_eliminar_consumer(stream: &str, consumer: &str) -> Publicacion {
+    Publicacion::new(
+        format!("$JS.API.CONSUMER.DELETE.{}.{}", stream, consumer),
+        Vec::new(),
+        None,
+        None,
+    )
+}
+
+/// Convierte nanosegundos desde epoch al formato de fecha que usa la API de JetStream
+fn formatear_tiempo(nanos: i64) -> String {
+    if nanos == 0 {
+        return "0001-01-01T00:00:00Z".to_string();
+    }
+    DateTime::from_timestamp_nanos(nanos).to_rfc3339()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            mpsc::{channel, Receiver},
+            Arc,
+        },
+        time::{Duration, Instant},
+    };
+
+    use lib::jet_stream::{
+        consumer_config::ConsumerConfig,
+        crear_consumer_peticion::JSPeticionCrearConsumer,
+        eliminar_mensaje_peticion::JSPeticionEliminarMensaje,
+        error::{JSError, JSErrorRespuesta},
+        pub_ack::JSPubAck,
+        purgar_stream_peticion::JSPeticionPurgarStream,
+        purgar_stream_respuesta::JSPurgarStreamRespuesta,
+        stream_config::{DiscardPolicy, StorageType, StreamConfig},
+        stream_info_peticion::JSPeticionStreamInfo,
+        stream_info_respuesta::JSStreamInfoRespuesta,
+        stream_source::StreamSource,
+    };
+    use lib::parseador::headers::Headers;
+
+    use crate::{
+        conexion::{r#trait::Conexion, tick_contexto::TickContexto},
+        hilo::{despertador::Aviso, emisor::EmisorConexion},
+        jetstream::{
+            actualizacion::ActualizacionJS,
+            almacenamiento::memoria::AlmacenamientoMemoria,
+            cuenta::{Cuenta, LimitesCuenta},
+        },
+        publicacion::mensaje::PublicacionMensaje,
+        registrador::Registrador,
+    };
+
+    use super::{JetStreamStream, INTERVALO_EXPIRACION};
+
+    const RESPUESTA: &str = "_INBOX.respuesta";
+
+    struct Prueba {
+        stream: JetStreamStream,
+        _rx_conexiones: Receiver<Box<dyn Conexion + Send>>,
+        _rx_actualizaciones: Receiver<ActualizacionJS>,
+    }
+
+    fn config(nombre: &str) -> StreamConfig {
+        StreamConfig {
+            name: nombre.to_string(),
+            subjects: vec![format!("{}.>", nombre)],
+            storage: StorageType::Memory,
+            num_replicas: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Stream en memoria ya preparado, con un directorio que no existe para que
+    /// no cargue consumers
+    fn prueba(config: StreamConfig) -> Prueba {
+        let (tx_conexiones, rx_conexiones) = channel();
+        let (tx_actualizaciones, rx_actualizaciones) = channel();
+        let directorio = std::env::temp_dir().join(format!("stream_prueba_{}", nuid::next()));
+
+        let mut stream = JetStreamStream::new(
+            config,
+            Box::new(AlmacenamientoMemoria::new()),
+            directorio,
+            EmisorConexion::new(tx_actualizaciones, Aviso::new()),
+            tx_conexiones,
+            Registrador::new(Some(false)),
+            Arc::new(Cuenta::new(LimitesCuenta::default())),
+        );
+        stream.tick(&mut TickContexto::new(0, 0));
+
+        Prueba {
+            stream,
+            _rx_conexiones: rx_conexiones,
+            _rx_actualizaciones: rx_actualizaciones,
+        }
+    }
+
+    impl Prueba {
+        /// Envía un mensaje por la suscripción `sid` y devuelve los payloads que
+        /// el stream publica en el próximo tick
+        fn enviar(&mut self, sid: &str, topico: &str, payload: &[u8]) -> Vec<String> {
+            respuestas(&self.enviar_con_contexto(sid, topico, payload))
+        }
+
+        fn enviar_con_contexto(&mut self, sid: &str, topico: &str, payload: &[u8]) -> TickContexto {
+            self.enviar_publicacion(&PublicacionMensaje::new(
+                sid.to_string(),
+                topico.to_string(),
+                payload.to_vec(),
+                None,
+                Some(RESPUESTA.to_string()),
+            ))
+        }
+
+        fn enviar_publicacion(&mut self, mensaje: &PublicacionMensaje) -> TickContexto {
+            self.stream.escribir_publicacion_mensaje(mensaje);
+            let mut contexto = TickContexto::new(0, 0);
+            self.stream.tick(&mut contexto);
+            contexto
+        }
+
+        fn tick(&mut self) -> Vec<String> {
+            let mut contexto = TickContexto::new(0, 0);
+            self.stream.tick(&mut contexto);
+            respuestas(&contexto)
+        }
+
+        fn publicar(&mut self, topico: &str, payload: &[u8]) -> Result<JSPubAck, JSError> {
+            let respuestas = self.enviar(&format!("mensaje|{}", topico), topico, payload);
+            pub_ack(&respuestas)
+        }
+
+        fn publicar_con_headers(
+            &mut self,
+            topico: &str,
+            headers: &Headers,
+            payload: &[u8],
+        ) -> Result<JSPubAck, JSError> {
+            let contexto = self.enviar_publicacion(&PublicacionMensaje::new(
+                format!("mensaje|{}", topico),
+                topico.to_string(),
+                payload.to_vec(),
+                Some(headers.serializar()),
+                Some(RESPUESTA.to_string()),
+            ));
+            pub_ack(&respuestas(&contexto))
+        }
+
+        fn eliminar_mensaje(&mut self, secuencia: u64) {
+            let peticion = JSPeticionEliminarMensaje::new(secuencia, false);
+            let topico = format!("$JS.API.STREAM.MSG.DELETE.{}", self.stream.config.name);
+            self.enviar(
+                "eliminar_mensaje",
+                &topico,
+                peticion.to_json().unwrap().as_bytes(),
+            );
+        }
+
+        fn info(&mut self, filtro: Option<&str>) -> JSStreamInfoRespuesta {
+            let peticion = JSPeticionStreamInfo {
+                subjects_filter: filtro.map(str::to_string),
+            };
+            let topico = format!("$JS.API.STREAM.INFO.{}", self.stream.config.name);
+            let respuestas = self.enviar("info", &topico, peticion.to_json().unwrap().as_bytes());
+            JSStreamInfoRespuesta::from_json(&respuestas[0]).unwrap()
+        }
+
+        fn actualizar(&mut self, config: &StreamConfig) -> TickContexto {
+            let topico = format!("$JS.API.STREAM.UPDATE.{}", self.stream.config.name);
+            self.enviar_con_contexto("actualizar", &topico, config.to_json().unwrap().as_bytes())
+        }
+
+        fn purgar(&mut self, peticion: &JSPeticionPurgarStream) -> u64 {
+            let topico = format!("$JS.API.STREAM.PURGE.{}", self.stream.config.name);
+            let respuestas = self.enviar("purgar", &topico, peticion.to_json().unwrap().as_bytes());
+            JSPurgarStreamRespuesta::from_json(&respuestas[0])
+                .unwrap()
+                .purged
+        }
+
+        /// Tópicos de los mensajes guardados, en orden
+        fn topicos(&self) -> Vec<String> {
+            let almacenamiento = self.stream.almacenamiento.bloquear();
+            almacenamiento
+                .secuencias()
+                .into_iter()
+                .filter_map(|secuencia| almacenamiento.topico(secuencia))
+                .map(str::to_string)
+                .collect()
+        }
+
+        fn crear_consumer(&mut self, nombre: &str) -> Vec<String> {
+            let peticion = JSPeticionCrearConsumer::new(ConsumerConfig {
+                durable_name: nombre.to_string(),
+                ..Default::default()
+            });
+            let topico = format!(
+                "$JS.API.CONSUMER.CREATE.{}.{}",
+                self.stream.config.name, nombre
+            );
+            self.enviar(
+                "crear_consumer",
+                &topico,
+                peticion.to_json().unwrap().as_bytes(),
+            )
+        }
+    }
+
+    fn respuestas(contexto: &TickContexto) -> Vec<String> {
+        contexto
+            .publicaciones()
+            .into_iter()
+            .filter(|publicacion| publicacion.topico == RESPUESTA)
+            .map(|publicacion| String::from_utf8_lossy(&publicacion.payload).to_string())
+            .collect()
+    }
+
+    /// Interpreta la primera respuesta como un PubAck o como el error que la rechazó
+    fn pub_ack(respuestas: &[String]) -> Result<JSPubAck, JSError> {
+        let respuesta = respuestas.first().expect("sin PubAck");
+        match JSPubAck::from_json(respuesta) {
+            Ok(ack) => Ok(ack),
+            Err(_) => Err(JSErrorRespuesta::from_json(respuesta).unwrap().error),
+        }
+    }
+
+    #[test]
+    fn discard_old_descarta_los_mas_viejos() {
+        let mut prueba = prueba(StreamConfig {
+            max_msgs: 3,
+            ..config("viejos")
+        });
+
+        for i in 1..=5 {
+            assert_eq!(prueba.publicar("viejos.a", b"x").unwrap().seq, i);
+        }
+
+        let estado = prueba.stream.estado(None);
+        assert_eq!(estado.messages, 3);
+        assert_eq!(estado.first_seq, 3);
+        assert_eq!(estado.last_seq, 5);
+    }
+
+    #[test]
+    fn discard_new_rechaza_los_nuevos() {
+        let mut prueba = prueba(StreamConfig {
+            max_msgs: 2,
+            max_bytes: 1000,
+            discard: DiscardPolicy::New,
+            ..config("nuevos")
+        });
+
+        prueba.publicar("nuevos.a", b"x").unwrap();
+        prueba.publicar("nuevos.a", b"x").unwrap();
+        assert_eq!(
+            prueba.publicar("nuevos.a", b"x"),
+            Err(JSError::maximo_mensajes_excedido())
+        );
+
+        prueba.stream.config.max_msgs = 0;
+        assert_eq!(
+            prueba.publicar("nuevos.a", &[0; 1000]),
+            Err(JSError::maximo_bytes_excedido())
+        );
+
+        let estado = prueba.stream.estado(None);
+        assert_eq!(estado.messages, 2);
+        assert_eq!(estado.last_seq, 2);
+    }
+
+    #[test]
+    fn max_age_expira_mensajes() {
+        let mut prueba = prueba(StreamConfig {
+            max_age: Duration::from_millis(50),
+            ..config("edad")
+        });
+
+        prueba.publicar("edad.a", b"x").unwrap();
+        prueba.publicar("edad.a", b"x").unwrap();
+        std::thread::sleep(Duration::from_millis(60));
+        prueba.publicar("edad.a", b"x").unwrap();
+
+        prueba.stream.ultima_expiracion = Instant::now() - INTERVALO_EXPIRACION;
+        prueba.tick();
+
+        let estado = prueba.stream.estado(None);
+        assert_eq!(estado.messages, 1);
+        assert_eq!(estado.first_seq, 3);
+    }
+
+    #[test]
+    fn max_msg_size_rechaza_mensajes_grandes() {
+        let mut prueba = prueba(StreamConfig {
+            max_msg_size: 4,
+            ..config("tamanio")
+        });
+
+        assert!(prueba.publicar("tamanio.a", b"1234").is_ok());
+        assert_eq!(
+            prueba.publicar("tamanio.a", b"12345"),
+            Err(JSError::mensaje_excede_maximo())
+        );
+        assert_eq!(prueba.stream.estado(None).messages, 1);
+    }
+
+    #[test]
+    fn max_msg_size_cuenta_los_headers() {
+        let mut prueba = prueba(StreamConfig {
+            max_msg_size: 32,
+            ..config("tamanio")
+        });
+        let mut headers = Headers::new();
+        headers.insertar("Relleno", &"x".repeat(32));
+
+        assert!(prueba.publicar("tamanio.a", b"1234").is_ok());
+        assert_eq!(
+            prueba.publicar_con_headers("tamanio.a", &headers, b"1234"),
+            Err(JSError::mensaje_excede_maximo())
+        );
+        assert_eq!(prueba.stream.estado(None).messages, 1);
+    }
+
+    #[test]
+    fn max_consumers_limita_los_consumers() {
+        let mut prueba = prueba(StreamConfig {
+            max_consumers: 1,
+            ..config("consumers")
+        });
+
+        let creado = prueba.crear_consumer("uno");
+        assert!(JSErrorRespuesta::from_json(&creado[0]).is_err());
+
+        let rechazado = prueba.crear_consumer("dos");
+        assert_eq!(
+            JSErrorRespuesta::from_json(&rechazado[0]).unwrap().error,
+            JSError::maximo_consumers_alcanzado()
+        );
+        assert_eq!(prueba.stream.estado(None).consumer_count, 1);
+    }
+
+    #[test]
+    fn estado_con_mensajes_eliminados_y_filtro() {
+        let mut prueba = prueba(config("estado"));
+
+        for topico in [
+            "estado.a.1",
+            "estado.a.2",
+            "estado.b",
+            "estado.a.1",
+            "estado.c",
+        ] {
+            prueba.publicar(topico, b"x").unwrap();
+        }
+        prueba.eliminar_mensaje(1);
+        prueba.eliminar_mensaje(3);
+
+        let estado = prueba.info(Some("estado.a.*")).state;
+        assert_eq!(estado.messages, 3);
+        assert_eq!(estado.first_seq, 2);
+        assert_eq!(estado.last_seq, 5);
+        assert_eq!(estado.num_deleted, 1);
+        assert_eq!(estado.num_subjects, 3);
+        assert_eq!(
+            estado.subjects,
+            Some(
+                [("estado.a.1".to_string(), 1), ("estado.a.2".to_string(), 1)]
+                    .into_iter()
+                    .collect()
+            )
+        );
+        assert_ne!(estado.first_ts, "0001-01-01T00:00:00Z");
+        assert!(estado.first_ts <= estado.last_ts);
+
+        // Sin filtro no se detallan los tópicos
+        assert_eq!(prueba.info(None).state.subjects, None);
+    }
+
+    #[test]
+    fn estado_de_stream_vacio() {
+        let mut prueba = prueba(config("vacio"));
+
+        prueba.publicar("vacio.a", b"x").unwrap();
+        prueba.eliminar_mensaje(1);
+
+        let estado = prueba.info(Some(">")).state;
+        assert_eq!(estado.messages, 0);
+        assert_eq!(estado.first_seq, 2);
+        assert_eq!(estado.last_seq, 1);
+        assert_eq!(estado.num_deleted, 0);
+        assert_eq!(estado.num_subjects, 0);
+        assert_eq!(estado.subjects, Some(Default::default()));
+        assert_eq!(estado.first_ts, "0001-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn actualizar_cambia_topicos_y_limites() {
+        let mut prueba = prueba(config("actualizado"));
+        for _ in 0..5 {
+            prueba.publicar("actualizado.a", b"x").unwrap();
+        }
+
+        let nueva = StreamConfig {
+            subjects: vec!["actualizado.>".to_string(), "otro.*".to_string()],
+            max_msgs: 2,
+            ..config("actualizado")
+        };
+        let contexto = prueba.actualizar(&nueva);
+        let respuestas = respuestas(&contexto);
+        assert!(JSErrorRespuesta::from_json(&respuestas[0]).is_err());
+
+        let suscritos = contexto
+            .suscripciones()
+            .iter()
+            .map(|suscripcion| suscripcion.id().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(suscritos, vec!["mensaje|otro.*".to_string()]);
+        assert!(contexto.desuscripciones().is_empty());
+
+        assert_eq!(prueba.stream.config, nueva);
+        let estado = prueba.stream.estado(None);
+        assert_eq!(estado.messages, 2);
+        assert_eq!(estado.first_seq, 4);
+
+        let sin_anterior = StreamConfig {
+            subjects: vec!["otro.*".to_string()],
+            ..nueva
+        };
+        let contexto = prueba.actualizar(&sin_anterior);
+        assert!(contexto.suscripciones().is_empty());
+        assert_eq!(
+            contexto.desuscripciones(),
+            vec!["mensaje|actualizado.>".to_string()]
+        );
+    }
+
+    #[test]
+    fn actualizar_rechaza_campos_inmutables() {
+        let mut prueba = prueba(config("inmutable"));
+        let original = prueba.stream.config.clone();
+
+        let cambios = [
+            (
+                StreamConfig {
+                    storage: StorageType::File,
+                    ..original.clone()
+                },
+                10052,
+            ),
+            (
+                StreamConfig {
+                    mirror: Some(StreamSource::new("otro")),
+                    subjects: vec![],
+                    ..original.clone()
+                },
+                10055,
+            ),
+            (
+                StreamConfig {
+                    subjects: vec!["inmutable.>.a".to_string()],
+                    ..original.clone()
+                },
+                10003,
+            ),
+        ];
+
+        for (config, codigo) in cambios {
+            let respuestas = respuestas(&prueba.actualizar(&config));
+            let error = JSErrorRespuesta::from_json(&respuestas[0]).unwrap().error;
+            assert_eq!(error.err_code, codigo, "{:?}", config);
+            assert_eq!(prueba.stream.config, original);
+        }
+
+        // Tampoco se puede cambiar el nombre
+        let respuestas = prueba.enviar(
+            "actualizar",
+            "$JS.API.STREAM.UPDATE.inmutable",
+            config("otro_nombre").to_json().unwrap().as_bytes(),
+        );
+        assert_eq!(
+            JSErrorRespuesta::from_json(&respuestas[0]).unwrap().error,
+            JSError::nombre_stream_no_coincide()
+        );
+    }
+
+    #[test]
+    fn purgar_por_filtro_keep_y_seq() {
+        let mut prueba = prueba(config("purga"));
+        let publicar = |prueba: &mut Prueba| {
+            for topico in ["purga.a", "purga.b", "purga.a", "purga.b", "purga.a"] {
+                prueba.publicar(topico, b"x").unwrap();
+            }
+        };
+
+        publicar(&mut prueba);
+        let filtro = JSPeticionPurgarStream {
+            filter: Some("purga.a".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(prueba.purgar(&filtro), 3);
+        assert_eq!(prueba.topicos(), vec!["purga.b", "purga.b"]);
+
+        prueba.purgar(&JSPeticionPurgarStream::default());
+        publicar(&mut prueba);
+        let conservar = JSPeticionPurgarStream {
+            filter: Some("purga.a".to_string()),
+            keep: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(prueba.purgar(&conservar), 2);
+        assert_eq!(prueba.topicos(), vec!["purga.b", "purga.b", "purga.a"]);
+
+        prueba.purgar(&JSPeticionPurgarStream::default());
+        publicar(&mut prueba);
+        let hasta = JSPeticionPurgarStream {
+            seq: Some(14),
+            ..Default::default()
+        };
+        assert_eq!(prueba.purgar(&hasta), 3);
+        assert_eq!(prueba.stream.estado(None).first_seq, 14);
+        assert_eq!(prueba.topicos(), vec!["purga.b", "purga.a"]);
+    }
+
+    #[test]
+    fn pub_ack_informa_stream_y_secuencia() {
+        let mut prueba = prueba(config("acks"));
+
+        for secuencia in 1..=3 {
+            let ack = prueba.publicar("acks.a", b"x").unwrap();
+            assert_eq!(ack.stream, "acks");
+            assert_eq!(ack.seq, secuencia);
+            assert!(!ack.duplicate);
+        }
+
+        // Sin reply_to se guarda pero no se responde
+        prueba
+            .stream
+            .escribir_publicacion_mensaje(&PublicacionMensaje::new(
+                "mensaje|acks.a".to_string(),
+                "acks.a".to_string(),
+                b"x".to_vec(),
+                None,
+                None,
+            ));
+        assert!(prueba.tick().is_empty());
+        assert_eq!(prueba.stream.estado(None).last_seq, 4);
+    }
+
+    #[test]
+    fn publicacion_rechazada_responde_error() {
+        let mut prueba = prueba(StreamConfig {
+            max_bytes: 10,
+            discard: DiscardPolicy::New,
+            ..config("rechazo")
+        });
+
+        let respuestas = prueba.enviar("mensaje|rechazo.a", "rechazo.a", &[0; 100]);
+        assert_eq!(respuestas.len(), 1);
+        let respuesta = JSErrorRespuesta::from_json(&respuestas[0]).unwrap();
+        assert_eq!(
+            respuesta.r#type,
+            "io.nats.jetstream.api.v1.pub_ack_response"
+        );
+        assert_eq!(respuesta.error, JSError::maximo_bytes_excedido());
+        assert!(JSPubAck::from_json(&respuestas[0]).is_err());
+
+        let estado = prueba.stream.estado(None);
+        assert_eq!(estado.messages, 0);
+        assert_eq!(estado.last_seq, 0);
+    }
+
+    #[test]
+    fn obtener_directo_requiere_allow_direct() {
+        let mut prueba = prueba(config("directo"));
+        prueba.publicar("directo.a", b"hola").unwrap();
+
+        let obtener = |prueba: &mut Prueba| {
+            let contexto = prueba.enviar_con_contexto(
+                "obtener_directo",
+                "$JS.API.DIRECT.GET.directo.directo.a",
+                b"",
+            );
+            let respuesta = contexto
+                .publicaciones()
+                .into_iter()
+                .find(|publicacion| publicacion.topico == RESPUESTA)
+                .unwrap();
+            let headers = Headers::parsear(&respuesta.header.unwrap()).unwrap();
+            (headers, respuesta.payload)
+        };
+
+        let (headers, payload) = obtener(&mut prueba);
+        assert_eq!(headers.estado, Some(503));
+        assert!(payload.is_empty());
+
+        prueba.stream.config.allow_direct = true;
+        let (headers, payload) = obtener(&mut prueba);
+        assert_eq!(headers.estado, None);
+        assert_eq!(headers.obtener("Nats-Sequence"), Some("1"));
+        assert_eq!(payload, b"hola");
+    }
+
+    #[test]
+    fn topico_invalido_no_impide_preparar_el_stream() {
+        let mut prueba = prueba(StreamConfig {
+            subjects: vec!["invalido.>.a".to_string(), "valido.a".to_string()],
+            ..config("invalido")
+        });
+
+        assert!(prueba.publicar("valido.a", b"x").is_ok());
+    }
+
+    #[test]
+    fn intervalo_tick_hasta_que_expira_el_primer_mensaje() {
+        let prueba_sin_limite = prueba(config("sin_edad"));
+        assert_eq!(prueba_sin_limite.stream.intervalo_tick(), None);
+
+        let mut prueba = prueba(StreamConfig {
+            max_age: Duration::from_secs(5),
+            ..config("edad")
+        });
+        assert_eq!(prueba.stream.intervalo_tick(), None);
+
+        prueba.publicar("edad.a", b"x").unwrap();
+        let intervalo = prueba.stream.intervalo_tick().unwrap();
+        assert!(intervalo <= Duration::from_secs(5));
+        assert!(intervalo > Duration::from_secs(4));
+    }
+}
@@ -1,5 +1,6 @@
 pub mod conexion;
 pub mod cuenta;
+pub mod estadisticas;
 pub mod hilo;
 pub mod jetstream;
 pub mod publicacion;
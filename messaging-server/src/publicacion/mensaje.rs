@@ -52,12 +52,12 @@ impl PublicacionMensaje {
         }
 
         if let Some(header) = &self.header {
+            // El total incluye los bytes de los headers
             bytes.extend_from_slice(header.len().to_string().as_bytes());
             bytes.extend_from_slice(b" ");
-            bytes.extend_from_slice(self.payload.len().to_string().as_bytes());
+            bytes.extend_from_slice((header.len() + self.payload.len()).to_string().as_bytes());
             bytes.extend_from_slice(b"\r\n");
             bytes.extend_from_slice(header);
-            bytes.extend_from_slice(b"\r\n");
         } else {
             bytes.extend_from_slice(self.payload.len().to_string().as_bytes());
             bytes.extend_from_slice(b"\r\n");
@@ -1,5 +1,7 @@
 use std::fmt::Debug;
 
+use lib::parseador::mensaje::formatear_payload_debug;
+
 use self::mensaje::PublicacionMensaje;
 
 pub mod mensaje;
@@ -11,6 +13,9 @@ pub struct Publicacion {
     pub payload: Vec<u8>,          // El mensaje que se va a enviar
     pub header: Option<Vec<u8>>,   // EL header del mensaje que se va a enviar
     pub replay_to: Option<String>, // Campo que tiene nats
+    /// Tópico que ve el suscriptor, si no es al que se envía. JetStream entrega
+    /// los mensajes al tópico del consumer con el tópico en que se publicaron
+    pub topico_original: Option<String>,
 }
 
 impl Publicacion {
@@ -25,13 +30,21 @@ impl Publicacion {
             payload,
             replay_to,
             header,
+            topico_original: None,
         }
     }
 
+    pub fn con_topico_original(mut self, topico_original: String) -> Self {
+        self.topico_original = Some(topico_original);
+        self
+    }
+
     pub fn mensaje(&self, sid: String) -> PublicacionMensaje {
         PublicacionMensaje::new(
             sid,
-            self.topico.clone(),
+            self.topico_original
+                .clone()
+                .unwrap_or_else(|| self.topico.clone()),
             self.payload.clone(),
             self.header.clone(),
             self.replay_to.clone(),
@@ -41,12 +54,7 @@ impl Publicacion {
 
 impl Debug for Publicacion {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let contenido = String::from_utf8_lossy(&self.payload);
-        let contenido_max_100_chars = if contenido.len() > 100 {
-            format!("{}...", &contenido[..100])
-        } else {
-            contenido.to_string()
-        };
+        let contenido_max_100_chars = formatear_payload_debug(&self.payload);
 
         f.debug_struct("Publicacion")
             .field("topico", &self.topico)
@@ -2,31 +2,42 @@ use std::{
     collections::HashMap,
     io,
     net::TcpListener,
+    path::PathBuf,
     sync::{
-        mpsc::{self, channel, Sender},
+        mpsc::{self, channel, Receiver, Sender},
         Arc,
     },
     thread::{self, JoinHandle},
 };
 
 use lib::{configuracion::Configuracion, stream::Stream};
+use mio::Poll;
 use native_tls::{HandshakeError, Identity, TlsAcceptor};
 
 use crate::{
-    conexion::{id::IdConexion, r#trait::Conexion},
+    conexion::{id::IdConexion, limites::LimitesConexion, r#trait::Conexion},
     cuenta::Cuenta,
-    hilo::id::IdHilo,
-    jetstream::admin::JestStreamAdminConexion,
+    estadisticas::{conexion::ConexionEstadisticas, Estadisticas},
+    hilo::{despertador::Despertador, emisor::Emisor, id::IdHilo},
+    jetstream::{admin::JestStreamAdminConexion, cuenta::LimitesCuenta},
     registrador::Registrador,
 };
 
 use super::{conexion::ConexionDeCliente, hilo::Hilo};
 
 type InfoHilo = (
-    Sender<(IdConexion, Box<dyn Conexion + Send>)>,
+    Emisor<(IdConexion, Box<dyn Conexion + Send>)>,
     JoinHandle<()>,
 );
 
+/// Lo que recibe el servidor para asignar a un hilo
+enum NuevaConexion {
+    /// Un cliente que se conectó a alguno de los puertos
+    Cliente(Box<dyn Stream + Send>),
+    /// Una conexión interna, como las de JetStream
+    Interna(Box<dyn Conexion + Send>),
+}
+
 pub struct Servidor {
     pub configuracion: Configuracion,
     hilos: Vec<InfoHilo>,
@@ -34,6 +45,8 @@ pub struct Servidor {
     ultimo_id_conexion: IdConexion, // Cada id tiene que ser único por cada conexion. Se incrementa cada vez que se crea una nueva conexion
     registrador: Registrador,
     pub cuentas: Option<Arc<Vec<Cuenta>>>,
+    /// Contadores compartidos por todas las conexiones
+    pub estadisticas: Arc<Estadisticas>,
 }
 
 impl Servidor {
@@ -54,18 +67,26 @@ impl Servidor {
 
         let cantidad = configuracion.obtener::<usize>("hilos").unwrap_or(4);
 
-        // Creamos los canales para enviar y recibir instrucciones entre los hilos
+        // Creamos los canales para enviar y recibir instrucciones entre los hilos.
+        // Cada hilo espera eventos con su poll, y los emisores lo despiertan
+        let mut polls = Vec::new();
         for _ in 0..cantidad {
+            let poll = Poll::new().expect("No se pudo crear el poll del hilo");
+            let despertador = Despertador::new(poll.registry())
+                .expect("No se pudo crear el despertador del hilo");
             let (tx, rx) = mpsc::channel();
-            canales_enviar.push(tx);
+            canales_enviar.push(Emisor::new(tx, despertador.clone()));
             canales_recibir.push(rx);
+            polls.push((poll, despertador));
         }
 
         // Para cada punta receptora en canales_recibir, se insertan las
         // puntas emisoras de los canales en canales_a_enviar_mensajes que
         // tiene las puntas emisoras a cada hilo para enviar instrucciones
         // a ellos
-        for (indice_hilo, rx) in canales_recibir.drain(..).enumerate() {
+        for (indice_hilo, (rx, (poll, despertador))) in
+            canales_recibir.drain(..).zip(polls).enumerate()
+        {
             // HashMap con las puntas emisoras a cada hilo para enviar instrucciones a los mismos
             let mut canales_a_enviar_mensajes = HashMap::new();
 
@@ -86,8 +107,11 @@ impl Servidor {
             // Establecemos el hilo actual para el registrador
             registrador.establecer_hilo(id_hilo);
             // Creamos el hilo
+            let tx_conexiones = Emisor::new(tx_conexiones, despertador.clone());
             let hilo = Hilo::new(
                 id_hilo,
+                poll,
+                despertador,
                 rx_conexiones,
                 canales_a_enviar_mensajes,
                 rx,
@@ -107,6 +131,7 @@ impl Servidor {
             ultimo_id_conexion: 0,
             registrador,
             cuentas: None,
+            estadisticas: Arc::new(Estadisticas::new()),
         }
     }
 
@@ -136,11 +161,9 @@ impl Servidor {
             let cert = std::fs::read(c)?;
             let key = std::fs::read(k)?;
 
-            let identity = Identity::from_pkcs8(&cert, &key)
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            let identity = Identity::from_pkcs8(&cert, &key).map_err(io::Error::other)?;
 
-            let acceptor =
-                TlsAcceptor::new(identity).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            let acceptor = TlsAcceptor::new(identity).map_err(io::Error::other)?;
 
             return Ok(Some(acceptor));
         }
@@ -164,6 +187,15 @@ impl Servidor {
             .unwrap_or(8222)
     }
 
+    /// Directorio donde se guardan los datos de los streams de JetStream
+    pub fn directorio_jetstream(&self) -> PathBuf {
+        PathBuf::from(
+            self.configuracion
+                .obtener::<String>("directorio_jetstream")
+                .unwrap_or("jetstream".to_string()),
+        )
+    }
+
     pub fn escuchar_sin_tls(&self, tx: Sender<Box<dyn Stream + Send>>) -> io::Result<()> {
         let listener = TcpListener::bind(format!("{}:{}", self.direccion(), self.puerto()))?;
 
@@ -252,6 +284,15 @@ impl Servidor {
             id_conexion,
             tx_conexiones.clone(),
             self.registrador.clone(),
+            self.directorio_jetstream(),
+            LimitesCuenta::desde_configuracion(&self.configuracion),
+        )));
+
+        let id_conexion = self.nuevo_id_conexion();
+        let _ = tx_conexiones.send(Box::new(ConexionEstadisticas::new(
+            id_conexion,
+            self.estadisticas.clone(),
+            self.registrador.clone(),
         )));
 
         let (tx, rx) = mpsc::channel();
@@ -262,51 +303,66 @@ impl Servidor {
         self.escuchar_con_tls(tx)
             .expect("No se pudo iniciar el servidor con tls");
 
-        loop {
-            while let Ok(stream) = rx.try_recv() {
-                // Creamos una copia del logger para la nueva conexion
-                let mut registrador_para_nueva_conexion = self.registrador.clone();
-                // Establecemos el hilo actual para la nueva conexion
-                registrador_para_nueva_conexion.establecer_hilo(self.proximo_id_hilo as IdHilo);
-
-                // Generamos un nuevo id único para la nueva conexión
-                let id_conexion = self.nuevo_id_conexion();
-
-                let conexion = ConexionDeCliente::new(
-                    id_conexion,
-                    stream,
-                    registrador_para_nueva_conexion,
-                    self.cuentas.clone(),
-                );
-
-                let (tx, _) = &self.hilos[self.proximo_id_hilo];
-                match tx.send((id_conexion, Box::new(conexion))) {
-                    // Envio la conexion al hilo
-                    Ok(_) => {
-                        self.proximo_id_hilo = (self.proximo_id_hilo + 1) % self.hilos.len();
-                    }
-                    Err(e) => {
-                        panic!("Error: {}", e);
-                    }
+        let limites_conexion = LimitesConexion::desde_configuracion(&self.configuracion);
+
+        // Se juntan los dos canales en uno para poder bloquear el hilo esperando
+        // cualquiera de los dos
+        let (tx_nuevas, rx_nuevas) = mpsc::channel();
+        reenviar(rx, tx_nuevas.clone(), NuevaConexion::Cliente);
+        reenviar(rx_conexiones, tx_nuevas, NuevaConexion::Interna);
+
+        for nueva in rx_nuevas {
+            let conexion: Box<dyn Conexion + Send> = match nueva {
+                NuevaConexion::Cliente(stream) => {
+                    // Creamos una copia del logger para la nueva conexion
+                    let mut registrador_para_nueva_conexion = self.registrador.clone();
+                    // Establecemos el hilo actual para la nueva conexion
+                    registrador_para_nueva_conexion.establecer_hilo(self.proximo_id_hilo as IdHilo);
+
+                    // Generamos un nuevo id único para la nueva conexión
+                    let id_conexion = self.nuevo_id_conexion();
+
+                    Box::new(ConexionDeCliente::new(
+                        id_conexion,
+                        stream,
+                        registrador_para_nueva_conexion,
+                        self.cuentas.clone(),
+                        limites_conexion,
+                        self.estadisticas.clone(),
+                    ))
                 }
-            }
-
-            while let Ok(mut conexion) = rx_conexiones.try_recv() {
-                let id_conexion = self.nuevo_id_conexion();
-
-                let (tx, _) = &self.hilos[self.proximo_id_hilo];
-
-                conexion.setear_id_conexion(id_conexion);
+                NuevaConexion::Interna(mut conexion) => {
+                    let id_conexion = self.nuevo_id_conexion();
+                    conexion.setear_id_conexion(id_conexion);
+                    conexion
+                }
+            };
 
-                match tx.send((conexion.obtener_id(), conexion)) {
-                    Ok(_) => {
-                        self.proximo_id_hilo = (self.proximo_id_hilo + 1) % self.hilos.len();
-                    }
-                    Err(e) => {
-                        panic!("Error: {}", e);
-                    }
+            let (tx, _) = &self.hilos[self.proximo_id_hilo];
+            match tx.send((conexion.obtener_id(), conexion)) {
+                // Envio la conexion al hilo
+                Ok(_) => {
+                    self.proximo_id_hilo = (self.proximo_id_hilo + 1) % self.hilos.len();
+                }
+                Err(e) => {
+                    panic!("Error: {}", e);
                 }
             }
         }
     }
 }
+
+/// Reenvía en un thread aparte lo que llega por `rx` a `tx`, convertido con `convertir`
+fn reenviar<T: Send + 'static>(
+    rx: Receiver<T>,
+    tx: Sender<NuevaConexion>,
+    convertir: fn(T) -> NuevaConexion,
+) {
+    thread::spawn(move || {
+        for valor in rx {
+            if tx.send(convertir(valor)).is_err() {
+                break;
+            }
+        }
+    });
+}
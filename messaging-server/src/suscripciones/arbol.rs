@@ -0,0 +1,214 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    str::Split,
+};
+
+use super::topico::{Segmento, Topico};
+
+/// Índice de valores por patrón de tópico, organizado por segmentos. Buscar
+/// los valores de un tópico recorre solo las ramas que pueden coincidir, en
+/// lugar de probar cada patrón guardado
+#[derive(Debug)]
+pub struct ArbolTopicos<T> {
+    raiz: Nodo<T>,
+}
+
+#[derive(Debug)]
+struct Nodo<T> {
+    hijos: HashMap<String, Nodo<T>>,
+    asterisco: Option<Box<Nodo<T>>>,
+    /// Patrones que terminan exactamente en este nodo
+    valores: HashSet<T>,
+    /// Patrones que terminan en `>` después de este nodo
+    resto: HashSet<T>,
+}
+
+impl<T> Default for Nodo<T> {
+    fn default() -> Self {
+        Self {
+            hijos: HashMap::new(),
+            asterisco: None,
+            valores: HashSet::new(),
+            resto: HashSet::new(),
+        }
+    }
+}
+
+impl<T> Default for ArbolTopicos<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> ArbolTopicos<T> {
+    pub fn new() -> Self {
+        Self {
+            raiz: Nodo::default(),
+        }
+    }
+}
+
+impl<T: Eq + Hash> ArbolTopicos<T> {
+    pub fn insertar(&mut self, topico: &Topico, valor: T) {
+        let mut nodo = &mut self.raiz;
+
+        for segmento in topico.segmentos() {
+            nodo = match segmento {
+                Segmento::Texto(texto) => nodo.hijos.entry(texto.to_owned()).or_default(),
+                Segmento::Asteriso => nodo.asterisco.get_or_insert_with(Box::default),
+            };
+        }
+
+        if topico.acepta_resto() {
+            nodo.resto.insert(valor);
+        } else {
+            nodo.valores.insert(valor);
+        }
+    }
+
+    pub fn eliminar(&mut self, topico: &Topico, valor: &T) {
+        self.raiz
+            .eliminar(topico.segmentos(), topico.acepta_resto(), valor);
+    }
+
+    /// Valores de todos los patrones que coinciden con el tópico
+    pub fn buscar(&self, topico: &str) -> Vec<&T> {
+        let mut resultado = Vec::new();
+        self.raiz.buscar(topico.split('.'), &mut resultado);
+        resultado
+    }
+}
+
+impl<T: Eq + Hash> Nodo<T> {
+    fn buscar<'a>(&'a self, mut segmentos: Split<'_, char>, resultado: &mut Vec<&'a T>) {
+        // Igual que `Topico::test`, `>` también acepta que no queden segmentos
+        resultado.extend(&self.resto);
+
+        match segmentos.next() {
+            None => resultado.extend(&self.valores),
+            Some(segmento) => {
+                if let Some(hijo) = self.hijos.get(segmento) {
+                    hijo.buscar(segmentos.clone(), resultado);
+                }
+                if let Some(hijo) = &self.asterisco {
+                    hijo.buscar(segmentos, resultado);
+                }
+            }
+        }
+    }
+
+    /// Devuelve si el nodo quedó vacío y se puede podar
+    fn eliminar(&mut self, segmentos: &[Segmento], acepta_resto: bool, valor: &T) -> bool {
+        match segmentos.split_first() {
+            None if acepta_resto => {
+                self.resto.remove(valor);
+            }
+            None => {
+                self.valores.remove(valor);
+            }
+            Some((Segmento::Texto(texto), resto)) => {
+                if let Some(hijo) = self.hijos.get_mut(texto) {
+                    if hijo.eliminar(resto, acepta_resto, valor) {
+                        self.hijos.remove(texto);
+                    }
+                }
+            }
+            Some((Segmento::Asteriso, resto)) => {
+                if let Some(hijo) = &mut self.asterisco {
+                    if hijo.eliminar(resto, acepta_resto, valor) {
+                        self.asterisco = None;
+                    }
+                }
+            }
+        }
+
+        self.esta_vacio()
+    }
+
+    fn esta_vacio(&self) -> bool {
+        self.hijos.is_empty()
+            && self.asterisco.is_none()
+            && self.valores.is_empty()
+            && self.resto.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::suscripciones::topico::Topico;
+
+    use super::ArbolTopicos;
+
+    const PATRONES: [&str; 8] = ["a", "a.b", "a.*", "*.b", "a.>", ">", "a.*.c", "_INBOX.x.>"];
+
+    const TOPICOS: [&str; 8] = [
+        "a",
+        "b",
+        "a.b",
+        "a.c",
+        "c.b",
+        "a.b.c",
+        "a.b.d",
+        "_INBOX.x.1",
+    ];
+
+    fn arbol() -> ArbolTopicos<usize> {
+        let mut arbol = ArbolTopicos::new();
+        for (i, patron) in PATRONES.iter().enumerate() {
+            arbol.insertar(&Topico::new(patron.to_string()).unwrap(), i);
+        }
+        arbol
+    }
+
+    fn coincidencias(arbol: &ArbolTopicos<usize>, topico: &str) -> Vec<usize> {
+        let mut valores = arbol
+            .buscar(topico)
+            .into_iter()
+            .copied()
+            .collect::<Vec<_>>();
+        valores.sort();
+        valores
+    }
+
+    #[test]
+    fn coincide_con_topico_test() {
+        let arbol = arbol();
+
+        for topico in TOPICOS {
+            let esperados = PATRONES
+                .iter()
+                .enumerate()
+                .filter(|(_, patron)| Topico::new(patron.to_string()).unwrap().test(topico))
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+
+            assert_eq!(coincidencias(&arbol, topico), esperados, "{}", topico);
+        }
+    }
+
+    #[test]
+    fn eliminar_poda_ramas_vacias() {
+        let mut arbol = arbol();
+
+        for (i, patron) in PATRONES.iter().enumerate() {
+            arbol.eliminar(&Topico::new(patron.to_string()).unwrap(), &i);
+        }
+
+        for topico in TOPICOS {
+            assert!(arbol.buscar(topico).is_empty());
+        }
+        assert!(arbol.raiz.esta_vacio());
+    }
+
+    #[test]
+    fn eliminar_solo_el_valor_indicado() {
+        let mut arbol = arbol();
+        let topico = Topico::new("a.*".to_string()).unwrap();
+
+        arbol.insertar(&topico, 10);
+        arbol.eliminar(&topico, &2);
+
+        assert_eq!(coincidencias(&arbol, "a.c"), vec![4, 5, 10]);
+    }
+}
@@ -0,0 +1,78 @@
+use std::{collections::HashMap, sync::Arc};
+
+use super::{arbol::ArbolTopicos, id::IdSuscripcion, suscripcion::Suscripcion, topico::Topico};
+
+/// Cantidad de tópicos publicados cuyas coincidencias se recuerdan
+const MAXIMO_CACHE: usize = 1024;
+
+#[derive(Debug, Default)]
+pub struct Coincidencias {
+    pub suscripciones: Vec<Arc<Suscripcion>>,
+    pub grupos: Vec<IdSuscripcion>,
+}
+
+impl Coincidencias {
+    fn buscar(
+        &mut self,
+        topico: &str,
+        arbol_suscripciones: &ArbolTopicos<Arc<Suscripcion>>,
+        arbol_grupos: &ArbolTopicos<IdSuscripcion>,
+    ) {
+        self.suscripciones.clear();
+        self.suscripciones
+            .extend(arbol_suscripciones.buscar(topico).into_iter().cloned());
+        self.grupos.clear();
+        self.grupos
+            .extend(arbol_grupos.buscar(topico).into_iter().cloned());
+    }
+}
+
+/// Resultado de buscar en los árboles por tópico publicado
+#[derive(Debug, Default)]
+pub struct CacheTopicos {
+    entradas: HashMap<String, Coincidencias>,
+    /// Resultado de la última búsqueda que no entró en el cache
+    fuera_de_cache: Coincidencias,
+    /// Búsquedas que no entraron desde que se llenó el cache
+    desbordes: usize,
+}
+
+impl CacheTopicos {
+    pub fn obtener(
+        &mut self,
+        topico: &str,
+        arbol_suscripciones: &ArbolTopicos<Arc<Suscripcion>>,
+        arbol_grupos: &ArbolTopicos<IdSuscripcion>,
+    ) -> &Coincidencias {
+        if self.entradas.contains_key(topico) {
+            return &self.entradas[topico];
+        }
+
+        if self.entradas.len() < MAXIMO_CACHE {
+            let mut coincidencias = Coincidencias::default();
+            coincidencias.buscar(topico, arbol_suscripciones, arbol_grupos);
+            return self
+                .entradas
+                .entry(topico.to_string())
+                .or_insert(coincidencias);
+        }
+
+        // Con el cache lleno, descartar entradas para guardar cada tópico nuevo
+        // cuesta más que buscarlo en los árboles. Se vacía cada tanto para que
+        // entren los tópicos en los que se publica ahora
+        self.desbordes += 1;
+        if self.desbordes >= MAXIMO_CACHE {
+            self.entradas.clear();
+            self.desbordes = 0;
+        }
+
+        self.fuera_de_cache
+            .buscar(topico, arbol_suscripciones, arbol_grupos);
+        &self.fuera_de_cache
+    }
+
+    /// Descarta los tópicos publicados que coinciden con el patrón
+    pub fn invalidar(&mut self, topico: &Topico) {
+        self.entradas.retain(|publicado, _| !topico.test(publicado));
+    }
+}
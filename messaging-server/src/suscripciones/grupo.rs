@@ -36,12 +36,16 @@ impl Grupo {
         self.suscripciones.remove(suscripcion);
     }
 
+    pub fn esta_vacio(&self) -> bool {
+        self.suscripciones.is_empty()
+    }
+
     pub fn suscripcion_random(&self) -> Option<&Suscripcion> {
         if self.suscripciones.is_empty() {
             return None;
         }
 
         let index = thread_rng().gen_range(0..self.suscripciones.len());
-        return self.suscripciones.iter().nth(index);
+        self.suscripciones.iter().nth(index)
     }
 }
@@ -1,9 +1,17 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use crate::{conexion::id::IdConexion, hilo::id::IdHilo};
 
-use self::{grupo::Grupo, id::IdSuscripcion, suscripcion::Suscripcion};
+use self::{
+    arbol::ArbolTopicos, cache::CacheTopicos, grupo::Grupo, id::IdSuscripcion,
+    suscripcion::Suscripcion, topico::Topico,
+};
 
+pub mod arbol;
+pub mod cache;
 pub mod grupo;
 pub mod id;
 pub mod suscripcion;
@@ -13,6 +21,13 @@ pub mod topico;
 pub struct Suscripciones {
     suscripciones: HashSet<Suscripcion>,
     grupos: HashMap<IdSuscripcion, Grupo>,
+    /// Suscripciones que no son de grupo, indexadas por su tópico
+    arbol_suscripciones: ArbolTopicos<Arc<Suscripcion>>,
+    /// Ids de los grupos, indexados por el tópico del grupo
+    arbol_grupos: ArbolTopicos<IdSuscripcion>,
+    /// Resultado de buscar en los árboles por tópico publicado. Se invalidan las
+    /// entradas que coinciden con cada tópico que se suscribe o desuscribe
+    cache: CacheTopicos,
 }
 
 impl Default for Suscripciones {
@@ -26,6 +41,9 @@ impl Suscripciones {
         Self {
             suscripciones: HashSet::new(),
             grupos: HashMap::new(),
+            arbol_suscripciones: ArbolTopicos::new(),
+            arbol_grupos: ArbolTopicos::new(),
+            cache: CacheTopicos::default(),
         }
     }
 
@@ -35,62 +53,105 @@ impl Suscripciones {
     // insertando la suscripcion en las suscripciones del grupo.
     pub fn suscribir(&mut self, suscripcion: Suscripcion) {
         self.suscripciones.insert(suscripcion.clone());
+        self.invalidar_cache(suscripcion.topico());
 
         if let Some(id_grupo) = suscripcion.id_grupo() {
             self.suscribir_grupo(suscripcion.clone(), id_grupo);
+        } else {
+            let topico = suscripcion.topico().clone();
+            self.arbol_suscripciones
+                .insertar(&topico, Arc::new(suscripcion));
         }
     }
 
     // Por cada suscripciones individual, si
     pub fn desuscribir(&mut self, id_conexion: IdConexion, id_suscripcion: &IdSuscripcion) {
-        let mut desuscripciones_grupos = Vec::new();
+        let mut desuscripciones = Vec::new();
 
         self.suscripciones.retain(|suscripcion| {
             if *suscripcion.id_conexion() == id_conexion && suscripcion.id().eq(id_suscripcion) {
-                if let Some(id_grupo) = suscripcion.id_grupo() {
-                    desuscripciones_grupos.push((suscripcion.clone(), id_grupo.clone()));
-                }
+                desuscripciones.push(suscripcion.clone());
                 false
             } else {
                 true
             }
         });
 
-        for (suscripcion, id_grupo) in desuscripciones_grupos {
-            self.desuscribir_grupo(&suscripcion, &id_grupo);
+        for suscripcion in desuscripciones {
+            self.invalidar_cache(suscripcion.topico());
+
+            if let Some(id_grupo) = suscripcion.id_grupo() {
+                self.desuscribir_grupo(&suscripcion, id_grupo);
+            } else {
+                let suscripcion = Arc::new(suscripcion);
+                self.arbol_suscripciones
+                    .eliminar(suscripcion.topico(), &suscripcion);
+            }
         }
     }
 
+    pub fn suscripcion(
+        &self,
+        id_conexion: IdConexion,
+        id_suscripcion: &IdSuscripcion,
+    ) -> Option<&Suscripcion> {
+        self.suscripciones.iter().find(|suscripcion| {
+            *suscripcion.id_conexion() == id_conexion && suscripcion.id().eq(id_suscripcion)
+        })
+    }
+
     fn suscribir_grupo(&mut self, suscripcion: Suscripcion, id_grupo: &IdSuscripcion) {
-        let grupo = self.grupos.entry(id_grupo.to_owned()).or_insert(Grupo::new(
-            id_grupo.to_owned(),
-            suscripcion.topico().clone(),
-        ));
+        if !self.grupos.contains_key(id_grupo) {
+            let topico = suscripcion.topico().clone();
+            self.arbol_grupos.insertar(&topico, id_grupo.to_owned());
+            self.grupos
+                .insert(id_grupo.to_owned(), Grupo::new(id_grupo.to_owned(), topico));
+        }
 
-        grupo.suscribir(suscripcion);
+        if let Some(grupo) = self.grupos.get_mut(id_grupo) {
+            grupo.suscribir(suscripcion);
+        }
     }
 
+    // Cuando el grupo se queda sin suscripciones se elimina, invalidando también
+    // los tópicos de su patrón, que puede ser distinto al de la suscripción
     fn desuscribir_grupo(&mut self, suscripcion: &Suscripcion, id_grupo: &IdSuscripcion) {
-        if let Some(grupo) = self.grupos.get_mut(id_grupo) {
-            grupo.desuscribir(suscripcion);
+        let Some(grupo) = self.grupos.get_mut(id_grupo) else {
+            return;
+        };
+
+        grupo.desuscribir(suscripcion);
+        if !grupo.esta_vacio() {
+            return;
+        }
+
+        if let Some(grupo) = self.grupos.remove(id_grupo) {
+            self.arbol_grupos.eliminar(grupo.topico(), id_grupo);
+            self.invalidar_cache(grupo.topico());
         }
     }
 
-    pub fn suscripciones_topico(&self, topico: &str) -> Vec<&Suscripcion> {
-        self.suscripciones
-            .iter()
-            .filter(|suscripcion| suscripcion.topico().test(topico) && !suscripcion.es_grupo())
-            .collect()
+    fn invalidar_cache(&mut self, topico: &Topico) {
+        self.cache.invalidar(topico);
     }
 
-    pub fn grupos_topico(&self, topico: &str) -> Vec<&Grupo> {
-        self.grupos
-            .values()
-            .filter(|grupo| grupo.topico().test(topico))
+    pub fn suscripciones_topico(&mut self, topico: &str) -> &[Arc<Suscripcion>] {
+        &self
+            .cache
+            .obtener(topico, &self.arbol_suscripciones, &self.arbol_grupos)
+            .suscripciones
+    }
+
+    pub fn grupos_topico(&mut self, topico: &str) -> Vec<&Grupo> {
+        self.cache
+            .obtener(topico, &self.arbol_suscripciones, &self.arbol_grupos)
+            .grupos
+            .iter()
+            .filter_map(|id_grupo| self.grupos.get(id_grupo))
             .collect()
     }
 
-    pub fn hilos_suscriptos_topico(&self, topico: &str) -> HashSet<IdHilo> {
+    pub fn hilos_suscriptos_topico(&mut self, topico: &str) -> HashSet<IdHilo> {
         let mut ids_hilos = HashSet::new();
 
         for suscripcion in self.suscripciones_topico(topico) {
@@ -107,3 +168,72 @@ impl Suscripciones {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{suscripcion::Suscripcion, topico::Topico, Suscripciones};
+
+    fn suscripcion(id_conexion: u64, topico: &str, grupo: Option<&str>) -> Suscripcion {
+        Suscripcion::new(
+            0,
+            id_conexion,
+            Topico::new(topico.to_string()).unwrap(),
+            "1".to_string(),
+            grupo.map(str::to_string),
+        )
+    }
+
+    fn conexiones_topico(suscripciones: &mut Suscripciones, topico: &str) -> Vec<u64> {
+        let mut conexiones = suscripciones
+            .suscripciones_topico(topico)
+            .iter()
+            .map(|suscripcion| *suscripcion.id_conexion())
+            .collect::<Vec<_>>();
+        conexiones.sort();
+        conexiones
+    }
+
+    #[test]
+    fn suscribir_invalida_topicos_cacheados() {
+        let mut suscripciones = Suscripciones::new();
+        suscripciones.suscribir(suscripcion(1, "a.b", None));
+
+        assert_eq!(conexiones_topico(&mut suscripciones, "a.b"), vec![1]);
+        assert!(conexiones_topico(&mut suscripciones, "a.c").is_empty());
+
+        suscripciones.suscribir(suscripcion(2, "a.*", None));
+
+        assert_eq!(conexiones_topico(&mut suscripciones, "a.b"), vec![1, 2]);
+        assert_eq!(conexiones_topico(&mut suscripciones, "a.c"), vec![2]);
+    }
+
+    #[test]
+    fn desuscribir_invalida_topicos_cacheados() {
+        let mut suscripciones = Suscripciones::new();
+        suscripciones.suscribir(suscripcion(1, "a.>", None));
+        suscripciones.suscribir(suscripcion(2, "a.b", None));
+
+        assert_eq!(conexiones_topico(&mut suscripciones, "a.b"), vec![1, 2]);
+
+        suscripciones.desuscribir(1, &"1".to_string());
+
+        assert_eq!(conexiones_topico(&mut suscripciones, "a.b"), vec![2]);
+        assert!(suscripciones.hilos_suscriptos_topico("a.c").is_empty());
+    }
+
+    #[test]
+    fn grupo_se_elimina_sin_suscripciones() {
+        let mut suscripciones = Suscripciones::new();
+        suscripciones.suscribir(suscripcion(1, "a.*", Some("g")));
+        suscripciones.suscribir(suscripcion(2, "a.*", Some("g")));
+
+        assert!(suscripciones.suscripciones_topico("a.b").is_empty());
+        assert_eq!(suscripciones.grupos_topico("a.b").len(), 1);
+
+        suscripciones.desuscribir(1, &"1".to_string());
+        assert_eq!(suscripciones.grupos_topico("a.b").len(), 1);
+
+        suscripciones.desuscribir(2, &"1".to_string());
+        assert!(suscripciones.grupos_topico("a.b").is_empty());
+    }
+}
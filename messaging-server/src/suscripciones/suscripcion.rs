@@ -1,14 +1,33 @@
+use std::{
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
 use crate::{conexion::id::IdConexion, hilo::id::IdHilo};
 
 use super::{id::IdSuscripcion, topico::Topico};
 
-#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+#[derive(Debug, Clone)]
 pub struct Suscripcion {
     id_hilo: IdHilo,
     id_cliente: IdConexion,
     id: IdSuscripcion,
     topico: Topico,
     id_grupo: Option<IdSuscripcion>,
+    /// Compartido por las copias de la suscripción que tiene cada hilo
+    entregas: Arc<Entregas>,
+}
+
+/// Mensajes entregados a una suscripción y cuántos puede recibir en total antes
+/// de eliminarse (`UNSUB <sid> <max_msgs>`)
+#[derive(Debug, Default)]
+struct Entregas {
+    entregados: AtomicU64,
+    /// Cero es sin límite
+    maximo: AtomicU64,
 }
 
 impl Suscripcion {
@@ -25,6 +44,7 @@ impl Suscripcion {
             topico,
             id,
             id_grupo: grupo,
+            entregas: Arc::new(Entregas::default()),
         }
     }
 
@@ -51,4 +71,91 @@ impl Suscripcion {
     pub fn es_grupo(&self) -> bool {
         self.id_grupo.is_some()
     }
+
+    /// Cuántos mensajes más puede recibir antes de eliminarse, si tiene límite
+    pub fn entregas_restantes(&self) -> Option<u64> {
+        let maximo = self.entregas.maximo.load(Ordering::Relaxed);
+        let entregados = self.entregas.entregados.load(Ordering::Relaxed);
+        (maximo > 0).then(|| maximo.saturating_sub(entregados))
+    }
+
+    /// Limita la cantidad total de mensajes que recibe la suscripción, contando
+    /// los que ya recibió. Devuelve si ya llegó al límite y hay que eliminarla
+    pub fn limitar_entregas(&self, maximo: u64) -> bool {
+        self.entregas.maximo.store(maximo, Ordering::Relaxed);
+        self.entregas_restantes() == Some(0)
+    }
+
+    /// Anota que se le entregó un mensaje. Devuelve si con este llegó al límite
+    /// y hay que eliminarla
+    pub fn registrar_entrega(&self) -> bool {
+        self.entregas.entregados.fetch_add(1, Ordering::Relaxed);
+        self.entregas_restantes() == Some(0)
+    }
+}
+
+// Las entregas cambian mientras la suscripción está guardada, así que no forman
+// parte de su identidad
+impl PartialEq for Suscripcion {
+    fn eq(&self, otra: &Self) -> bool {
+        self.id_hilo == otra.id_hilo
+            && self.id_cliente == otra.id_cliente
+            && self.id == otra.id
+            && self.topico == otra.topico
+            && self.id_grupo == otra.id_grupo
+    }
+}
+
+impl Eq for Suscripcion {}
+
+impl Hash for Suscripcion {
+    fn hash<H: Hasher>(&self, estado: &mut H) {
+        self.id_hilo.hash(estado);
+        self.id_cliente.hash(estado);
+        self.id.hash(estado);
+        self.topico.hash(estado);
+        self.id_grupo.hash(estado);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::suscripciones::topico::Topico;
+
+    use super::Suscripcion;
+
+    fn suscripcion() -> Suscripcion {
+        Suscripcion::new(
+            0,
+            1,
+            Topico::new("a".to_string()).unwrap(),
+            "1".to_string(),
+            None,
+        )
+    }
+
+    #[test]
+    fn limite_de_entregas() {
+        let suscripcion = suscripcion();
+        let copia = suscripcion.clone();
+        assert_eq!(suscripcion.entregas_restantes(), None);
+        assert!(!suscripcion.registrar_entrega());
+
+        assert!(!copia.limitar_entregas(3));
+        assert_eq!(suscripcion.entregas_restantes(), Some(2));
+        assert!(!copia.registrar_entrega());
+        assert!(suscripcion.registrar_entrega());
+        assert_eq!(copia.entregas_restantes(), Some(0));
+    }
+
+    #[test]
+    fn limite_ya_superado() {
+        let suscripcion = suscripcion();
+        for _ in 0..3 {
+            suscripcion.registrar_entrega();
+        }
+
+        assert!(suscripcion.limitar_entregas(1));
+        assert_eq!(suscripcion.entregas_restantes(), Some(0));
+    }
 }
@@ -1,13 +1,11 @@
-use std::hash::Hash;
-
-#[derive(Debug, Clone)]
-enum Segmento {
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(super) enum Segmento {
     Texto(String),
     Asteriso,
 }
 
 /// Meter dentro de un hashMap
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Topico {
     patron: Vec<Segmento>,
     exacto: bool,
@@ -39,30 +37,27 @@ impl Topico {
     }
 
     pub fn test(&self, subject: &str) -> bool {
-        let segmentos = subject.split('.').collect::<Vec<&str>>();
-        if self.patron.len() > segmentos.len() {
-            return false;
-        }
-
-        if self.exacto && segmentos.len() != self.patron.len() {
-            return false;
-        }
+        let mut segmentos = subject.split('.');
 
-        for (i, segmento) in segmentos.iter().enumerate() {
-            if i >= self.patron.len() {
-                return true;
+        for segmento_patron in &self.patron {
+            match (segmento_patron, segmentos.next()) {
+                (_, None) => return false,
+                (Segmento::Texto(t), Some(segmento)) if t != segmento => return false,
+                _ => {}
             }
+        }
 
-            let segmento_patron = &self.patron[i];
+        !self.exacto || segmentos.next().is_none()
+    }
 
-            if let Segmento::Texto(t) = segmento_patron {
-                if !t.eq(segmento) {
-                    return false;
-                }
-            }
-        }
+    /// Segmentos del patrón sin el `>` final
+    pub(super) fn segmentos(&self) -> &[Segmento] {
+        &self.patron
+    }
 
-        true
+    /// Si termina en `>` y acepta cualquier resto de segmentos
+    pub fn acepta_resto(&self) -> bool {
+        !self.exacto
     }
 
     pub fn a_texto(&self) -> String {
@@ -82,17 +77,3 @@ impl Topico {
         s
     }
 }
-
-impl Hash for Topico {
-    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        self.a_texto().hash(state)
-    }
-}
-
-impl PartialEq for Topico {
-    fn eq(&self, other: &Self) -> bool {
-        self.a_texto().eq(&other.a_texto())
-    }
-}
-
-impl Eq for Topico {}
@@ -76,7 +76,7 @@ impl Listar {
                 .show(ui.ctx(), |ui| {
                     egui::ScrollArea::vertical().show(ui, |ui| {
                         let mut sorted_camaras = camaras.iter().collect::<Vec<&Camara>>();
-                        sorted_camaras.sort_by(|a, b| a.id.cmp(&b.id));
+                        sorted_camaras.sort_by_key(|a| a.id);
 
                         for camara in sorted_camaras {
                             let nombre =
@@ -161,7 +161,7 @@ impl Listar {
                 .show(ui.ctx(), |ui| {
                     egui::ScrollArea::vertical().show(ui, |ui| {
                         let mut drones_ordenados = drones.iter().collect::<Vec<&Dron>>();
-                        drones_ordenados.sort_by(|a, b| a.id.cmp(&b.id));
+                        drones_ordenados.sort_by_key(|a| a.id);
                         for dron in drones_ordenados {
                             let id = dron.id.to_string();
 
@@ -64,7 +64,7 @@ impl Estado {
     /// Muestra todos los incidentes activos por orden de inicio.
     pub fn incidentes(&self) -> Vec<Incidente> {
         let mut v: Vec<Incidente> = self.incidentes.values().cloned().collect();
-        v.sort_by(|a, b| b.inicio.cmp(&a.inicio));
+        v.sort_by_key(|b| std::cmp::Reverse(b.inicio));
         v
     }
 
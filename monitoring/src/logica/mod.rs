@@ -11,7 +11,9 @@ use lib::{
     deteccion::Deteccion,
     dron::Dron,
     incidente::Incidente,
-    jet_stream::{consumer_config::ConsumerConfig, stream_config::StreamConfig},
+    jet_stream::{
+        consumer_config::ConsumerConfig, stream_config::StreamConfig, stream_source::StreamSource,
+    },
     serializables::{
         deserializar_vec,
         guardar::{cargar_serializable, guardar_serializable},
@@ -29,6 +31,12 @@ use self::{comando::Comando, estado::Estado};
 pub mod comando;
 pub mod estado;
 
+/// Tiempo que se conservan los mensajes en el stream `incidentes`
+const RETENCION_INCIDENTES: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Tiempo que se conservan en `incidentes-archivo`, que copia los de `incidentes`
+const RETENCION_ARCHIVO: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
 /// Sistema de monitoreo.
 pub struct Sistema {
     pub estado: Estado,
@@ -128,8 +136,7 @@ impl Sistema {
             self.estado.mensaje_error = None;
             drop(sub_conectado);
         } else {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
+            return Err(io::Error::other(
                 "No se pudo conectar al sistema".to_string(),
             ));
         }
@@ -151,6 +158,14 @@ impl Sistema {
                 "incidentes.*.creado".to_string(),
                 "incidentes.*.finalizado".to_string(),
             ],
+            max_age: RETENCION_INCIDENTES,
+            ..Default::default()
+        })?;
+
+        jet_stream.crear_stream(&StreamConfig {
+            name: "incidentes-archivo".to_string(),
+            sources: vec![StreamSource::new("incidentes")],
+            max_age: RETENCION_ARCHIVO,
             ..Default::default()
         })?;
 
@@ -461,20 +476,18 @@ impl Sistema {
                         self.requerir_actualizar_estado_ui();
                     }
                 }
-                Comando::CamaraNuevaUbicacion(id, lat, lon) => {
-                    if self.estado.camara(id).is_some() {
-                        cliente.publicar(
-                            "comandos.camaras",
-                            format!("modificar ubicacion {} {} {}", id, lat, lon).as_bytes(),
-                            None,
-                        )?;
-                    }
+                Comando::CamaraNuevaUbicacion(id, lat, lon) if self.estado.camara(id).is_some() => {
+                    cliente.publicar(
+                        "comandos.camaras",
+                        format!("modificar ubicacion {} {} {}", id, lat, lon).as_bytes(),
+                        None,
+                    )?;
                 }
                 Comando::Desconectar => {
                     self.estado.conectado = false;
                     self.configuracion = Configuracion::default();
                     self.requerir_actualizar_estado_ui();
-                    return Err(io::Error::new(io::ErrorKind::Other, "".to_string()));
+                    return Err(io::Error::other("".to_string()));
                 }
                 Comando::CamaraNuevoRango(id, rango) => {
                     if let Some(_camara) = self.estado.camara(id) {
@@ -698,10 +711,7 @@ impl Sistema {
     /// Actualiza el estado de la interfaz de usuario
     fn actualizar_estado_ui(&mut self) -> io::Result<()> {
         self.enviar_estado.send(self.estado.clone()).map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::Other,
-                format!("Error al enviar estado a la interfaz: {}", e),
-            )
+            io::Error::other(format!("Error al enviar estado a la interfaz: {}", e))
         })?;
 
         self.actualizar_estado = false;